@@ -0,0 +1,128 @@
+//! Timings for the write paths of [`fmtbuf::WriteBuf`], run with `cargo bench`. Each case reports the mean time of one
+//! iteration, so a regression in the hot path shows up as a change in these numbers.
+
+use fmtbuf::WriteBuf;
+use std::fmt::Write;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 100_000;
+
+/// Run `f` for [`ITERATIONS`] iterations and print the mean time of one. The sum of the lengths returned by `f` is
+/// printed as well, so the optimizer can not skip the work.
+fn bench<F>(name: &str, mut f: F)
+where
+    F: FnMut() -> usize,
+{
+    // Warm up caches and branch predictors before measuring
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let mut total = 0usize;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        total = total.wrapping_add(f());
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<32} {:>10.1?} per iteration (checksum {})",
+        name,
+        elapsed / ITERATIONS,
+        total
+    );
+}
+
+fn main() {
+    let large = "0123456789abcdef".repeat(64);
+    let mut buf = vec![0u8; 2048];
+
+    bench("many small writes", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        for _ in 0..256 {
+            let _ = writer.write_str("abc");
+        }
+        writer.position()
+    });
+
+    bench("many small writes with reserve", || {
+        let mut writer = WriteBuf::with_reserve(&mut buf, 16);
+        for _ in 0..256 {
+            let _ = writer.write_str("abc");
+        }
+        writer.position()
+    });
+
+    bench("one large write", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        let _ = writer.write_str(&large);
+        writer.position()
+    });
+
+    bench("one large truncated write", || {
+        let mut writer = WriteBuf::new(&mut buf[..1001]);
+        let _ = writer.write_str(&large);
+        writer.position()
+    });
+
+    bench("formatted writes", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        for x in 0..64 {
+            let _ = write!(writer, "{}={:x};", x, x * 31);
+        }
+        writer.position()
+    });
+
+    bench("integers with write!", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        for x in 0..128u64 {
+            let _ = write!(writer, "{}", x * 1_000_003);
+        }
+        writer.position()
+    });
+
+    bench("integers with write_u64", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        for x in 0..128u64 {
+            let _ = writer.write_u64(x * 1_000_003);
+        }
+        writer.position()
+    });
+
+    bench("floats with write!", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        for x in 0..128u32 {
+            let _ = write!(writer, "{:.3}", f64::from(x) * 1.0001);
+        }
+        writer.position()
+    });
+
+    bench("floats with write_f64", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        for x in 0..128u32 {
+            let _ = writer.write_f64(f64::from(x) * 1.0001, 3);
+        }
+        writer.position()
+    });
+
+    bench("hex bytes with write!", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        for byte in large.as_bytes() {
+            let _ = write!(writer, "{:02x}", byte);
+        }
+        writer.position()
+    });
+
+    bench("hex bytes with write_hex_bytes", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        let _ = writer.write_hex_bytes(large.as_bytes(), false);
+        writer.position()
+    });
+
+    bench("many chars", || {
+        let mut writer = WriteBuf::new(&mut buf);
+        for c in "héllo wörld 🚀".chars().cycle().take(512) {
+            let _ = writer.write_char(c);
+        }
+        writer.position()
+    });
+}
@@ -58,9 +58,9 @@ fn main() {
     let _ = writer.write_str(&cli.input);
     let result = match (cli.finish_with, cli.truncate_with) {
         (None, None) => writer.finish(),
-        (Some(finish), None) => writer.finish_with(finish),
-        (None, Some(truncate)) => writer.finish_with_or("", truncate),
-        (Some(finish), Some(truncate)) => writer.finish_with_or(finish, truncate),
+        (Some(finish), None) => writer.finish_with(&finish),
+        (None, Some(truncate)) => writer.finish_with_or("", &truncate),
+        (Some(finish), Some(truncate)) => writer.finish_with_or(&finish, &truncate),
     };
     let (written_len, truncated) = match result {
         Ok(len) => (len, false),
@@ -0,0 +1,541 @@
+use core::fmt;
+
+use crate::{target::sealed::Storage, WriteBuf};
+
+/// The escape sequence which turns off all styling.
+const RESET: &str = "\x1b[0m";
+
+/// A writer for text with ANSI escape sequences in it, such as colored log lines for a terminal or serial console,
+/// which are kept intact when the content is truncated.
+///
+/// ```
+/// use fmtbuf::AnsiBuf;
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 24] = [0xff; 24];
+/// let mut writer = AnsiBuf::new(&mut buf);
+/// write!(writer, "\x1b[31merror: {}\x1b[0m", "disk quota exceeded").unwrap_err();
+/// // The reset at the end of the input was cut off, so one is added before the suffix
+/// let write_len = writer.finish_with_or("", "…").unwrap_err();
+/// assert_eq!("\x1b[31merror: disk \x1b[0m…", std::str::from_utf8(&buf[..write_len]).unwrap());
+/// ```
+///
+/// Content is written like with a [`WriteBuf`], except that an escape sequence is never cut: if one does not entirely
+/// fit, it is removed, even the part of it written by an earlier write. An escape sequence is an ESC followed by any
+/// intermediate bytes (`0x20` to `0x2F`) and a final byte (`0x30` to `0x7E`), or a CSI sequence, which is an `ESC [`
+/// followed by any parameter and intermediate bytes (`0x20` to `0x3F`) and a final byte (`0x40` to `0x7E`). A byte which
+/// can not be part of the sequence it is in, such as an ESC starting another sequence, ends it early, leaving what came
+/// before it as it is. Other kinds of sequences, such as OSC, are not recognized, so only their start is kept whole.
+///
+/// Styling set by an SGR sequence (a CSI sequence ending in `m`) is open until it is reset by an SGR sequence with
+/// a parameter of 0 or no parameters. Any other SGR sequence, including one which only turns something off, counts as
+/// opening styling, since the worst that can come of it is a reset which was not needed. While styling is open, room
+/// for `"\x1b[0m"` is kept at the end of the target, and finishing puts it right after the content, before the suffix,
+/// so styling never leaks into whatever is written after the target. See [`AnsiBuf::set_auto_reset`].
+pub struct AnsiBuf<'a> {
+    buf: WriteBuf<'a>,
+    state: State,
+    /// The position of the escape sequence being written, if `state` is not [`State::Text`].
+    escape_start: usize,
+    style_open: bool,
+    auto_reset: bool,
+}
+
+impl<'a> AnsiBuf<'a> {
+    /// Create an instance that will write to the given `target`. The contents of the target do not matter, as they will
+    /// be overwritten by writing.
+    pub fn new(target: &'a mut [u8]) -> Self {
+        Self {
+            buf: WriteBuf::new(target),
+            state: State::Text,
+            escape_start: 0,
+            style_open: false,
+            auto_reset: true,
+        }
+    }
+
+    /// Set if styling left open is reset when finishing, which also keeps room for the reset while it is open. It is by
+    /// default. Without it, escape sequences are still kept whole, but the content is finished the same way as
+    /// [`WriteBuf::finish_with_or`].
+    pub fn set_auto_reset(&mut self, auto_reset: bool) {
+        self.auto_reset = auto_reset;
+        if self.state == State::Text {
+            self.buf.set_reserve(self._reset_len());
+        }
+    }
+
+    /// Get if styling left open is reset when finishing.
+    pub fn is_auto_reset(&self) -> bool {
+        self.auto_reset
+    }
+
+    /// Get if the styling set by the written content is still open.
+    pub fn style_open(&self) -> bool {
+        self.style_open
+    }
+
+    /// Get the size of the target buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Get the number of bytes written so far.
+    pub fn position(&self) -> usize {
+        self.buf.position()
+    }
+
+    /// Get if a write was truncated. See [`WriteBuf::truncated`].
+    pub fn truncated(&self) -> bool {
+        self.buf.truncated()
+    }
+
+    /// Get the content written so far. This includes the start of an escape sequence which is still being written.
+    pub fn as_str(&self) -> &str {
+        self.buf.written()
+    }
+
+    /// Finish writing, resetting the styling if it is open. See [`AnsiBuf::finish_with_or`].
+    pub fn finish(self) -> Result<usize, usize> {
+        self.finish_with_or("", "")
+    }
+
+    /// Finish writing with `suffix` at the end, no matter if the content was truncated. See [`AnsiBuf::finish_with_or`].
+    pub fn finish_with(self, suffix: &str) -> Result<usize, usize> {
+        self.finish_with_or(suffix, suffix)
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with_or`], but with `"\x1b[0m"` between the content and the
+    /// suffix if styling is open at the end of the content.
+    ///
+    /// Written content is rolled back as needed for the reset and the suffix to fit, never to the middle of an escape
+    /// sequence, and since rolling back can close styling by removing the sequence which opened it, the reset is only
+    /// added if the content which is kept needs it. An escape sequence which was started but never finished is removed,
+    /// which counts as truncation, so `truncated_suffix` is used. If `truncated_suffix` does not fit in the target
+    /// buffer, it is cut the same way as in [`WriteBuf::finish_with`], which leaves no room for a reset, so no content
+    /// is kept either.
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`].
+    pub fn finish_with_or(mut self, normal_suffix: &str, truncated_suffix: &str) -> Result<usize, usize> {
+        let buf = &mut self.buf;
+        buf.set_reserve(0);
+        let reset_len = if self.auto_reset { RESET.len() } else { 0 };
+        let len = buf.capacity();
+        let position = buf.position();
+        let mut suffix = normal_suffix;
+        let (mut end, mut reset) = fit(buf.written_bytes(), len.saturating_sub(suffix.len()), reset_len);
+        if buf.truncated() || end < position || len < suffix.len() {
+            if !buf.truncated() {
+                buf._degrade_suffix(suffix.len() + if reset { RESET.len() } else { 0 });
+            }
+            suffix = truncated_suffix;
+            if suffix.len() > len {
+                buf._finish_with(suffix, suffix);
+                return buf._result();
+            }
+            (end, reset) = fit(buf.written_bytes(), len - suffix.len(), reset_len);
+            if end < position {
+                buf._degrade_suffix(suffix.len() + if reset { RESET.len() } else { 0 });
+            }
+        }
+
+        buf._drop_content_after(end);
+        let reset = if reset { RESET } else { "" };
+        buf.target.write_at(end, reset.as_bytes());
+        buf.target.write_at(end + reset.len(), suffix.as_bytes());
+        buf.position.set(end + reset.len() + suffix.len());
+        buf._result()
+    }
+
+    /// Get the number of bytes to keep for resetting the styling.
+    fn _reset_len(&self) -> usize {
+        if self.style_open && self.auto_reset {
+            RESET.len()
+        } else {
+            0
+        }
+    }
+
+    /// Write as much of the escape sequence at the start of `s` as it has, returning the number of bytes of it which
+    /// were written.
+    fn _write_escape(&mut self, s: &str) -> Result<usize, fmt::Error> {
+        let mut len = 0;
+        let mut end = None;
+        for b in s.bytes() {
+            let (state, step) = self.state.step(b);
+            match step {
+                Step::Continue => {
+                    self.state = state;
+                    len += 1;
+                },
+                Step::Complete => {
+                    len += 1;
+                    end = Some(true);
+                    break;
+                },
+                Step::Abort => {
+                    end = Some(false);
+                    break;
+                },
+            }
+        }
+
+        if fmt::Write::write_str(&mut self.buf, &s[..len]).is_err() {
+            self._abandon_escape();
+            return Err(fmt::Error);
+        }
+        if let Some(complete) = end {
+            self._end_escape(complete)?;
+        }
+        Ok(len)
+    }
+
+    /// Finish the escape sequence which was just written, which is `complete` if it ended with its final byte instead of
+    /// a byte which could not be part of it. If it leaves styling open without room to reset it, it is removed.
+    fn _end_escape(&mut self, complete: bool) -> fmt::Result {
+        self.state = State::Text;
+        let style_open = if complete {
+            sgr_style_open(&self.buf.written_bytes()[self.escape_start..], self.style_open)
+        } else {
+            self.style_open
+        };
+        if style_open && self.auto_reset && self.buf.remaining() < RESET.len() {
+            self._abandon_escape();
+            self.buf._mark_truncated(RESET.len());
+            return Err(fmt::Error);
+        }
+        self.style_open = style_open;
+        self.buf.set_reserve(self._reset_len());
+        Ok(())
+    }
+
+    /// Remove the escape sequence being written.
+    fn _abandon_escape(&mut self) {
+        self.buf._drop_content_after(self.escape_start);
+        self.state = State::Text;
+        self.buf.set_reserve(self._reset_len());
+    }
+}
+
+impl<'a> fmt::Write for AnsiBuf<'a> {
+    /// Append `s`, truncating it in the same way as writing to a [`WriteBuf`], except that an escape sequence is never
+    /// cut.
+    ///
+    /// # Error
+    ///
+    /// If `s` did not entirely fit, the same as [`WriteBuf`]. This includes an escape sequence which opens styling
+    /// without room left to reset it.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s;
+        while !rest.is_empty() {
+            if self.state == State::Text {
+                let text_len = rest.find('\x1b').unwrap_or(rest.len());
+                self.buf.write_str(&rest[..text_len])?;
+                rest = &rest[text_len..];
+                if rest.is_empty() {
+                    break;
+                }
+                // The sequence might turn off the styling, so it can use the room kept for the reset
+                self.escape_start = self.buf.position();
+                self.buf.set_reserve(0);
+            }
+            let len = self._write_escape(rest)?;
+            rest = &rest[len..];
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Debug for AnsiBuf<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnsiBuf")
+            .field("written", &self.as_str())
+            .field("capacity", &self.capacity())
+            .field("truncated", &self.truncated())
+            .field("style_open", &self.style_open)
+            .field("auto_reset", &self.auto_reset)
+            .finish()
+    }
+}
+
+/// Where a scan through content is relative to escape sequences.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Not in an escape sequence.
+    Text,
+    /// After the ESC starting an escape sequence and any intermediate bytes after it.
+    Escape,
+    /// After the `ESC [` starting a CSI sequence and any parameter or intermediate bytes after it.
+    Csi,
+}
+
+/// What scanning a byte did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    /// The byte is text, or it starts or continues an escape sequence.
+    Continue,
+    /// The byte is the final byte of the escape sequence.
+    Complete,
+    /// The byte can not be part of the escape sequence, which ended before it. It is scanned again as if it came after
+    /// text.
+    Abort,
+}
+
+impl State {
+    /// Get the state after `b`.
+    fn step(self, b: u8) -> (State, Step) {
+        match (self, b) {
+            (State::Text, 0x1b) => (State::Escape, Step::Continue),
+            (State::Text, _) => (State::Text, Step::Continue),
+            (State::Escape, b'[') => (State::Csi, Step::Continue),
+            (State::Escape, 0x20..=0x2f) => (State::Escape, Step::Continue),
+            (State::Escape, 0x30..=0x7e) => (State::Text, Step::Complete),
+            (State::Csi, 0x20..=0x3f) => (State::Csi, Step::Continue),
+            (State::Csi, 0x40..=0x7e) => (State::Text, Step::Complete),
+            (_, _) => (State::Text, Step::Abort),
+        }
+    }
+}
+
+/// Get if styling is open after the complete escape `sequence`, when it was `style_open` before it.
+fn sgr_style_open(sequence: &[u8], mut style_open: bool) -> bool {
+    let params = match sequence.strip_prefix(b"\x1b[").and_then(|s| s.strip_suffix(b"m")) {
+        // Private sequences, which start with one of `<=>?`, and sequences with intermediate bytes are not SGR
+        Some(params) if params.iter().all(|b| b.is_ascii_digit() || *b == b';' || *b == b':') => params,
+        _ => return style_open,
+    };
+
+    let mut params = params.split(|b| *b == b';');
+    while let Some(param) = params.next() {
+        match sgr_code(param) {
+            0 => style_open = false,
+            38 | 48 | 58 => {
+                // Unless the color is given in sub-parameters, it is given by the parameters after it, which can be 0
+                if !param.contains(&b':') {
+                    let skip = match params.next().map(sgr_code) {
+                        Some(5) => 1,
+                        Some(2) => 3,
+                        _ => 0,
+                    };
+                    params.by_ref().take(skip).for_each(drop);
+                }
+                style_open = true;
+            },
+            _ => style_open = true,
+        }
+    }
+    style_open
+}
+
+/// Get the number at the start of an SGR parameter, which is 0 if it is empty.
+fn sgr_code(param: &[u8]) -> u32 {
+    param.iter().take_while(|b| b.is_ascii_digit()).fold(0u32, |code, b| {
+        code.saturating_mul(10).saturating_add(u32::from(b - b'0'))
+    })
+}
+
+/// Find the longest prefix of `content` which does not end inside of an escape sequence, and which fits in `room` bytes
+/// along with `reset_len` bytes to reset the styling, if it is left open. The prefix always ends on a `char` boundary and
+/// might be empty.
+///
+/// # Returns
+///
+/// The length of the prefix and if it needs the reset.
+fn fit(content: &[u8], room: usize, reset_len: usize) -> (usize, bool) {
+    let mut best = (0, false);
+    let mut consider = |idx: usize, style_open: bool| {
+        let reset = style_open && reset_len > 0;
+        if idx + if reset { reset_len } else { 0 } <= room {
+            best = (idx, reset);
+        }
+    };
+
+    let mut state = State::Text;
+    let mut escape_start = 0;
+    let mut style_open = false;
+    for (idx, &b) in content.iter().enumerate() {
+        if state == State::Text && (b & 0xc0) != 0x80 {
+            consider(idx, style_open);
+        }
+        let (next, step) = state.step(b);
+        match step {
+            Step::Continue => {
+                if state == State::Text && next != State::Text {
+                    escape_start = idx;
+                }
+                state = next;
+            },
+            Step::Complete => {
+                style_open = sgr_style_open(&content[escape_start..=idx], style_open);
+                state = State::Text;
+            },
+            Step::Abort => {
+                consider(idx, style_open);
+                state = State::Text.step(b).0;
+                if state != State::Text {
+                    escape_start = idx;
+                }
+            },
+        }
+    }
+    if state == State::Text {
+        consider(content.len(), style_open);
+    }
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    /// Write `pieces` to an [`AnsiBuf`] over `N` bytes, then finish it with `"!"` or `"…"`.
+    fn finish<const N: usize>(auto_reset: bool, pieces: &[&str]) -> ([u8; N], Result<usize, usize>) {
+        let mut buf = [0xff; N];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.set_auto_reset(auto_reset);
+        for piece in pieces {
+            let _ = writer.write_str(piece);
+        }
+        let result = writer.finish_with_or("!", "…");
+        (buf, result)
+    }
+
+    /// Get the content finished in `buf`.
+    fn as_str(buf: &[u8], result: Result<usize, usize>) -> Result<&str, &str> {
+        let as_str = |len: usize| core::str::from_utf8(&buf[..len]).unwrap();
+        result.map(as_str).map_err(as_str)
+    }
+
+    #[test]
+    fn escapes_kept_whole() {
+        let mut buf: [u8; 5] = [0xff; 5];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.write_str("ab").unwrap();
+        // Only "\x1b[2" would fit
+        writer.write_str("\x1b[2K").unwrap_err();
+        assert_eq!("ab", writer.as_str());
+        assert!(writer.truncated());
+
+        // The part of the sequence from earlier writes is removed too
+        let mut buf: [u8; 5] = [0xff; 5];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.write_str("ab\x1b[").unwrap();
+        assert_eq!("ab\x1b[", writer.as_str());
+        writer.write_str("12K").unwrap_err();
+        assert_eq!("ab", writer.as_str());
+
+        // The sequence is longer than the entire target
+        let mut buf: [u8; 3] = [0xff; 3];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.write_str("\x1b[38;5;196m").unwrap_err();
+        assert_eq!("", writer.as_str());
+        assert_eq!(Err(0), writer.finish());
+
+        // Cutting text after a sequence is the same as with a `WriteBuf`
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.write_str("\x1b[2K見見").unwrap_err();
+        assert_eq!("\x1b[2K見", writer.as_str());
+    }
+
+    #[test]
+    fn malformed_escapes() {
+        // A byte which can not be in the sequence ends it, and an ESC starts a new one
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.write_str("\x1b[3é\x1b\x1b(Bx").unwrap();
+        assert!(!writer.style_open());
+        assert_eq!("\x1b[3é\x1b\x1b(Bx", writer.as_str());
+        assert_eq!(Ok(10), writer.finish());
+    }
+
+    #[test]
+    fn finish_rolls_back_before_escapes() {
+        let (buf, result) = finish::<16>(true, &["ab\x1b[2Kcd"]);
+        assert_eq!(Ok("ab\x1b[2Kcd!"), as_str(&buf, result));
+        // The normal suffix does not fit, so content is rolled back for the truncated one
+        let (buf, result) = finish::<10>(true, &["ab\x1b[2Kcdef"]);
+        assert_eq!(Err("ab\x1b[2Kc…"), as_str(&buf, result));
+        let (buf, result) = finish::<8>(true, &["ab\x1b[2Kcd"]);
+        assert_eq!(Err("ab…"), as_str(&buf, result));
+        // A sequence which was never finished is removed
+        let (buf, result) = finish::<16>(true, &["ab", "\x1b[3"]);
+        assert_eq!(Err("ab…"), as_str(&buf, result));
+        // The truncated suffix does not fit at all
+        let (buf, result) = finish::<2>(true, &["ab"]);
+        assert_eq!(Err(""), as_str(&buf, result));
+    }
+
+    #[test]
+    fn style_reset() {
+        for (input, expected) in [
+            ("\x1b[1mbold", "\x1b[1mbold\x1b[0m!"),
+            // Styling is closed by a 0 or no parameters
+            ("\x1b[1mb\x1b[0m", "\x1b[1mb\x1b[0m!"),
+            ("\x1b[1mb\x1b[m", "\x1b[1mb\x1b[m!"),
+            ("\x1b[1mb\x1b[1;0m", "\x1b[1mb\x1b[1;0m!"),
+            ("\x1b[0;1mb", "\x1b[0;1mb\x1b[0m!"),
+            // The 0 is a color, not a reset
+            ("\x1b[38;5;0mb", "\x1b[38;5;0mb\x1b[0m!"),
+            ("\x1b[1mb\x1b[38:5:0m", "\x1b[1mb\x1b[38:5:0m\x1b[0m!"),
+            // Other sequences do not change the styling
+            ("\x1b[1mb\x1b[2K", "\x1b[1mb\x1b[2K\x1b[0m!"),
+            ("b\x1b[?0m\x1b[0 m", "b\x1b[?0m\x1b[0 m!"),
+        ] {
+            let (buf, result) = finish::<32>(true, &[input]);
+            assert_eq!(Ok(expected), as_str(&buf, result), "input={:?}", input);
+        }
+
+        // The parameters are read once the sequence is complete, even if it was split across writes
+        let (buf, result) = finish::<32>(true, &["\x1b[", "3", "1mx"]);
+        assert_eq!(Ok("\x1b[31mx\x1b[0m!"), as_str(&buf, result));
+    }
+
+    #[test]
+    fn style_reset_reserved() {
+        // The text is cut to keep room for the reset
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.write_str("\x1b[31mabcdefghij").unwrap_err();
+        assert!(writer.style_open());
+        assert_eq!("\x1b[31mabc", writer.as_str());
+        assert_eq!(Err(12), writer.finish());
+        assert_eq!(b"\x1b[31mabc\x1b[0m", &buf);
+
+        // A sequence closing the styling can use the room kept for the reset
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.write_str("\x1b[31mabc").unwrap();
+        writer.write_str("\x1b[0m").unwrap();
+        assert!(!writer.style_open());
+        assert_eq!(Ok(12), writer.finish());
+
+        // A sequence opening the styling without room left for the reset is removed
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.write_str("ab\x1b[31m").unwrap_err();
+        assert!(!writer.style_open());
+        assert_eq!("ab", writer.as_str());
+
+        // Rolling back for the suffix removes the sequence which opened the styling, so there is nothing to reset
+        let (buf, result) = finish::<9>(true, &["a\x1b[1mbcd"]);
+        assert_eq!(Err("a…"), as_str(&buf, result));
+    }
+
+    #[test]
+    fn without_auto_reset() {
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = AnsiBuf::new(&mut buf);
+        writer.set_auto_reset(false);
+        assert!(!writer.is_auto_reset());
+        writer.write_str("\x1b[31mabcdefghij").unwrap_err();
+        assert!(writer.style_open());
+        assert_eq!(Err(12), writer.finish());
+        assert_eq!(b"\x1b[31mabcdefg", &buf);
+
+        let (buf, result) = finish::<12>(false, &["\x1b[31mabcdefghij"]);
+        assert_eq!(Err("\x1b[31mabcd…"), as_str(&buf, result));
+    }
+}
@@ -0,0 +1,149 @@
+//! Scanning written content for delimiters which were opened but not closed, used by
+//! [`crate::WriteBuf::finish_balanced`].
+//!
+//! The rules are intentionally simple:
+//!
+//! * `(`, `[` and `{` open a bracket, which is closed by any of `)`, `]` or `}`. A closing bracket with nothing open is
+//!   ignored.
+//! * `"` and `'` open a quoted section, which is closed by the same quote. Brackets inside of a quoted section are
+//!   ignored, and a `\` inside of one escapes the character after it.
+
+use crate::utf8::utf8_char_width;
+
+/// The state of a scan through content.
+#[derive(Default)]
+struct Scan {
+    depth: usize,
+    quote: Option<u8>,
+    escaped: bool,
+}
+
+impl Scan {
+    /// Advance the scan over `cu`. If it opens a bracket, the bracket is returned.
+    fn step(&mut self, cu: u8) -> Option<u8> {
+        if let Some(quote) = self.quote {
+            if self.escaped {
+                self.escaped = false;
+            } else if cu == b'\\' {
+                self.escaped = true;
+            } else if cu == quote {
+                self.quote = None;
+            }
+            return None;
+        }
+
+        match cu {
+            b'"' | b'\'' => self.quote = Some(cu),
+            b'(' | b'[' | b'{' => {
+                self.depth += 1;
+                return Some(cu);
+            },
+            b')' | b']' | b'}' => self.depth = self.depth.saturating_sub(1),
+            _ => {},
+        }
+        None
+    }
+
+    fn closers_len(&self) -> usize {
+        self.depth + usize::from(self.quote.is_some())
+    }
+
+    fn run(content: &[u8]) -> Self {
+        let mut scan = Self::default();
+        for cu in content {
+            scan.step(*cu);
+        }
+        scan
+    }
+}
+
+/// Find the longest prefix of `content` that can be followed by the delimiters which close everything left open in it
+/// within `room` bytes. The prefix always ends on a `char` boundary and might be empty.
+///
+/// # Returns
+///
+/// The length of the prefix and the number of bytes needed to close it.
+pub(crate) fn fit(content: &[u8], room: usize) -> (usize, usize) {
+    let mut scan = Scan::default();
+    let mut best = (0, 0);
+    for (idx, cu) in content.iter().enumerate() {
+        if utf8_char_width(*cu).is_some() && idx + scan.closers_len() <= room {
+            best = (idx, scan.closers_len());
+        }
+        scan.step(*cu);
+    }
+    if content.len() + scan.closers_len() <= room {
+        best = (content.len(), scan.closers_len());
+    }
+    best
+}
+
+/// Write the delimiters which close everything left open in `content` into `closers`, innermost first. The length of
+/// `closers` must be the one returned by [`fit`] for `content`.
+pub(crate) fn write_closers(content: &[u8], closers: &mut [u8]) {
+    let end = Scan::run(content);
+    let quoted = usize::from(end.quote.is_some());
+    if let Some(quote) = end.quote {
+        closers[0] = quote;
+    }
+
+    // The bracket open at some depth at the end of the content is the last one which opened to that depth, so write
+    // each opened bracket's closer in the slot for its depth and let later ones overwrite it
+    let mut scan = Scan::default();
+    for cu in content {
+        if let Some(bracket) = scan.step(*cu) {
+            if scan.depth <= end.depth {
+                closers[quoted + end.depth - scan.depth] = match bracket {
+                    b'[' => b']',
+                    b'{' => b'}',
+                    _ => b')',
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn closers<'b>(content: &str, out: &'b mut [u8; 16]) -> &'b str {
+        let (end, len) = fit(content.as_bytes(), usize::MAX);
+        assert_eq!(content.len(), end);
+        write_closers(content.as_bytes(), &mut out[..len]);
+        core::str::from_utf8(&out[..len]).unwrap()
+    }
+
+    #[test]
+    fn closers_examples() {
+        let mut out = [0u8; 16];
+        assert_eq!("", closers("", &mut out));
+        assert_eq!("", closers("f(a[1], {b})", &mut out));
+        assert_eq!(")", closers("f(a", &mut out));
+        assert_eq!("}]", closers("[1, {a: 2", &mut out));
+        assert_eq!("\"})", closers("f({body=\"abc", &mut out));
+        assert_eq!("'", closers("'([{", &mut out));
+        assert_eq!("\"", closers("\"esc \\\" (", &mut out));
+        assert_eq!("", closers("\"esc \\\\\"", &mut out));
+        assert_eq!("]", closers("[a)] (b][c", &mut out));
+        assert_eq!(")", closers("(見 🚀", &mut out));
+    }
+
+    #[test]
+    fn fit_examples() {
+        assert_eq!((0, 0), fit(b"", 0));
+        assert_eq!((3, 0), fit(b"abc", 3));
+        assert_eq!((2, 0), fit(b"abc", 2));
+        assert_eq!((2, 1), fit(b"a(b", 3));
+        assert_eq!((1, 0), fit(b"a(b", 2));
+        // the closer for the quote makes the prefix shorter, but closing it again makes room
+        assert_eq!((5, 0), fit(b"a'b'c'd", 6));
+        assert_eq!((4, 0), fit(b"a'b'c'd", 4));
+        assert_eq!((2, 1), fit(b"a'b'c'd", 3));
+        // never split a multibyte character
+        assert_eq!((1, 1), fit("(🚀".as_bytes(), 4));
+        assert_eq!((5, 1), fit("(🚀".as_bytes(), 6));
+        assert_eq!((0, 0), fit(b"((((", 1));
+        assert_eq!((2, 2), fit(b"((((", 4));
+    }
+}
@@ -0,0 +1,292 @@
+//! Writing bytes in base64, used by [`crate::WriteBuf::write_base64`] and [`Base64Writer`].
+
+use core::fmt;
+
+use crate::{SliceTarget, Target, WriteBuf};
+
+const STANDARD_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// How [`crate::WriteBuf::write_base64`] encodes bytes: the alphabet and the padding of the last group. The default is
+/// [`Base64Config::STANDARD`].
+///
+/// ```
+/// use fmtbuf::{Base64Config, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 32] = [0xff; 32];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// writer.write_base64(&[0xfb, 0xff], Base64Config::STANDARD).unwrap();
+/// writer.write_str(" ").unwrap();
+/// writer.write_base64(&[0xfb, 0xff], Base64Config::URL_SAFE.padding(false)).unwrap();
+/// assert_eq!("+/8= -_8", writer.written());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Base64Config {
+    url_safe: bool,
+    padding: bool,
+}
+
+impl Base64Config {
+    /// The alphabet of RFC 4648 section 4, with `+` and `/`, padded with `=`.
+    pub const STANDARD: Self = Self {
+        url_safe: false,
+        padding: true,
+    };
+
+    /// The URL and filename safe alphabet of RFC 4648 section 5, with `-` and `_`, padded with `=`.
+    pub const URL_SAFE: Self = Self {
+        url_safe: true,
+        padding: true,
+    };
+
+    /// Set if a last group of fewer than 3 bytes is padded to 4 characters with `=`.
+    pub const fn padding(mut self, padding: bool) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    fn alphabet(&self) -> &'static [u8; 64] {
+        if self.url_safe {
+            URL_SAFE_ALPHABET
+        } else {
+            STANDARD_ALPHABET
+        }
+    }
+
+    /// Get the number of characters `len` bytes are encoded as.
+    pub(crate) fn encoded_len(&self, len: usize) -> usize {
+        (len / 3).saturating_mul(4).saturating_add(self.tail_len(len % 3))
+    }
+
+    /// Get the number of characters of a last group of `len` bytes, which is below 3.
+    pub(crate) fn tail_len(&self, len: usize) -> usize {
+        match (len, self.padding) {
+            (0, _) => 0,
+            (_, true) => 4,
+            (len, false) => len + 1,
+        }
+    }
+
+    /// Encode `group`, which is 1 to 3 bytes, into the start of `out`, returning the number of characters.
+    pub(crate) fn encode_group(&self, group: &[u8], out: &mut [u8]) -> usize {
+        let alphabet = self.alphabet();
+        let byte = |idx: usize| group.get(idx).copied().map_or(0, u32::from);
+        let bits = (byte(0) << 16) | (byte(1) << 8) | byte(2);
+        for (idx, out) in out[..4].iter_mut().enumerate() {
+            *out = alphabet[((bits >> (18 - 6 * idx)) & 0x3f) as usize];
+        }
+        let len = self.tail_len(group.len() % 3).max(4 * (group.len() / 3));
+        for out in &mut out[group.len() + 1..len] {
+            *out = b'=';
+        }
+        len
+    }
+}
+
+impl Default for Base64Config {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+/// An encoder for base64 of input which arrives in chunks, made by [`WriteBuf::base64_writer`]. Each group of 3 bytes
+/// is written to the buffer as soon as it is complete, and [`Base64Writer::finish`] writes the last group.
+///
+/// ```
+/// use fmtbuf::{Base64Config, WriteBuf};
+///
+/// let mut buf: [u8; 32] = [0xff; 32];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// let mut encoder = writer.base64_writer(Base64Config::STANDARD);
+/// encoder.update(b"foo").unwrap();
+/// encoder.update(b"ba").unwrap();
+/// encoder.finish().unwrap();
+/// assert_eq!("Zm9vYmE=", writer.written());
+/// ```
+///
+/// The output is the same as encoding all of the input at once, so it is only cut after a whole group. Once a group
+/// does not fit, the encoder is truncated and writes nothing more, so the output is always the start of the encoding of
+/// the input, which can be decoded on its own. Call [`Base64Writer::finish`] at the end, since the last group is not
+/// written until then.
+pub struct Base64Writer<'b, 'a, T = SliceTarget<'a>> {
+    buf: &'b mut WriteBuf<'a, T>,
+    config: Base64Config,
+    /// The bytes of a group which is not complete yet.
+    pending: [u8; 3],
+    pending_len: usize,
+    truncated: bool,
+}
+
+impl<'b, 'a, T: Target<'a>> Base64Writer<'b, 'a, T> {
+    pub(crate) fn new(buf: &'b mut WriteBuf<'a, T>, config: Base64Config) -> Self {
+        Self {
+            buf,
+            config,
+            pending: [0; 3],
+            pending_len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Encode `bytes`, writing each group of 3 bytes which is complete and keeping the rest for the next call.
+    ///
+    /// # Errors
+    ///
+    /// If a group does not fit in the buffer, or this was already truncated. Nothing more is written after that.
+    pub fn update(&mut self, mut bytes: &[u8]) -> fmt::Result {
+        if self.pending_len > 0 {
+            let taken = bytes.len().min(3 - self.pending_len);
+            self.pending[self.pending_len..self.pending_len + taken].copy_from_slice(&bytes[..taken]);
+            self.pending_len += taken;
+            bytes = &bytes[taken..];
+            if self.pending_len < 3 {
+                return self._result();
+            }
+            let pending = self.pending;
+            self.pending_len = 0;
+            self._write(&pending)?;
+        }
+
+        let complete = bytes.len() / 3 * 3;
+        self.pending[..bytes.len() - complete].copy_from_slice(&bytes[complete..]);
+        self.pending_len = bytes.len() - complete;
+        self._write(&bytes[..complete])
+    }
+
+    /// Write the last group, which is padded if the config has padding.
+    ///
+    /// # Errors
+    ///
+    /// If the last group does not fit in the buffer, or this was already truncated.
+    pub fn finish(mut self) -> fmt::Result {
+        let pending = self.pending;
+        self._write(&pending[..self.pending_len])
+    }
+
+    /// Get if a group did not fit, after which nothing more is written.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn _write(&mut self, bytes: &[u8]) -> fmt::Result {
+        if self.truncated {
+            let dropped = self.config.encoded_len(bytes.len());
+            self.buf.dropped_input_bytes = self.buf.dropped_input_bytes.saturating_add(dropped);
+            return Err(fmt::Error);
+        }
+        let result = self.buf.write_base64(bytes, self.config);
+        self.truncated = result.is_err();
+        result
+    }
+
+    fn _result(&self) -> fmt::Result {
+        if self.truncated {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The test vectors of RFC 4648 section 10.
+    const VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn rfc_4648_vectors() {
+        for (input, expected) in VECTORS {
+            let mut buf: [u8; 16] = [0xff; 16];
+            let mut writer = WriteBuf::new(&mut buf);
+            writer.write_base64(input.as_bytes(), Base64Config::STANDARD).unwrap();
+            assert_eq!(expected, writer.written(), "input={}", input);
+
+            let mut buf: [u8; 16] = [0xff; 16];
+            let mut writer = WriteBuf::new(&mut buf);
+            writer
+                .write_base64(input.as_bytes(), Base64Config::STANDARD.padding(false))
+                .unwrap();
+            assert_eq!(expected.trim_end_matches('='), writer.written(), "input={}", input);
+            assert_eq!(
+                writer.position(),
+                Base64Config::STANDARD.padding(false).encoded_len(input.len())
+            );
+        }
+    }
+
+    #[test]
+    fn alphabets() {
+        let input = [0xfb, 0xef, 0xbe, 0x00, 0x10, 0x83];
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_base64(&input, Base64Config::STANDARD).unwrap();
+        writer.write_base64(&input, Base64Config::URL_SAFE).unwrap();
+        assert_eq!("++++ABCD----ABCD", writer.written());
+        assert_eq!(Base64Config::STANDARD, Base64Config::default());
+    }
+
+    #[test]
+    fn long_input_is_written_in_chunks() {
+        let input = [0u8; 300];
+        let mut buf: [u8; 512] = [0xff; 512];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_base64(&input, Base64Config::STANDARD).unwrap();
+        assert_eq!(400, writer.position());
+        assert!(writer.written().bytes().all(|c| c == b'A'));
+    }
+
+    #[test]
+    fn writer_matches_one_shot_for_every_split() {
+        let input = b"the quick brown fox";
+        let mut expected_buf: [u8; 32] = [0xff; 32];
+        let mut expected = WriteBuf::new(&mut expected_buf);
+        expected.write_base64(input, Base64Config::URL_SAFE).unwrap();
+
+        for first in 0..=input.len() {
+            for second in first..=input.len() {
+                let mut buf: [u8; 32] = [0xff; 32];
+                let mut writer = WriteBuf::new(&mut buf);
+                let mut encoder = writer.base64_writer(Base64Config::URL_SAFE);
+                encoder.update(&input[..first]).unwrap();
+                encoder.update(&input[first..second]).unwrap();
+                encoder.update(&input[second..]).unwrap();
+                encoder.finish().unwrap();
+                assert_eq!(
+                    expected.written(),
+                    writer.written(),
+                    "first={} second={}",
+                    first,
+                    second
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn writer_stops_after_truncation() {
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::new(&mut buf);
+        let mut encoder = writer.base64_writer(Base64Config::STANDARD);
+        encoder.update(b"foo").unwrap();
+        encoder.update(b"ba").unwrap();
+        // "bar" completes a group and is written, then the group "foo" does not fit in the 2 bytes left
+        encoder.update(b"rfoob").unwrap_err();
+        assert!(encoder.truncated());
+        // Nothing more is written, even what would fit
+        encoder.update(b"").unwrap_err();
+        encoder.finish().unwrap_err();
+        assert_eq!("Zm9vYmFy", writer.written());
+        assert!(writer.truncated());
+    }
+}
@@ -0,0 +1,191 @@
+//! Scanning written content for bidirectional formatting characters which were opened but not closed, used by
+//! [`crate::WriteBuf::finish_bidi_safe_with_or`].
+//!
+//! This follows the rules of the Unicode Bidirectional Algorithm (UAX #9) for matching them up:
+//!
+//! * LRE, RLE, LRO and RLO (U+202A, U+202B, U+202D and U+202E) open an embedding, which is closed by a PDF (U+202C).
+//!   A PDF only closes an embedding opened inside of the current isolate.
+//! * LRI, RLI and FSI (U+2066, U+2067 and U+2068) open an isolate, which is closed by a PDI (U+2069). A PDI also closes
+//!   every embedding opened inside of the isolate it closes. A PDI with no isolate open is ignored.
+//! * Nothing opens past a depth of 125, and the closers for what did not open are ignored.
+
+use crate::written_str;
+
+/// The deepest embeddings and isolates can be nested.
+const MAX_DEPTH: usize = 125;
+
+/// The number of bytes each closer is encoded in.
+const CLOSER_LEN: usize = 3;
+
+const PDF: char = '\u{202C}';
+const PDI: char = '\u{2069}';
+
+/// The state of a scan through content.
+#[derive(Default)]
+struct Scan {
+    /// A bit for each level which is open, set if it is an isolate.
+    isolates: u128,
+    depth: usize,
+    overflow_isolates: usize,
+    overflow_embeddings: usize,
+}
+
+impl Scan {
+    fn step(&mut self, c: char) {
+        match c {
+            '\u{202A}' | '\u{202B}' | '\u{202D}' | '\u{202E}' => self._open(false),
+            '\u{2066}' | '\u{2067}' | '\u{2068}' => self._open(true),
+            // A PDF inside of an isolate which did not open can not close anything outside of it
+            PDF if self.overflow_isolates > 0 => {},
+            PDF => {
+                if self.overflow_embeddings > 0 {
+                    self.overflow_embeddings -= 1;
+                } else if self.depth > 0 && !self._top_is_isolate() {
+                    self.depth -= 1;
+                }
+            },
+            PDI => {
+                if self.overflow_isolates > 0 {
+                    self.overflow_isolates -= 1;
+                } else if self._isolate_open() {
+                    self.overflow_embeddings = 0;
+                    while !self._top_is_isolate() {
+                        self.depth -= 1;
+                    }
+                    self.depth -= 1;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn _open(&mut self, isolate: bool) {
+        if self.depth < MAX_DEPTH && self.overflow_isolates == 0 && self.overflow_embeddings == 0 {
+            let bit = 1u128 << self.depth;
+            if isolate {
+                self.isolates |= bit;
+            } else {
+                self.isolates &= !bit;
+            }
+            self.depth += 1;
+        } else if isolate {
+            self.overflow_isolates += 1;
+        } else if self.overflow_isolates == 0 {
+            self.overflow_embeddings += 1;
+        }
+    }
+
+    fn _top_is_isolate(&self) -> bool {
+        self.depth > 0 && self.isolates & (1u128 << (self.depth - 1)) != 0
+    }
+
+    fn _isolate_open(&self) -> bool {
+        // The bits past the depth are left over from levels which were closed
+        self.isolates & ((1u128 << self.depth) - 1) != 0
+    }
+
+    fn closers_len(&self) -> usize {
+        self.depth * CLOSER_LEN
+    }
+
+    fn run(content: &str) -> Self {
+        let mut scan = Self::default();
+        for c in content.chars() {
+            scan.step(c);
+        }
+        scan
+    }
+}
+
+/// Find the longest prefix of `content` that can be followed by the characters which close everything left open in it
+/// within `room` bytes. The prefix always ends on a `char` boundary and might be empty.
+///
+/// # Returns
+///
+/// The length of the prefix and the number of bytes needed to close it.
+pub(crate) fn fit(content: &[u8], room: usize) -> (usize, usize) {
+    let content = written_str(content);
+    let mut scan = Scan::default();
+    let mut best = (0, 0);
+    for (idx, c) in content.char_indices() {
+        if idx + scan.closers_len() <= room {
+            best = (idx, scan.closers_len());
+        }
+        scan.step(c);
+    }
+    if content.len() + scan.closers_len() <= room {
+        best = (content.len(), scan.closers_len());
+    }
+    best
+}
+
+/// Write the characters which close everything left open in `content` into `closers`, innermost first. The length of
+/// `closers` must be the one returned by [`fit`] for `content`.
+pub(crate) fn write_closers(content: &[u8], closers: &mut [u8]) {
+    let mut scan = Scan::run(written_str(content));
+    let mut idx = 0;
+    while scan.depth > 0 {
+        let closer = if scan._top_is_isolate() { PDI } else { PDF };
+        closer.encode_utf8(&mut closers[idx..idx + CLOSER_LEN]);
+        idx += CLOSER_LEN;
+        scan.depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn closers<'b>(content: &str, out: &'b mut [u8; 32]) -> &'b str {
+        let (end, len) = fit(content.as_bytes(), usize::MAX);
+        assert_eq!(content.len(), end);
+        write_closers(content.as_bytes(), &mut out[..len]);
+        core::str::from_utf8(&out[..len]).unwrap()
+    }
+
+    #[test]
+    fn closers_examples() {
+        let mut out = [0u8; 32];
+        assert_eq!("", closers("", &mut out));
+        assert_eq!("", closers("a\u{202E}b\u{202C}c \u{2067}d\u{2069}", &mut out));
+        assert_eq!("\u{202C}", closers("a\u{202E}bc", &mut out));
+        // Nested, closed innermost first
+        assert_eq!(
+            "\u{202C}\u{2069}\u{202C}",
+            closers("\u{202B}a\u{2068}b\u{202D}c", &mut out)
+        );
+        // A PDI closes the embeddings inside of its isolate
+        assert_eq!("\u{202C}", closers("\u{202A}\u{2066}\u{202E}x\u{2069}", &mut out));
+        // An unmatched PDI is ignored, and a PDF can not close an embedding outside of the isolate it is in
+        assert_eq!(
+            "\u{2069}\u{202C}",
+            closers("\u{2069}\u{202A}\u{2066}\u{202C}", &mut out)
+        );
+    }
+
+    #[test]
+    fn depth_is_limited() {
+        let mut scan = Scan::default();
+        for _ in 0..130 {
+            scan.step('\u{202A}');
+        }
+        assert_eq!(MAX_DEPTH, scan.depth);
+        // The closers for the embeddings past the limit are used up first
+        for _ in 0..5 {
+            scan.step(PDF);
+        }
+        assert_eq!(MAX_DEPTH, scan.depth);
+        scan.step(PDF);
+        assert_eq!(MAX_DEPTH - 1, scan.depth);
+    }
+
+    #[test]
+    fn fit_examples() {
+        let rlo = "a\u{202E}bc";
+        assert_eq!((6, 3), fit(rlo.as_bytes(), 9));
+        // Rolling back keeps the embedding open until the content before it is all that fits
+        assert_eq!((5, 3), fit(rlo.as_bytes(), 8));
+        assert_eq!((1, 0), fit(rlo.as_bytes(), 5));
+        assert_eq!((0, 0), fit(rlo.as_bytes(), 0));
+    }
+}
@@ -0,0 +1,350 @@
+use core::fmt;
+
+use crate::{Segment, SliceTarget, Target, WriteBuf};
+
+/// The longest text [`DebugStruct`] closes with, which is `", … }"` or `" { … }"`.
+const STRUCT_CLOSE_LEN: usize = 8;
+
+/// The longest text [`DebugList`] closes with, which is `", …]"`.
+const LIST_CLOSE_LEN: usize = 6;
+
+/// The state shared by [`DebugStruct`] and [`DebugList`].
+struct Builder<'b, 'a, T> {
+    buf: &'b mut WriteBuf<'a, T>,
+    /// The number of bytes added to the reserve of `buf` to keep room for the closing text. This is `None` if the
+    /// opening text did not fit, in which case nothing is written, or after the builder was finished.
+    close_reserve: Option<usize>,
+    has_entries: bool,
+    dropped: bool,
+    /// If an entry was kept although a builder nested in it dropped some of its own entries.
+    nested_dropped: bool,
+}
+
+impl<'b, 'a, T: Target<'a>> Builder<'b, 'a, T> {
+    fn new(buf: &'b mut WriteBuf<'a, T>, open: &str, close_reserve: usize) -> Self {
+        let needed = open.len() + close_reserve;
+        let close_reserve = if buf._latched() || !buf.fits(needed) {
+            buf._mark_truncated(needed);
+            buf.dropped_input_bytes = buf.dropped_input_bytes.saturating_add(open.len());
+            None
+        } else {
+            buf._start_segment();
+            // It fits, so this can not fail
+            let _ = buf._write(open.as_bytes());
+            buf.reserve += close_reserve;
+            Some(close_reserve)
+        };
+        Self {
+            buf,
+            close_reserve,
+            has_entries: false,
+            dropped: false,
+            nested_dropped: false,
+        }
+    }
+
+    /// Write `sep` followed by what `f` writes, or roll it all back if it did not entirely fit or `f` failed. An entry
+    /// which ends with a nested builder that dropped some of its entries but was still closed is kept.
+    fn entry<F>(&mut self, first_sep: &str, sep: &str, f: F) -> bool
+    where
+        F: FnOnce(&mut WriteBuf<'a, T>) -> fmt::Result,
+    {
+        if self.close_reserve.is_none() {
+            self.dropped = true;
+            return false;
+        }
+
+        let checkpoint = self.buf.checkpoint();
+        let sep = if self.has_entries { sep } else { first_sep };
+        let _ = self.buf._write(sep.as_bytes());
+        let result = f(self.buf);
+        let position = self.buf.position();
+        if position > checkpoint.position && self.buf.partial_close == (position, self.buf.dropped_input_bytes) {
+            self.nested_dropped = true;
+        } else if result.is_err() || self.buf._truncated_since(&checkpoint) {
+            self.buf._drop_content_after(checkpoint.position);
+            self.buf._mark_truncated(0);
+            self.dropped = true;
+            return false;
+        }
+        self.has_entries = true;
+        true
+    }
+
+    /// Give back the room kept for the closing text and write `close` into it.
+    fn close(&mut self, close: &str) -> fmt::Result {
+        let close_reserve = match self.close_reserve.take() {
+            Some(close_reserve) => close_reserve,
+            None => return Err(fmt::Error),
+        };
+        debug_assert!(close.len() <= close_reserve);
+        self.buf.reserve = self.buf.reserve.saturating_sub(close_reserve);
+        self.buf._write_reserved(close);
+        if self.dropped || self.nested_dropped {
+            self.buf.partial_close = (self.buf.position(), self.buf.dropped_input_bytes);
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Writes a struct in the same format as [`fmt::Formatter::debug_struct`], dropping whole fields which do not fit.
+/// Made by [`WriteBuf::debug_struct`].
+///
+/// Room for the closing `}` is kept from the start, so the output is always complete: fields which did not fit are
+/// replaced by a `…` marker, as in `Foo { bar: 1, … }`. If the builder is dropped without calling
+/// [`DebugStruct::finish`], it is finished then.
+#[must_use = "must eventually call `finish()` on Debug builders"]
+pub struct DebugStruct<'b, 'a, T: Target<'a> = SliceTarget<'a>>(Builder<'b, 'a, T>);
+
+impl<'b, 'a, T: Target<'a>> DebugStruct<'b, 'a, T> {
+    pub(crate) fn new(buf: &'b mut WriteBuf<'a, T>, name: &str) -> Self {
+        Self(Builder::new(buf, name, STRUCT_CLOSE_LEN))
+    }
+
+    /// Add a field, written with its [`fmt::Debug`] implementation. If the field does not entirely fit, none of it is
+    /// written and the buffer is marked as truncated.
+    pub fn field(&mut self, name: &str, value: &dyn fmt::Debug) -> &mut Self {
+        self.field_with(name, |buf| fmt::write(&mut Segment(buf), format_args!("{:?}", value)))
+    }
+
+    /// Add a field whose value is written by `f`, such as with a nested builder. The field is dropped whole if it does
+    /// not entirely fit or if `f` returns `Err`, unless the `Err` comes from a nested builder which dropped some of its
+    /// entries but was still closed, as in `items: [1, 2, …]`.
+    pub fn field_with<F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut WriteBuf<'a, T>) -> fmt::Result,
+    {
+        self.0.entry(" { ", ", ", |buf| {
+            let _ = buf._write(name.as_bytes());
+            let _ = buf._write(b": ");
+            f(buf)
+        });
+        self
+    }
+
+    /// Write the closing `}`, along with the `…` marker if any fields were dropped.
+    ///
+    /// # Errors
+    ///
+    /// If any fields were dropped, including by a nested builder, or if the name of the struct did not fit, in which
+    /// case nothing was written at all.
+    pub fn finish(&mut self) -> fmt::Result {
+        let close = match (self.0.has_entries, self.0.dropped) {
+            (true, false) => " }",
+            (true, true) => ", … }",
+            (false, true) => " { … }",
+            (false, false) => "",
+        };
+        self.0.close(close)
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> Drop for DebugStruct<'b, 'a, T> {
+    fn drop(&mut self) {
+        if self.0.close_reserve.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
+
+/// Writes a list in the same format as [`fmt::Formatter::debug_list`], dropping whole entries which do not fit. Made by
+/// [`WriteBuf::debug_list`].
+///
+/// Room for the closing `]` is kept from the start, so the output is always complete: entries which did not fit are
+/// replaced by a `…` marker, as in `[1, 2, …]`. If the builder is dropped without calling [`DebugList::finish`], it is
+/// finished then.
+#[must_use = "must eventually call `finish()` on Debug builders"]
+pub struct DebugList<'b, 'a, T: Target<'a> = SliceTarget<'a>>(Builder<'b, 'a, T>);
+
+impl<'b, 'a, T: Target<'a>> DebugList<'b, 'a, T> {
+    pub(crate) fn new(buf: &'b mut WriteBuf<'a, T>) -> Self {
+        Self(Builder::new(buf, "[", LIST_CLOSE_LEN))
+    }
+
+    /// Add an entry, written with its [`fmt::Debug`] implementation. If the entry does not entirely fit, none of it is
+    /// written and the buffer is marked as truncated.
+    pub fn entry(&mut self, value: &dyn fmt::Debug) -> &mut Self {
+        self.entry_with(|buf| fmt::write(&mut Segment(buf), format_args!("{:?}", value)))
+    }
+
+    /// Add an entry which is written by `f`, such as with a nested builder. The entry is dropped whole if it does not
+    /// entirely fit or if `f` returns `Err`, unless the `Err` comes from a nested builder which dropped some of its
+    /// entries but was still closed, as in `[1, 2, …]`.
+    pub fn entry_with<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut WriteBuf<'a, T>) -> fmt::Result,
+    {
+        self.0.entry("", ", ", f);
+        self
+    }
+
+    /// Add each of `entries`, stopping at the first one which does not fit.
+    pub fn entries<D, I>(&mut self, entries: I) -> &mut Self
+    where
+        D: fmt::Debug,
+        I: IntoIterator<Item = D>,
+    {
+        for entry in entries {
+            let written = self.0.entry("", ", ", |buf| {
+                fmt::write(&mut Segment(buf), format_args!("{:?}", entry))
+            });
+            if !written {
+                break;
+            }
+        }
+        self
+    }
+
+    /// Write the closing `]`, along with the `…` marker if any entries were dropped.
+    ///
+    /// # Errors
+    ///
+    /// If any entries were dropped, including by a nested builder, or if the opening `[` did not fit, in which case
+    /// nothing was written at all.
+    pub fn finish(&mut self) -> fmt::Result {
+        let close = match (self.0.has_entries, self.0.dropped) {
+            (true, true) => ", …]",
+            (false, true) => "…]",
+            (_, false) => "]",
+        };
+        self.0.close(close)
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> Drop for DebugList<'b, 'a, T> {
+    fn drop(&mut self) {
+        if self.0.close_reserve.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::WriteBuf;
+
+    #[test]
+    fn debug_struct_matches_formatter() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer
+            .debug_struct("Foo")
+            .field("bar", &1)
+            .field("baz", &"hi")
+            .finish()
+            .unwrap();
+        assert_eq!(r#"Foo { bar: 1, baz: "hi" }"#, writer.written());
+        assert!(!writer.truncated());
+
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.debug_struct("Unit").finish().unwrap();
+        writer.debug_list().finish().unwrap();
+        writer.debug_list().entries(1..4).finish().unwrap();
+        assert_eq!("Unit[][1, 2, 3]", writer.written());
+    }
+
+    #[test]
+    fn debug_struct_drops_whole_fields() {
+        let mut buf: [u8; 24] = [0xff; 24];
+        let mut writer = WriteBuf::new(&mut buf);
+        let result = writer
+            .debug_struct("Foo")
+            .field("bar", &1)
+            .field("baz", &"this does not fit")
+            .field("q", &2)
+            .finish();
+        assert!(result.is_err());
+        assert_eq!("Foo { bar: 1, … }", writer.written());
+        assert!(writer.truncated());
+
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.debug_struct("Foo").field("long_name", &1).finish().unwrap_err();
+        assert_eq!("Foo { … }", writer.written());
+
+        // Not even the name and the room to close fit, so nothing is written
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.debug_struct("Foo").field("a", &1).finish().unwrap_err();
+        assert_eq!("", writer.written());
+        assert!(writer.truncated());
+    }
+
+    #[test]
+    fn debug_list_closes_when_dropped() {
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut list = writer.debug_list();
+            list.entries(["ab", "cd", "ef"].iter());
+        }
+        assert_eq!(r#"["ab", …]"#, writer.written());
+    }
+
+    #[test]
+    fn nested_builders() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer
+            .debug_struct("Foo")
+            .field_with("items", |buf| buf.debug_list().entries(1..4).finish())
+            .field("n", &4)
+            .finish()
+            .unwrap();
+        assert_eq!("Foo { items: [1, 2, 3], n: 4 }", writer.written());
+
+        // The nested list drops entries but is still closed, so the field is kept and the struct reports the drop
+        let mut buf: [u8; 40] = [0xff; 40];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer
+            .debug_struct("Foo")
+            .field("n", &4)
+            .field_with("items", |buf| buf.debug_list().entries(100..200).finish())
+            .finish()
+            .unwrap_err();
+        assert_eq!("Foo { n: 4, items: [100, …] }", writer.written());
+        assert!(writer.truncated());
+
+        // Fields after it are dropped as usual
+        let mut buf: [u8; 48] = [0xff; 48];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer
+            .debug_list()
+            .entry_with(|buf| {
+                buf.debug_struct("P")
+                    .field_with("xs", |buf| buf.debug_list().entries(100..200).finish())
+                    .finish()
+            })
+            .entry(&1)
+            .finish()
+            .unwrap_err();
+        assert_eq!("[P { xs: [100, 101, 102, 103, …] }, …]", writer.written());
+
+        // A nested builder which fails for another reason still drops the field
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer
+            .debug_struct("Foo")
+            .field_with("items", |buf| {
+                let _ = buf.debug_list().entries(1..3).finish();
+                Err(core::fmt::Error)
+            })
+            .finish()
+            .unwrap_err();
+        assert_eq!("Foo { … }", writer.written());
+
+        // A struct nested in a list
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer
+            .debug_list()
+            .entry(&1)
+            .entry_with(|buf| buf.debug_struct("P").field("x", &2).finish())
+            .finish()
+            .unwrap();
+        assert_eq!("[1, P { x: 2 }]", writer.written());
+    }
+}
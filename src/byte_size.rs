@@ -0,0 +1,208 @@
+use crate::int;
+
+/// How [`crate::WriteBuf::write_bytes_human`] writes a number of bytes: the units and the digits after the decimal
+/// point.
+///
+/// ```
+/// use fmtbuf::{ByteStyle, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 32] = [0xff; 32];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// writer.write_bytes_human(1_468_006, ByteStyle::BINARY).unwrap();
+/// writer.write_str(", ").unwrap();
+/// writer.write_bytes_human(1_468_006, ByteStyle::DECIMAL.precision(2)).unwrap();
+/// assert_eq!("1.4 MiB, 1.47 MB", writer.written());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ByteStyle {
+    binary: bool,
+    precision: usize,
+}
+
+impl ByteStyle {
+    /// Units of 1024 bytes, written as `KiB`, `MiB` and so on, with one digit after the decimal point.
+    pub const BINARY: Self = Self {
+        binary: true,
+        precision: 1,
+    };
+
+    /// Units of 1000 bytes, written as `kB`, `MB` and so on, with one digit after the decimal point.
+    pub const DECIMAL: Self = Self {
+        binary: false,
+        precision: 1,
+    };
+
+    /// The most digits after the decimal point.
+    pub const MAX_PRECISION: usize = 18;
+
+    /// Set the number of digits after the decimal point, which is clamped to at most [`ByteStyle::MAX_PRECISION`]. A
+    /// number of bytes below the first unit, such as `512 B`, never has any.
+    pub const fn precision(mut self, precision: usize) -> Self {
+        self.precision = if precision > Self::MAX_PRECISION {
+            Self::MAX_PRECISION
+        } else {
+            precision
+        };
+        self
+    }
+
+    fn base(&self) -> u64 {
+        if self.binary {
+            1024
+        } else {
+            1000
+        }
+    }
+
+    fn units(&self) -> &'static [&'static str; UNITS] {
+        if self.binary {
+            &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]
+        } else {
+            &["B", "kB", "MB", "GB", "TB", "PB", "EB"]
+        }
+    }
+}
+
+impl Default for ByteStyle {
+    fn default() -> Self {
+        Self::BINARY
+    }
+}
+
+/// The number of units, which go up to exbibytes, since `u64::MAX` bytes is just below 16 EiB.
+const UNITS: usize = 7;
+
+/// A number of bytes split up for writing in a unit.
+pub(crate) struct ByteSize {
+    whole: u64,
+    /// The digits after the decimal point, as an integer below `10^precision`.
+    fraction: u64,
+    precision: usize,
+    unit: &'static str,
+}
+
+impl ByteSize {
+    pub(crate) fn new(bytes: u64, style: ByteStyle) -> Self {
+        let base = style.base();
+        let mut unit = 0;
+        let mut divisor = 1u64;
+        while unit + 1 < UNITS && bytes / divisor >= base {
+            divisor *= base;
+            unit += 1;
+        }
+        if unit == 0 {
+            return Self {
+                whole: bytes,
+                fraction: 0,
+                precision: 0,
+                unit: style.units()[0],
+            };
+        }
+
+        let scale = 10u64.pow(style.precision as u32);
+        let mut rounded = round_div(u128::from(bytes) * u128::from(scale), u128::from(divisor));
+        // Rounding can carry into the next unit, such as 1023.96 KiB to 1024.0 KiB, which is 1.0 MiB
+        if rounded >= u128::from(base) * u128::from(scale) && unit + 1 < UNITS {
+            divisor *= base;
+            unit += 1;
+            rounded = round_div(u128::from(bytes) * u128::from(scale), u128::from(divisor));
+        }
+        Self {
+            whole: (rounded / u128::from(scale)) as u64,
+            fraction: (rounded % u128::from(scale)) as u64,
+            precision: style.precision,
+            unit: style.units()[unit],
+        }
+    }
+
+    /// Get the number of bytes written.
+    pub(crate) fn len(&self) -> usize {
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        let whole = int::decimal_u64(self.whole, false, &mut scratch).len();
+        let fraction = if self.precision == 0 { 0 } else { 1 + self.precision };
+        whole + fraction + 1 + self.unit.len()
+    }
+
+    /// Pass the output to `out` in pieces. The pieces add up to [`ByteSize::len`] bytes.
+    pub(crate) fn render(&self, mut out: impl FnMut(&[u8])) {
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        out(int::decimal_u64(self.whole, false, &mut scratch));
+        if self.precision > 0 {
+            let mut padded = [b'0'; ByteStyle::MAX_PRECISION];
+            let padded = &mut padded[..self.precision];
+            int::digits_u64(self.fraction, padded);
+            out(b".");
+            out(padded);
+        }
+        out(b" ");
+        out(self.unit.as_bytes());
+    }
+}
+
+/// Divide `numerator` by `divisor`, rounding to the nearest integer with a tie going to the even one.
+fn round_div(numerator: u128, divisor: u128) -> u128 {
+    let (quotient, remainder) = (numerator / divisor, numerator % divisor);
+    let twice = remainder * 2;
+    if twice > divisor || (twice == divisor && quotient % 2 == 1) {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+    use core::fmt::Write;
+
+    fn human(bytes: u64, style: ByteStyle) -> StackString<64> {
+        let size = ByteSize::new(bytes, style);
+        let mut out: StackString<64> = StackString::new();
+        size.render(|piece| {
+            out.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+        });
+        assert_eq!(size.len(), out.as_str().len(), "bytes={}", bytes);
+        out
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!("0 B", human(0, ByteStyle::BINARY));
+        assert_eq!("512 B", human(512, ByteStyle::BINARY));
+        assert_eq!("1023 B", human(1023, ByteStyle::BINARY));
+        assert_eq!("1.0 KiB", human(1024, ByteStyle::BINARY));
+        assert_eq!("1.5 KiB", human(1536, ByteStyle::BINARY));
+        assert_eq!("999 B", human(999, ByteStyle::DECIMAL));
+        assert_eq!("1.0 kB", human(1000, ByteStyle::DECIMAL));
+        assert_eq!("1.4 MiB", human(1_468_006, ByteStyle::BINARY));
+        assert_eq!("16.0 EiB", human(u64::MAX, ByteStyle::BINARY));
+        assert_eq!("18.4 EB", human(u64::MAX, ByteStyle::DECIMAL));
+        assert_eq!(ByteStyle::BINARY, ByteStyle::default());
+    }
+
+    #[test]
+    fn rounding_carries_into_next_unit() {
+        // 1023.96 KiB
+        assert_eq!("1.0 MiB", human(1_048_535, ByteStyle::BINARY));
+        assert_eq!("1023.9 KiB", human(1_048_473, ByteStyle::BINARY));
+        assert_eq!("1.0 MB", human(999_960, ByteStyle::DECIMAL));
+        assert_eq!("999.96 kB", human(999_960, ByteStyle::DECIMAL.precision(2)));
+        assert_eq!("1 MB", human(999_960, ByteStyle::DECIMAL.precision(0)));
+        // Exact ties go to even
+        assert_eq!("2 KiB", human(2560, ByteStyle::BINARY.precision(0)));
+        assert_eq!("4 KiB", human(3584, ByteStyle::BINARY.precision(0)));
+    }
+
+    #[test]
+    fn precision() {
+        assert_eq!("1.000 KiB", human(1024, ByteStyle::BINARY.precision(3)));
+        assert_eq!("1.001 KiB", human(1025, ByteStyle::BINARY.precision(3)));
+        assert_eq!("512 B", human(512, ByteStyle::BINARY.precision(3)));
+        assert_eq!(
+            "15.999999999999999999 EiB",
+            human(u64::MAX, ByteStyle::BINARY.precision(100))
+        );
+    }
+}
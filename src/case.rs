@@ -0,0 +1,229 @@
+use core::fmt;
+
+use crate::{written_str, SliceTarget, Target, WriteBuf};
+
+/// The number of bytes mapped at a time before they are written to the buffer.
+const CHUNK_LEN: usize = 64;
+
+/// The most bytes a single `char` can map to. Changing the case of a `char` produces at most 3 `char`s.
+const MAX_MAPPED_LEN: usize = 3 * 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Case {
+    Upper,
+    Lower,
+}
+
+/// Writes to a [`WriteBuf`], changing the case of everything written on the way in. This is useful for displays and
+/// protocols which only accept uppercase, without formatting a copy of the message first.
+///
+/// ```
+/// use fmtbuf::{CaseWriter, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 32] = [0xff; 32];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// let mut out = CaseWriter::upper(&mut writer);
+/// write!(out, "temp {}°c, {}", 21, "straße").unwrap();
+/// assert_eq!("TEMP 21°C, STRASSE", writer.written());
+/// ```
+///
+/// ASCII is mapped directly. Anything else is mapped with [`char::to_uppercase`] or [`char::to_lowercase`], unless
+/// [`CaseWriter::set_ascii_only`] is set, which leaves it as it is. A single `char` can map to several: `'ß'` is
+/// uppercased to `"SS"`. When the mapped output does not entirely fit, it is truncated at a `char` boundary like any
+/// other write, which can leave part of the mapping of a `char`, such as a single `'S'`.
+///
+/// The mapping is the same for every language, as it does not know about locales: uppercasing `'i'` always gives
+/// `'I'`, never the Turkish dotted `'İ'`, while lowercasing `'İ'` gives `"i\u{307}"` (`'i'` followed by a combining dot
+/// above) and the dotless `'ı'` uppercases to a plain `'I'`. Since each `char` is mapped on its own, `'Σ'` always
+/// lowercases to `'σ'`, even at the end of a word, where [`str::to_lowercase`] would give `'ς'`.
+pub struct CaseWriter<'b, 'a, T = SliceTarget<'a>> {
+    buf: &'b mut WriteBuf<'a, T>,
+    case: Case,
+    ascii_only: bool,
+}
+
+impl<'b, 'a, T: Target<'a>> CaseWriter<'b, 'a, T> {
+    /// Create an instance which writes everything to `buf` in uppercase.
+    pub fn upper(buf: &'b mut WriteBuf<'a, T>) -> Self {
+        Self::new(buf, Case::Upper)
+    }
+
+    /// Create an instance which writes everything to `buf` in lowercase.
+    pub fn lower(buf: &'b mut WriteBuf<'a, T>) -> Self {
+        Self::new(buf, Case::Lower)
+    }
+
+    fn new(buf: &'b mut WriteBuf<'a, T>, case: Case) -> Self {
+        Self {
+            buf,
+            case,
+            ascii_only: false,
+        }
+    }
+
+    /// Set if only the case of ASCII characters is changed, leaving everything else as it is. This skips the Unicode
+    /// case tables, so it is faster. It is not set by default.
+    pub fn set_ascii_only(&mut self, ascii_only: bool) {
+        self.ascii_only = ascii_only;
+    }
+
+    /// Get if only the case of ASCII characters is changed. See [`CaseWriter::set_ascii_only`].
+    pub fn ascii_only(&self) -> bool {
+        self.ascii_only
+    }
+
+    /// Add the mapping of `c`, which is not ASCII, to the end of `chunk`, returning the new length.
+    fn _map_char(&self, c: char, chunk: &mut [u8], mut len: usize) -> usize {
+        if self.ascii_only {
+            return len + c.encode_utf8(&mut chunk[len..]).len();
+        }
+        match self.case {
+            Case::Upper => {
+                for mapped in c.to_uppercase() {
+                    len += mapped.encode_utf8(&mut chunk[len..]).len();
+                }
+            },
+            Case::Lower => {
+                for mapped in c.to_lowercase() {
+                    len += mapped.encode_utf8(&mut chunk[len..]).len();
+                }
+            },
+        }
+        len
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for CaseWriter<'b, 'a, T> {
+    /// Write `s` with its case changed.
+    ///
+    /// # Error
+    ///
+    /// If the mapped `s` did not entirely fit, the same as [`WriteBuf`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut chunk = [0u8; CHUNK_LEN];
+        let mut len = 0;
+        let mut idx = 0;
+        while idx < s.len() {
+            if CHUNK_LEN - len < MAX_MAPPED_LEN {
+                self.buf.write_str(written_str(&chunk[..len]))?;
+                len = 0;
+            }
+
+            let b = s.as_bytes()[idx];
+            if b.is_ascii() {
+                chunk[len] = match self.case {
+                    Case::Upper => b.to_ascii_uppercase(),
+                    Case::Lower => b.to_ascii_lowercase(),
+                };
+                len += 1;
+                idx += 1;
+            } else {
+                let c = s[idx..].chars().next().unwrap_or_default();
+                len = self._map_char(c, &mut chunk, len);
+                idx += c.len_utf8();
+            }
+        }
+        self.buf.write_str(written_str(&chunk[..len]))
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Debug for CaseWriter<'b, 'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CaseWriter")
+            .field("buf", &self.buf)
+            .field("case", &self.case)
+            .field("ascii_only", &self.ascii_only)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+    use core::fmt::Write;
+
+    #[test]
+    fn maps_case() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        CaseWriter::upper(&mut writer).write_str("Grüße, ß!").unwrap();
+        assert_eq!("GRÜSSE, SS!", writer.written());
+
+        let mut writer = WriteBuf::new(&mut buf);
+        CaseWriter::lower(&mut writer).write_str("ÀB Σ").unwrap();
+        assert_eq!("àb σ", writer.written());
+    }
+
+    #[test]
+    fn turkish_i_is_not_locale_aware() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        CaseWriter::upper(&mut writer).write_str("iı").unwrap();
+        assert_eq!("II", writer.written());
+
+        let mut writer = WriteBuf::new(&mut buf);
+        CaseWriter::lower(&mut writer).write_str("İI").unwrap();
+        assert_eq!("i\u{307}i", writer.written());
+    }
+
+    #[test]
+    fn ascii_only() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        let mut out = CaseWriter::upper(&mut writer);
+        out.set_ascii_only(true);
+        assert!(out.ascii_only());
+        out.write_str("straße éa").unwrap();
+        assert_eq!("STRAßE éA", writer.written());
+    }
+
+    #[test]
+    fn long_input_is_chunked() {
+        let mut buf: [u8; 256] = [0xff; 256];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = CaseWriter::upper(&mut writer);
+            for _ in 0..20 {
+                out.write_str("abßc ").unwrap();
+            }
+        }
+        assert_eq!(120, writer.position());
+        assert!(writer.written().split(' ').take(20).all(|word| word == "ABSSC"));
+
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = CaseWriter::upper(&mut writer);
+            // Each "ß" is 2 bytes but maps to 2 "S"s, so this is longer than a chunk either way
+            let mut input = StackString::<80>::new();
+            for _ in 0..40 {
+                input.write_char('ß').unwrap();
+            }
+            out.write_str(&input).unwrap();
+        }
+        assert_eq!(80, writer.position());
+        assert!(writer.written().bytes().all(|b| b == b'S'));
+    }
+
+    #[test]
+    fn truncated_during_expansion() {
+        let mut buf: [u8; 3] = [0xff; 3];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = CaseWriter::upper(&mut writer);
+            // "ß" maps to "SS", only one of which fits
+            out.write_str("abß").unwrap_err();
+            out.write_str("").unwrap();
+            out.write_str("c").unwrap_err();
+        }
+        assert!(writer.truncated());
+        assert_eq!("ABS", writer.written());
+
+        // The 2 byte "à" maps to the 2 byte "À", which does not fit in the 1 byte left
+        let mut buf: [u8; 3] = [0xff; 3];
+        let mut writer = WriteBuf::new(&mut buf);
+        CaseWriter::upper(&mut writer).write_str("xyà").unwrap_err();
+        assert_eq!("XY", writer.written());
+    }
+}
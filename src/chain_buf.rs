@@ -0,0 +1,307 @@
+use core::fmt;
+
+use crate::{utf8::rfind_utf8_end, written_str};
+
+/// A writer over `N` separate slices, which fills them in order as if they were one buffer. This is for output regions
+/// which are not contiguous, such as a chain of DMA descriptors.
+///
+/// A UTF-8 sequence is never split across two slices: a `char` which does not fit in the rest of the current slice is
+/// written at the start of the next one instead, so each slice always holds valid UTF-8 on its own. The bytes skipped
+/// at the end of a slice are not touched; the written length of each slice is returned by the finishing functions and
+/// [`ChainBuf::written_lens`].
+///
+/// ```
+/// use fmtbuf::ChainBuf;
+/// use std::fmt::Write;
+///
+/// let (mut a, mut b) = ([0xff; 4], [0xff; 8]);
+/// let mut writer = ChainBuf::new([&mut a[..], &mut b[..]]);
+/// write!(writer, "ab{}", "見").unwrap();
+/// // "見" is 3 bytes, so it goes in the second slice
+/// assert_eq!(Ok([2, 3]), writer.finish());
+/// assert_eq!(b"ab", &a[..2]);
+/// assert_eq!("見", std::str::from_utf8(&b[..3]).unwrap());
+/// ```
+pub struct ChainBuf<'a, const N: usize> {
+    slices: [&'a mut [u8]; N],
+    /// The number of bytes written into each slice. Only the slices up to `current` are written to.
+    lens: [usize; N],
+    /// The index of the slice being written into.
+    current: usize,
+    truncated: bool,
+}
+
+impl<'a, const N: usize> ChainBuf<'a, N> {
+    /// Create an instance that will write to `slices`, in order. The contents of the slices do not matter, as they will
+    /// be overwritten by writing.
+    pub fn new(slices: [&'a mut [u8]; N]) -> Self {
+        Self {
+            slices,
+            lens: [0; N],
+            current: 0,
+            truncated: false,
+        }
+    }
+
+    /// Get the total number of bytes of all the slices.
+    pub fn capacity(&self) -> usize {
+        self.slices.iter().map(|slice| slice.len()).sum()
+    }
+
+    /// Get the total number of bytes written, which does not count the bytes skipped at the end of a slice.
+    pub fn position(&self) -> usize {
+        self.lens.iter().sum()
+    }
+
+    /// Get the number of bytes written into each slice.
+    pub fn written_lens(&self) -> [usize; N] {
+        self.lens
+    }
+
+    /// Get the content written into the slice at `idx`.
+    ///
+    /// # Panics
+    ///
+    /// If `idx` is not less than `N`.
+    pub fn written(&self, idx: usize) -> &str {
+        written_str(&self.slices[idx][..self.lens[idx]])
+    }
+
+    /// Get if a write was truncated. See [`crate::WriteBuf::truncated`].
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Finish writing.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written into each slice, in `Ok` if nothing was truncated or in `Err` if it was.
+    pub fn finish(self) -> Result<[usize; N], [usize; N]> {
+        self.finish_with_or("", "")
+    }
+
+    /// Finish writing with `suffix` after the content. See [`ChainBuf::finish_with_or`].
+    pub fn finish_with(self, suffix: &str) -> Result<[usize; N], [usize; N]> {
+        self.finish_with_or(suffix, suffix)
+    }
+
+    /// Finish writing with `normal_suffix` after the content if nothing was truncated or `truncated_suffix` if it was.
+    /// This works the same as [`crate::WriteBuf::finish_with_or`], treating the slices as one buffer: the suffix is
+    /// written as though it was content, so it can continue into the next slice, and if it does not fit, content is
+    /// rolled back a `char` at a time, even into an earlier slice, until it does. If the suffix does not fit even
+    /// without any content, the start of it is cut off.
+    ///
+    /// # Returns
+    ///
+    /// The same as [`ChainBuf::finish`].
+    pub fn finish_with_or(mut self, normal_suffix: &str, truncated_suffix: &str) -> Result<[usize; N], [usize; N]> {
+        self._retreat_to_content();
+        if !self.truncated && self._fits(self.current, normal_suffix) {
+            self._write_fitting(normal_suffix);
+            return Ok(self.lens);
+        }
+        self.truncated = true;
+
+        while !self._fits(self.current, truncated_suffix) && self._pop_char() {}
+        let mut suffix = truncated_suffix;
+        while !self._fits(self.current, suffix) {
+            let mut chars = suffix.chars();
+            chars.next();
+            suffix = chars.as_str();
+        }
+        self._write_fitting(suffix);
+        Err(self.lens)
+    }
+
+    /// Check if writing `s` after the current content would fit, starting in the slice at `current`.
+    fn _fits(&self, mut current: usize, s: &str) -> bool {
+        let mut len = self.lens.get(current).copied().unwrap_or_default();
+        for c in s.chars() {
+            loop {
+                let slice_len = match self.slices.get(current) {
+                    Some(slice) => slice.len(),
+                    None => return false,
+                };
+                if c.len_utf8() <= slice_len - len {
+                    len += c.len_utf8();
+                    break;
+                }
+                current += 1;
+                len = 0;
+            }
+        }
+        true
+    }
+
+    /// Write `s`, which was checked to entirely fit.
+    fn _write_fitting(&mut self, s: &str) {
+        let result = self._write(s);
+        debug_assert!(result.is_ok());
+    }
+
+    /// Write as many `char`s of `s` as fit, moving to the next slice when one does not fit in the current one.
+    fn _write(&mut self, s: &str) -> fmt::Result {
+        let mut encoded = [0u8; 4];
+        for c in s.chars() {
+            let bytes = c.encode_utf8(&mut encoded).as_bytes();
+            loop {
+                let slice = match self.slices.get_mut(self.current) {
+                    Some(slice) => slice,
+                    None => return Err(fmt::Error),
+                };
+                let len = self.lens[self.current];
+                if bytes.len() <= slice.len() - len {
+                    slice[len..len + bytes.len()].copy_from_slice(bytes);
+                    self.lens[self.current] = len + bytes.len();
+                    break;
+                }
+                if self.current + 1 == N {
+                    return Err(fmt::Error);
+                }
+                self.current += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the last written `char`, going back to an earlier slice if that leaves the current one empty. Returns
+    /// `false` if there was nothing to remove.
+    fn _pop_char(&mut self) -> bool {
+        self._retreat_to_content();
+        let len = match self.lens.get(self.current) {
+            Some(len) if *len > 0 => *len,
+            _ => return false,
+        };
+        self.lens[self.current] = rfind_utf8_end(&self.slices[self.current][..len - 1]);
+        self._retreat_to_content();
+        true
+    }
+
+    /// Move back to the last slice with content in it, so writing continues right after the content. Writing can leave
+    /// the current slice empty, when a `char` which did not fit in the previous one did not fit anywhere.
+    fn _retreat_to_content(&mut self) {
+        while self.current > 0 && self.lens[self.current] == 0 {
+            self.current -= 1;
+        }
+    }
+}
+
+impl<'a, const N: usize> fmt::Write for ChainBuf<'a, N> {
+    /// Append `s`, continuing into the next slice when the current one is full. If it does not entirely fit, as many
+    /// whole `char`s as fit are written and this is marked as truncated, which makes every later non-empty write fail,
+    /// like [`crate::WriteBuf`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return if s.is_empty() { Ok(()) } else { Err(fmt::Error) };
+        }
+        let result = self._write(s);
+        self.truncated = result.is_err();
+        result
+    }
+}
+
+impl<'a, const N: usize> fmt::Debug for ChainBuf<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainBuf")
+            .field("written_lens", &self.lens)
+            .field("capacity", &self.capacity())
+            .field("truncated", &self.truncated)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn char_never_spans_slices() {
+        let (mut a, mut b, mut c) = ([0xff; 4], [0xff; 2], [0xff; 5]);
+        let mut writer = ChainBuf::new([&mut a[..], &mut b[..], &mut c[..]]);
+        assert_eq!(11, writer.capacity());
+        writer.write_str("ab").unwrap();
+        // "見" is 3 bytes, which does not fit in the 2 bytes left in the first slice or the 2 bytes of the second
+        writer.write_str("見").unwrap();
+        assert_eq!([2, 0, 3], writer.written_lens());
+        assert_eq!(5, writer.position());
+        writer.write_str("xy").unwrap();
+        writer.write_char('z').unwrap_err();
+        assert!(writer.truncated());
+        // Truncated writes latch, even if they would fit
+        writer.write_str("").unwrap();
+        assert_eq!(
+            ("ab", "", "見xy"),
+            (writer.written(0), writer.written(1), writer.written(2))
+        );
+        assert_eq!(Err([2, 0, 5]), writer.finish());
+        // The bytes skipped at the end of the slices are not touched
+        assert_eq!([b'a', b'b', 0xff, 0xff], a);
+        assert_eq!([0xff; 2], b);
+    }
+
+    #[test]
+    fn suffix_continues_into_next_slice() {
+        let (mut a, mut b) = ([0xff; 4], [0xff; 4]);
+        let mut writer = ChainBuf::new([&mut a[..], &mut b[..]]);
+        writer.write_str("abc").unwrap();
+        assert_eq!(Ok([4, 3]), writer.finish_with_or(".…", "!"));
+        assert_eq!(b"abc.", &a);
+        assert_eq!("…", core::str::from_utf8(&b[..3]).unwrap());
+    }
+
+    #[test]
+    fn suffix_rolls_back_across_slices() {
+        let (mut a, mut b) = ([0xff; 4], [0xff; 3]);
+        let mut writer = ChainBuf::new([&mut a[..], &mut b[..]]);
+        writer.write_str("ab").unwrap();
+        writer.write_str("見c").unwrap_err();
+        assert_eq!([2, 3], writer.written_lens());
+        // Rolling back "見" leaves the rest of the first slice free again, so the suffix starts there
+        assert_eq!(Err([4, 3]), writer.finish_with_or("", "xy…"));
+        assert_eq!(b"abxy", &a);
+        assert_eq!("…", core::str::from_utf8(&b).unwrap());
+
+        let (mut a, mut b) = ([0xff; 4], [0xff; 3]);
+        let mut writer = ChainBuf::new([&mut a[..], &mut b[..]]);
+        writer.write_str("abcd見").unwrap();
+        writer.write_str("x").unwrap_err();
+        // "見" and then "d" and "c" in the first slice are rolled back before "?!…" fits
+        assert_eq!(Err([4, 3]), writer.finish_with("?!…"));
+        assert_eq!(b"ab?!", &a);
+        assert_eq!("…", core::str::from_utf8(&b).unwrap());
+
+        let (mut a, mut b) = ([0xff; 4], [0xff; 3]);
+        let mut writer = ChainBuf::new([&mut a[..], &mut b[..]]);
+        writer.write_str("abcd").unwrap();
+        writer.write_str("efgh").unwrap_err();
+        // Rolling back "g" leaves room for "?" in the second slice
+        assert_eq!(Err([4, 3]), writer.finish_with("?"));
+        assert_eq!((b"abcd", b"ef?"), (&a, &b));
+
+        // A char which did not fit anywhere leaves the writer in the last slice, but the suffix goes after the content
+        let (mut a, mut b) = ([0xff; 3], [0xff; 2]);
+        let mut writer = ChainBuf::new([&mut a[..], &mut b[..]]);
+        writer.write_str("a見").unwrap_err();
+        assert_eq!(Err([3, 0]), writer.finish_with(".."));
+        assert_eq!(b"a..", &a);
+    }
+
+    #[test]
+    fn suffix_too_long_for_any_slice() {
+        let (mut a, mut b) = ([0xff; 2], [0xff; 2]);
+        let mut writer = ChainBuf::new([&mut a[..], &mut b[..]]);
+        writer.write_str("abcde").unwrap_err();
+        // A 3 byte "…" can never fit, so it is cut off and the content is rolled back entirely
+        assert_eq!(Err([0, 0]), writer.finish_with("…"));
+
+        let (mut a, mut b) = ([0xff; 2], [0xff; 2]);
+        let writer = ChainBuf::new([&mut a[..], &mut b[..]]);
+        assert_eq!(Err([2, 1]), writer.finish_with("[見]ab"));
+        assert_eq!((b"]a", &b"b"[..]), (&a, &b[..1]));
+
+        let writer = ChainBuf::<0>::new([]);
+        assert_eq!(Err([]), writer.finish_with("x"));
+    }
+}
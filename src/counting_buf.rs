@@ -0,0 +1,132 @@
+use core::fmt;
+
+/// A writer with no target, which only counts the number of bytes written to it. This measures how long formatted
+/// output is without writing it anywhere, such as to pick a buffer large enough for it.
+///
+/// ```
+/// use fmtbuf::CountingBuf;
+/// use std::fmt::Write;
+///
+/// let mut counter = CountingBuf::new();
+/// write!(counter, "{}: {:?}", "name", "見").unwrap();
+/// assert_eq!(11, counter.len());
+/// ```
+///
+/// See [`measure`] for measuring a single format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CountingBuf {
+    len: usize,
+}
+
+impl CountingBuf {
+    /// Create an instance which has counted nothing.
+    pub const fn new() -> Self {
+        Self { len: 0 }
+    }
+
+    /// Get the number of bytes written so far. This saturates at `usize::MAX` instead of overflowing.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get if nothing has been written.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reset the count to 0.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl fmt::Write for CountingBuf {
+    /// Count the bytes of `s`. This always succeeds.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len = self.len.saturating_add(s.len());
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.len = self.len.saturating_add(c.len_utf8());
+        Ok(())
+    }
+}
+
+/// Get the number of bytes `args` is formatted as, without writing it anywhere. This is usually used with
+/// [`format_args!`].
+///
+/// ```
+/// let id = 0x7f3a9c0e_u32;
+/// assert_eq!(11, fmtbuf::measure(format_args!("id={id:x}")));
+/// ```
+///
+/// If a `Display` or `Debug` implementation in `args` returns `Err`, formatting stops there, so this is the length of
+/// the output before the error.
+pub fn measure(args: fmt::Arguments<'_>) -> usize {
+    let mut counter = CountingBuf::new();
+    let _ = fmt::write(&mut counter, args);
+    counter.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    /// Writes its contents through a nested `Display` with padding, one `char` at a time.
+    struct Nested<'s>(&'s str);
+
+    impl<'s> fmt::Display for Nested<'s> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_char('<')?;
+            for c in self.0.chars() {
+                f.write_char(c)?;
+            }
+            write!(f, "|{:>4}>", self.0.len())
+        }
+    }
+
+    /// Fails after writing a prefix.
+    struct Failing;
+
+    impl fmt::Display for Failing {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("abc")?;
+            Err(fmt::Error)
+        }
+    }
+
+    #[test]
+    fn counts_bytes() {
+        let mut counter = CountingBuf::default();
+        assert!(counter.is_empty());
+        counter.write_str("ab").unwrap();
+        counter.write_char('見').unwrap();
+        counter.write_char('🚀').unwrap();
+        counter.write_str("").unwrap();
+        assert_eq!(9, counter.len());
+
+        let nested = Nested("x見");
+        write!(counter, "[{}]", nested).unwrap();
+        assert_eq!(9 + 13, counter.len());
+
+        counter.clear();
+        assert_eq!(CountingBuf::new(), counter);
+    }
+
+    #[test]
+    fn measure_matches_written() {
+        let nested = Nested("🚀 and 見");
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = crate::WriteBuf::new(&mut buf);
+        write!(writer, "{:?} {} {:08.3}", "q\"", nested, 1.5).unwrap();
+        assert_eq!(
+            writer.position(),
+            measure(format_args!("{:?} {} {:08.3}", "q\"", nested, 1.5))
+        );
+
+        // Counting stops at an error
+        assert_eq!(5, measure(format_args!("->{}{}", Failing, Nested(""))));
+    }
+}
@@ -0,0 +1,288 @@
+use core::{
+    ffi::{c_char, CStr},
+    fmt,
+};
+
+use crate::{target::sealed::Storage, WriteBuf};
+
+/// What a [`CStrBuf`] does with a NUL in written content, which would otherwise end the C string early. Set with
+/// [`CStrBuf::set_nul_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NulPolicy {
+    /// Reject a write containing a NUL: nothing of it is written and it returns `Err`. Later writes are still accepted,
+    /// but [`CStrBuf::finish`] returns `Err` to say content is missing. This is the default.
+    #[default]
+    Reject,
+    /// Write the given `char` in place of every NUL. The replacement is written like any other content, so it can be
+    /// truncated.
+    Replace(char),
+}
+
+/// A writer for C `char` arrays, such as the `ifr_name` field of `struct ifreq`.
+///
+/// The last byte of the target is kept for the NUL terminator, which is always present after the written content, so
+/// the target holds a valid C string after every write. Content is truncated at a `char` boundary like with a
+/// [`WriteBuf`], so the C string is also valid UTF-8. Written content never contains a NUL: what happens to one is
+/// decided by the [`NulPolicy`].
+///
+/// ```
+/// use fmtbuf::CStrBuf;
+/// use std::{ffi::c_char, fmt::Write};
+///
+/// let mut ifr_name: [c_char; 16] = [0; 16];
+/// let mut writer = CStrBuf::from_c_chars(&mut ifr_name);
+/// write!(writer, "wlp{}s{}f{}", 0, 20, 3).unwrap();
+/// assert_eq!(b"wlp0s20f3", writer.as_c_str().to_bytes());
+/// assert_eq!(b"wlp0s20f3", writer.finish().unwrap().to_bytes());
+/// ```
+pub struct CStrBuf<'a> {
+    buf: WriteBuf<'a>,
+    nul_policy: NulPolicy,
+    nul_rejected: bool,
+}
+
+impl<'a> CStrBuf<'a> {
+    /// Create an instance that will write to the given `target`, which holds at most `target.len() - 1` bytes of
+    /// content. An empty `target` can not even hold the terminator, so every non-empty write to it is truncated.
+    pub fn new(target: &'a mut [u8]) -> Self {
+        let mut out = Self {
+            buf: WriteBuf::new_cstr(target),
+            nul_policy: NulPolicy::default(),
+            nul_rejected: false,
+        };
+        out._terminate();
+        out
+    }
+
+    /// Create an instance that will write to an array of C `char`s, whether they are signed or not on this platform.
+    /// See [`CStrBuf::new`].
+    pub fn from_c_chars(target: &'a mut [c_char]) -> Self {
+        // safety: `c_char` is either `i8` or `u8`, which both have the same layout as `u8`, and every bit pattern is
+        // valid for all of them
+        Self::new(unsafe { &mut *(target as *mut [c_char] as *mut [u8]) })
+    }
+
+    /// Create an instance that will write to an array of signed bytes, which is what C `char` is on most platforms.
+    /// Bytes of multibyte sequences are stored with the same bits, so they read back as negative values. See
+    /// [`CStrBuf::new`].
+    pub fn from_i8(target: &'a mut [i8]) -> Self {
+        // safety: `i8` has the same layout as `u8` and every bit pattern is valid for both
+        Self::new(unsafe { &mut *(target as *mut [i8] as *mut [u8]) })
+    }
+
+    /// Set what happens to a NUL in content written after this call. See [`NulPolicy`].
+    ///
+    /// # Panics
+    ///
+    /// If the policy is [`NulPolicy::Replace`] with a NUL as the replacement.
+    pub fn set_nul_policy(&mut self, nul_policy: NulPolicy) {
+        assert!(
+            nul_policy != NulPolicy::Replace('\0'),
+            "a NUL can not be replaced with a NUL"
+        );
+        self.nul_policy = nul_policy;
+    }
+
+    /// Get what happens to a NUL in written content.
+    pub fn nul_policy(&self) -> NulPolicy {
+        self.nul_policy
+    }
+
+    /// Get the number of bytes of content this can hold, which does not include the terminator.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity().saturating_sub(1)
+    }
+
+    /// Get the number of bytes of content written so far, which does not include the terminator.
+    pub fn position(&self) -> usize {
+        self.buf.position()
+    }
+
+    /// Get if a write was truncated. See [`WriteBuf::truncated`].
+    pub fn truncated(&self) -> bool {
+        self.buf.truncated()
+    }
+
+    /// Get if a write was rejected for containing a NUL. See [`NulPolicy::Reject`].
+    pub fn nul_rejected(&self) -> bool {
+        self.nul_rejected
+    }
+
+    /// Get the content written so far.
+    pub fn as_str(&self) -> &str {
+        self.buf.written()
+    }
+
+    /// Get the content written so far as a C string. If the target is empty, there is no room for a terminator, so this
+    /// is an empty `CStr` which does not point into the target.
+    pub fn as_c_str(&self) -> &CStr {
+        if self.buf.capacity() == 0 {
+            return Default::default();
+        }
+        let terminated = self.buf.target.read_back(self.position() + 1);
+        // The terminator is written after every write and written content never contains a NUL
+        CStr::from_bytes_with_nul(terminated).expect("content should be followed by a single terminator")
+    }
+
+    /// Finish writing and get the C string written into the target.
+    ///
+    /// # Returns
+    ///
+    /// The `Ok` case indicates nothing was lost, while `Err` indicates that content was truncated or that a write was
+    /// rejected for containing a NUL. Either way, the `CStr` is the same as [`CStrBuf::as_c_str`].
+    pub fn finish(self) -> Result<&'a CStr, &'a CStr> {
+        let nul_rejected = self.nul_rejected;
+        match self.buf.finish_cstr() {
+            Ok(cstr) if !nul_rejected => Ok(cstr),
+            Ok(cstr) | Err(cstr) => Err(cstr),
+        }
+    }
+
+    /// Write `s`, which does not contain a NUL, then move the terminator to after it.
+    fn _write_nul_free(&mut self, s: &str) -> fmt::Result {
+        let result = fmt::Write::write_str(&mut self.buf, s);
+        self._terminate();
+        result
+    }
+
+    /// Write the terminator after the written content. The reserve keeps room for it unless the target is empty.
+    fn _terminate(&mut self) {
+        let position = self.position();
+        if position < self.buf.capacity() {
+            self.buf.target.write_at(position, b"\0");
+        }
+    }
+}
+
+impl<'a> fmt::Write for CStrBuf<'a> {
+    /// Append `s`, truncating it in the same way as writing to a [`WriteBuf`]. A NUL in `s` is handled by the
+    /// [`NulPolicy`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if !s.contains('\0') {
+            return self._write_nul_free(s);
+        }
+        match self.nul_policy {
+            NulPolicy::Reject => {
+                self.nul_rejected = true;
+                Err(fmt::Error)
+            },
+            NulPolicy::Replace(replacement) => {
+                let mut encoded = [0u8; 4];
+                let replacement = replacement.encode_utf8(&mut encoded);
+                let mut pieces = s.split('\0');
+                let mut result = self._write_nul_free(pieces.next().unwrap_or_default());
+                for piece in pieces {
+                    result = result
+                        .and_then(|_| self._write_nul_free(replacement))
+                        .and_then(|_| self._write_nul_free(piece));
+                }
+                result
+            },
+        }
+    }
+}
+
+impl<'a> fmt::Debug for CStrBuf<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CStrBuf")
+            .field("written", &self.as_c_str())
+            .field("capacity", &self.capacity())
+            .field("truncated", &self.truncated())
+            .field("nul_rejected", &self.nul_rejected)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn always_terminated() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = CStrBuf::new(&mut buf);
+        assert_eq!(7, writer.capacity());
+        assert_eq!(b"", writer.as_c_str().to_bytes());
+        writer.write_str("ab").unwrap();
+        assert_eq!(b"ab", writer.as_c_str().to_bytes());
+        // "見" is 3 bytes, so the second one does not fit before the terminator
+        writer.write_str("c見見").unwrap_err();
+        assert_eq!("abc見", writer.as_str());
+        assert_eq!("abc見", writer.as_c_str().to_str().unwrap());
+        assert_eq!(b"abc\xe8\xa6\x8b", writer.finish().unwrap_err().to_bytes());
+        assert_eq!(b"abc\xe8\xa6\x8b\0\xff", &buf);
+
+        let mut buf: [u8; 1] = [0xff; 1];
+        let mut writer = CStrBuf::new(&mut buf);
+        assert_eq!(0, writer.capacity());
+        writer.write_str("a").unwrap_err();
+        assert_eq!(b"", writer.finish().unwrap_err().to_bytes());
+        assert_eq!([0], buf);
+
+        let mut writer = CStrBuf::new(&mut []);
+        assert_eq!(b"", writer.as_c_str().to_bytes());
+        writer.write_str("").unwrap();
+        writer.write_str("a").unwrap_err();
+        assert_eq!(b"", writer.finish().unwrap_err().to_bytes());
+    }
+
+    #[test]
+    fn signed_chars() {
+        let mut buf: [i8; 6] = [-1; 6];
+        let mut writer = CStrBuf::from_i8(&mut buf);
+        write!(writer, "é{}", 1).unwrap();
+        assert_eq!("é1", writer.finish().unwrap().to_str().unwrap());
+        // 'é' is b"\xc3\xa9", which are negative as `i8`
+        assert_eq!([0xc3_u8 as i8, 0xa9_u8 as i8, b'1' as i8, 0, -1, -1], buf);
+
+        let mut buf: [c_char; 4] = [0; 4];
+        let mut writer = CStrBuf::from_c_chars(&mut buf);
+        writer.write_str("éé").unwrap_err();
+        assert_eq!("é", writer.finish().unwrap_err().to_str().unwrap());
+        let message = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!("é", message.to_str().unwrap());
+    }
+
+    #[test]
+    fn interior_nul_rejected() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = CStrBuf::new(&mut buf);
+        assert_eq!(NulPolicy::Reject, writer.nul_policy());
+        writer.write_str("ab").unwrap();
+        writer.write_str("c\0d").unwrap_err();
+        assert!(writer.nul_rejected());
+        assert!(!writer.truncated());
+        writer.write_char('\0').unwrap_err();
+        // Writes without a NUL are still accepted
+        writer.write_str("ef").unwrap();
+        assert_eq!(b"abef", writer.as_c_str().to_bytes());
+        assert_eq!(b"abef", writer.finish().unwrap_err().to_bytes());
+    }
+
+    #[test]
+    fn interior_nul_replaced() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = CStrBuf::new(&mut buf);
+        writer.set_nul_policy(NulPolicy::Replace('�'));
+        let nul = '\0';
+        write!(writer, "\0a{}\0\0b\0", nul).unwrap();
+        assert_eq!("�a���b�", writer.as_str());
+        assert!(!writer.nul_rejected());
+
+        // The replacement is truncated like other content
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = CStrBuf::new(&mut buf);
+        writer.set_nul_policy(NulPolicy::Replace('·'));
+        writer.write_str("ab\0\0c").unwrap_err();
+        assert_eq!(b"ab\xc2\xb7", writer.finish().unwrap_err().to_bytes());
+    }
+
+    #[test]
+    #[should_panic(expected = "a NUL can not be replaced with a NUL")]
+    fn nul_replacement_can_not_be_nul() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        CStrBuf::new(&mut buf).set_nul_policy(NulPolicy::Replace('\0'));
+    }
+}
@@ -0,0 +1,213 @@
+//! Writing a [`Duration`], such as `1h23m45s` or `01:23:45.678`, used by [`crate::WriteBuf::write_duration`].
+
+use core::time::Duration;
+
+use crate::int;
+
+/// How [`crate::WriteBuf::write_duration`] writes a [`Duration`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DurationStyle {
+    /// The largest units which make sense, such as `1h23m45s`, `3.2s`, `782ms` or `15µs`. From a minute up, this is
+    /// hours, minutes and whole seconds, where hours are left off if there are none. Below a minute, it is a single unit
+    /// with up to 3 digits after the decimal point, with zeros at the end left off. This is the default.
+    #[default]
+    Compact,
+    /// Hours, minutes, seconds and milliseconds, such as `01:23:45.678`. Hours take more than 2 digits if they need to.
+    Clock,
+}
+
+/// The longest text of a [`Duration`], which is `u64::MAX` seconds: 16 digits of hours and `:59:59.999`.
+const MAX_LEN: usize = 32;
+
+const NANOS_PER_MILLI: u32 = 1_000_000;
+const NANOS_PER_MICRO: u32 = 1_000;
+
+/// The text of a [`Duration`], built on the stack so it can be written entirely or not at all.
+pub(crate) struct DurationText {
+    bytes: [u8; MAX_LEN],
+    len: usize,
+}
+
+impl DurationText {
+    pub(crate) fn new(duration: Duration, style: DurationStyle) -> Self {
+        let mut out = Self {
+            bytes: [0; MAX_LEN],
+            len: 0,
+        };
+        let (secs, nanos) = (duration.as_secs(), duration.subsec_nanos());
+        match style {
+            DurationStyle::Compact if secs >= 60 => {
+                let hours = secs / 3600;
+                if hours > 0 {
+                    out.push_int(hours);
+                    out.push(b"h");
+                }
+                out.push_int(secs / 60 % 60);
+                out.push(b"m");
+                out.push_int(secs % 60);
+                out.push(b"s");
+            },
+            DurationStyle::Compact => {
+                // Only digits down to a thousandth of the unit are written, which keeps noise out of the output
+                let (whole, fraction, unit): (u64, u32, &[u8]) = if secs > 0 {
+                    (secs, nanos / NANOS_PER_MILLI, b"s")
+                } else if nanos >= NANOS_PER_MILLI {
+                    (
+                        u64::from(nanos / NANOS_PER_MILLI),
+                        nanos / NANOS_PER_MICRO % 1000,
+                        b"ms",
+                    )
+                } else if nanos >= NANOS_PER_MICRO {
+                    (u64::from(nanos / NANOS_PER_MICRO), nanos % 1000, "µs".as_bytes())
+                } else if nanos > 0 {
+                    (u64::from(nanos), 0, b"ns")
+                } else {
+                    (0, 0, b"s")
+                };
+                out.push_int(whole);
+                out.push_fraction(fraction);
+                out.push(unit);
+            },
+            DurationStyle::Clock => {
+                let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+                let hours = int::decimal_u64(secs / 3600, false, &mut scratch);
+                if hours.len() < 2 {
+                    out.push(b"0");
+                }
+                out.push(hours);
+                out.push(b":");
+                out.push_padded(secs / 60 % 60, 2);
+                out.push(b":");
+                out.push_padded(secs % 60, 2);
+                out.push(b".");
+                out.push_padded(u64::from(nanos / NANOS_PER_MILLI), 3);
+            },
+        }
+        out
+    }
+
+    /// Get the text of a duration of `ticks` of a clock which ticks `hz` times a second, which must not be 0.
+    pub(crate) fn from_ticks(ticks: u64, hz: u32, style: DurationStyle) -> Self {
+        let hz = u64::from(hz);
+        // The remainder is below 2^32, so it can be multiplied by a billion without overflowing
+        let nanos = ticks % hz * 1_000_000_000 / hz;
+        Self::new(Duration::new(ticks / hz, nanos as u32), style)
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn push_int(&mut self, value: u64) {
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        self.push(int::decimal_u64(value, false, &mut scratch));
+    }
+
+    /// Write `value` with leading zeros to `width` digits.
+    fn push_padded(&mut self, value: u64, width: usize) {
+        let mut padded = [b'0'; 3];
+        int::digits_u64(value, &mut padded[..width]);
+        self.push(&padded[..width]);
+    }
+
+    /// Write thousandths as digits after a decimal point, leaving off zeros at the end, and the point if all of them are.
+    fn push_fraction(&mut self, thousandths: u32) {
+        if thousandths == 0 {
+            return;
+        }
+        let (mut value, mut width) = (thousandths, 3);
+        while value % 10 == 0 {
+            value /= 10;
+            width -= 1;
+        }
+        self.push(b".");
+        self.push_padded(u64::from(value), width);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(duration: Duration, style: DurationStyle) -> DurationText {
+        DurationText::new(duration, style)
+    }
+
+    #[test]
+    fn compact() {
+        let cases: &[(Duration, &str)] = &[
+            (Duration::ZERO, "0s"),
+            (Duration::from_nanos(1), "1ns"),
+            (Duration::from_nanos(999), "999ns"),
+            (Duration::from_nanos(15_400), "15.4µs"),
+            (Duration::from_nanos(15_000), "15µs"),
+            (Duration::from_micros(782_000), "782ms"),
+            // Digits past a thousandth of the unit are cut, not rounded
+            (Duration::from_nanos(782_345_678), "782.345ms"),
+            (Duration::from_nanos(999_999_999), "999.999ms"),
+            (Duration::from_millis(3200), "3.2s"),
+            (Duration::from_millis(3000), "3s"),
+            (Duration::from_millis(59_999), "59.999s"),
+            (Duration::from_secs(60), "1m0s"),
+            (Duration::from_millis(125_500), "2m5s"),
+            (Duration::from_secs(3600), "1h0m0s"),
+            (Duration::from_secs(5025), "1h23m45s"),
+            (Duration::from_secs(100 * 3600 + 1), "100h0m1s"),
+            (Duration::MAX, "5124095576030431h0m15s"),
+        ];
+        for (duration, expected) in cases {
+            let text = text(*duration, DurationStyle::Compact);
+            assert_eq!(expected.as_bytes(), text.as_bytes(), "duration={:?}", duration);
+        }
+    }
+
+    #[test]
+    fn clock() {
+        let cases: &[(Duration, &str)] = &[
+            (Duration::ZERO, "00:00:00.000"),
+            (Duration::from_millis(5_025_678), "01:23:45.678"),
+            (Duration::from_nanos(999_999_999), "00:00:00.999"),
+            (Duration::from_secs(100 * 3600), "100:00:00.000"),
+            (Duration::MAX, "5124095576030431:00:15.999"),
+        ];
+        for (duration, expected) in cases {
+            let text = text(*duration, DurationStyle::Clock);
+            assert_eq!(expected.as_bytes(), text.as_bytes(), "duration={:?}", duration);
+        }
+    }
+
+    #[test]
+    fn ticks() {
+        assert_eq!(
+            b"1.5s",
+            DurationText::from_ticks(1500, 1000, DurationStyle::Compact).as_bytes()
+        );
+        assert_eq!(
+            b"10ms",
+            DurationText::from_ticks(1, 100, DurationStyle::Compact).as_bytes()
+        );
+        // A 32768 Hz crystal, which does not divide a second evenly
+        assert_eq!(
+            "30.517µs".as_bytes(),
+            DurationText::from_ticks(1, 32_768, DurationStyle::Compact).as_bytes()
+        );
+        assert_eq!(
+            b"00:01:00.000",
+            DurationText::from_ticks(60 * 32_768, 32_768, DurationStyle::Clock).as_bytes()
+        );
+        assert_eq!(
+            text(
+                Duration::from_secs(u64::MAX / u64::from(u32::MAX)),
+                DurationStyle::Compact
+            )
+            .as_bytes(),
+            DurationText::from_ticks(u64::MAX, u32::MAX, DurationStyle::Compact).as_bytes()
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use core::fmt;
+
+/// The ways a write to a [`crate::WriteBuf`] can fail.
+///
+/// The [`core::fmt::Write`] implementation can only report `fmt::Error`, so methods which are not part of it return
+/// this instead to say why a write failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WriteError {
+    /// The write needed more space than was left in the target buffer.
+    Capacity {
+        /// The number of bytes the write needed.
+        needed: usize,
+        /// The number of bytes which could be written without touching the reserve.
+        available: usize,
+    },
+    /// The write would fit in the target buffer, but only by writing into the reserve.
+    ReserveBlocked {
+        /// The number of bytes the write needed.
+        needed: usize,
+        /// The number of bytes which could be written without touching the reserve.
+        available: usize,
+    },
+    /// The buffer was already truncated, so nothing can be written until it is rolled back or reset.
+    AlreadyTruncated,
+    /// An argument was not valid for the written content, such as an index which is not on a `char` boundary or bytes
+    /// which are not valid UTF-8.
+    InvalidInput,
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::Capacity { needed, available } => {
+                write!(f, "write of {needed} bytes does not fit in the {available} bytes left")
+            },
+            WriteError::ReserveBlocked { needed, available } => {
+                write!(
+                    f,
+                    "write of {needed} bytes would go past the {available} bytes before the reserve"
+                )
+            },
+            WriteError::AlreadyTruncated => f.write_str("buffer was already truncated"),
+            WriteError::InvalidInput => f.write_str("invalid input for the written content"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WriteError {}
+
+/// A write to a [`crate::WriteBuf`] which did not entirely fit, returned by [`crate::WriteBuf::write_str_partial`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PartialWrite {
+    /// The number of bytes of the input which were written. This always ends on a `char` boundary of the input.
+    pub written: usize,
+    /// The number of bytes the entire input needed.
+    pub required: usize,
+}
+
+impl fmt::Display for PartialWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "only {} of {} bytes were written", self.written, self.required)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PartialWrite {}
+
+/// A reserve which can not be honored, returned by [`crate::WriteBuf::try_set_reserve`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ReserveError {
+    /// The number of bytes which were requested to be reserved.
+    pub requested: usize,
+    /// The number of bytes which have not been written to.
+    pub available: usize,
+}
+
+impl fmt::Display for ReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "can not reserve {} bytes with only {} bytes left",
+            self.requested, self.available
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReserveError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn display_messages() {
+        let mut buf: [u8; 256] = [0; 256];
+        let mut writer = crate::WriteBuf::new(&mut buf);
+        for error in [
+            WriteError::Capacity {
+                needed: 5,
+                available: 2,
+            },
+            WriteError::ReserveBlocked {
+                needed: 5,
+                available: 2,
+            },
+            WriteError::AlreadyTruncated,
+            WriteError::InvalidInput,
+        ] {
+            writeln!(writer, "{error}").unwrap();
+        }
+        assert_eq!(
+            "write of 5 bytes does not fit in the 2 bytes left\n\
+             write of 5 bytes would go past the 2 bytes before the reserve\n\
+             buffer was already truncated\n\
+             invalid input for the written content\n",
+            writer.written()
+        );
+    }
+}
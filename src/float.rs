@@ -0,0 +1,582 @@
+//! Writing a float with a fixed number of fraction digits without `core::fmt`, used by [`crate::WriteBuf::write_f64`].
+//!
+//! Every finite float is an integer times a power of 2, so its exact decimal expansion is finite. The integer part and
+//! the fraction are kept as fixed-size big integers, the fraction is turned into digits by multiplying it by 10, and
+//! whatever is left after the last digit decides the rounding, with an exact tie rounding to an even digit. This gives
+//! the same output as `{:.N}` in `core::fmt`.
+
+use crate::int;
+
+/// The number of 32-bit limbs in a [`Big`]. This is enough for the integer part of `f64::MAX`, which is 1024 bits, and
+/// for the fraction of the smallest subnormal times 10, which is 1078 bits.
+const LIMBS: usize = 34;
+
+/// The number of 9-digit chunks needed for the 309 digits of the integer part of `f64::MAX`.
+const INT_CHUNKS: usize = 35;
+
+/// Each chunk of the integer part is below this.
+const CHUNK: u32 = 1_000_000_000;
+
+/// The number of digits a chunk of the integer part is padded to, unless it is the first.
+const CHUNK_DIGITS: usize = 9;
+
+/// An unsigned integer of [`LIMBS`] 32-bit limbs, least significant first.
+#[derive(Clone, Copy)]
+struct Big {
+    limbs: [u32; LIMBS],
+    /// The number of limbs up to the last one which is not zero, so small values are fast to work with.
+    len: usize,
+}
+
+impl Big {
+    /// Create the value `value << shift`.
+    fn shifted(value: u64, shift: usize) -> Self {
+        let mut limbs = [0; LIMBS];
+        let wide = u128::from(value) << (shift % 32);
+        for (idx, limb) in limbs.iter_mut().skip(shift / 32).take(3).enumerate() {
+            *limb = (wide >> (idx * 32)) as u32;
+        }
+        let mut out = Self {
+            limbs,
+            len: (shift / 32 + 3).min(LIMBS),
+        };
+        out.trim();
+        out
+    }
+
+    /// Drop the zero limbs at the end from `len`.
+    fn trim(&mut self) {
+        while self.len > 0 && self.limbs[self.len - 1] == 0 {
+            self.len -= 1;
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.len == 0
+    }
+
+    fn is_odd(&self) -> bool {
+        self.limbs[0] & 1 == 1
+    }
+
+    fn add_one(&mut self) {
+        for (idx, limb) in self.limbs.iter_mut().enumerate() {
+            let (sum, carry) = limb.overflowing_add(1);
+            *limb = sum;
+            if !carry {
+                self.len = self.len.max(idx + 1);
+                return;
+            }
+        }
+    }
+
+    fn mul_small(&mut self, factor: u32) {
+        let mut carry = 0u64;
+        for limb in self.limbs[..self.len].iter_mut() {
+            let product = u64::from(*limb) * u64::from(factor) + carry;
+            *limb = product as u32;
+            carry = product >> 32;
+        }
+        if carry > 0 {
+            self.limbs[self.len] = carry as u32;
+            self.len += 1;
+        }
+    }
+
+    /// Divide by `divisor`, returning the remainder.
+    fn div_small(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+        for limb in self.limbs[..self.len].iter_mut().rev() {
+            let dividend = (remainder << 32) | u64::from(*limb);
+            *limb = (dividend / u64::from(divisor)) as u32;
+            remainder = dividend % u64::from(divisor);
+        }
+        self.trim();
+        remainder as u32
+    }
+
+    /// Remove and return the bits at and above bit `bit`, which must be fewer than 4 bits, such as the digit in front
+    /// of a fraction of `bit` bits after multiplying it by 10.
+    fn take_above(&mut self, bit: usize) -> u32 {
+        let (limb, shift) = (bit / 32, bit % 32);
+        let mut above = u64::from(self.limbs[limb]);
+        self.limbs[limb] &= (1u32 << shift) - 1;
+        // The taken bits end in the limb after the one `bit` is in at the latest
+        if let Some(next) = self.limbs.get_mut(limb + 1) {
+            above |= u64::from(*next) << 32;
+            *next = 0;
+        }
+        self.len = self.len.min(limb + 1);
+        self.trim();
+        (above >> shift) as u32
+    }
+
+    /// Compare a fraction of `bits` bits to one half.
+    fn cmp_half(&self, bits: usize) -> core::cmp::Ordering {
+        let half = bits - 1;
+        let (limb, shift) = (half / 32, half % 32);
+        if self.limbs[limb] >> shift & 1 == 0 {
+            return core::cmp::Ordering::Less;
+        }
+        let below_half = self.limbs[limb] & ((1u32 << shift) - 1) != 0 || self.limbs[..limb].iter().any(|l| *l != 0);
+        if below_half {
+            core::cmp::Ordering::Greater
+        } else {
+            core::cmp::Ordering::Equal
+        }
+    }
+}
+
+/// An `f64` ready to be written with a fixed number of fraction digits.
+pub(crate) struct Fixed {
+    /// The text written instead of digits, for NaN and the infinities.
+    special: Option<&'static str>,
+    negative: bool,
+    /// The integer part in chunks of [`CHUNK_DIGITS`] digits, least significant first.
+    int_chunks: [u32; INT_CHUNKS],
+    int_chunk_count: usize,
+    /// The fraction, as an integer of `frac_bits` bits after the binary point.
+    frac: Big,
+    frac_bits: usize,
+    precision: usize,
+    /// The number of fraction digits written as they are generated. After them comes the digit which is rounded up, if
+    /// `bump` is set, and then zeros.
+    kept: usize,
+    bump: bool,
+}
+
+impl Fixed {
+    pub(crate) fn new(value: f64, precision: usize) -> Self {
+        let mut out = Self {
+            special: None,
+            negative: value.is_sign_negative(),
+            int_chunks: [0; INT_CHUNKS],
+            int_chunk_count: 1,
+            frac: Big::shifted(0, 0),
+            frac_bits: 0,
+            precision,
+            kept: precision,
+            bump: false,
+        };
+        if value.is_nan() {
+            out.special = Some("NaN");
+            return out;
+        }
+        if value.is_infinite() {
+            out.special = Some(if out.negative { "-inf" } else { "inf" });
+            return out;
+        }
+
+        let (mut int, frac, frac_bits) = split(value);
+        out.frac = frac;
+        out.frac_bits = frac_bits;
+
+        if out.rounds_up(&int) {
+            match out.last_non_nine() {
+                Some(idx) => {
+                    out.kept = idx;
+                    out.bump = true;
+                },
+                None => {
+                    int.add_one();
+                    out.kept = 0;
+                },
+            }
+        }
+
+        out.int_chunk_count = 0;
+        loop {
+            out.int_chunks[out.int_chunk_count] = int.div_small(CHUNK);
+            out.int_chunk_count += 1;
+            if int.is_zero() {
+                break;
+            }
+        }
+        out
+    }
+
+    /// Get if what is left of the fraction after the last digit rounds the digits up, with `int` being the integer part
+    /// for when there are no fraction digits.
+    fn rounds_up(&self, int: &Big) -> bool {
+        let mut frac = self.frac;
+        let mut last_odd = int.is_odd();
+        for _ in 0..self.precision {
+            if frac.is_zero() {
+                return false;
+            }
+            frac.mul_small(10);
+            last_odd = frac.take_above(self.frac_bits) % 2 == 1;
+        }
+        if frac.is_zero() {
+            return false;
+        }
+        match frac.cmp_half(self.frac_bits) {
+            core::cmp::Ordering::Less => false,
+            core::cmp::Ordering::Equal => last_odd,
+            core::cmp::Ordering::Greater => true,
+        }
+    }
+
+    /// Get the index of the last fraction digit which is not a 9, which is where rounding up stops carrying.
+    fn last_non_nine(&self) -> Option<usize> {
+        let mut frac = self.frac;
+        let mut found = None;
+        for idx in 0..self.precision {
+            frac.mul_small(10);
+            if frac.take_above(self.frac_bits) != 9 {
+                found = Some(idx);
+            }
+        }
+        found
+    }
+
+    /// Get the number of bytes written, saturating at `usize::MAX`.
+    pub(crate) fn len(&self) -> usize {
+        if let Some(special) = self.special {
+            return special.len();
+        }
+        let top = self.int_chunks[self.int_chunk_count - 1];
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        let int_len = int::decimal_u64(u64::from(top), self.negative, &mut scratch).len()
+            + (self.int_chunk_count - 1) * CHUNK_DIGITS;
+        if self.precision == 0 {
+            int_len
+        } else {
+            int_len.saturating_add(1).saturating_add(self.precision)
+        }
+    }
+
+    /// Pass the output to `out` in pieces. The pieces add up to [`Fixed::len`] bytes.
+    pub(crate) fn render(&self, mut out: impl FnMut(&[u8])) {
+        if let Some(special) = self.special {
+            out(special.as_bytes());
+            return;
+        }
+
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        let top = self.int_chunks[self.int_chunk_count - 1];
+        out(int::decimal_u64(u64::from(top), self.negative, &mut scratch));
+        for chunk in self.int_chunks[..self.int_chunk_count - 1].iter().rev() {
+            let mut padded = [b'0'; CHUNK_DIGITS];
+            int::digits_u64(u64::from(*chunk), &mut padded);
+            out(&padded);
+        }
+        if self.precision == 0 {
+            return;
+        }
+
+        out(b".");
+        let mut frac = self.frac;
+        let generated = self.kept + usize::from(self.bump);
+        let mut digits = [b'0'; FRACTION_PIECE];
+        let (mut used, mut written) = (0, 0);
+        while written < generated && !frac.is_zero() {
+            frac.mul_small(10);
+            let digit = frac.take_above(self.frac_bits) + u32::from(written == self.kept);
+            digits[used] = b'0' + digit as u8;
+            used += 1;
+            written += 1;
+            if used == digits.len() {
+                out(&digits);
+                used = 0;
+            }
+        }
+        out(&digits[..used]);
+
+        // Everything after the generated digits is a zero
+        let mut zeros = self.precision - written;
+        let padding = [b'0'; FRACTION_PIECE];
+        while zeros > 0 {
+            let piece = zeros.min(padding.len());
+            out(&padding[..piece]);
+            zeros -= piece;
+        }
+    }
+}
+
+/// The number of fraction digits gathered on the stack before they are passed on.
+const FRACTION_PIECE: usize = 32;
+
+/// Split the finite `value` into its integer part and its fraction, as an integer of the returned number of bits after
+/// the binary point.
+fn split(value: f64) -> (Big, Big, usize) {
+    // value = mantissa * 2^exponent
+    let bits = value.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    let (mantissa, exponent) = if biased == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | (1 << 52), biased - 1075)
+    };
+
+    if exponent >= 0 {
+        return (Big::shifted(mantissa, exponent as usize), Big::shifted(0, 0), 0);
+    }
+    let frac_bits = exponent.unsigned_abs() as usize;
+    if frac_bits < 64 {
+        let int = Big::shifted(mantissa >> frac_bits, 0);
+        (int, Big::shifted(mantissa & ((1 << frac_bits) - 1), 0), frac_bits)
+    } else {
+        (Big::shifted(0, 0), Big::shifted(mantissa, 0), frac_bits)
+    }
+}
+
+/// Fill `digits` with the first significant digits of the exact decimal expansion of `magnitude`, which is finite and
+/// above zero, rounded with an exact tie going to an even digit. This gives the same digits as `{:.N$e}` in `core::fmt`
+/// with `N` one less than `digits.len()`.
+///
+/// # Returns
+///
+/// The power of 10 of the first digit.
+pub(crate) fn significant_digits(magnitude: f64, digits: &mut [u8]) -> i32 {
+    let mut expansion = Expansion::new(magnitude);
+    let mut exponent = expansion.int_len as i32 - 1;
+    if expansion.int_len == 0 {
+        // Skip the zeros at the start of the fraction
+        let mut first = expansion.next();
+        while first == 0 {
+            exponent -= 1;
+            first = expansion.next();
+        }
+        expansion.pushed_back = Some(first);
+    }
+
+    for digit in digits.iter_mut() {
+        *digit = b'0' + expansion.next();
+    }
+    let next = expansion.next();
+    let last_odd = digits.last().map_or(false, |digit| digit % 2 == 1);
+    if next > 5 || (next == 5 && (!expansion.rest_is_zero() || last_odd)) {
+        match digits.iter().rposition(|digit| *digit != b'9') {
+            Some(idx) => {
+                digits[idx] += 1;
+                digits[idx + 1..].fill(b'0');
+            },
+            // All 9s round up to the next power of 10
+            None => {
+                digits.fill(b'0');
+                digits[0] = b'1';
+                exponent += 1;
+            },
+        }
+    }
+    exponent
+}
+
+/// The decimal digits of a float, from the most significant one, used by [`significant_digits`].
+struct Expansion {
+    /// The digits of the integer part, without zeros in front.
+    int_digits: [u8; INT_CHUNKS * CHUNK_DIGITS],
+    int_len: usize,
+    /// The number of digits of `int_digits` taken so far.
+    taken: usize,
+    frac: Big,
+    frac_bits: usize,
+    /// A digit given back to be taken again.
+    pushed_back: Option<u8>,
+}
+
+impl Expansion {
+    fn new(value: f64) -> Self {
+        let (mut int, frac, frac_bits) = split(value);
+        let mut chunks = [0u32; INT_CHUNKS];
+        let mut chunk_count = 0;
+        while !int.is_zero() {
+            chunks[chunk_count] = int.div_small(CHUNK);
+            chunk_count += 1;
+        }
+
+        let mut int_digits = [b'0'; INT_CHUNKS * CHUNK_DIGITS];
+        for (idx, chunk) in chunks[..chunk_count].iter().rev().enumerate() {
+            let start = idx * CHUNK_DIGITS;
+            int::digits_u64(u64::from(*chunk), &mut int_digits[start..start + CHUNK_DIGITS]);
+        }
+        let all = chunk_count * CHUNK_DIGITS;
+        let leading = int_digits[..all].iter().take_while(|digit| **digit == b'0').count();
+        int_digits.copy_within(leading..all, 0);
+        Self {
+            int_digits,
+            int_len: all - leading,
+            taken: 0,
+            frac,
+            frac_bits,
+            pushed_back: None,
+        }
+    }
+
+    /// Take the next digit, which is 0 once the expansion ended.
+    fn next(&mut self) -> u8 {
+        if let Some(digit) = self.pushed_back.take() {
+            return digit;
+        }
+        if self.taken < self.int_len {
+            self.taken += 1;
+            return self.int_digits[self.taken - 1] - b'0';
+        }
+        if self.frac.is_zero() {
+            return 0;
+        }
+        self.frac.mul_small(10);
+        self.frac.take_above(self.frac_bits) as u8
+    }
+
+    /// Get if every digit which was not taken yet is a zero.
+    fn rest_is_zero(&self) -> bool {
+        self.pushed_back.map_or(true, |digit| digit == 0)
+            && self.int_digits[self.taken..self.int_len]
+                .iter()
+                .all(|digit| *digit == b'0')
+            && self.frac.is_zero()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::WriteBuf;
+    use core::fmt::Write;
+
+    /// The longest output checked: the integer part of `f64::MAX` or the exact fraction of the smallest subnormal, plus
+    /// some zeros after it.
+    const MAX_LEN: usize = 1200;
+
+    /// Check that [`Fixed`] writes `value` the same as `core::fmt` does with `precision`.
+    fn check(value: f64, precision: usize) {
+        let fixed = Fixed::new(value, precision);
+        let mut actual = [0u8; MAX_LEN];
+        let mut len = 0;
+        fixed.render(|piece| {
+            actual[len..len + piece.len()].copy_from_slice(piece);
+            len += piece.len();
+        });
+        assert_eq!(fixed.len(), len, "value={:e} precision={}", value, precision);
+
+        let mut expected = [0u8; MAX_LEN];
+        let mut writer = WriteBuf::new(&mut expected);
+        write!(writer, "{:.*}", precision, value).unwrap();
+        assert_eq!(
+            writer.written(),
+            core::str::from_utf8(&actual[..len]).unwrap(),
+            "value={:e} precision={}",
+            value,
+            precision
+        );
+    }
+
+    #[test]
+    fn rounding() {
+        // 0.005 is really 0.005000000000000000104..., so it rounds up
+        check(0.005, 2);
+        check(0.015, 2);
+        check(2.675, 2);
+        // Exact ties round to even
+        for value in [0.125, 0.375, 0.5, 1.5, 2.5, 3.5, -0.5, -2.5, 1e22 + 0.5] {
+            for precision in 0..4 {
+                check(value, precision);
+            }
+        }
+        // Rounding carries through 9s, into the integer part
+        for value in [0.999, 9.9999, 99.95, 0.0999, 999_999_999.9999999] {
+            for precision in 0..6 {
+                check(value, precision);
+            }
+        }
+    }
+
+    #[test]
+    fn special_values() {
+        for precision in [0, 2] {
+            for value in [0.0, -0.0, -0.001, f64::NAN, -f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+                check(value, precision);
+            }
+        }
+    }
+
+    #[test]
+    fn extremes() {
+        // The integer part takes every chunk
+        check(f64::MAX, 2);
+        check(f64::MIN, 0);
+        check(1e300, 5);
+        check(f64::from(f32::MAX), 3);
+        check(u64::MAX as f64, 1);
+        check(2f64.powi(64) + 2f64.powi(12), 1);
+        // Subnormals, with their entire exact expansion and past it
+        let smallest = f64::from_bits(1);
+        for precision in [0, 1, 323, 324, 325, 1074, 1075, 1100] {
+            check(smallest, precision);
+            check(f64::MIN_POSITIVE, precision);
+            check(f64::from_bits(0x000f_ffff_ffff_ffff), precision);
+        }
+    }
+
+    /// Check that [`significant_digits`] gives the same digits and exponent as `{:.N$e}` in `core::fmt`.
+    fn check_significant(value: f64, count: usize) {
+        let mut digits = [0u8; 20];
+        let exponent = significant_digits(value, &mut digits[..count]);
+
+        let mut expected = [0u8; 40];
+        let mut writer = WriteBuf::new(&mut expected);
+        write!(writer, "{:.*e}", count - 1, value).unwrap();
+        let (mantissa, expected_exponent) = writer.written().split_once('e').unwrap();
+        let expected_digits = mantissa.bytes().filter(|c| *c != b'.');
+        assert!(
+            expected_digits.eq(digits[..count].iter().copied()),
+            "value={:e} count={} digits={:?}",
+            value,
+            count,
+            core::str::from_utf8(&digits[..count])
+        );
+        assert_eq!(expected_exponent.parse::<i32>().unwrap(), exponent, "value={:e}", value);
+    }
+
+    #[test]
+    fn significant_digits_match_fmt() {
+        for (value, count) in [
+            (0.45, 1),
+            (71.55, 3),
+            (2.225, 3),
+            (1e300, 17),
+            (2.5, 1),
+            (9.9996, 4),
+            (999.5, 3),
+        ] {
+            check_significant(value, count);
+        }
+        check_significant(f64::MAX, 17);
+        check_significant(f64::from_bits(1), 17);
+        check_significant(f64::MIN_POSITIVE, 3);
+
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for _ in 0..5000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let count = (state % 17) as usize + 1;
+            let value = f64::from_bits(state >> 1);
+            if value.is_finite() && value > 0.0 {
+                check_significant(value, count);
+            }
+            check_significant((state >> 11) as f64 / (1u64 << 30) as f64 + 1e-9, count);
+        }
+    }
+
+    #[test]
+    fn matches_fmt() {
+        // xorshift, so the same values are checked every run
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let value = f64::from_bits(state);
+            let precision = (state % 24) as usize;
+            check(value, precision);
+            // Values in a more typical range, where rounding matters more
+            let scaled = (state >> 11) as f64 / (1u64 << 40) as f64;
+            check(scaled, precision % 8);
+            check(f64::from(scaled as f32), precision % 8);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use core::{fmt, ops};
+
+/// A set of properties the output of a writer is guaranteed to have, reported by [`crate::WriteBuf::guarantees`].
+///
+/// Generic code which receives a writer can check that the properties it relies on hold before writing anything:
+///
+/// ```
+/// use fmtbuf::{Guarantees, WriteBuf};
+///
+/// let mut buf: [u8; 16] = [0; 16];
+/// let writer = WriteBuf::new(&mut buf);
+/// let required = Guarantees::VALID_UTF8 | Guarantees::CHAR_BOUNDARY;
+/// assert!(
+///     writer.guarantees().contains(required),
+///     "writer is missing {:?}",
+///     writer.guarantees().missing(required)
+/// );
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Guarantees(u32);
+
+impl Guarantees {
+    /// The written output is always valid UTF-8.
+    pub const VALID_UTF8: Self = Self(1 << 0);
+
+    /// Truncation never splits a UTF-8 encoded code point.
+    pub const CHAR_BOUNDARY: Self = Self(1 << 1);
+
+    /// Truncation never splits an extended grapheme cluster.
+    pub const GRAPHEME_BOUNDARY: Self = Self(1 << 2);
+
+    /// The writer limits output by display width rather than just by bytes.
+    pub const WIDTH_ACCOUNTING: Self = Self(1 << 3);
+
+    /// The written output only contains ASCII.
+    pub const ASCII_ONLY: Self = Self(1 << 4);
+
+    const NAMES: [(Self, &'static str); 5] = [
+        (Self::VALID_UTF8, "VALID_UTF8"),
+        (Self::CHAR_BOUNDARY, "CHAR_BOUNDARY"),
+        (Self::GRAPHEME_BOUNDARY, "GRAPHEME_BOUNDARY"),
+        (Self::WIDTH_ACCOUNTING, "WIDTH_ACCOUNTING"),
+        (Self::ASCII_ONLY, "ASCII_ONLY"),
+    ];
+
+    /// Get the set with no guarantees.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Get the raw bit representation of this set.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Check if this set has no guarantees.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check if every guarantee in `other` is also in this set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Get the guarantees in `required` which are not in this set.
+    pub const fn missing(self, required: Self) -> Self {
+        Self(required.0 & !self.0)
+    }
+
+    /// Get the guarantees in either this set or `other`.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Add the guarantees in `other` to this set.
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Remove the guarantees in `other` from this set.
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Add or remove the guarantees in `other` based on `value`.
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+}
+
+impl ops::BitOr for Guarantees {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl ops::BitOrAssign for Guarantees {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.insert(rhs);
+    }
+}
+
+impl ops::BitAnd for Guarantees {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl fmt::Debug for Guarantees {
+    /// Lists the names of the contained guarantees, such as `Guarantees(VALID_UTF8 | CHAR_BOUNDARY)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Guarantees(")?;
+        let mut first = true;
+        for (flag, name) in Self::NAMES.iter() {
+            if self.contains(*flag) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn set_operations() {
+        let mut guarantees = Guarantees::VALID_UTF8 | Guarantees::CHAR_BOUNDARY;
+        assert!(guarantees.contains(Guarantees::VALID_UTF8));
+        assert!(guarantees.contains(Guarantees::empty()));
+        assert!(!guarantees.contains(Guarantees::VALID_UTF8 | Guarantees::ASCII_ONLY));
+        assert_eq!(
+            Guarantees::ASCII_ONLY,
+            guarantees.missing(Guarantees::VALID_UTF8 | Guarantees::ASCII_ONLY)
+        );
+
+        guarantees.remove(Guarantees::VALID_UTF8);
+        assert_eq!(Guarantees::CHAR_BOUNDARY, guarantees);
+        guarantees.set(Guarantees::WIDTH_ACCOUNTING, true);
+        assert_eq!(Guarantees::CHAR_BOUNDARY | Guarantees::WIDTH_ACCOUNTING, guarantees);
+        guarantees.set(Guarantees::WIDTH_ACCOUNTING, false);
+        guarantees.remove(Guarantees::CHAR_BOUNDARY);
+        assert!(guarantees.is_empty());
+    }
+
+    #[test]
+    fn debug_lists_names() {
+        let mut buf: [u8; 64] = [0; 64];
+        let mut writer = crate::WriteBuf::new(&mut buf);
+        write!(writer, "{:?}", Guarantees::VALID_UTF8 | Guarantees::GRAPHEME_BOUNDARY).unwrap();
+        assert_eq!("Guarantees(VALID_UTF8 | GRAPHEME_BOUNDARY)", writer.written());
+    }
+}
@@ -0,0 +1,238 @@
+//! Writing bytes as a hex dump with offsets and an ASCII column, used by [`crate::WriteBuf::write_hex_dump`].
+
+use crate::int::IntFormat;
+
+/// How [`crate::WriteBuf::write_hex_dump`] lays out the lines of a dump. The default is the layout of `hexdump -C`: 16
+/// bytes per line in groups of 8, with the offset in front and the ASCII column after them.
+///
+/// ```
+/// use fmtbuf::{HexDumpOpts, WriteBuf};
+///
+/// let mut buf: [u8; 64] = [0xff; 64];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// let opts = HexDumpOpts::new().bytes_per_line(4).group(2).offset(false);
+/// writer.write_hex_dump(b"fmtbuf", opts).unwrap();
+/// assert_eq!("66 6d  74 62  |fmtb|\n75 66         |uf|\n", writer.written());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HexDumpOpts {
+    bytes_per_line: usize,
+    group: usize,
+    offset: bool,
+    ascii: bool,
+}
+
+impl HexDumpOpts {
+    /// Create options for the layout of `hexdump -C`.
+    pub const fn new() -> Self {
+        Self {
+            bytes_per_line: 16,
+            group: 8,
+            offset: true,
+            ascii: true,
+        }
+    }
+
+    /// Set the number of bytes on each line, which is at least 1.
+    pub const fn bytes_per_line(mut self, bytes_per_line: usize) -> Self {
+        self.bytes_per_line = if bytes_per_line == 0 { 1 } else { bytes_per_line };
+        self
+    }
+
+    /// Put an extra space between each group of `bytes` bytes on a line. A `bytes` of 0 turns grouping off.
+    pub const fn group(mut self, bytes: usize) -> Self {
+        self.group = bytes;
+        self
+    }
+
+    /// Set if each line starts with the offset of its first byte in hexadecimal, with at least 8 digits.
+    pub const fn offset(mut self, offset: bool) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set if each line ends with its bytes as ASCII between `|`s, with anything which is not printable as `.`.
+    pub const fn ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+}
+
+impl Default for HexDumpOpts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const OFFSET_FORMAT: IntFormat = IntFormat::new().min_digits(8);
+
+/// The lines of a hex dump of some bytes.
+pub(crate) struct HexDump<'b> {
+    bytes: &'b [u8],
+    opts: HexDumpOpts,
+}
+
+impl<'b> HexDump<'b> {
+    pub(crate) fn new(bytes: &'b [u8], opts: HexDumpOpts) -> Self {
+        Self { bytes, opts }
+    }
+
+    /// Get the number of lines, which is 0 if there are no bytes.
+    pub(crate) fn lines(&self) -> usize {
+        let len = self.bytes.len();
+        len / self.opts.bytes_per_line + usize::from(len % self.opts.bytes_per_line != 0)
+    }
+
+    /// Get the number of bytes written for all of the lines.
+    pub(crate) fn len(&self) -> usize {
+        (0..self.lines()).fold(0usize, |len, line| len.saturating_add(self.line_len(line)))
+    }
+
+    /// Get the number of bytes written for `line`, including the `\n` at the end of it. This saturates at `usize::MAX`
+    /// for a huge [`HexDumpOpts::bytes_per_line`], which never fits.
+    pub(crate) fn line_len(&self, line: usize) -> usize {
+        let count = self.line_bytes(line).len();
+        let offset = if self.opts.offset {
+            OFFSET_FORMAT.len(self.line_offset(line), 4) + 2
+        } else {
+            0
+        };
+        // The hex column is padded to its full width when the ASCII column comes after it, so that one lines up
+        let hex = if self.opts.ascii {
+            self.hex_len(self.opts.bytes_per_line).saturating_add(count + 4)
+        } else {
+            self.hex_len(count)
+        };
+        hex.saturating_add(offset + 1)
+    }
+
+    /// Pass the text of `line` to `out` in pieces. The pieces add up to [`HexDump::line_len`] bytes.
+    pub(crate) fn render_line(&self, line: usize, mut out: impl FnMut(&[u8])) {
+        if self.opts.offset {
+            OFFSET_FORMAT.render(self.line_offset(line), 4, "", &mut out);
+            out(b"  ");
+        }
+
+        let bytes = self.line_bytes(line);
+        for (idx, byte) in bytes.iter().enumerate() {
+            if idx > 0 {
+                out(if self.starts_group(idx) { b"  " } else { b" " });
+            }
+            out(&[HEX_DIGITS[usize::from(byte >> 4)], HEX_DIGITS[usize::from(byte & 0xf)]]);
+        }
+
+        if self.opts.ascii {
+            let padding = self.hex_len(self.opts.bytes_per_line) - self.hex_len(bytes.len());
+            for _ in 0..padding + 2 {
+                out(b" ");
+            }
+            out(b"|");
+            for byte in bytes {
+                out(if (0x20..0x7f).contains(byte) {
+                    core::slice::from_ref(byte)
+                } else {
+                    b"."
+                });
+            }
+            out(b"|");
+        }
+        out(b"\n");
+    }
+
+    fn line_bytes(&self, line: usize) -> &'b [u8] {
+        let start = line * self.opts.bytes_per_line;
+        let end = self.bytes.len().min(start.saturating_add(self.opts.bytes_per_line));
+        &self.bytes[start..end]
+    }
+
+    fn line_offset(&self, line: usize) -> u64 {
+        (line * self.opts.bytes_per_line) as u64
+    }
+
+    /// Get if the byte at `idx` of a line is the first of a group, other than the first one.
+    fn starts_group(&self, idx: usize) -> bool {
+        self.opts.group > 0 && idx % self.opts.group == 0
+    }
+
+    /// Get the width of the hex column with `count` bytes in it, saturating at `usize::MAX`.
+    fn hex_len(&self, count: usize) -> usize {
+        if count == 0 {
+            return 0;
+        }
+        let groups = (count - 1).checked_div(self.opts.group).unwrap_or(0);
+        count.saturating_mul(3).saturating_add(groups) - 1
+    }
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+    use core::fmt::Write;
+
+    fn dump(bytes: &[u8], opts: HexDumpOpts) -> StackString<512> {
+        let dump = HexDump::new(bytes, opts);
+        let mut out: StackString<512> = StackString::new();
+        for line in 0..dump.lines() {
+            let start = out.as_str().len();
+            dump.render_line(line, |piece| {
+                out.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+            });
+            assert_eq!(dump.line_len(line), out.as_str().len() - start, "line={}", line);
+        }
+        assert_eq!(dump.len(), out.as_str().len());
+        out
+    }
+
+    #[test]
+    fn hexdump_layout() {
+        let expected = "\
+00000000  48 65 6c 6c 6f 20 77 6f  72 6c 64 0a 00 7f ff 61  |Hello world....a|
+00000010  62 63 64 65 66 67 68 69  6a 6b                    |bcdefghijk|
+";
+        assert_eq!(
+            expected,
+            dump(b"Hello world\n\x00\x7f\xffabcdefghijk", HexDumpOpts::default())
+        );
+        assert_eq!("", dump(b"", HexDumpOpts::new()));
+    }
+
+    #[test]
+    fn ascii_column_lines_up_for_every_remainder() {
+        let bytes: [u8; 32] = core::array::from_fn(|idx| b'A' + idx as u8);
+        let full_line = dump(&bytes[..16], HexDumpOpts::new());
+        let column = full_line.as_str().find('|').unwrap();
+        assert_eq!(60, column);
+        for remainder in 1..16 {
+            let text = dump(&bytes[..16 + remainder], HexDumpOpts::new());
+            let last = text.as_str().lines().nth(1).unwrap();
+            assert_eq!(Some(column), last.find('|'), "remainder={}", remainder);
+            assert_eq!(column + remainder + 2, last.len(), "remainder={}", remainder);
+            assert!(last.starts_with("00000010  51 "), "remainder={}", remainder);
+        }
+    }
+
+    #[test]
+    fn options() {
+        let bytes = b"\x01\x02\x03\x04\x05";
+        assert_eq!(
+            "01 02 03 04\n05\n",
+            dump(bytes, HexDumpOpts::new().bytes_per_line(4).offset(false).ascii(false))
+        );
+        assert_eq!(
+            "00000000  01 02 03  04\n00000004  05\n",
+            dump(bytes, HexDumpOpts::new().bytes_per_line(4).group(3).ascii(false))
+        );
+        assert_eq!(
+            "01 02 03 04 05  |.....|\n",
+            dump(bytes, HexDumpOpts::new().bytes_per_line(5).group(0).offset(false))
+        );
+        assert_eq!(
+            "00000000  01  |.|\n00000001  02  |.|\n",
+            dump(&bytes[..2], HexDumpOpts::new().bytes_per_line(0))
+        );
+        assert_eq!(HexDumpOpts::new(), HexDumpOpts::default());
+    }
+}
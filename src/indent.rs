@@ -0,0 +1,173 @@
+use core::fmt::{self, Write};
+
+use crate::{SliceTarget, Target, WriteBuf};
+
+/// Writes to a [`WriteBuf`], starting every line with the current indentation. This is useful for dumping nested
+/// structures, where each level of nesting is indented one more time.
+///
+/// ```
+/// use fmtbuf::{IndentWriter, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 64] = [0xff; 64];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// let mut out = IndentWriter::new(&mut writer);
+/// writeln!(out, "root {{").unwrap();
+/// out.indent();
+/// writeln!(out, "child {{\nid: {}", 7).unwrap();
+/// out.dedent();
+/// write!(out, "}}").unwrap();
+/// assert_eq!("root {\n  child {\n  id: 7\n}", writer.written());
+/// ```
+///
+/// The indentation is written before the first character of each line, so it is never written for an empty line, and
+/// a line which starts in a later write than the `\n` before it is still indented. Indentation is written like any other
+/// content, so it is truncated the same way.
+pub struct IndentWriter<'b, 'a, T = SliceTarget<'a>> {
+    buf: &'b mut WriteBuf<'a, T>,
+    unit: &'b str,
+    level: usize,
+    at_line_start: bool,
+}
+
+impl<'b, 'a, T: Target<'a>> IndentWriter<'b, 'a, T> {
+    /// Create an instance which writes to `buf`, indenting by two spaces per level. It starts at level 0.
+    pub fn new(buf: &'b mut WriteBuf<'a, T>) -> Self {
+        Self::with_unit(buf, "  ")
+    }
+
+    /// Create an instance which writes to `buf`, indenting by `unit` per level. It starts at level 0.
+    pub fn with_unit(buf: &'b mut WriteBuf<'a, T>, unit: &'b str) -> Self {
+        let at_line_start = buf.position() == 0 || buf.ends_with("\n");
+        Self {
+            buf,
+            unit,
+            level: 0,
+            at_line_start,
+        }
+    }
+
+    /// Get the number of units each line is indented by.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Set the number of units each line is indented by, starting with the next line.
+    pub fn set_level(&mut self, level: usize) {
+        self.level = level;
+    }
+
+    /// Indent lines by one more unit, starting with the next line.
+    pub fn indent(&mut self) {
+        self.level = self.level.saturating_add(1);
+    }
+
+    /// Indent lines by one less unit, starting with the next line. This does nothing at level 0.
+    pub fn dedent(&mut self) {
+        self.level = self.level.saturating_sub(1);
+    }
+
+    /// Write the indentation for the current level.
+    fn _write_indent(&mut self) -> fmt::Result {
+        for _ in 0..self.level {
+            self.buf.write_str(self.unit)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for IndentWriter<'b, 'a, T> {
+    /// Write `s`, with the indentation before each line of it which has anything in it.
+    ///
+    /// # Error
+    ///
+    /// If the indentation or `s` did not entirely fit, the same as [`WriteBuf`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for line in s.split_inclusive('\n') {
+            if self.at_line_start && line != "\n" {
+                self._write_indent()?;
+            }
+            self.buf.write_str(line)?;
+            self.at_line_start = line.ends_with('\n');
+        }
+        Ok(())
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Debug for IndentWriter<'b, 'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IndentWriter")
+            .field("buf", &self.buf)
+            .field("unit", &self.unit)
+            .field("level", &self.level)
+            .field("at_line_start", &self.at_line_start)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn indents_lines() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = IndentWriter::with_unit(&mut writer, "->");
+            out.write_str("a\n").unwrap();
+            out.indent();
+            out.indent();
+            assert_eq!(2, out.level());
+            // Empty lines are not indented
+            out.write_str("b\n\n").unwrap();
+            // A newline at the end of one write and the line in the next
+            out.write_str("c\n").unwrap();
+            out.dedent();
+            out.write_str("d").unwrap();
+            // The level only changes where lines start
+            out.set_level(0);
+            out.write_str("e\n\nf").unwrap();
+            out.dedent();
+            assert_eq!(0, out.level());
+        }
+        assert_eq!("a\n->->b\n\n->->c\n->de\n\nf", writer.written());
+    }
+
+    #[test]
+    fn continues_buffer_content() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("x: ").unwrap();
+        {
+            // The buffer does not end with a newline, so the first line is not indented
+            let mut out = IndentWriter::new(&mut writer);
+            out.indent();
+            out.write_str("1\n2").unwrap();
+        }
+        writer.write_str("\n").unwrap();
+        {
+            let mut out = IndentWriter::new(&mut writer);
+            out.indent();
+            out.write_str("3").unwrap();
+        }
+        assert_eq!("x: 1\n  2\n  3", writer.written());
+    }
+
+    #[test]
+    fn truncated_mid_indent() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = IndentWriter::with_unit(&mut writer, "...");
+            out.write_str("ab\n").unwrap();
+            out.indent();
+            out.indent();
+            out.write_str("c\n").unwrap_err();
+            // Truncation latches on the buffer, so nothing more is written
+            out.write_str("\n").unwrap_err();
+        }
+        assert!(writer.truncated());
+        assert_eq!("ab\n.....", writer.written());
+    }
+}
@@ -0,0 +1,460 @@
+/// The most bytes an integer takes in decimal: the 39 digits of `u128::MAX`, or those of `i128::MIN` along with its sign.
+pub(crate) const MAX_DECIMAL_LEN: usize = 40;
+
+/// The two digits of every number from 0 to 99, so two digits are produced with each division.
+static DIGIT_PAIRS: &[u8; 200] = b"\
+    0001020304050607080910111213141516171819\
+    2021222324252627282930313233343536373839\
+    4041424344454647484950515253545556575859\
+    6061626364656667686970717273747576777879\
+    8081828384858687888990919293949596979899";
+
+/// The largest power of 10 which fits in a `u64`, used to split a `u128` into parts which are formatted as `u64`.
+const U64_CHUNK: u64 = 10_000_000_000_000_000_000;
+
+/// The most digits of a part below [`U64_CHUNK`], which every part but the first is padded to.
+const U64_CHUNK_DIGITS: usize = 19;
+
+/// Format `value` in decimal into the end of `scratch`, with a leading `-` if `negative`.
+///
+/// # Returns
+///
+/// The formatted value, which is the end of `scratch`.
+pub(crate) fn decimal_u64(value: u64, negative: bool, scratch: &mut [u8; MAX_DECIMAL_LEN]) -> &[u8] {
+    let start = digits_u64(value, &mut scratch[..]);
+    sign(negative, scratch, start)
+}
+
+/// Format `value` in decimal into the end of `scratch`, with a leading `-` if `negative`. See [`decimal_u64`].
+pub(crate) fn decimal_u128(mut value: u128, negative: bool, scratch: &mut [u8; MAX_DECIMAL_LEN]) -> &[u8] {
+    let mut end = scratch.len();
+    // Dividing a u128 is slow, so only do it to get the value down to something which fits in a u64
+    while value > u128::from(u64::MAX) {
+        let chunk = (value % u128::from(U64_CHUNK)) as u64;
+        value /= u128::from(U64_CHUNK);
+        let chunk_start = end - U64_CHUNK_DIGITS;
+        let start = digits_u64(chunk, &mut scratch[..end]);
+        scratch[chunk_start..start].fill(b'0');
+        end = chunk_start;
+    }
+    let start = digits_u64(value as u64, &mut scratch[..end]);
+    sign(negative, scratch, start)
+}
+
+/// Write the decimal digits of `value` into the end of `out`, returning the index the digits start at.
+pub(crate) fn digits_u64(mut value: u64, out: &mut [u8]) -> usize {
+    let mut start = out.len();
+    while value >= 100 {
+        let pair = (value % 100) as usize * 2;
+        value /= 100;
+        start -= 2;
+        out[start..start + 2].copy_from_slice(&DIGIT_PAIRS[pair..pair + 2]);
+    }
+    if value >= 10 {
+        let pair = value as usize * 2;
+        start -= 2;
+        out[start..start + 2].copy_from_slice(&DIGIT_PAIRS[pair..pair + 2]);
+    } else {
+        start -= 1;
+        out[start] = b'0' + value as u8;
+    }
+    start
+}
+
+/// Put a `-` in front of the digits starting at `start` if `negative`, returning all of the formatted value.
+fn sign(negative: bool, scratch: &mut [u8; MAX_DECIMAL_LEN], mut start: usize) -> &[u8] {
+    if negative {
+        start -= 1;
+        scratch[start] = b'-';
+    }
+    &scratch[start..]
+}
+
+/// How [`crate::WriteBuf::write_hex`], [`crate::WriteBuf::write_oct`] and [`crate::WriteBuf::write_bin`] write a
+/// number. The default is lowercase digits with no prefix, padding or grouping.
+///
+/// ```
+/// use fmtbuf::{IntFormat, WriteBuf};
+///
+/// const REGISTER: IntFormat = IntFormat::new().uppercase(true).prefix(true).min_digits(8).group('_', 4);
+///
+/// let mut buf: [u8; 16] = [0xff; 16];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// writer.write_hex(0xbeef, REGISTER).unwrap();
+/// assert_eq!("0x0000_BEEF", writer.written());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IntFormat {
+    uppercase: bool,
+    prefix: bool,
+    min_digits: usize,
+    separator: char,
+    group: usize,
+}
+
+impl IntFormat {
+    /// Create a format with lowercase digits and no prefix, padding or grouping.
+    pub const fn new() -> Self {
+        Self {
+            uppercase: false,
+            prefix: false,
+            min_digits: 0,
+            separator: '_',
+            group: 0,
+        }
+    }
+
+    /// Set if hexadecimal digits above 9 are written as `A` to `F` instead of `a` to `f`. The prefix is always lowercase.
+    pub const fn uppercase(mut self, uppercase: bool) -> Self {
+        self.uppercase = uppercase;
+        self
+    }
+
+    /// Set if the number starts with `0x`, `0o` or `0b`, like the `#` flag of `core::fmt`.
+    pub const fn prefix(mut self, prefix: bool) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Set the fewest digits written, padding the number with leading zeros to reach it. Neither the prefix nor group
+    /// separators count toward it.
+    pub const fn min_digits(mut self, min_digits: usize) -> Self {
+        self.min_digits = min_digits;
+        self
+    }
+
+    /// Put `separator` between each group of `digits` digits, counting from the last digit, so `0xdeadbeef` written with
+    /// `group('_', 4)` is `0xdead_beef`. Padding zeros are grouped like the other digits. A `digits` of 0 turns
+    /// grouping off.
+    pub const fn group(mut self, separator: char, digits: usize) -> Self {
+        self.separator = separator;
+        self.group = digits;
+        self
+    }
+
+    /// Get the number of bytes `value` takes with `bits` bits per digit, saturating at `usize::MAX`.
+    pub(crate) fn len(&self, value: u64, bits: u32) -> usize {
+        let digits = self.digits(value, bits);
+        let separators = self.separators(digits);
+        let prefix = if self.prefix { RADIX_PREFIX_LEN } else { 0 };
+        digits
+            .saturating_add(separators.saturating_mul(self.separator.len_utf8()))
+            .saturating_add(prefix)
+    }
+
+    /// Format `value` with `bits` bits per digit and `prefix` in front of it, passing the output to `out` in pieces of
+    /// at most [`RADIX_SCRATCH_LEN`] bytes. The pieces add up to [`IntFormat::len`] bytes.
+    pub(crate) fn render(&self, value: u64, bits: u32, prefix: &str, mut out: impl FnMut(&[u8])) {
+        if self.prefix {
+            out(prefix.as_bytes());
+        }
+
+        let digit_chars: &[u8; 16] = if self.uppercase {
+            b"0123456789ABCDEF"
+        } else {
+            b"0123456789abcdef"
+        };
+        let mut separator = [0u8; 4];
+        let separator = self.separator.encode_utf8(&mut separator).as_bytes();
+        let mask = (1u64 << bits) - 1;
+        let mut scratch = [0u8; RADIX_SCRATCH_LEN];
+        let mut used = 0;
+        // Digits are produced from the most significant one, which is digit `power` counting from the last digit as 0
+        for power in (0..self.digits(value, bits)).rev() {
+            // Leave room for a digit and a separator, the longest thing written in one step
+            if used + 1 + separator.len() > scratch.len() {
+                out(&scratch[..used]);
+                used = 0;
+            }
+            let shift = power.saturating_mul(bits as usize);
+            let digit = if shift < 64 { (value >> shift) & mask } else { 0 };
+            scratch[used] = digit_chars[digit as usize];
+            used += 1;
+            if self.group > 0 && power > 0 && power % self.group == 0 {
+                scratch[used..used + separator.len()].copy_from_slice(separator);
+                used += separator.len();
+            }
+        }
+        out(&scratch[..used]);
+    }
+
+    /// Get the number of digits of `value` with `bits` bits per digit, including the padding zeros.
+    fn digits(&self, value: u64, bits: u32) -> usize {
+        let significant = (64 - value.leading_zeros()).max(1);
+        let digits = ((significant + bits - 1) / bits) as usize;
+        digits.max(self.min_digits)
+    }
+
+    /// Get the number of group separators between `digits` digits.
+    fn separators(&self, digits: usize) -> usize {
+        (digits - 1).checked_div(self.group).unwrap_or(0)
+    }
+}
+
+impl Default for IntFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where [`crate::WriteBuf::write_uint_grouped`] puts separators between the digits of a number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Grouping {
+    /// Groups of 3 digits, such as `1,234,567`. This is the default.
+    #[default]
+    Thousands,
+    /// A group of 3 digits at the end and groups of 2 digits before it, such as `12,34,567`, as used in India.
+    Indian,
+}
+
+impl Grouping {
+    /// Get the number of bytes a number of `digits` digits takes with `separator` between the groups.
+    pub(crate) fn len(self, digits: usize, separator: char) -> usize {
+        let separators = (1..digits).filter(|power| self.separates(*power)).count();
+        digits + separators * separator.len_utf8()
+    }
+
+    /// Pass `digits` to `out` with `separator` between the groups and a leading `-` if `negative`. The pieces add up to
+    /// [`Grouping::len`] bytes, plus 1 for the sign.
+    pub(crate) fn render(self, digits: &[u8], negative: bool, separator: char, mut out: impl FnMut(&[u8])) {
+        if negative {
+            out(b"-");
+        }
+        let mut encoded = [0u8; 4];
+        let separator = separator.encode_utf8(&mut encoded).as_bytes();
+        let mut scratch = [0u8; RADIX_SCRATCH_LEN];
+        let mut used = 0;
+        for (idx, digit) in digits.iter().enumerate() {
+            if used + 1 + separator.len() > scratch.len() {
+                out(&scratch[..used]);
+                used = 0;
+            }
+            scratch[used] = *digit;
+            used += 1;
+            if self.separates(digits.len() - 1 - idx) {
+                scratch[used..used + separator.len()].copy_from_slice(separator);
+                used += separator.len();
+            }
+        }
+        out(&scratch[..used]);
+    }
+
+    /// Get if a separator goes after the digit `power` places before the last digit.
+    fn separates(self, power: usize) -> bool {
+        match self {
+            Self::Thousands => power > 0 && power % 3 == 0,
+            Self::Indian => power == 3 || (power > 3 && power % 2 == 1),
+        }
+    }
+}
+
+/// The length of each of the `0x`, `0o` and `0b` prefixes.
+const RADIX_PREFIX_LEN: usize = 2;
+
+/// The size of the stack buffer digits are gathered in by [`IntFormat::render`] and [`Grouping::render`] before they
+/// are passed on.
+const RADIX_SCRATCH_LEN: usize = 32;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+    use core::fmt::Write;
+
+    /// Check that formatting `value` with [`decimal_u128`], and with [`decimal_u64`] if it fits, matches `Display`.
+    fn check(value: i128) {
+        let mut expected: StackString<MAX_DECIMAL_LEN> = StackString::new();
+        write!(expected, "{}", value).unwrap();
+
+        let mut scratch = [0xff; MAX_DECIMAL_LEN];
+        let formatted = decimal_u128(value.unsigned_abs(), value < 0, &mut scratch);
+        assert_eq!(expected.as_str().as_bytes(), formatted, "value={}", value);
+        if value.unsigned_abs() <= u128::from(u64::MAX) {
+            let mut scratch = [0xff; MAX_DECIMAL_LEN];
+            let formatted = decimal_u64(value.unsigned_abs() as u64, value < 0, &mut scratch);
+            assert_eq!(expected.as_str().as_bytes(), formatted, "value={}", value);
+        }
+    }
+
+    #[test]
+    fn decimal_digit_counts() {
+        let mut power: i128 = 1;
+        for _ in 0..38 {
+            for value in [power - 1, power, power + 1, power * 5] {
+                check(value);
+                check(-value);
+            }
+            power *= 10;
+        }
+    }
+
+    #[test]
+    fn decimal_chunks_keep_zeros() {
+        // The low chunk of each of these is all or mostly zeros, which have to be padded back in
+        check(i128::from(U64_CHUNK));
+        check(u64::MAX as i128 + 1);
+        check(10_i128.pow(19));
+        check(10_i128.pow(19) * 7 + 3);
+        check(10_i128.pow(38));
+        check(10_i128.pow(38) + 1);
+        check(i128::MAX);
+        check(i128::MIN + 1);
+    }
+
+    /// Format `value` with [`IntFormat::render`], checking that it is as long as [`IntFormat::len`] says.
+    fn render(value: u64, bits: u32, format: IntFormat) -> StackString<256> {
+        let prefix = match bits {
+            4 => "0x",
+            3 => "0o",
+            _ => "0b",
+        };
+        let mut out: StackString<256> = StackString::new();
+        format.render(value, bits, prefix, |piece| {
+            out.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+        });
+        assert_eq!(
+            format.len(value, bits),
+            out.as_str().len(),
+            "value={} {:?}",
+            value,
+            format
+        );
+        out
+    }
+
+    /// Check that [`IntFormat`] without grouping matches `core::fmt` for `value` in each base.
+    fn check_radix(value: u64) {
+        let mut expected: StackString<256> = StackString::new();
+        for width in [0, 1, 8, 30, 70] {
+            let format = IntFormat::new().min_digits(width);
+            macro_rules! compare {
+                ($bits:expr, $format:expr, $spec:literal) => {
+                    expected.clear();
+                    write!(expected, $spec, value, width = width).unwrap();
+                    assert_eq!(
+                        expected.as_str(),
+                        render(value, $bits, $format).as_str(),
+                        "value={} width={}",
+                        value,
+                        width
+                    );
+                };
+            }
+            compare!(4, format, "{:0width$x}");
+            compare!(4, format.uppercase(true), "{:0width$X}");
+            compare!(3, format, "{:0width$o}");
+            compare!(1, format, "{:0width$b}");
+            // core::fmt counts the prefix in the width, and IntFormat does not
+            compare!(
+                4,
+                format.prefix(true).min_digits(width.saturating_sub(2)),
+                "{:#0width$x}"
+            );
+            compare!(
+                3,
+                format.prefix(true).min_digits(width.saturating_sub(2)),
+                "{:#0width$o}"
+            );
+            compare!(
+                1,
+                format.prefix(true).min_digits(width.saturating_sub(2)),
+                "{:#0width$b}"
+            );
+        }
+    }
+
+    #[test]
+    fn radix_matches_fmt() {
+        check_radix(0);
+        check_radix(1);
+        check_radix(0xbeef);
+        check_radix(u64::from(u32::MAX));
+        check_radix(1 << 63);
+        check_radix(u64::MAX);
+        for shift in 0..64 {
+            check_radix((1 << shift) - 1);
+        }
+    }
+
+    #[test]
+    fn radix_groups() {
+        let register = IntFormat::new().prefix(true).min_digits(8).group('_', 4);
+        assert_eq!("0x0000_beef", render(0xbeef, 4, register));
+        assert_eq!("0x0000_0000", render(0, 4, register));
+        assert_eq!("0xffff_ffff_ffff_ffff", render(u64::MAX, 4, register));
+        assert_eq!("0x1_0000_0000", render(1 << 32, 4, register));
+        assert_eq!(
+            "0o1_777_777_777_777_777_777_777",
+            render(u64::MAX, 3, IntFormat::new().prefix(true).group('_', 3))
+        );
+        assert_eq!("1010_0101", render(0xa5, 1, IntFormat::new().group('_', 4)));
+        // A multi-byte separator, with more separators than fit in the scratch buffer at once
+        let wide = IntFormat::new().group('·', 1);
+        let written = render(u64::MAX, 1, wide);
+        assert_eq!(64 + 63 * '·'.len_utf8(), written.as_str().len());
+        assert!(written.as_str().starts_with("1·1·1"));
+        assert!(written.as_str().ends_with("1·1"));
+        assert_eq!("0", render(0, 1, wide));
+        // No grouping
+        assert_eq!("deadbeef", render(0xdeadbeef, 4, IntFormat::new().group('_', 0)));
+    }
+
+    /// Format `value` with [`Grouping::render`], checking that it is as long as [`Grouping::len`] says.
+    fn grouped(value: i64, separator: char, grouping: Grouping) -> StackString<64> {
+        let mut scratch = [0xff; MAX_DECIMAL_LEN];
+        let digits = decimal_u64(value.unsigned_abs(), false, &mut scratch);
+        let mut out: StackString<64> = StackString::new();
+        grouping.render(digits, value < 0, separator, |piece| {
+            out.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+        });
+        let len = grouping.len(digits.len(), separator) + usize::from(value < 0);
+        assert_eq!(len, out.as_str().len(), "value={} {:?}", value, grouping);
+        out
+    }
+
+    #[test]
+    fn grouping() {
+        let cases: &[(i64, &str, &str)] = &[
+            (0, "0", "0"),
+            (999, "999", "999"),
+            (1000, "1,000", "1,000"),
+            (-1000, "-1,000", "-1,000"),
+            (99_999, "99,999", "99,999"),
+            (100_000, "100,000", "1,00,000"),
+            (1_234_567, "1,234,567", "12,34,567"),
+            (-12_345_678, "-12,345,678", "-1,23,45,678"),
+            (i64::MIN, "-9,223,372,036,854,775,808", "-92,23,37,20,36,85,47,75,808"),
+        ];
+        for (value, thousands, indian) in cases {
+            assert_eq!(*thousands, grouped(*value, ',', Grouping::Thousands));
+            assert_eq!(*indian, grouped(*value, ',', Grouping::Indian));
+        }
+        assert_eq!(Grouping::Thousands, Grouping::default());
+    }
+
+    #[test]
+    fn grouping_multibyte_separator() {
+        // More separators than fit in the scratch buffer at once
+        assert_eq!(
+            "-9\u{2009}223\u{2009}372\u{2009}036\u{2009}854\u{2009}775\u{2009}808",
+            grouped(i64::MIN, '\u{2009}', Grouping::Thousands)
+        );
+        assert_eq!("1🚀000", grouped(1000, '🚀', Grouping::Indian));
+    }
+
+    #[test]
+    fn decimal_extremes() {
+        let mut scratch = [0xff; MAX_DECIMAL_LEN];
+        assert_eq!(
+            b"340282366920938463463374607431768211455",
+            decimal_u128(u128::MAX, false, &mut scratch)
+        );
+        assert_eq!(
+            b"-170141183460469231731687303715884105728",
+            decimal_u128(i128::MIN.unsigned_abs(), true, &mut scratch)
+        );
+        assert_eq!(b"0", decimal_u64(0, false, &mut scratch));
+        assert_eq!(b"18446744073709551615", decimal_u64(u64::MAX, false, &mut scratch));
+    }
+}
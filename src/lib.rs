@@ -10,15 +10,14 @@
 //! if let Err(e) = write!(&mut writer, "🚀🚀🚀") {
 //!     println!("write error: {e:?}");
 //! }
-//! let written_len = match writer.finish_with_or("!", "…") {
-//!     Ok(len) => len, // <- won't be hit since 🚀🚀🚀 is 12 bytes
-//!     Err(len) => {
+//! let written = match writer.finish_with_or_str("!", "…") {
+//!     Ok(written) => written, // <- won't be hit since 🚀🚀🚀 is 12 bytes
+//!     Err(written) => {
 //!         println!("writing was truncated");
-//!         len
+//!         written
 //!     }
 //! };
-//! let written = &buf[..written_len];
-//! assert_eq!("🚀…", std::str::from_utf8(written).unwrap());
+//! assert_eq!("🚀…", written);
 //! ```
 //!
 //! A few things happened in that example:
@@ -33,21 +32,104 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+mod ansi_buf;
+mod balance;
+mod base64;
+mod bidi;
+mod builders;
+mod byte_size;
+mod case;
+mod chain_buf;
+mod counting_buf;
+mod cstr_buf;
+mod duration;
+mod error;
+mod float;
+mod guarantees;
+mod hex_dump;
+mod indent;
+mod int;
+mod line_buf;
+mod line_limit;
+mod net;
+mod policy;
+mod prefix;
+mod rwrite_buf;
+mod si;
+#[cfg(feature = "std")]
+mod spare;
+mod stack_string;
+mod tail_buf;
+mod target;
+mod tee_buf;
+mod timestamp;
 mod utf8;
+mod uuid_style;
+mod wide_buf;
+mod wrap;
 
-use core::fmt;
+use core::{fmt, mem::MaybeUninit, time::Duration};
+
+pub use ansi_buf::AnsiBuf;
+pub use base64::{Base64Config, Base64Writer};
+pub use builders::{DebugList, DebugStruct};
+pub use byte_size::ByteStyle;
+pub use case::CaseWriter;
+pub use chain_buf::ChainBuf;
+pub use counting_buf::{measure, CountingBuf};
+pub use cstr_buf::{CStrBuf, NulPolicy};
+pub use duration::DurationStyle;
+pub use error::{PartialWrite, ReserveError, WriteError};
+pub use guarantees::Guarantees;
+pub use hex_dump::HexDumpOpts;
+pub use indent::IndentWriter;
+pub use int::{Grouping, IntFormat};
+pub use line_buf::LineBuf;
+pub use line_limit::LineLimit;
+pub use policy::{CodePoint, LineBoundary, TruncationPolicy, WordBoundary};
+pub use prefix::PrefixWriter;
+pub use rwrite_buf::RWriteBuf;
+#[cfg(feature = "std")]
+pub use spare::SpareWriteBuf;
+pub use stack_string::StackString;
+pub use tail_buf::TailBuf;
+pub use target::{SliceTarget, Target};
+pub use tee_buf::TeeBuf;
+pub use timestamp::TimestampOpts;
+pub use uuid_style::UuidStyle;
+pub use wide_buf::WideBuf;
+pub use wrap::WrapWriter;
 
 #[deprecated]
 pub use utf8::rfind_utf8_end;
 
+/// Write formatted data into a [`WriteBuf`] completely or not at all, using [`WriteBuf::write_fmt_atomic`].
+///
+/// ```
+/// use fmtbuf::{write_atomic, WriteBuf};
+///
+/// let mut buf: [u8; 8] = [0xff; 8];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// let id = 0x7f3a9c0e_u32;
+/// write_atomic!(writer, "id={id:x}").unwrap_err();
+/// write_atomic!(writer, "id=?").unwrap();
+/// assert_eq!("id=?", writer.written());
+/// ```
+#[macro_export]
+macro_rules! write_atomic {
+    ($dst:expr, $($arg:tt)*) => {
+        $dst.write_fmt_atomic(::core::format_args!($($arg)*))
+    };
+}
+
 /// A write buffer pointing to a `&mut [u8]`.
 ///
 /// ```
 /// use fmtbuf::WriteBuf;
 /// use std::fmt::Write;
 ///
-/// // The buffer to write into. The contents can be uninitialized, but using a
-/// // bogus `\xff` sigil for demonstration.
+/// // The buffer to write into. The contents do not matter, but using a bogus
+/// // `\xff` sigil for demonstration.
 /// let mut buf: [u8; 128] = [0xff; 128];
 /// let mut writer = WriteBuf::new(&mut buf);
 ///
@@ -59,23 +141,46 @@ pub use utf8::rfind_utf8_end;
 /// let written = std::str::from_utf8(&buf[..write_len]).unwrap();
 /// assert_eq!(written, "some data: 420");
 /// ```
-pub struct WriteBuf<'a> {
-    target: &'a mut [u8],
-    position: usize,
+///
+/// The storage is a [`Target`], which is a [`SliceTarget`] for every constructor. All the writing and finishing logic is
+/// generic over it, so new kinds of storage behave the same way.
+pub struct WriteBuf<'a, T = SliceTarget<'a>> {
+    target: T,
+    position: Position,
     reserve: usize,
-    truncated: bool,
+    content_truncated: bool,
+    capacity_truncated: bool,
+    suffix_degraded: bool,
+    segment_start: usize,
+    /// The [`WriteBuf::dropped_bytes`] when the segment at `segment_start` was started.
+    segment_dropped_input_bytes: usize,
+    /// The position and [`WriteBuf::dropped_bytes`] right after a debug builder which dropped some of its entries was
+    /// closed, so a builder it is nested in can tell it was still written whole.
+    partial_close: (usize, usize),
+    dropped_input_bytes: usize,
+    write_calls: usize,
+    atomic_truncates: bool,
+    strict: bool,
+    latch: bool,
+    suffixes: Option<(&'a str, &'a str)>,
+    front: &'a mut [u8],
+    policy: &'a dyn TruncationPolicy,
+    on_truncate: Option<TruncateHook>,
+    truncate_hook_repeats: bool,
 }
 
+/// A function called by [`WriteBuf`] when content is dropped. See [`WriteBuf::on_truncate`].
+pub type TruncateHook = fn(&TruncationEvent);
+
+/// Value of [`WriteBuf::segment_start`] when there is no segment to undo.
+const NO_SEGMENT: usize = usize::MAX;
+
 impl<'a> WriteBuf<'a> {
-    /// Create an instance that will write to the given `target`. The contents of the target do not need to have been
-    /// initialized before this, as they will be overwritten by writing.
+    /// Create an instance that will write to the given `target`. The contents of the target do not matter, as they will
+    /// be overwritten by writing. To write into memory which has not been initialized at all, use
+    /// [`WriteBuf::new_uninit`].
     pub fn new(target: &'a mut [u8]) -> Self {
-        Self {
-            target,
-            position: 0,
-            reserve: 0,
-            truncated: false,
-        }
+        Self::with_reserve(target, 0)
     }
 
     /// Create an instance that will write to the given `target` and `reserve` bytes at the end that will not be written
@@ -85,393 +190,8024 @@ impl<'a> WriteBuf<'a> {
     /// the [`WriteBuf::finish_with`] family of functions), so `write_str` should not bother writing to it. This is
     /// useful when you know that you will always `finish_with` a null terminator or other character.
     ///
-    /// It is allowed to have `target.len() < reserve`, but this can never be written to.
+    /// It is allowed to have `target.len() < reserve`, but this can never be written to: every non-empty write is
+    /// truncated without copying anything and [`WriteBuf::remaining`] is 0. The [`WriteBuf::finish_with`] family of
+    /// functions still place the suffix the same way as for any other truncated buffer.
     pub fn with_reserve(target: &'a mut [u8], reserve: usize) -> Self {
+        Self::_with_target(SliceTarget::new(target), reserve)
+    }
+
+    /// Create an instance that will write to the given `target`, which does not need to be initialized. This avoids
+    /// filling a large buffer before writing to it, such as on a microcontroller with a slow memory bus.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::{fmt::Write, mem::MaybeUninit};
+    ///
+    /// let mut buf = [MaybeUninit::<u8>::uninit(); 64];
+    /// let mut writer = WriteBuf::new_uninit(&mut buf);
+    /// write!(writer, "x={}", 7).unwrap();
+    /// assert_eq!(Ok("x=7!"), writer.finish_with_str("!"));
+    /// ```
+    ///
+    /// Only the written content is ever read, so the returned `&str` of the `finish_str` family of functions is the way
+    /// to get at it. The lengths returned by the other finishing functions are just as accurate, but reading the target
+    /// through them needs `unsafe`. The functions which hand out unwritten parts of the target, such as
+    /// [`WriteBuf::spare_capacity_mut`] and [`WriteBuf::finish_split`], zero them first.
+    pub fn new_uninit(target: &'a mut [MaybeUninit<u8>]) -> Self {
+        Self::with_reserve_uninit(target, 0)
+    }
+
+    /// Create an instance that will write to the given `target`, which does not need to be initialized, with `reserve`
+    /// bytes at the end. See [`WriteBuf::new_uninit`] and [`WriteBuf::with_reserve`].
+    pub fn with_reserve_uninit(target: &'a mut [MaybeUninit<u8>], reserve: usize) -> Self {
+        Self::_with_target(SliceTarget::new_uninit(target), reserve)
+    }
+
+    /// Create an instance that will write to the spare capacity of `vec`, which is added to it by
+    /// [`SpareWriteBuf::commit`]. The `vec` never grows, so this is useful for formatting without allocating, such as on
+    /// a real-time thread: [`Vec::reserve`] ahead of time, then anything past the spare capacity is truncated.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut out = Vec::with_capacity(64);
+    /// out.extend_from_slice(b"\x01");
+    /// let mut writer = WriteBuf::from_vec_spare(&mut out);
+    /// write!(writer, "code={}", 404).unwrap();
+    /// assert_eq!(Ok(8), writer.commit());
+    /// assert_eq!(b"\x01code=404", &out[..]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn from_vec_spare(vec: &'a mut Vec<u8>) -> SpareWriteBuf<'a> {
+        // safety: A `Vec<u8>` has no requirements on its content
+        unsafe { SpareWriteBuf::new(vec) }
+    }
+
+    /// Create an instance that will write to the spare capacity of `s`, which is added to it by
+    /// [`SpareWriteBuf::commit`]. See [`WriteBuf::from_vec_spare`].
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut line = String::with_capacity(64);
+    /// line.push_str("status: ");
+    /// let mut writer = WriteBuf::from_string_spare(&mut line);
+    /// write!(writer, "{}", "ok").unwrap();
+    /// writer.commit().unwrap();
+    /// assert_eq!("status: ok", line);
+    /// ```
+    ///
+    /// Committing only appends the written content, which is always complete UTF-8 sequences, so `s` stays valid even
+    /// when writing was truncated or a suffix rolled back content.
+    #[cfg(feature = "std")]
+    pub fn from_string_spare(s: &'a mut String) -> SpareWriteBuf<'a> {
+        // safety: The content of a `String` is valid UTF-8
+        unsafe { SpareWriteBuf::new(s.as_mut_vec()) }
+    }
+
+    fn _with_target(target: SliceTarget<'a>, reserve: usize) -> Self {
         Self {
             target,
-            position: 0,
+            position: Position::new(0),
             reserve,
-            truncated: false,
+            content_truncated: false,
+            capacity_truncated: false,
+            suffix_degraded: false,
+            segment_start: NO_SEGMENT,
+            segment_dropped_input_bytes: 0,
+            partial_close: (NO_SEGMENT, 0),
+            dropped_input_bytes: 0,
+            write_calls: 0,
+            atomic_truncates: false,
+            strict: false,
+            latch: true,
+            suffixes: None,
+            front: &mut [],
+            policy: &CodePoint,
+            on_truncate: None,
+            truncate_hook_repeats: false,
         }
     }
 
-    /// Get the position in the target buffer. The value is one past the end of written content and the next position to
-    /// be written to.
-    pub fn position(&self) -> usize {
-        self.position
+    /// Create an instance that will write to the given `target`, reserving enough bytes at the end for the longer of
+    /// `normal_suffix` and `truncated_suffix`. The suffixes are stored, so [`WriteBuf::finish_reserved`] can apply them
+    /// without repeating them, and the reserve can not get out of sync with the suffixes it is for.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::with_reserve_for(&mut buf, "", "…");
+    /// assert_eq!(3, writer.reserve());
+    /// write!(writer, "{}", "#".repeat(20)).unwrap_err();
+    /// assert_eq!(Err(12), writer.finish_reserved());
+    /// assert_eq!("#########…", std::str::from_utf8(&buf).unwrap());
+    /// ```
+    ///
+    /// Finishing with other suffixes is still possible, but in debug builds, those longer than the reserve cause a
+    /// panic, since they can not be placed without rolling back content.
+    pub fn with_reserve_for(target: &'a mut [u8], normal_suffix: &'a str, truncated_suffix: &'a str) -> Self {
+        let mut out = Self::with_reserve(target, normal_suffix.len().max(truncated_suffix.len()));
+        out.suffixes = Some((normal_suffix, truncated_suffix));
+        out
     }
 
-    /// Get if a truncated write has happened.
-    pub fn truncated(&self) -> bool {
-        self.truncated
+    /// Create an instance that will write to the given `target`, holding back the first `reserve` bytes for a header
+    /// which is filled in by [`WriteBuf::finish_with_header`] once the body has been written. The held back bytes are
+    /// zeroed.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::with_front_reserve(&mut buf, 2);
+    /// write!(writer, "hello {}", "world").unwrap();
+    /// assert_eq!("hello world", writer.written());
+    /// let len = writer
+    ///     .finish_with_header(|header, body_len| header.copy_from_slice(&(body_len as u16).to_be_bytes()))
+    ///     .unwrap();
+    /// assert_eq!(b"\x00\x0bhello world", &buf[..len]);
+    /// ```
+    ///
+    /// All of the other functions only see the body, which is the part of `target` after the front reserve: the
+    /// [`WriteBuf::position`] and [`WriteBuf::written`] content start at the body, so an index of 0 is the first byte
+    /// after the front reserve. The exception is the length returned when finishing, which includes the front reserve,
+    /// so `&target[..len]` is the entire output. If `reserve` is at least `target.len()`, there is no room for a body,
+    /// so the buffer starts out truncated.
+    pub fn with_front_reserve(target: &'a mut [u8], reserve: usize) -> Self {
+        let leaves_no_body = reserve > 0 && reserve >= target.len();
+        let (front, body) = target.split_at_mut(reserve.min(target.len()));
+        front.fill(0);
+        let mut out = Self::new(body);
+        out.front = front;
+        if leaves_no_body {
+            out.content_truncated = true;
+            out.capacity_truncated = true;
+        }
+        out
     }
 
-    /// Get the count of reserved bytes.
-    pub fn reserve(&self) -> usize {
-        self.reserve
+    /// Create a strict instance that will write to the given `target`. A strict writer never emits partial content:
+    /// a write which does not entirely fit writes nothing and marks the buffer as truncated, and the same goes for all
+    /// of the pieces of a single `write!` call. The [`WriteBuf::finish_with`] family of functions never roll back written
+    /// content to make room for the suffix.
+    ///
+    /// This is how the two modes differ on the example from the crate documentation:
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 10] = [0; 10];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(&mut writer, "🚀🚀🚀").unwrap_err();
+    /// assert_eq!(Err("🚀…"), writer.finish_with_or_str("!", "…"));
+    ///
+    /// let mut writer = WriteBuf::new_strict(&mut buf);
+    /// write!(&mut writer, "🚀🚀🚀").unwrap_err();
+    /// assert_eq!(Err("…"), writer.finish_with_or_str("!", "…"));
+    /// ```
+    ///
+    /// If the suffix does not fit after the written content, a strict writer leaves it off, so the result is the
+    /// untouched [`WriteBuf::position`] in the `Err` case. The other finishing functions follow the same rule: they
+    /// place what they would add only if it fits after the written content.
+    pub fn new_strict(target: &'a mut [u8]) -> Self {
+        Self::with_reserve_strict(target, 0)
     }
 
-    /// Set the reserve bytes to `count`. If the written section has already encroached on the reserve space, this has
-    /// no immediate effect, but it will prevent future writes. If [`WriteBuf::truncated`] has already been triggered,
-    /// it will not be reset.
-    pub fn set_reserve(&mut self, count: usize) {
-        self.reserve = count;
+    /// Create a strict instance that will write to the given `target`, with `reserve` bytes at the end. See
+    /// [`WriteBuf::new_strict`] and [`WriteBuf::with_reserve`].
+    pub fn with_reserve_strict(target: &'a mut [u8], reserve: usize) -> Self {
+        let mut out = Self::with_reserve(target, reserve);
+        out.strict = true;
+        out
     }
 
-    /// Get the contents that have been written so far.
-    pub fn written_bytes(&self) -> &[u8] {
-        &self.target[..self.position]
+    /// Create an instance that will write to the given `target`, cutting content which does not fit where `policy`
+    /// says to instead of at the last complete code point.
+    ///
+    /// ```
+    /// use fmtbuf::{LineBoundary, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+    /// write!(writer, "ok\nretrying request").unwrap_err();
+    /// assert_eq!(Err("ok\n[...]"), writer.finish_with_str("[...]"));
+    /// ```
+    ///
+    /// See [`TruncationPolicy`] for where the policy is used.
+    pub fn with_policy(target: &'a mut [u8], policy: &'a dyn TruncationPolicy) -> Self {
+        let mut out = Self::new(target);
+        out.policy = policy;
+        out
     }
 
-    /// Get the contents that have been written so far.
-    pub fn written(&self) -> &str {
-        #[cfg(debug_assertions)]
-        return core::str::from_utf8(self.written_bytes()).expect("contents of buffer should have been UTF-8 encoded");
+    /// Create an instance that will write to the given `target`, reserving the last byte for the NUL terminator added by
+    /// [`WriteBuf::finish_cstr`].
+    pub fn new_cstr(target: &'a mut [u8]) -> Self {
+        Self::with_reserve(target, 1)
+    }
 
-        // safety: The only way to write into the buffer is with valid UTF-8, so there is no reason to check the
-        // contents for validity. They're still checked in debug builds just in case, though.
-        #[cfg(not(debug_assertions))]
-        unsafe {
-            core::str::from_utf8_unchecked(self.written_bytes())
-        }
+    /// Create an instance that will write to the `len` bytes at `ptr`. This is for buffers which come from C, so the
+    /// safety reasoning for turning them into a slice is in one place instead of at every call site.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::{ffi::c_char, fmt::Write};
+    ///
+    /// /// Fill `buf` with a NUL-terminated status message and store its length, without the terminator, in `out_len`.
+    /// /// Returns `false` if the message was truncated.
+    /// #[no_mangle]
+    /// pub unsafe extern "C" fn mylib_status(buf: *mut c_char, buf_len: usize, out_len: *mut usize) -> bool {
+    ///     let mut writer = unsafe { WriteBuf::from_raw_parts_with_reserve(buf as *mut u8, buf_len, 1) };
+    ///     let _ = write!(writer, "{} connections open", 3);
+    ///     let (len, fit) = match writer.finish_with("\0") {
+    ///         Ok(len) => (len, true),
+    ///         Err(len) => (len, false),
+    ///     };
+    ///     unsafe { *out_len = len.saturating_sub(1) };
+    ///     fit
+    /// }
+    ///
+    /// let mut buf: [c_char; 32] = [0; 32];
+    /// let mut len = 0;
+    /// assert!(unsafe { mylib_status(buf.as_mut_ptr(), buf.len(), &mut len) });
+    /// let message = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    /// assert_eq!(b"3 connections open", message.to_bytes());
+    /// assert_eq!(18, len);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Unless `len` is 0 (when `ptr` may be null or dangling), every requirement of [`core::slice::from_raw_parts_mut`]
+    /// applies:
+    ///
+    /// * `ptr` must be non-null and valid for reads and writes of `len` bytes, all within a single allocation, and the
+    ///   bytes must be initialized. Their values do not matter, so zeroing an uninitialized C buffer is enough.
+    /// * Nothing else may read or write the `len` bytes at `ptr` while the returned writer exists, since it has
+    ///   exclusive access to them.
+    /// * The bytes must stay valid for the lifetime `'a` chosen by the caller. This is not checked, so take care that
+    ///   the returned writer does not outlive the buffer, such as by not letting it escape the C callback.
+    /// * `len` must be no larger than `isize::MAX`.
+    pub unsafe fn from_raw_parts(ptr: *mut u8, len: usize) -> Self {
+        Self::from_raw_parts_with_reserve(ptr, len, 0)
     }
 
-    /// Finish writing to the buffer. This returns control of the target buffer to the caller (it is no longer mutably
-    /// borrowed) and returns the number of bytes written.
+    /// Create an instance that will write to the `len` bytes at `ptr`, with `reserve` bytes at the end. See
+    /// [`WriteBuf::from_raw_parts`] and [`WriteBuf::with_reserve`].
     ///
-    /// # Returns
+    /// # Safety
     ///
-    /// In both the `Ok` and `Err` cases, the [`WriteBuf::position`] is returned. The `Ok` case indicates the truncation
-    /// did not occur, while `Err` indicates that it did.
-    pub fn finish(self) -> Result<usize, usize> {
-        if self.truncated() {
-            Err(self.position())
+    /// The same as [`WriteBuf::from_raw_parts`].
+    pub unsafe fn from_raw_parts_with_reserve(ptr: *mut u8, len: usize, reserve: usize) -> Self {
+        let target: &'a mut [u8] = if len == 0 {
+            &mut []
         } else {
-            Ok(self.position())
+            // safety: The caller has promised that `ptr` is valid for `len` initialized bytes, not aliased for `'a`
+            unsafe { core::slice::from_raw_parts_mut(ptr, len) }
+        };
+        Self::with_reserve(target, reserve)
+    }
+
+    /// Split this writer into two independent writers over `target[..idx]` and `target[idx..]`. This is useful for
+    /// formatting a fixed-size region, such as a header, separately from the content which follows it.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 16] = [b' '; 16];
+    /// let (mut header, mut body) = WriteBuf::new(&mut buf).split_at(4).ok().unwrap();
+    /// write!(body, "hello").unwrap();
+    /// let body_len = body.finish().unwrap();
+    /// write!(header, "{body_len:03}:").unwrap();
+    /// header.finish().unwrap();
+    /// assert_eq!(b"005:hello", &buf[..4 + body_len]);
+    /// ```
+    ///
+    /// The first writer keeps any content written so far, along with its truncation state. The second writer starts
+    /// empty and takes over the [`WriteBuf::reserve`], since the reserve is at the end of the buffer; the first writer
+    /// has no reserve.
+    ///
+    /// # Errors
+    ///
+    /// If `idx` is before [`WriteBuf::position`] (splitting would cut written content) or past the end of the target,
+    /// this writer is returned unchanged.
+    // Giving the writer back unchanged is the point of the `Err` case, and boxing it is not an option without `std`
+    #[allow(clippy::result_large_err)]
+    pub fn split_at(self, idx: usize) -> Result<(WriteBuf<'a>, WriteBuf<'a>), WriteBuf<'a>> {
+        if idx < self.position() || idx > self.capacity() {
+            return Err(self);
         }
+
+        let (front, back) = self.target.split_at(idx);
+        let front = WriteBuf {
+            target: front,
+            position: self.position,
+            reserve: 0,
+            content_truncated: self.content_truncated,
+            capacity_truncated: self.capacity_truncated,
+            suffix_degraded: self.suffix_degraded,
+            segment_start: self.segment_start,
+            segment_dropped_input_bytes: self.segment_dropped_input_bytes,
+            partial_close: self.partial_close,
+            dropped_input_bytes: self.dropped_input_bytes,
+            write_calls: self.write_calls,
+            atomic_truncates: self.atomic_truncates,
+            strict: self.strict,
+            latch: self.latch,
+            suffixes: None,
+            front: self.front,
+            policy: self.policy,
+            on_truncate: self.on_truncate,
+            truncate_hook_repeats: self.truncate_hook_repeats,
+        };
+        let mut back = WriteBuf::_with_target(back, self.reserve);
+        back.suffixes = self.suffixes;
+        back.policy = self.policy;
+        back.on_truncate = self.on_truncate;
+        back.truncate_hook_repeats = self.truncate_hook_repeats;
+        Ok((front, back))
     }
+}
 
-    /// Finish the buffer, adding the `suffix` to the end. A common use case for this is to add a null terminator.
+impl<'a, T: Target<'a>> WriteBuf<'a, T> {
+    /// Lend this writer out as a [`WriteBufRef`], which can be passed by value to helper functions. Everything done
+    /// through the returned value is done to this writer, so it can continue to be used once the borrow ends.
     ///
-    /// This operates slightly differently than the normal format writing function `write_str` in that the `suffix` is
-    /// always put at the end. The only case where this will not happen is when `suffix.len()` is less than the size of
-    /// the buffer originally provided. In this case, the last bit of `suffix` will be copied (starting at a valid UTF-8
-    /// sequence start; e.g.: writing `"🚀..."` to a 5 byte buffer will leave you with just `"..."`, no matter what was
-    /// written before).
+    /// ```
+    /// use fmtbuf::{WriteBuf, WriteBufRef};
+    /// use std::fmt::{self, Write};
+    ///
+    /// fn write_header(mut buf: WriteBufRef) -> fmt::Result {
+    ///     write!(buf, "v{}:", 1)
+    /// }
+    ///
+    /// fn write_body(mut buf: WriteBufRef) -> fmt::Result {
+    ///     buf.write_str("body")
+    /// }
+    ///
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write_header(writer.reborrow()).unwrap();
+    /// write_body(writer.reborrow()).unwrap();
+    /// assert_eq!(Ok(7), writer.finish());
+    /// assert_eq!(b"v1:body", &buf[..7]);
+    /// ```
+    pub fn reborrow(&mut self) -> WriteBufRef<'_, 'a, T> {
+        WriteBufRef(self)
+    }
+
+    /// Borrow this writer as a [`Lossy`] writer, whose [`fmt::Write`] implementation never returns `Err`. Truncation is
+    /// still recorded on this writer, so it can be checked once with [`WriteBuf::truncated`] when all the writing is
+    /// done, instead of at every write site.
     ///
     /// ```
     /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
     ///
-    /// let mut buf: [u8; 4] = [0xff; 4];
+    /// let mut buf: [u8; 8] = [0xff; 8];
     /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer.lossy(), "{}", "much too long").unwrap();
+    /// write!(writer.lossy(), "{}", "!").unwrap();
+    /// assert!(writer.truncated());
+    /// assert_eq!(Err(8), writer.finish());
+    /// assert_eq!(b"much too", &buf);
+    /// ```
+    pub fn lossy(&mut self) -> Lossy<'_, 'a, T> {
+        Lossy(self)
+    }
+
+    /// Borrow this writer as a [`SubWriteBuf`], which writes to this buffer but uses at most `budget` more bytes of it.
+    /// This keeps code which is handed the sub-writer from using up the entire buffer.
     ///
-    /// // Finish writing with too many bytes:
-    /// let write_len = writer.finish_with("12345").unwrap_err();
-    /// assert_eq!(write_len, 4);
-    /// let buf_str = std::str::from_utf8(&buf).unwrap();
-    /// assert_eq!(buf_str, "2345");
     /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
     ///
-    /// # Returns
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_str("[").unwrap();
+    /// {
+    ///     let mut sub = writer.sub_writer(4);
+    ///     write!(sub, "{}", "too long").unwrap_err();
+    ///     assert!(sub.truncated());
+    /// }
+    /// writer.write_str("]").unwrap();
+    /// assert_eq!("[too ]", writer.written());
+    /// assert!(!writer.truncated());
+    /// ```
     ///
-    /// The returned value has the same meaning as [`WriteBuf::finish`].
-    pub fn finish_with(self, suffix: impl AsRef<[u8]>) -> Result<usize, usize> {
-        let suffix = suffix.as_ref();
-        self._finish_with(suffix, suffix)
+    /// The sub-writer tracks if it was truncated on its own. Hitting `budget` does not mark this buffer as truncated,
+    /// but running out of room in this buffer (before its reserve) does, in the same way as writing to it directly.
+    pub fn sub_writer(&mut self, budget: usize) -> SubWriteBuf<'_, 'a, T> {
+        self._start_segment();
+        let start = self.position();
+        let available = self.remaining();
+        SubWriteBuf {
+            end: start + budget.min(available),
+            available,
+            start,
+            truncated: false,
+            buf: self,
+        }
     }
 
-    /// Finish the buffer by adding `normal_suffix` if not truncated or `truncated_suffix` if the buffer will be
-    /// truncated. This operates the same as [`WriteBuf::finish_with`] in every other way.
-    pub fn finish_with_or(
-        self,
-        normal_suffix: impl AsRef<[u8]>,
-        truncated_suffix: impl AsRef<[u8]>,
-    ) -> Result<usize, usize> {
-        self._finish_with(normal_suffix.as_ref(), truncated_suffix.as_ref())
+    /// Get a pointer to the start of the target buffer. Together with [`WriteBuf::capacity`], this describes the same
+    /// memory given to [`WriteBuf::from_raw_parts`] (after the front reserve, see [`WriteBuf::with_front_reserve`]).
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.target.as_mut_ptr()
     }
 
-    fn _finish_with(mut self, normal: &[u8], truncated: &[u8]) -> Result<usize, usize> {
-        let remaining = self.target.len() - self.position();
+    /// Get the length of the target buffer, including the reserve (but not the front reserve).
+    pub fn capacity(&self) -> usize {
+        self.target.capacity()
+    }
 
-        // If the truncated case is shorter than the normal case, then writing it might still work
-        for (suffix, should_test) in [(normal, !self.truncated), (truncated, true)] {
-            if !should_test {
-                continue;
-            }
+    /// Get the position in the target buffer. The value is one past the end of written content and the next position to
+    /// be written to.
+    pub fn position(&self) -> usize {
+        self.position.get()
+    }
 
-            // enough room in the buffer to write entire suffix, so just write it
-            if suffix.len() <= remaining {
-                self.target[self.position..self.position + suffix.len()].copy_from_slice(suffix);
-                self.position += suffix.len();
-                return if self.truncated() {
-                    Err(self.position())
-                } else {
-                    Ok(self.position())
-                };
-            }
+    /// Get if a truncated write has happened. This is true if either [`WriteBuf::content_truncated`] or
+    /// [`WriteBuf::suffix_degraded`] is.
+    pub fn truncated(&self) -> bool {
+        self.content_truncated || self.suffix_degraded
+    }
 
-            // we attempted to perform a write, but rejected it
-            self.truncated = true;
-        }
+    /// Get if content passed to `write_str` did not fit into the buffer. This indicates some portion of the formatted
+    /// message was lost.
+    pub fn content_truncated(&self) -> bool {
+        self.content_truncated
+    }
 
-        let suffix = truncated;
+    /// Get if the [`WriteBuf::finish_with`] family of functions could not place the requested suffix after the written
+    /// content. This happens when the normal suffix was rejected in favor of the truncated one, when written content
+    /// had to be rolled back to make room for the suffix, or when the suffix itself had to be cut. If the content was
+    /// not otherwise truncated, this usually indicates the reserve is too small for the suffix.
+    pub fn suffix_degraded(&self) -> bool {
+        self.suffix_degraded
+    }
 
-        // if the suffix is larger than the entire target buffer, copy the last N
-        if self.target.len() < suffix.len() {
-            let copyable_suffix = &suffix[suffix.len() - self.target.len()..];
-            let Some(valid_utf8_idx) = copyable_suffix
-                .iter()
-                .enumerate()
-                .find(|(_, cu)| utf8::utf8_char_width(**cu).is_some())
-                .map(|(idx, _)| idx)
-            else {
-                return Err(0);
-            };
-            let copyable_suffix = &copyable_suffix[valid_utf8_idx..];
-            self.target[..copyable_suffix.len()].copy_from_slice(copyable_suffix);
-            return Err(copyable_suffix.len());
-        }
+    /// Get the number of bytes passed to `write_str` which did not fit. This includes the entire length of writes which
+    /// were rejected because the buffer was already truncated.
+    pub fn dropped_bytes(&self) -> usize {
+        self.dropped_input_bytes
+    }
 
-        // Scan backwards to find the position we should write to (can't interrupt a UTF-8 multibyte sequence)
-        let potential_end_idx = self.target.len() - suffix.len();
-        let write_idx = rfind_utf8_end(&self.target[..potential_end_idx]);
-        self.target[write_idx..write_idx + suffix.len()].copy_from_slice(suffix);
-        Err(write_idx + suffix.len())
+    /// Get the length the written content would have if the target buffer were large enough to fit all of it, like the
+    /// return value of C's `snprintf`. This makes it possible to retry with a large enough buffer without formatting
+    /// twice just to measure:
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// fn render(target: &mut [u8]) -> Result<usize, usize> {
+    ///     let mut writer = WriteBuf::with_reserve(target, 1);
+    ///     let _ = write!(writer, "{}: {}", "status", "disconnected");
+    ///     if writer.truncated() {
+    ///         return Err(writer.required_len() + writer.reserve());
+    ///     }
+    ///     writer.finish_with("\n")
+    /// }
+    ///
+    /// let mut buf = vec![0u8; 8];
+    /// let required = render(&mut buf).unwrap_err();
+    /// assert_eq!(21, required);
+    /// buf.resize(required, 0);
+    /// assert_eq!(Ok(21), render(&mut buf));
+    /// assert_eq!(b"status: disconnected\n", &buf[..]);
+    /// ```
+    ///
+    /// Content which was removed after it was written, such as by [`WriteBuf::rollback_to`], is not counted, but
+    /// bytes dropped by the writes before the removal still are, so the result is an upper bound in that case.
+    pub fn required_len(&self) -> usize {
+        self.position().saturating_add(self.dropped_input_bytes)
     }
 
-    fn _write(&mut self, input: &[u8]) -> fmt::Result {
-        if self.truncated() {
-            return Err(fmt::Error);
-        }
+    /// Get the set of properties the output of this writer is guaranteed to have. These are the same for every writer,
+    /// whatever its [`TruncationPolicy`], latching and reserve are, since every cut is moved back to a `char` boundary
+    /// before it is made.
+    pub fn guarantees(&self) -> Guarantees {
+        Guarantees::VALID_UTF8 | Guarantees::CHAR_BOUNDARY
+    }
 
-        let remaining = self.target.len() - self.position();
-        if remaining < self.reserve() {
-            self.truncated = true;
-            return Err(fmt::Error);
-        }
-        let remaining = remaining - self.reserve();
+    /// Get if this writer was created in strict mode. See [`WriteBuf::new_strict`].
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
 
-        let (input, result) = if remaining >= input.len() {
-            (input, Ok(()))
-        } else {
-            let to_write = &input[..remaining];
-            self.truncated = true;
-            (&input[..rfind_utf8_end(to_write)], Err(fmt::Error))
-        };
+    /// Set if truncation latches. By default, once a write is truncated, all later writes fail without being attempted.
+    /// With latching off, later writes still get to use whatever space remains, which is useful for collecting
+    /// independent pieces like "first error: … last error: …".
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.set_latch(false);
+    /// let (first, last) = ("🚀🚀🚀", "ok");
+    /// // "🚀🚀🚀" is cut to "🚀🚀", but the 3 bytes left over still fit "|ok"
+    /// write!(writer, "<{}|{}", first, last).unwrap_err();
+    /// assert!(writer.truncated());
+    /// assert_eq!(Err(12), writer.finish());
+    /// assert_eq!("<🚀🚀|ok", std::str::from_utf8(&buf).unwrap());
+    /// ```
+    ///
+    /// [`WriteBuf::truncated`] still reports that something was cut, so the [`WriteBuf::finish_with_or`] family of
+    /// functions still use the truncated suffix. A single `write!` is a series of `write_str` calls, one for each piece
+    /// of the format, and without latching, every piece is attempted: a short piece can land after a longer one before
+    /// it was cut, and the `write!` returns `Err` if any of its pieces were cut. Every piece is still cut at a `char`
+    /// boundary, so the content is always valid UTF-8.
+    pub fn set_latch(&mut self, latch: bool) {
+        self.latch = latch;
+    }
 
-        self.target[self.position..self.position + input.len()].copy_from_slice(input);
-        self.position += input.len();
+    /// Get if truncation latches. See [`WriteBuf::set_latch`].
+    pub fn is_latching(&self) -> bool {
+        self.latch
+    }
 
-        result
+    /// Set where content which does not fit is cut, for later writes and finishing. See [`WriteBuf::with_policy`].
+    pub fn set_policy(&mut self, policy: &'a dyn TruncationPolicy) {
+        self.policy = policy;
     }
-}
 
-impl<'a> fmt::Write for WriteBuf<'a> {
-    /// Append `s` to the target buffer.
+    /// Call `hook` when content is dropped, such as to count truncated log records without checking
+    /// [`WriteBuf::truncated`] after every write. By default, it is only called when the buffer goes from not truncated
+    /// to truncated; see [`WriteBuf::set_truncate_hook_repeats`] to call it every time.
     ///
-    /// # Error
+    /// ```
+    /// use fmtbuf::{TruncationEvent, TruncationSource, WriteBuf};
+    /// use std::fmt::Write;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
     ///
-    /// An error is returned if the entirety of `s` can not fit in the target buffer or if a previous `write_str`
-    /// operation failed. If this occurs, as much as `s` that can fit into the buffer will be written up to the last
-    /// valid Unicode code point. In other words, if the target buffer have 6 writable bytes left and `s` is the two
-    /// code points `"♡🐶"` (a.k.a.: the 7 byte `b"\xe2\x99\xa1\xf0\x9f\x90\xb6"`), then only `♡` will make it to the
-    /// output buffer, making the target of your ♡ ambiguous.
+    /// static TRUNCATED_WRITES: AtomicUsize = AtomicUsize::new(0);
     ///
-    /// Truncation marks this buffer as truncated, which can be observed with [`WriteBuf::truncated`]. Future write
-    /// attempts will immediately return in `Err`. This also affects the behavior of [`WriteBuf::finish`] family of
-    /// functions, which will always return the `Err` case to indicate truncation. For [`WriteBuf::finish_with_or`],
-    /// the `normal_suffix` will not be attempted.
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self._write(s.as_bytes())
+    /// fn count(event: &TruncationEvent) {
+    ///     if event.source == TruncationSource::Write {
+    ///         TRUNCATED_WRITES.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// }
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.on_truncate(count);
+    /// write!(writer, "{}", "a long message").unwrap_err();
+    /// write!(writer, "{}", "more").unwrap_err();
+    /// assert_eq!(1, TRUNCATED_WRITES.load(Ordering::Relaxed));
+    /// ```
+    ///
+    /// The hook gets a [`TruncationEvent`] describing what did not fit, and not the buffer itself, so it can not change
+    /// the buffer in the middle of a write. It is free to format into other buffers.
+    pub fn on_truncate(&mut self, hook: TruncateHook) {
+        self.on_truncate = Some(hook);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use core::fmt::Write;
+    /// Stop calling the hook set by [`WriteBuf::on_truncate`].
+    pub fn clear_on_truncate(&mut self) {
+        self.on_truncate = None;
+    }
 
-    /// * `.0`: Input string
-    /// * `.1`: The end position if the last byte was chopped off
-    static TEST_CASES: &[(&str, usize)] = &[
-        ("", 0),
-        ("James", 4),
-        ("_ø", 1),
-        ("磨", 0),
-        ("here: 见/見", 10),
-        ("𨉟呐㗂越", 10),
-        ("🚀", 0),
-        ("🚀🚀🚀", 8),
-        ("rocket: 🚀", 8),
-    ];
+    /// Set if the hook set by [`WriteBuf::on_truncate`] is called every time content is dropped, instead of only when
+    /// the buffer goes from not truncated to truncated. This matters when truncation does not latch, since later
+    /// writes are still attempted, and for the `finish_with` family of functions, which can drop the normal suffix and
+    /// then have to roll back content for the truncated suffix.
+    pub fn set_truncate_hook_repeats(&mut self, repeats: bool) {
+        self.truncate_hook_repeats = repeats;
+    }
 
-    #[test]
+    /// Get if the hook set by [`WriteBuf::on_truncate`] is called every time content is dropped. See
+    /// [`WriteBuf::set_truncate_hook_repeats`].
+    pub fn is_truncate_hook_repeating(&self) -> bool {
+        self.truncate_hook_repeats
+    }
+
+    /// Get if this buffer rejects writes because it was truncated and truncation latches.
+    fn _latched(&self) -> bool {
+        self.latch && self.truncated()
+    }
+
+    /// Get if a write since `checkpoint` was taken truncated.
+    fn _truncated_since(&self, checkpoint: &Checkpoint) -> bool {
+        let was_truncated = checkpoint.content_truncated || checkpoint.suffix_degraded;
+        self.truncated() && (!was_truncated || self.dropped_input_bytes != checkpoint.dropped_input_bytes)
+    }
+
+    /// Get the count of reserved bytes.
+    pub fn reserve(&self) -> usize {
+        self.reserve
+    }
+
+    /// Set the reserve bytes to `count`. If the written section has already encroached on the reserve space, this has
+    /// no immediate effect, but it will prevent future writes. If [`WriteBuf::truncated`] has already been triggered,
+    /// it will not be reset.
+    pub fn set_reserve(&mut self, count: usize) {
+        self.reserve = count;
+    }
+
+    /// Set the reserve bytes to `count` if the reserve can still be honored, which is when the unwritten part of the
+    /// target buffer has at least `count` bytes.
+    ///
+    /// # Errors
+    ///
+    /// If written content already encroaches on `count` bytes at the end of the target buffer, the reserve is not
+    /// changed and the returned [`ReserveError`] says how much space is left.
+    pub fn try_set_reserve(&mut self, count: usize) -> Result<(), ReserveError> {
+        let available = self._unwritten_len();
+        if count > available {
+            return Err(ReserveError {
+                requested: count,
+                available,
+            });
+        }
+        self.reserve = count;
+        Ok(())
+    }
+
+    /// Reduce the reserve by `count` bytes, making them available to writes. If every write which truncated the content
+    /// would have fit without the reserve, the content is no longer considered truncated, so writes can continue.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::with_reserve(&mut buf, 5);
+    /// writer.write_str("id=").unwrap();
+    /// writer.write_str("42").unwrap_err();
+    /// assert!(writer.truncated());
+    ///
+    /// // The suffix turned out to be shorter than planned, so give the space back and try again
+    /// writer.release_reserve(4);
+    /// assert!(!writer.truncated());
+    /// writer.write_str("42").unwrap();
+    /// assert_eq!(Ok(6), writer.finish_with("\0"));
+    /// ```
+    ///
+    /// The bytes which were cut by the reserve are not restored, so as the example shows, the cut write usually needs
+    /// to be repeated. They are still counted by [`WriteBuf::dropped_bytes`].
+    pub fn release_reserve(&mut self, count: usize) {
+        self.reserve = self.reserve.saturating_sub(count);
+        if !self.capacity_truncated {
+            self.content_truncated = false;
+        }
+    }
+
+    /// Check if writing `len` more bytes would fit without truncating. This accounts for the reserve and for an already
+    /// truncated buffer rejecting writes (see [`WriteBuf::set_latch`]), so when this returns `true`, a `write_str` of
+    /// `len` bytes is guaranteed to succeed.
+    pub fn fits(&self, len: usize) -> bool {
+        len == 0 || (!self._latched() && len <= self.remaining())
+    }
+
+    /// Check if writing `s` would fit without truncating. See [`WriteBuf::fits`].
+    pub fn fits_str(&self, s: &str) -> bool {
+        self.fits(s.len())
+    }
+
+    /// Check if formatting `args` would fit without truncating. This formats `args` with [`measure`] to get its length
+    /// without touching the target buffer, so it is formatted again when it is written. See [`WriteBuf::fits`].
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let id = 0x7f3a9c0e_u32;
+    /// if writer.would_fit(format_args!("id={id:x}")) {
+    ///     write!(writer, "id={id:x}").unwrap();
+    /// } else {
+    ///     write!(writer, "id=?").unwrap();
+    /// }
+    /// assert_eq!("id=?", writer.written());
+    /// ```
+    pub fn would_fit(&self, args: fmt::Arguments<'_>) -> bool {
+        self.fits(measure(args))
+    }
+
+    /// Write `s` only if all of it fits. This is useful for optional content which should be skipped entirely instead
+    /// of being cut.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_str("id=7").unwrap();
+    /// assert!(!writer.write_str_if_fits(" trace=0123456789"));
+    /// assert!(writer.write_str_if_fits(" ok"));
+    /// assert_eq!(Ok(7), writer.finish());
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// If `s` was written. If it was not, nothing is written and [`WriteBuf::truncated`] is not set.
+    pub fn write_str_if_fits(&mut self, s: &str) -> bool {
+        if !self.fits_str(s) {
+            return false;
+        }
+        self._start_segment();
+        self._write(s.as_bytes()).is_ok()
+    }
+
+    /// Write `char`s from `iter` until the next one does not fit, and report how many were written. A `char` which does
+    /// not fit is left in `iter`, so the same iterator can be resumed into another buffer, which makes this useful for
+    /// splitting long content into chunks.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut chars = "ab🚀cdef".chars().peekable();
+    ///
+    /// let mut buf: [u8; 6] = [0xff; 6];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let result = writer.write_iter(&mut chars);
+    /// assert_eq!((3, false), (result.consumed, result.exhausted));
+    /// assert_eq!("ab🚀", writer.written());
+    ///
+    /// let mut buf: [u8; 6] = [0xff; 6];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let result = writer.write_iter(&mut chars);
+    /// assert_eq!((4, true), (result.consumed, result.exhausted));
+    /// assert_eq!("cdef", writer.written());
+    /// ```
+    ///
+    /// The iterator is taken as a [`core::iter::Peekable`] so the `char` which did not fit can be looked at without
+    /// being taken from it.
+    ///
+    /// Like [`WriteBuf::write_str_if_fits`], stopping early does not set [`WriteBuf::truncated`], since the rest of
+    /// the content is still in `iter`. If the buffer is already truncated and truncation latches, nothing is written.
+    pub fn write_iter<I>(&mut self, iter: &mut core::iter::Peekable<I>) -> WriteIterResult
+    where
+        I: Iterator<Item = char>,
+    {
+        self._start_segment();
+        let mut consumed = 0;
+        while let Some(&c) = iter.peek() {
+            let len = c.len_utf8();
+            if !self.fits(len) {
+                return WriteIterResult {
+                    consumed,
+                    exhausted: false,
+                };
+            }
+            self.write_calls = self.write_calls.saturating_add(1);
+            self._write_char_unchecked(c);
+            iter.next();
+            consumed += 1;
+        }
+        WriteIterResult {
+            consumed,
+            exhausted: true,
+        }
+    }
+
+    /// Write `&str`s from `iter` until the next one does not entirely fit, and report how many were written. This has
+    /// the same semantics as [`WriteBuf::write_iter`]: an item is either written whole or left in `iter`.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut words = "one two three".split(' ').peekable();
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let result = writer.write_iter_str(&mut words);
+    /// assert_eq!((2, false), (result.consumed, result.exhausted));
+    /// assert_eq!("onetwo", writer.written());
+    /// assert_eq!(Some(&"three"), words.peek());
+    /// ```
+    pub fn write_iter_str<'s, I>(&mut self, iter: &mut core::iter::Peekable<I>) -> WriteIterResult
+    where
+        I: Iterator<Item = &'s str>,
+    {
+        self._start_segment();
+        let mut consumed = 0;
+        while let Some(&s) = iter.peek() {
+            if !self.fits_str(s) {
+                return WriteIterResult {
+                    consumed,
+                    exhausted: false,
+                };
+            }
+            // This can not fail, since it fits
+            let _ = self._write(s.as_bytes());
+            iter.next();
+            consumed += 1;
+        }
+        WriteIterResult {
+            consumed,
+            exhausted: true,
+        }
+    }
+
+    /// Write as much of `s` as fits, like [`fmt::Write::write_str`], and report how much of it was written. This makes
+    /// it possible to continue the rest of `s` somewhere else, such as in another buffer.
+    ///
+    /// ```
+    /// use fmtbuf::{PartialWrite, WriteBuf};
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let s = "status: ok";
+    /// let partial = writer.write_str_partial(s).unwrap_err();
+    /// assert_eq!(PartialWrite { written: 8, required: 10 }, partial);
+    /// assert_eq!("ok", &s[partial.written..]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If `s` does not entirely fit, the returned [`PartialWrite`] has the number of bytes of `s` which were written,
+    /// which always ends on a `char` boundary. This truncates the buffer exactly as `write_str` does, so if it is
+    /// already truncated and truncation latches, nothing is written.
+    pub fn write_str_partial(&mut self, s: &str) -> Result<usize, PartialWrite> {
+        self._start_segment();
+        self._write_partial(s.as_bytes())
+            .map(|_| s.len())
+            .map_err(|written| PartialWrite {
+                written,
+                required: s.len(),
+            })
+    }
+
+    /// Write all of `s` or nothing at all. This is useful for tokens like identifiers or numbers, where writing part of
+    /// the value would be misleading.
+    ///
+    /// ```
+    /// use fmtbuf::{WriteBuf, WriteError};
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_str_atomic("id=").unwrap();
+    /// assert_eq!(
+    ///     Err(WriteError::Capacity { needed: 16, available: 9 }),
+    ///     writer.write_str_atomic("7f3a9c0e11d24b6a"),
+    /// );
+    /// writer.write_str_atomic("none").unwrap();
+    /// assert_eq!(Ok(7), writer.finish());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If `s` does not fit, nothing is written and [`WriteError::Capacity`] is returned, or [`WriteError::ReserveBlocked`]
+    /// if `s` would only fit by writing into the reserve. This does not mark the buffer as [`WriteBuf::truncated`]
+    /// unless enabled by [`WriteBuf::set_atomic_truncates`], since no content was cut. If the buffer is already
+    /// truncated and truncation latches, this returns [`WriteError::AlreadyTruncated`].
+    pub fn write_str_atomic(&mut self, s: &str) -> Result<(), WriteError> {
+        if s.is_empty() {
+            return Ok(());
+        }
+        if self._latched() {
+            return Err(WriteError::AlreadyTruncated);
+        }
+        if s.len() > self.remaining() {
+            let error = self._capacity_error(s.len());
+            if self.atomic_truncates {
+                self._mark_truncated(s.len());
+            }
+            return Err(error);
+        }
+
+        self._start_segment();
+        self._write(s.as_bytes()).map_err(|_| WriteError::AlreadyTruncated)
+    }
+
+    /// Format `args` into the buffer completely or not at all, so a formatted value is never cut in the middle. This is
+    /// usually used through the [`write_atomic!`] macro. See [`WriteBuf::write_str_atomic`].
+    ///
+    /// `args` is formatted twice: once to measure its length, without touching the target buffer, and then, only if it
+    /// fits, again to write it. A value whose output changes between the two passes is the caller's problem: if the
+    /// second pass does not fit after all, everything it wrote is rolled back and the write is rejected.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`WriteBuf::write_str_atomic`], where the needed length is the measured length of `args`. If a
+    /// `Display` implementation in `args` returns `Err`, nothing is written and [`WriteError::InvalidInput`] is returned.
+    pub fn write_fmt_atomic(&mut self, args: fmt::Arguments<'_>) -> Result<(), WriteError> {
+        if self._latched() {
+            return Err(WriteError::AlreadyTruncated);
+        }
+        let mut counter = CountingBuf::new();
+        if fmt::write(&mut counter, args).is_err() {
+            return Err(WriteError::InvalidInput);
+        }
+        let needed = counter.len();
+        if needed > self.remaining() {
+            let error = self._capacity_error(needed);
+            if self.atomic_truncates {
+                self._mark_truncated(needed);
+            }
+            return Err(error);
+        }
+
+        let checkpoint = self.checkpoint();
+        self._start_segment();
+        if fmt::write(&mut Segment(self), args).is_ok() && !self._truncated_since(&checkpoint) {
+            return Ok(());
+        }
+
+        // The output changed since it was measured, so report how much the second pass needed
+        let needed =
+            self.position() - checkpoint.position() + (self.dropped_input_bytes - checkpoint.dropped_input_bytes);
+        // Rolling back to a checkpoint from this writer can not fail
+        let _ = self.rollback_to(checkpoint);
+        Err(self._capacity_error(needed))
+    }
+
+    /// Write the `items` with `sep` between them, as in `a, b, c`. Each item is written whole or not at all: if an item
+    /// does not fit, it is rolled back along with the separator before it, the buffer is marked as truncated and the
+    /// rest of `items` is not taken, so a dangling separator is never left behind.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// assert_eq!(Ok(3), writer.write_separated([1, 22, 333, 4444], ", "));
+    /// assert_eq!("1, 22, 333", writer.written());
+    /// assert!(writer.truncated());
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The number of items which were written. If this is less than the number of items, the buffer is
+    /// [`WriteBuf::truncated`].
+    ///
+    /// # Errors
+    ///
+    /// [`WriteError::AlreadyTruncated`] if the buffer is already truncated and truncation latches.
+    pub fn write_separated<I, D>(&mut self, items: I, sep: &str) -> Result<usize, WriteError>
+    where
+        I: IntoIterator<Item = D>,
+        D: fmt::Display,
+    {
+        if self._latched() {
+            return Err(WriteError::AlreadyTruncated);
+        }
+
+        self._start_segment();
+        let mut written = 0;
+        for item in items {
+            let checkpoint = self.checkpoint();
+            if written > 0 {
+                let _ = self._write(sep.as_bytes());
+            }
+            let _ = fmt::write(&mut Segment(self), format_args!("{}", item));
+            if self._truncated_since(&checkpoint) {
+                self._drop_content_after(checkpoint.position);
+                break;
+            }
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Write the `items` like [`WriteBuf::write_separated`], surrounded by `open` and `close`. Room for `close` is
+    /// kept while writing the items, so it is still written when they are truncated.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// assert_eq!(Ok(2), writer.write_list(["one", "two", "three"], "[", ", ", "]"));
+    /// assert_eq!("[one, two]", writer.written());
+    /// assert!(writer.truncated());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`WriteError::AlreadyTruncated`] if the buffer is already truncated and truncation latches. If `open` and `close`
+    /// do not fit together, nothing is written, the buffer is marked as truncated and the error is the same as
+    /// [`WriteBuf::write_str_atomic`] would give for them.
+    pub fn write_list<I, D>(&mut self, items: I, open: &str, sep: &str, close: &str) -> Result<usize, WriteError>
+    where
+        I: IntoIterator<Item = D>,
+        D: fmt::Display,
+    {
+        if self._latched() {
+            return Err(WriteError::AlreadyTruncated);
+        }
+        let brackets = open.len() + close.len();
+        if !self.fits(brackets) {
+            let error = self._capacity_error(brackets);
+            self._mark_truncated(brackets);
+            self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(brackets);
+            return Err(error);
+        }
+
+        // The brackets fit, so `open` can be written normally
+        let _ = self._write(open.as_bytes());
+        self.reserve += close.len();
+        let result = self.write_separated(items, sep);
+        self.reserve = self.reserve.saturating_sub(close.len());
+
+        self._write_reserved(close);
+        result
+    }
+
+    /// Format `value` with at most `max_bytes` of its output, independent of the room left in the buffer. If the output
+    /// of `value` is longer than that, it is cut at a `char` boundary and `marker` is written after it.
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 64] = [0xff; 64];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let user = "a very long user name";
+    /// writer.write_str("user=").unwrap();
+    /// writer.write_display_truncated(&user, 6, "...").unwrap();
+    /// writer.write_str(" ok").unwrap();
+    /// assert_eq!("user=a very... ok", writer.written());
+    /// assert!(!writer.truncated());
+    /// ```
+    ///
+    /// The marker is not counted in `max_bytes`. Formatting stops once `max_bytes` is reached, so a `value` with a lot
+    /// of output is not formatted entirely.
+    ///
+    /// # Errors
+    ///
+    /// Only hitting `max_bytes` is not an error and does not mark the buffer as truncated. If the buffer runs out of
+    /// room, either while writing the value or the marker, this behaves the same as `write!`.
+    pub fn write_display_truncated(
+        &mut self,
+        value: &impl fmt::Display,
+        max_bytes: usize,
+        marker: &str,
+    ) -> fmt::Result {
+        let start = self.position();
+        self._start_segment();
+        let dropped_input_bytes = self.dropped_input_bytes;
+        let mut capped = Capped {
+            buf: self,
+            left: max_bytes,
+            exceeded: false,
+        };
+        let mut result = fmt::write(&mut capped, format_args!("{}", value));
+        if capped.exceeded {
+            result = fmt::Write::write_str(&mut Segment(self), marker);
+        }
+        if self.dropped_input_bytes != dropped_input_bytes {
+            result = Err(fmt::Error);
+        }
+        if result.is_err() && self.strict {
+            self.position.set(start);
+        }
+        result
+    }
+
+    /// Write `value` in decimal. This does not go through `core::fmt`, so it is much faster than `write!` and does not
+    /// pull the formatting machinery into the binary.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_u32(4_000_000_000).unwrap();
+    /// writer.write_u32(123).unwrap_err();
+    /// assert_eq!("4000000000", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A number cut short reads as a different number, so the number is written entirely or not at all. If it does not
+    /// fit before the reserve, nothing is written and the buffer is marked as truncated, the same as a `char` which does
+    /// not fit in [`fmt::Write::write_char`].
+    pub fn write_u32(&mut self, value: u32) -> fmt::Result {
+        self._write_decimal(u64::from(value), false)
+    }
+
+    /// Write `value` in decimal, with a leading `-` if it is negative. See [`WriteBuf::write_u32`].
+    pub fn write_i32(&mut self, value: i32) -> fmt::Result {
+        self._write_decimal(u64::from(value.unsigned_abs()), value < 0)
+    }
+
+    /// Write `value` in decimal. See [`WriteBuf::write_u32`].
+    pub fn write_u8(&mut self, value: u8) -> fmt::Result {
+        self._write_decimal(u64::from(value), false)
+    }
+
+    /// Write `value` in decimal. See [`WriteBuf::write_u32`].
+    pub fn write_u16(&mut self, value: u16) -> fmt::Result {
+        self._write_decimal(u64::from(value), false)
+    }
+
+    /// Write `value` in decimal. See [`WriteBuf::write_u32`].
+    pub fn write_u64(&mut self, value: u64) -> fmt::Result {
+        self._write_decimal(value, false)
+    }
+
+    /// Write `value` in decimal. See [`WriteBuf::write_u32`].
+    pub fn write_u128(&mut self, value: u128) -> fmt::Result {
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        let formatted = int::decimal_u128(value, false, &mut scratch);
+        self._write_whole(formatted)
+    }
+
+    /// Write `value` in decimal. See [`WriteBuf::write_u32`].
+    pub fn write_usize(&mut self, value: usize) -> fmt::Result {
+        self._write_decimal(value as u64, false)
+    }
+
+    /// Write `value` in decimal, with a leading `-` if it is negative. See [`WriteBuf::write_u32`].
+    pub fn write_i8(&mut self, value: i8) -> fmt::Result {
+        self._write_decimal(u64::from(value.unsigned_abs()), value < 0)
+    }
+
+    /// Write `value` in decimal, with a leading `-` if it is negative. See [`WriteBuf::write_u32`].
+    pub fn write_i16(&mut self, value: i16) -> fmt::Result {
+        self._write_decimal(u64::from(value.unsigned_abs()), value < 0)
+    }
+
+    /// Write `value` in decimal, with a leading `-` if it is negative. See [`WriteBuf::write_u32`].
+    pub fn write_i64(&mut self, value: i64) -> fmt::Result {
+        self._write_decimal(value.unsigned_abs(), value < 0)
+    }
+
+    /// Write `value` in decimal, with a leading `-` if it is negative. See [`WriteBuf::write_u32`].
+    pub fn write_i128(&mut self, value: i128) -> fmt::Result {
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        let formatted = int::decimal_u128(value.unsigned_abs(), value < 0, &mut scratch);
+        self._write_whole(formatted)
+    }
+
+    /// Write `value` in decimal, with a leading `-` if it is negative. See [`WriteBuf::write_u32`].
+    pub fn write_isize(&mut self, value: isize) -> fmt::Result {
+        self._write_decimal(value.unsigned_abs() as u64, value < 0)
+    }
+
+    /// Write `value` in decimal with `separator` between groups of digits, to make large numbers easier to read.
+    ///
+    /// ```
+    /// use fmtbuf::{Grouping, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_uint_grouped(1234567, ',', Grouping::Thousands).unwrap();
+    /// writer.write_str(" / ").unwrap();
+    /// writer.write_uint_grouped(1234567, ',', Grouping::Indian).unwrap();
+    /// assert_eq!("1,234,567 / 12,34,567", writer.written());
+    /// ```
+    ///
+    /// `separator` can be any `char`, such as a thin space (U+2009), which takes 3 bytes each time it is written.
+    ///
+    /// # Errors
+    ///
+    /// The number is written entirely or not at all, including the separators. If it does not fit before the reserve,
+    /// nothing is written and the buffer is marked as truncated.
+    pub fn write_uint_grouped(&mut self, value: u64, separator: char, grouping: Grouping) -> fmt::Result {
+        self._write_grouped(value, false, separator, grouping)
+    }
+
+    /// Write `value` in decimal with `separator` between groups of digits, with a leading `-` if it is negative. See
+    /// [`WriteBuf::write_uint_grouped`].
+    pub fn write_int_grouped(&mut self, value: i64, separator: char, grouping: Grouping) -> fmt::Result {
+        self._write_grouped(value.unsigned_abs(), value < 0, separator, grouping)
+    }
+
+    /// Write `value` in hexadecimal as described by `format`, such as for a register dump. Like
+    /// [`WriteBuf::write_u32`], this does not go through `core::fmt`.
+    ///
+    /// ```
+    /// use fmtbuf::{IntFormat, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_hex(0xc0ffee, IntFormat::new().prefix(true)).unwrap();
+    /// writer.write_str(" ").unwrap();
+    /// writer.write_hex(0x1f, IntFormat::new().uppercase(true).min_digits(4)).unwrap();
+    /// assert_eq!("0xc0ffee 001F", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The number is written entirely or not at all, including the prefix, padding and separators. If it does not fit
+    /// before the reserve, nothing is written and the buffer is marked as truncated.
+    pub fn write_hex(&mut self, value: u64, format: IntFormat) -> fmt::Result {
+        self._write_radix(value, 4, "0x", format)
+    }
+
+    /// Write `value` in octal as described by `format`, with a `0o` prefix if it has one. See [`WriteBuf::write_hex`].
+    pub fn write_oct(&mut self, value: u64, format: IntFormat) -> fmt::Result {
+        self._write_radix(value, 3, "0o", format)
+    }
+
+    /// Write `value` in binary as described by `format`, with a `0b` prefix if it has one. See [`WriteBuf::write_hex`].
+    pub fn write_bin(&mut self, value: u64, format: IntFormat) -> fmt::Result {
+        self._write_radix(value, 1, "0b", format)
+    }
+
+    /// Write `value` in decimal with exactly `precision` digits after the decimal point, the same as
+    /// `write!(buf, "{:.precision$}", value)`. This does not go through `core::fmt`, which keeps its float formatting out
+    /// of the binary.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_f64(2.675, 2).unwrap();
+    /// writer.write_char(' ').unwrap();
+    /// writer.write_f64(0.125, 2).unwrap();
+    /// writer.write_char(' ').unwrap();
+    /// writer.write_f64(-1e-9, 3).unwrap();
+    /// assert_eq!("2.67 0.12 -0.000", writer.written());
+    /// ```
+    ///
+    /// The digits come from the exact value of `value`, so `2.675`, which is really `2.67499999…`, rounds down. An exact
+    /// tie, such as `0.125`, rounds to an even digit. The sign of a negative number is kept even if it rounds to zero.
+    /// NaN is written as `NaN`, and the infinities as `inf` and `-inf`.
+    ///
+    /// # Errors
+    ///
+    /// The number is written entirely or not at all. If it does not fit before the reserve, nothing is written and the
+    /// buffer is marked as truncated.
+    pub fn write_f64(&mut self, value: f64, precision: usize) -> fmt::Result {
+        let fixed = float::Fixed::new(value, precision);
+        self._write_rendered(fixed.len(), |out| fixed.render(out))
+    }
+
+    /// Write `value` in decimal with exactly `precision` digits after the decimal point. See [`WriteBuf::write_f64`].
+    pub fn write_f32(&mut self, value: f32, precision: usize) -> fmt::Result {
+        self.write_f64(f64::from(value), precision)
+    }
+
+    /// Write `value` with `sig_figs` significant figures and an SI prefix from pico to tera, followed by `unit`. The
+    /// prefix is picked so the number before it is at least 1 and below 1000.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_si(12_345.0, "Hz", 3).unwrap();
+    /// writer.write_str(", ").unwrap();
+    /// writer.write_si(0.0047e-3, "A", 2).unwrap();
+    /// writer.write_str(", ").unwrap();
+    /// writer.write_si(999.96, "V", 3).unwrap();
+    /// assert_eq!("12.3 kHz, 4.7 µA, 1.00 kV", writer.written());
+    /// ```
+    ///
+    /// The exact value is rounded to `sig_figs` once, the same as `{:e}` rounds it, and this happens before the prefix
+    /// is picked, so a value which rounds up to 1000 is written with the next prefix. A value outside of the range of the prefixes is written with an exponent instead, such as `1.00e15 Hz`.
+    /// `sig_figs` is clamped to between 1 and 17, the most an `f64` has. The micro prefix `µ` takes 2 bytes; see
+    /// [`WriteBuf::write_si_ascii`] to write it as `u`. NaN is written as `NaN`, and the infinities as `inf` and
+    /// `-inf`, followed by `unit`.
+    ///
+    /// # Errors
+    ///
+    /// The value is written entirely or not at all, including the unit. If it does not fit before the reserve, nothing
+    /// is written and the buffer is marked as truncated.
+    pub fn write_si(&mut self, value: f64, unit: &str, sig_figs: usize) -> fmt::Result {
+        let si = si::Si::new(value, sig_figs, false);
+        self._write_rendered(si.len(unit), |out| si.render(unit, out))
+    }
+
+    /// Write `value` with an SI prefix like [`WriteBuf::write_si`], but with the micro prefix written as `u`, so the
+    /// output is ASCII as long as `unit` is.
+    pub fn write_si_ascii(&mut self, value: f64, unit: &str, sig_figs: usize) -> fmt::Result {
+        let si = si::Si::new(value, sig_figs, true);
+        self._write_rendered(si.len(unit), |out| si.render(unit, out))
+    }
+
+    /// Write a number of `bytes` in the largest unit it is at least 1 of, such as `1.4 MiB`, for status lines and
+    /// summaries. See [`ByteStyle`] for the units and the digits after the decimal point.
+    ///
+    /// ```
+    /// use fmtbuf::{ByteStyle, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_bytes_human(512, ByteStyle::BINARY).unwrap();
+    /// writer.write_str(", ").unwrap();
+    /// writer.write_bytes_human(1_048_535, ByteStyle::BINARY).unwrap();
+    /// assert_eq!("512 B, 1.0 MiB", writer.written());
+    /// ```
+    ///
+    /// The number is rounded before the unit is picked, so `1_048_535`, which is 1023.96 KiB, is written as `1.0 MiB`
+    /// instead of `1024.0 KiB`. A tie rounds to an even digit.
+    ///
+    /// # Errors
+    ///
+    /// The number is written entirely or not at all, including the unit. If it does not fit before the reserve, nothing
+    /// is written and the buffer is marked as truncated.
+    pub fn write_bytes_human(&mut self, bytes: u64, style: ByteStyle) -> fmt::Result {
+        let size = byte_size::ByteSize::new(bytes, style);
+        self._write_rendered(size.len(), |out| size.render(out))
+    }
+
+    /// Write a [`Duration`] for logs and status lines, such as `1h23m45s` or `782ms`. See [`DurationStyle`] for the
+    /// styles. Only integers are used, so this is as cheap on a target without floating point as anywhere else.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use fmtbuf::{DurationStyle, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 48] = [0xff; 48];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_duration(Duration::from_secs(5025), DurationStyle::Compact).unwrap();
+    /// writer.write_str(", ").unwrap();
+    /// writer.write_duration(Duration::from_millis(3250), DurationStyle::Compact).unwrap();
+    /// writer.write_str(", ").unwrap();
+    /// writer.write_duration(Duration::from_millis(5_025_678), DurationStyle::Clock).unwrap();
+    /// assert_eq!("1h23m45s, 3.25s, 01:23:45.678", writer.written());
+    /// ```
+    ///
+    /// Digits below what the style shows are cut instead of rounded, the way a clock shows time, so a duration is never
+    /// written as longer than it is.
+    ///
+    /// # Errors
+    ///
+    /// The duration is written entirely or not at all, including the units. If it does not fit before the reserve,
+    /// nothing is written and the buffer is marked as truncated.
+    pub fn write_duration(&mut self, duration: Duration, style: DurationStyle) -> fmt::Result {
+        self._write_whole(duration::DurationText::new(duration, style).as_bytes())
+    }
+
+    /// Write a duration of `ticks` of a clock which ticks `hz` times a second, like [`WriteBuf::write_duration`]. This is
+    /// for tick counters, such as those of an RTOS, which do not have to be turned into a [`Duration`] first. Clocks
+    /// which do not divide a second evenly, such as 32768 Hz, are written as exactly as the style allows.
+    ///
+    /// ```
+    /// use fmtbuf::{DurationStyle, WriteBuf};
+    ///
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_duration_ticks(1500, 1000, DurationStyle::Compact).unwrap();
+    /// assert_eq!("1.5s", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Like [`WriteBuf::write_duration`], nothing is written if the duration does not fit.
+    ///
+    /// # Panics
+    ///
+    /// If `hz` is 0.
+    pub fn write_duration_ticks(&mut self, ticks: u64, hz: u32, style: DurationStyle) -> fmt::Result {
+        assert!(hz != 0, "a clock must tick at least once a second");
+        self._write_whole(duration::DurationText::from_ticks(ticks, hz, style).as_bytes())
+    }
+
+    /// Write a Unix timestamp as an RFC 3339 date and time, such as `2024-05-17T13:45:12.345Z`, for log lines. See
+    /// [`TimestampOpts`] for the digits of the seconds, the UTC offset and the basic format.
+    ///
+    /// ```
+    /// use fmtbuf::{TimestampOpts, WriteBuf};
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_rfc3339(1_715_953_512, 345_678_901, TimestampOpts::new()).unwrap();
+    /// assert_eq!("2024-05-17T13:45:12.345Z", writer.written());
+    /// ```
+    ///
+    /// Dates are in the proleptic Gregorian calendar, so every `unix_secs` has one, including those before 1970 and after
+    /// 2038. Leap seconds are not counted, like in Unix time itself. A `subsec_nanos` above 999,999,999 is treated as
+    /// 999,999,999.
+    ///
+    /// # Errors
+    ///
+    /// The timestamp is written entirely or not at all, since half of one misleads anything which parses the output. If
+    /// it does not fit before the reserve, nothing is written and the buffer is marked as truncated.
+    pub fn write_rfc3339(&mut self, unix_secs: i64, subsec_nanos: u32, opts: TimestampOpts) -> fmt::Result {
+        let timestamp = timestamp::Timestamp::new(unix_secs, subsec_nanos, opts);
+        self._write_rendered(timestamp.len(), |out| timestamp.render(out))
+    }
+
+    /// Write an IPv4 address in dotted decimal, such as `192.0.2.1`, without going through `core::fmt`.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_ipv4([192, 0, 2, 1]).unwrap();
+    /// assert_eq!("192.0.2.1", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The address is written entirely or not at all, like every address writer. If it does not fit before the
+    /// reserve, nothing is written and the buffer is marked as truncated.
+    pub fn write_ipv4(&mut self, octets: [u8; 4]) -> fmt::Result {
+        self._write_whole(net::AddrText::ipv4(octets).as_bytes())
+    }
+
+    /// Write an IPv6 address in the canonical form of RFC 5952, such as `2001:db8::1`. Hexadecimal digits are lowercase
+    /// without leading zeros, and the longest run of two or more zero segments is replaced by `::`, which is the first
+    /// of them if there is a tie. An IPv4-mapped address is written as `::ffff:192.0.2.1`. This is the same text as the
+    /// `Display` of `Ipv6Addr`.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_ipv6([0x2001, 0xdb8, 0, 0, 1, 0, 0, 1]).unwrap();
+    /// writer.write_str(" ").unwrap();
+    /// writer.write_ipv6([0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+    /// assert_eq!("2001:db8::1:0:0:1 ::1", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Like [`WriteBuf::write_ipv4`], nothing is written if the address does not fit.
+    pub fn write_ipv6(&mut self, segments: [u16; 8]) -> fmt::Result {
+        self._write_whole(net::AddrText::ipv6(segments).as_bytes())
+    }
+
+    /// Write an IPv4 address and a port, such as `192.0.2.1:8080`.
+    ///
+    /// # Errors
+    ///
+    /// Like [`WriteBuf::write_ipv4`], nothing is written if the address and port do not fit.
+    pub fn write_socket_addr_v4(&mut self, octets: [u8; 4], port: u16) -> fmt::Result {
+        self._write_whole(net::AddrText::socket_v4(octets, port).as_bytes())
+    }
+
+    /// Write an IPv6 address like [`WriteBuf::write_ipv6`] in brackets and a port, such as `[2001:db8::1]:8080`.
+    ///
+    /// # Errors
+    ///
+    /// Like [`WriteBuf::write_ipv4`], nothing is written if the address and port do not fit.
+    pub fn write_socket_addr_v6(&mut self, segments: [u16; 8], port: u16) -> fmt::Result {
+        self._write_whole(net::AddrText::socket_v6(segments, 0, port).as_bytes())
+    }
+
+    /// Write an [`IpAddr`](std::net::IpAddr) like [`WriteBuf::write_ipv4`] or [`WriteBuf::write_ipv6`].
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::net::{IpAddr, Ipv6Addr};
+    ///
+    /// let address = IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0x1ff, 0xfe23, 0x4567, 0x890a));
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_ip_addr(address).unwrap();
+    /// assert_eq!(address.to_string(), writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Like [`WriteBuf::write_ipv4`], nothing is written if the address does not fit.
+    #[cfg(feature = "std")]
+    pub fn write_ip_addr(&mut self, address: std::net::IpAddr) -> fmt::Result {
+        match address {
+            std::net::IpAddr::V4(address) => self.write_ipv4(address.octets()),
+            std::net::IpAddr::V6(address) => self.write_ipv6(address.segments()),
+        }
+    }
+
+    /// Write a [`SocketAddr`](std::net::SocketAddr) like [`WriteBuf::write_socket_addr_v4`] or
+    /// [`WriteBuf::write_socket_addr_v6`]. The scope ID of an IPv6 socket address is written after a `%` when it is not
+    /// 0, such as `[fe80::1%2]:8080`, as `Display` does.
+    ///
+    /// # Errors
+    ///
+    /// Like [`WriteBuf::write_ipv4`], nothing is written if the address and port do not fit.
+    #[cfg(feature = "std")]
+    pub fn write_socket_addr(&mut self, address: std::net::SocketAddr) -> fmt::Result {
+        let text = match address {
+            std::net::SocketAddr::V4(address) => net::AddrText::socket_v4(address.ip().octets(), address.port()),
+            std::net::SocketAddr::V6(address) => {
+                net::AddrText::socket_v6(address.ip().segments(), address.scope_id(), address.port())
+            },
+        };
+        self._write_whole(text.as_bytes())
+    }
+
+    /// Write a MAC address in lowercase hexadecimal with `separator` between the bytes, which is usually `:` or `-`, such
+    /// as `00:1a:2b:3c:4d:5e`.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_mac([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e], '-').unwrap();
+    /// assert_eq!("00-1a-2b-3c-4d-5e", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Like [`WriteBuf::write_ipv4`], nothing is written if the address does not fit.
+    pub fn write_mac(&mut self, bytes: [u8; 6], separator: char) -> fmt::Result {
+        self._write_whole(net::AddrText::mac(bytes, separator).as_bytes())
+    }
+
+    /// Write a UUID from its 16 bytes in the order they are written, such as `67e55044-10b1-426f-9247-bb680e5fe0c8`. See
+    /// [`UuidStyle`] for the other styles.
+    ///
+    /// ```
+    /// use fmtbuf::{UuidStyle, WriteBuf};
+    ///
+    /// let uuid = [
+    ///     0x67, 0xe5, 0x50, 0x44, 0x10, 0xb1, 0x42, 0x6f, 0x92, 0x47, 0xbb, 0x68, 0x0e, 0x5f, 0xe0, 0xc8,
+    /// ];
+    /// let mut buf: [u8; 40] = [0xff; 40];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_uuid(&uuid, UuidStyle::HYPHENATED).unwrap();
+    /// assert_eq!("67e55044-10b1-426f-9247-bb680e5fe0c8", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The UUID is written entirely or not at all, including the braces or the `urn:uuid:`. If it does not fit before the
+    /// reserve, nothing is written and the buffer is marked as truncated.
+    pub fn write_uuid(&mut self, bytes: &[u8; 16], style: UuidStyle) -> fmt::Result {
+        self._write_rendered(style.len(), |out| style.render(bytes, out))
+    }
+
+    /// Write `fill` until the position reaches `column`, for lining up columns of text. Like
+    /// [`WriteBuf::position`], `column` is counted in bytes from the start of the buffer, which is the column of a
+    /// single line of ASCII. Nothing is written if the position is already at or past `column`.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_str("name").unwrap();
+    /// writer.pad_to(8, '.').unwrap();
+    /// writer.write_str("value").unwrap();
+    /// assert_eq!("name....value", writer.written());
+    /// ```
+    ///
+    /// If `fill` is a multibyte character and the gap is not a multiple of its encoded length, as many whole characters
+    /// as fit in the gap are written, which stops short of `column`.
+    ///
+    /// # Errors
+    ///
+    /// This is truncated like any other write if the buffer ends before `column`: as many whole `fill` characters as fit
+    /// are written and the buffer is marked as truncated.
+    pub fn pad_to(&mut self, column: usize, fill: char) -> fmt::Result {
+        let gap = column.saturating_sub(self.position());
+        self._write_repeated(fill, gap / fill.len_utf8())
+    }
+
+    /// Write `c` `count` times, such as for a divider line of `─`. This is the same as writing a string of `count` `c`
+    /// characters, without having to make one.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_char_repeated('─', 8).unwrap();
+    /// assert_eq!("────────", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If all of them do not fit, as many whole characters as fit are written and the buffer is marked as truncated, as
+    /// with [`WriteBuf::pad_to`].
+    pub fn write_char_repeated(&mut self, c: char, count: usize) -> fmt::Result {
+        self._write_repeated(c, count)
+    }
+
+    /// Write `s` in a field of `width` characters, filling the rest of it with `fill`, like `{:<10}`, `{:>10}` and
+    /// `{:^10}` of `core::fmt`. The width is counted in `char`s, not bytes, so multibyte content lines up with ASCII
+    /// content in a monospace font as long as each `char` is one column wide. When the field is centered and the
+    /// leftover is odd, the extra `fill` goes after `s`.
+    ///
+    /// ```
+    /// use fmtbuf::{Align, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_padded("left", 6, Align::Left, ' ').unwrap();
+    /// writer.write_str("|").unwrap();
+    /// writer.write_padded("right", 7, Align::Right, ' ').unwrap();
+    /// writer.write_str("|").unwrap();
+    /// writer.write_padded("mid", 6, Align::Center, '*').unwrap();
+    /// assert_eq!("left  |  right|*mid**", writer.written());
+    /// ```
+    ///
+    /// If `s` is longer than `width`, it is written as it is, without any `fill`.
+    ///
+    /// # Errors
+    ///
+    /// The fill and `s` are truncated like any other write, with the end of the fill cut at a whole `fill`. This only
+    /// returns `Ok` if all of it was written.
+    pub fn write_padded(&mut self, s: &str, width: usize, align: Align, fill: char) -> fmt::Result {
+        let (before, after) = align.split(width.saturating_sub(s.chars().count()));
+        let start = self.position();
+        self._start_segment();
+        let result = self
+            ._fill(fill, before)
+            .and(self._write(s.as_bytes()))
+            .and(self._fill(fill, after));
+        if result.is_err() && self.strict {
+            self.position.set(start);
+        }
+        result
+    }
+
+    /// Format `value` in a field of `width` characters like [`WriteBuf::write_padded`], without formatting it somewhere
+    /// else first. The value is formatted twice: once to count its characters, then to write it.
+    ///
+    /// ```
+    /// use fmtbuf::{Align, WriteBuf};
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_display_padded(&-42, 6, Align::Right, ' ').unwrap();
+    /// writer.write_display_padded(&'é', 3, Align::Center, '-').unwrap();
+    /// assert_eq!("   -42-é-", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Like [`WriteBuf::write_padded`], the fill and the output of `value` are truncated like any other write.
+    pub fn write_display_padded(
+        &mut self,
+        value: &impl fmt::Display,
+        width: usize,
+        align: Align,
+        fill: char,
+    ) -> fmt::Result {
+        let mut counter = CharCounter(0);
+        let _ = fmt::write(&mut counter, format_args!("{}", value));
+        let (before, after) = align.split(width.saturating_sub(counter.0));
+
+        let start = self.position();
+        self._start_segment();
+        let dropped_input_bytes = self.dropped_input_bytes;
+        let mut result = self
+            ._fill(fill, before)
+            .and(fmt::write(&mut Segment(self), format_args!("{}", value)))
+            .and(self._fill(fill, after));
+        if self.dropped_input_bytes != dropped_input_bytes {
+            result = Err(fmt::Error);
+        }
+        if result.is_err() && self.strict {
+            self.position.set(start);
+        }
+        result
+    }
+
+    /// Write `bytes` in hexadecimal, two digits per byte, such as `deadbeef`, for IDs and hashes. The digits above 9 are
+    /// `A` to `F` if `upper` is set.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_hex_bytes(&[0xde, 0xad, 0xbe, 0xef], false).unwrap();
+    /// writer.write_str(" ").unwrap();
+    /// writer.write_hex_bytes(&[0x0a, 0xff], true).unwrap();
+    /// assert_eq!("deadbeef 0AFF", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Unlike the numeric writers, a long input is cut like a string, but only after the digits of a whole byte, so what
+    /// was written is still the start of the input. The buffer is marked as truncated.
+    pub fn write_hex_bytes(&mut self, bytes: &[u8], upper: bool) -> fmt::Result {
+        self._write_hex_bytes(bytes, upper, None)
+    }
+
+    /// Write `bytes` in lowercase hexadecimal like [`WriteBuf::write_hex_bytes`], with `separator` between the bytes,
+    /// such as `de:ad:be:ef`.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_hex_bytes_sep(&[0xde, 0xad, 0xbe, 0xef], ':').unwrap();
+    /// assert_eq!("de:ad:be:ef", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A long input is cut after the digits of a whole byte, never after a separator, and the buffer is marked as
+    /// truncated.
+    pub fn write_hex_bytes_sep(&mut self, bytes: &[u8], separator: char) -> fmt::Result {
+        self._write_hex_bytes(bytes, false, Some(separator))
+    }
+
+    /// Write `bytes` as a hex dump for debugging, with a line for each [`HexDumpOpts::bytes_per_line`] bytes, each ending
+    /// in `\n`. See [`HexDumpOpts`] for the layout, which is that of `hexdump -C` by default.
+    ///
+    /// ```
+    /// use fmtbuf::{HexDumpOpts, WriteBuf};
+    ///
+    /// let mut buf: [u8; 160] = [0xff; 160];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_hex_dump(b"Hello world\n\x00\x01", HexDumpOpts::new()).unwrap();
+    /// assert_eq!(
+    ///     "00000000  48 65 6c 6c 6f 20 77 6f  72 6c 64 0a 00 01        |Hello world...|\n",
+    ///     writer.written()
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A dump is only cut between lines, so a truncated dump is still readable. As many whole lines as fit are written
+    /// and the buffer is marked as truncated.
+    pub fn write_hex_dump(&mut self, bytes: &[u8], opts: HexDumpOpts) -> fmt::Result {
+        let dump = hex_dump::HexDump::new(bytes, opts);
+        self._start_segment();
+        self.write_calls = self.write_calls.saturating_add(1);
+        let needed = dump.len();
+        let latched = self._latched();
+        let room = if latched || (self.strict && needed > self.remaining()) {
+            0
+        } else {
+            self.remaining()
+        };
+        let mut lines = 0;
+        let mut written = 0usize;
+        while lines < dump.lines() && written.saturating_add(dump.line_len(lines)) <= room {
+            written += dump.line_len(lines);
+            lines += 1;
+        }
+        if lines < dump.lines() && !latched {
+            self._mark_truncated(needed);
+        }
+
+        let mut end = self.position();
+        let target = &mut self.target;
+        for line in 0..lines {
+            dump.render_line(line, |piece| {
+                target.write_at(end, piece);
+                end += piece.len();
+            });
+        }
+        self.position.set(end);
+        if lines < dump.lines() {
+            self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(needed - written);
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+
+    /// Write `bytes` in base64, such as for signatures and nonces in a text protocol. See [`Base64Config`] for the
+    /// alphabets and padding, and [`WriteBuf::base64_writer`] for input which arrives in chunks.
+    ///
+    /// ```
+    /// use fmtbuf::{Base64Config, WriteBuf};
+    ///
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_base64(b"foobar", Base64Config::STANDARD).unwrap();
+    /// assert_eq!("Zm9vYmFy", writer.written());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A long input is cut, but only after a whole group of 4 characters, which is 3 bytes of input, so what was
+    /// written can be decoded on its own. The last group of fewer than 3 bytes is written entirely or not at all. The
+    /// buffer is marked as truncated.
+    pub fn write_base64(&mut self, bytes: &[u8], config: Base64Config) -> fmt::Result {
+        self._write_base64(bytes, config)
+    }
+
+    /// Borrow this writer as a [`Base64Writer`], which encodes input passed to it in chunks in base64, the same as
+    /// [`WriteBuf::write_base64`] would encode all of it at once.
+    pub fn base64_writer(&mut self, config: Base64Config) -> Base64Writer<'_, 'a, T> {
+        Base64Writer::new(self, config)
+    }
+
+    /// Write `s` with at most `max_bytes` of it, independent of the room left in the buffer. If `s` is longer than that,
+    /// the middle of it is replaced by `marker`, keeping the start and the end, which is the most readable way to
+    /// shorten things like URLs and hashes. This keeps as much of the start as of the end; see
+    /// [`WriteBuf::write_middle_truncated_with`] to change that.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 64] = [0xff; 64];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_middle_truncated("https://example.com/a1b2c3d4e5f6", 22, "…").unwrap();
+    /// assert_eq!("https://e…b2c3d4e5f6", writer.written());
+    /// ```
+    ///
+    /// Unlike [`WriteBuf::write_display_truncated`], the marker is counted in `max_bytes`. See
+    /// [`WriteBuf::write_middle_truncated_with`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Only hitting `max_bytes` is not an error and does not mark the buffer as truncated. If the buffer runs out of
+    /// room, this behaves the same as `write!`.
+    pub fn write_middle_truncated(&mut self, s: &str, max_bytes: usize, marker: &str) -> fmt::Result {
+        self.write_middle_truncated_with(s, max_bytes, marker, 50)
+    }
+
+    /// Write `s` with at most `max_bytes` of it like [`WriteBuf::write_middle_truncated`], giving `head_percent` percent
+    /// of the room left after the marker to the start of `s` and the rest to the end of it.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 64] = [0xff; 64];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// // Keep more of the end of a hash, where it is usually told apart
+    /// writer.write_middle_truncated_with("9f86d081884c7d659a2feaa0c55ad015", 12, "..", 20).unwrap();
+    /// assert_eq!("9f..c55ad015", writer.written());
+    /// ```
+    ///
+    /// Both the start and the end are cut at `char` boundaries, and when that leaves room unused at the start, the end
+    /// gets it. A `head_percent` over 100 is the same as 100. If `max_bytes` is too small for even the marker, only as
+    /// much of the start of the marker as fits is written, up to the last `char` boundary, and none of `s`. The whole
+    /// write is a single write for [`WriteBuf::undo_last_write`], and with a strict writer, it is all-or-nothing.
+    ///
+    /// # Errors
+    ///
+    /// Only hitting `max_bytes` is not an error and does not mark the buffer as truncated. If the buffer runs out of
+    /// room, this behaves the same as `write!`.
+    pub fn write_middle_truncated_with(
+        &mut self,
+        s: &str,
+        max_bytes: usize,
+        marker: &str,
+        head_percent: u8,
+    ) -> fmt::Result {
+        self._write_pieces(&split_middle(s, max_bytes, marker, head_percent))
+    }
+
+    /// Write `path` in the space left in the buffer, dropping whole directories from the start of it if it does not
+    /// fit, so the file name is kept. The dropped directories are replaced by `"…"` and `sep`.
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 28] = [0xff; 28];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_str("open ").unwrap();
+    /// writer.write_path_truncated("/home/build/fmtbuf/src/lib.rs", '/').unwrap();
+    /// assert_eq!("open …/fmtbuf/src/lib.rs", writer.written());
+    /// ```
+    ///
+    /// Components are separated by one or more `sep`, so `"a//b"` has the two components `"a"` and `"b"`, and the
+    /// separators after the last component, such as in `"logs/2024/"`, are kept with it. As many components as it takes
+    /// to fit are dropped, but never the last one. If the last component does not fit along with `"…"` and `sep`, it is
+    /// written like [`WriteBuf::write_middle_truncated`] with a `"…"` marker, keeping the start and the end of it, with
+    /// nothing of the directories before it. It is cut this way even if it would fit on its own, so what was dropped is
+    /// always marked, and when there is not enough room for any of it, only the marker is written. The whole write is a
+    /// single write for [`WriteBuf::undo_last_write`], and with a strict writer, it is all-or-nothing.
+    ///
+    /// # Errors
+    ///
+    /// Dropping directories is not an error and does not mark the buffer as truncated, but cutting the last component
+    /// is: the buffer is marked as truncated and `Err` is returned. If the buffer runs out of room, this behaves the
+    /// same as `write!`.
+    pub fn write_path_truncated(&mut self, path: &str, sep: char) -> fmt::Result {
+        let mut encoded = [0u8; 4];
+        let sep_str = sep.encode_utf8(&mut encoded);
+        let start = self.position();
+        let (pieces, cut) = split_path(path, sep, sep_str, self.remaining());
+        self._write_pieces(&pieces)?;
+        if cut {
+            if self.strict {
+                self.position.set(start);
+            }
+            self._mark_truncated(path.len());
+            let written = self.position() - start;
+            self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(path.len() - written);
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+
+    /// Write each of `pieces` as a single write, which is all-or-nothing with a strict writer.
+    fn _write_pieces(&mut self, pieces: &[&str]) -> fmt::Result {
+        let start = self.position();
+        self._start_segment();
+        let dropped_input_bytes = self.dropped_input_bytes;
+        let mut result = Ok(());
+        for piece in pieces {
+            result = result.and(fmt::Write::write_str(&mut Segment(self), piece));
+        }
+        if self.dropped_input_bytes != dropped_input_bytes {
+            result = Err(fmt::Error);
+        }
+        if result.is_err() && self.strict {
+            self.position.set(start);
+        }
+        result
+    }
+
+    /// Start writing a struct like [`fmt::Formatter::debug_struct`], where each field is written whole or dropped.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 24] = [0xff; 24];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let result = writer
+    ///     .debug_struct("Foo")
+    ///     .field("bar", &1)
+    ///     .field("baz", &"does not fit")
+    ///     .finish();
+    /// assert!(result.is_err());
+    /// assert_eq!("Foo { bar: 1, … }", writer.written());
+    /// ```
+    ///
+    /// See [`DebugStruct`] for details.
+    pub fn debug_struct(&mut self, name: &str) -> DebugStruct<'_, 'a, T> {
+        DebugStruct::new(self, name)
+    }
+
+    /// Start writing a list like [`fmt::Formatter::debug_list`], where each entry is written whole or dropped. See
+    /// [`DebugList`] for details.
+    pub fn debug_list(&mut self) -> DebugList<'_, 'a, T> {
+        DebugList::new(self)
+    }
+
+    /// Set if a write rejected by [`WriteBuf::write_str_atomic`] or [`WriteBuf::write_fmt_atomic`] for not fitting
+    /// marks this buffer as [`WriteBuf::truncated`]. This is off by default.
+    pub fn set_atomic_truncates(&mut self, truncates: bool) {
+        self.atomic_truncates = truncates;
+    }
+
+    /// Get the number of bytes which can be written before reaching the reserve. This is 0 if the written content
+    /// already encroaches on the reserve.
+    pub fn remaining(&self) -> usize {
+        self._unwritten_len().saturating_sub(self.reserve())
+    }
+
+    /// Get the contents that have been written so far.
+    pub fn written_bytes(&self) -> &[u8] {
+        self.target.read_back(self.position())
+    }
+
+    /// Get the contents that have been written so far.
+    pub fn written(&self) -> &str {
+        written_str(self.written_bytes())
+    }
+
+    /// Get a mutable view of the contents that have been written so far. This is useful for modifying content in place,
+    /// such as with [`str::make_ascii_uppercase`].
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "warning").unwrap();
+    /// writer.as_mut_str().make_ascii_uppercase();
+    /// write!(writer, ": disk almost full").unwrap();
+    /// assert_eq!(writer.written(), "WARNING: disk almost full");
+    /// ```
+    pub fn as_mut_str(&mut self) -> &mut str {
+        let position = self.position();
+        let written = self.target.read_back_mut(position);
+
+        #[cfg(debug_assertions)]
+        return core::str::from_utf8_mut(written).expect("contents of buffer should have been UTF-8 encoded");
+
+        // safety: See `written`
+        #[cfg(not(debug_assertions))]
+        unsafe {
+            core::str::from_utf8_unchecked_mut(written)
+        }
+    }
+
+    /// Get the unwritten portion of the target buffer, not including the reserve. This can be used to write into the
+    /// buffer directly with something other than [`fmt::Write`], then claim the bytes with [`WriteBuf::advance`].
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "answer=").unwrap();
+    ///
+    /// let spare = writer.spare_capacity_mut();
+    /// spare[..2].copy_from_slice(b"42");
+    /// writer.advance(2).unwrap();
+    ///
+    /// write!(writer, "!").unwrap();
+    /// assert_eq!(writer.written(), "answer=42!");
+    /// ```
+    ///
+    /// For a writer made by [`WriteBuf::new_uninit`], the part of the spare capacity which was never written is zeroed
+    /// the first time it is handed out.
+    pub fn spare_capacity_mut(&mut self) -> &mut [u8] {
+        let end = self
+            .target
+            .capacity()
+            .saturating_sub(self.reserve())
+            .max(self.position());
+        let position = self.position();
+        &mut self.target.read_back_mut(end)[position..]
+    }
+
+    /// Claim `n` bytes written directly into [`WriteBuf::spare_capacity_mut`] as written content.
+    ///
+    /// # Errors
+    ///
+    /// If a previous write was truncated and the buffer latches (see [`WriteBuf::set_latch`]),
+    /// [`WriteError::AlreadyTruncated`] is returned, and if the claimed bytes are not valid UTF-8,
+    /// [`WriteError::InvalidInput`] is. In both cases, the position does not move. If `n` is larger than the spare
+    /// capacity, the buffer is marked as truncated and [`WriteError::Capacity`] or [`WriteError::ReserveBlocked`] is
+    /// returned; the caller should not have been able to write those bytes in the first place.
+    pub fn advance(&mut self, n: usize) -> Result<(), WriteError> {
+        if self._latched() {
+            return Err(WriteError::AlreadyTruncated);
+        }
+        let claimed = match self.spare_capacity_mut().get(..n) {
+            Some(claimed) => claimed,
+            None => {
+                let error = self._capacity_error(n);
+                self._mark_truncated(n);
+                return Err(error);
+            },
+        };
+        if core::str::from_utf8(claimed).is_err() {
+            return Err(WriteError::InvalidInput);
+        }
+
+        self._advance_over_initialized(n);
+        Ok(())
+    }
+
+    /// Claim `n` bytes written directly into [`WriteBuf::spare_capacity_mut`] as written content without checking
+    /// them. See [`WriteBuf::advance`] for the checked version.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` bytes of [`WriteBuf::spare_capacity_mut`] must be valid UTF-8 and `n` must not be larger than the
+    /// spare capacity. For a writer made by [`WriteBuf::new_uninit`], the bytes must be written through the slice returned
+    /// by [`WriteBuf::spare_capacity_mut`], since it zeroes bytes which were never written the first time it is called.
+    pub unsafe fn advance_unchecked(&mut self, n: usize) {
+        debug_assert!(n <= self.spare_capacity_mut().len(), "advanced past the spare capacity");
+        debug_assert!(
+            core::str::from_utf8(&self.spare_capacity_mut()[..n]).is_ok(),
+            "advanced over invalid UTF-8"
+        );
+        self._advance_over_initialized(n);
+    }
+
+    /// Move the position forward by `n` bytes which were written without going through this writer.
+    fn _advance_over_initialized(&mut self, n: usize) {
+        let position = self.position() + n;
+        // safety: The caller wrote the bytes, either through `spare_capacity_mut` or the pointer to the target
+        unsafe { self.target.assume_init(position) };
+        self.position.set(position);
+    }
+
+    /// Get the contents that have been written so far from an asynchronous reader, such as a signal handler which
+    /// interrupted the thread writing to this buffer.
+    ///
+    /// Writes into the buffer copy bytes first, then publish the new [`WriteBuf::position`] with a single atomic store.
+    /// So while a write is in progress, this returns the contents from before the write started, and it never returns
+    /// a range which covers bytes that have not been copied. When written contents are rolled back (by
+    /// [`WriteBuf::pop`] or a suffix needing more room), the position is moved back before the bytes are overwritten.
+    ///
+    /// This is **not** thread-safety: there must only ever be a single writer. It only guarantees consistency
+    /// between that writer and readers which observe the buffer while a write is in progress.
+    #[cfg(feature = "signal-safe")]
+    pub fn written_bytes_relaxed(&self) -> &[u8] {
+        self.target.read_back(self.position.get_published())
+    }
+
+    /// Remove the last code point from the written contents and return it, or `None` if nothing has been written. The
+    /// [`WriteBuf::position`] moves back by the encoded width of the character.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// for item in ["a", "b", "c"] {
+    ///     write!(writer, "{item},").unwrap();
+    /// }
+    /// assert_eq!(writer.pop(), Some(','));
+    /// assert_eq!(writer.written(), "a,b,c");
+    /// ```
+    ///
+    /// Popping does not affect [`WriteBuf::truncated`]. If a previous write was truncated, removing content will not
+    /// allow future writes to succeed.
+    pub fn pop(&mut self) -> Option<char> {
+        let (c, width) = utf8::last_char(self.written_bytes())?;
+        self.position.set(self.position() - width);
+        Some(c)
+    }
+
+    /// Get the last code point of the written contents, or `None` if nothing has been written.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "Sentence ends with a period.").unwrap();
+    /// let suffix = if writer.last_char() == Some('.') { "" } else { "." };
+    /// let write_len = writer.finish_with(suffix).unwrap();
+    /// assert_eq!(&buf[..write_len], b"Sentence ends with a period.");
+    /// ```
+    pub fn last_char(&self) -> Option<char> {
+        utf8::last_char(self.written_bytes()).map(|(c, _)| c)
+    }
+
+    /// Check if the written contents end with `suffix`.
+    pub fn ends_with(&self, suffix: &str) -> bool {
+        self.written_bytes().ends_with(suffix.as_bytes())
+    }
+
+    /// Remove up to `n` code points from the end of the written contents. This behaves like calling [`WriteBuf::pop`]
+    /// `n` times.
+    ///
+    /// # Returns
+    ///
+    /// The number of code points removed, which is less than `n` if the written contents were shorter than that.
+    pub fn pop_n(&mut self, n: usize) -> usize {
+        let mut popped = 0;
+        while popped < n && self.pop().is_some() {
+            popped += 1;
+        }
+        popped
+    }
+
+    /// Insert `s` at byte index `idx` of the written contents, shifting everything after it to the right.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "disk almost full").unwrap();
+    /// writer.insert_str(0, "[WARN] ").unwrap();
+    /// assert_eq!(writer.written(), "[WARN] disk almost full");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If `idx` is not on a `char` boundary of the written contents, [`WriteError::InvalidInput`] is returned. If the
+    /// contents would no longer fit in the buffer (not counting the reserve), [`WriteError::Capacity`] or
+    /// [`WriteError::ReserveBlocked`] is returned. Either way, the buffer is left untouched. This does not mark the
+    /// buffer as truncated.
+    pub fn insert_str(&mut self, idx: usize, s: &str) -> Result<(), WriteError> {
+        let position = self.position();
+        if !self.written().is_char_boundary(idx) {
+            return Err(WriteError::InvalidInput);
+        }
+        let end = position + s.len();
+        if end > self.target.capacity().saturating_sub(self.reserve()) {
+            return Err(self._capacity_error(s.len()));
+        }
+
+        // Hide the contents being shifted from readers until the insert is complete
+        self.position.set(idx);
+        self.target.copy_within(idx..position, idx + s.len());
+        self.target.write_at(idx, s.as_bytes());
+        // safety: The shifted contents end at `end` and the write filled the gap in front of them
+        unsafe { self.target.assume_init(end) };
+        self.position.set(end);
+        // The start of the last write moved, so it can no longer be undone
+        self.segment_start = NO_SEGMENT;
+        Ok(())
+    }
+
+    /// Replace the written bytes starting at `idx` with `s`, without moving anything else. This is useful for filling in
+    /// a placeholder once the real value is known.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "status=").unwrap();
+    /// let status_idx = writer.position();
+    /// write!(writer, "---- items=12").unwrap();
+    /// writer.overwrite(status_idx, "DONE").unwrap();
+    /// assert_eq!(writer.written(), "status=DONE items=12");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The range `idx..idx + s.len()` must be within the written contents and both ends of it must be on `char`
+    /// boundaries, so the contents stay valid UTF-8. Otherwise, [`WriteError::InvalidInput`] is returned and nothing is
+    /// changed. This never changes [`WriteBuf::position`] or [`WriteBuf::truncated`].
+    pub fn overwrite(&mut self, idx: usize, s: &str) -> Result<(), WriteError> {
+        let end = match idx.checked_add(s.len()) {
+            Some(end) if end <= self.position() => end,
+            _ => return Err(WriteError::InvalidInput),
+        };
+        let written = self.written();
+        if !written.is_char_boundary(idx) || !written.is_char_boundary(end) {
+            return Err(WriteError::InvalidInput);
+        }
+
+        // Hide the contents being patched from readers until the copy is complete
+        let position = self.position();
+        self.position.set(idx);
+        self.target.write_at(idx, s.as_bytes());
+        self.position.set(position);
+        // The start of the last write might be in the middle of a patched char now, so it can no longer be undone
+        if idx < self.segment_start && self.segment_start < end {
+            self.segment_start = NO_SEGMENT;
+        }
+        Ok(())
+    }
+
+    /// Remove the written bytes in `range`, shifting everything after it to the left. Both ends of `range` must be on
+    /// `char` boundaries of the written contents.
+    fn _remove(&mut self, range: core::ops::Range<usize>) {
+        let position = self.position();
+        debug_assert!(range.start <= range.end && range.end <= position);
+        debug_assert!(self.written().is_char_boundary(range.start) && self.written().is_char_boundary(range.end));
+
+        let end = position - range.len();
+        self.position.set(range.start);
+        self.target.copy_within(range.end..position, range.start);
+        self.position.set(end);
+        // The start of the last write moved, so it can no longer be undone
+        self.segment_start = NO_SEGMENT;
+    }
+
+    /// Capture the current position and truncation state, so speculative writes can be undone with
+    /// [`WriteBuf::rollback_to`].
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 20] = [0xff; 20];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "msg=hello").unwrap();
+    /// for (key, value) in [("user", "ann"), ("path", "/very/long/path")] {
+    ///     let checkpoint = writer.checkpoint();
+    ///     if write!(writer, " {key}={value}").is_err() {
+    ///         // drop the whole pair instead of leaving part of it
+    ///         writer.rollback_to(checkpoint).unwrap();
+    ///     }
+    /// }
+    /// assert_eq!(writer.written(), "msg=hello user=ann");
+    /// assert!(!writer.truncated());
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            target: self.target.as_ptr() as usize,
+            position: self.position(),
+            content_truncated: self.content_truncated,
+            capacity_truncated: self.capacity_truncated,
+            suffix_degraded: self.suffix_degraded,
+            dropped_input_bytes: self.dropped_input_bytes,
+        }
+    }
+
+    /// Restore the position and truncation state captured by [`WriteBuf::checkpoint`]. Anything written after the
+    /// checkpoint was taken is discarded and if truncation happened after the checkpoint, it is cleared so future writes
+    /// can succeed.
+    ///
+    /// # Errors
+    ///
+    /// The checkpoint is rejected with [`WriteError::InvalidInput`] and nothing changes if it was taken from a different
+    /// writer, if its position is beyond the current [`WriteBuf::position`] (such as a checkpoint taken before an
+    /// earlier rollback) or if its position is no longer on a `char` boundary, since [`WriteBuf::overwrite`] replaced
+    /// the content around it.
+    pub fn rollback_to(&mut self, checkpoint: Checkpoint) -> Result<(), WriteError> {
+        if checkpoint.target != self.target.as_ptr() as usize
+            || checkpoint.position > self.position()
+            || !self.written().is_char_boundary(checkpoint.position)
+        {
+            return Err(WriteError::InvalidInput);
+        }
+
+        self.position.set(checkpoint.position);
+        self.content_truncated = checkpoint.content_truncated;
+        self.capacity_truncated = checkpoint.capacity_truncated;
+        self.suffix_degraded = checkpoint.suffix_degraded;
+        self.segment_start = NO_SEGMENT;
+        Ok(())
+    }
+
+    /// Undo the last write to this buffer, restoring the [`WriteBuf::position`] to where it was when that write started
+    /// and clearing truncation if it happened during that write, along with the bytes that write added to
+    /// [`WriteBuf::dropped_bytes`]. A single `write!` invocation counts as one write, even though it might result in
+    /// multiple `write_str` calls.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 32] = [0xff; 32];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "status: ").unwrap();
+    /// write!(writer, "{} of {} done", 3, 4).unwrap();
+    /// assert!(writer.undo_last_write());
+    /// assert_eq!(writer.written(), "status: ");
+    /// assert!(!writer.undo_last_write());
+    /// ```
+    ///
+    /// Only the most recent write is remembered, so calling this twice in a row does nothing the second time. A write
+    /// which was attempted after truncation already happened can not be undone, nor can anything after a
+    /// [`WriteBuf::rollback_to`].
+    ///
+    /// # Returns
+    ///
+    /// `true` if a write was undone or `false` if there was nothing to undo.
+    pub fn undo_last_write(&mut self) -> bool {
+        let start = core::mem::replace(&mut self.segment_start, NO_SEGMENT);
+        if start == NO_SEGMENT || start > self.position() {
+            return false;
+        }
+
+        // Segments are only started when the buffer was not truncated
+        self.position.set(start);
+        self.content_truncated = false;
+        self.capacity_truncated = false;
+        self.suffix_degraded = false;
+        self.dropped_input_bytes = self.segment_dropped_input_bytes;
+        true
+    }
+
+    /// Note the start of a write for [`WriteBuf::undo_last_write`].
+    fn _start_segment(&mut self) {
+        self.segment_start = if self.truncated() { NO_SEGMENT } else { self.position() };
+        self.segment_dropped_input_bytes = self.dropped_input_bytes;
+    }
+
+    /// Run `f` as an all-or-nothing group of writes. If `f` returns an error or any write inside of it was truncated,
+    /// the buffer is rolled back to where it was before `f` ran (see [`WriteBuf::rollback_to`]), which also clears the
+    /// truncation.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "request failed").unwrap();
+    ///
+    /// // either get the whole parenthetical or nothing
+    /// let result = writer.transaction(|w| write!(w, " ({} retries)", 3));
+    /// assert!(result.is_err());
+    /// assert_eq!(writer.written(), "request failed");
+    /// assert!(!writer.truncated());
+    /// ```
+    ///
+    /// Transactions can be nested: an inner transaction rolling back only undoes the writes of the inner closure, and
+    /// the outer closure can keep writing after it. If `f` removes content which was written before the transaction
+    /// started (for example, by calling [`WriteBuf::pop`]), that content can not be restored; only the truncation state
+    /// is rolled back.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if the writes in `f` were committed or `Err` if they were rolled back.
+    pub fn transaction<F>(&mut self, f: F) -> fmt::Result
+    where
+        F: FnOnce(&mut Self) -> fmt::Result,
+    {
+        let checkpoint = self.checkpoint();
+        let result = f(self);
+        if result.is_ok() && !self._truncated_since(&checkpoint) {
+            return Ok(());
+        }
+
+        if self.rollback_to(checkpoint).is_err() {
+            self.content_truncated = checkpoint.content_truncated;
+            self.capacity_truncated = checkpoint.capacity_truncated;
+            self.suffix_degraded = checkpoint.suffix_degraded;
+        }
+        Err(fmt::Error)
+    }
+
+    /// Finish writing to the buffer. This returns control of the target buffer to the caller (it is no longer mutably
+    /// borrowed) and returns the number of bytes written.
+    ///
+    /// # Returns
+    ///
+    /// In both the `Ok` and `Err` cases, the [`WriteBuf::position`] is returned. The `Ok` case indicates the truncation
+    /// did not occur, while `Err` indicates that it did.
+    pub fn finish(self) -> Result<usize, usize> {
+        self._result()
+    }
+
+    /// Finish the buffer by calling `f` with the bytes held back by [`WriteBuf::with_front_reserve`] and the length of the
+    /// body written after them, so it can fill in a header. If this writer was not created with a front reserve, `f` is
+    /// given an empty slice.
+    ///
+    /// # Returns
+    ///
+    /// The total length of the header and the body, with the same meaning as [`WriteBuf::finish`].
+    pub fn finish_with_header<F>(self, f: F) -> Result<usize, usize>
+    where
+        F: FnOnce(&mut [u8], usize),
+    {
+        let result = self._result();
+        f(self.front, self.position());
+        result
+    }
+
+    /// Finish the buffer with the suffixes given to [`WriteBuf::with_reserve_for`], the same way as
+    /// [`WriteBuf::finish_with_or`]. If this writer was not created with suffixes, this is the same as
+    /// [`WriteBuf::finish`].
+    pub fn finish_reserved(self) -> Result<usize, usize> {
+        let (normal, truncated) = self.suffixes.unwrap_or(("", ""));
+        self.finish_with_or(normal, truncated)
+    }
+
+    /// Finish the buffer, adding the `suffix` to the end. A common use case for this is to add a null terminator.
+    ///
+    /// This operates slightly differently than the normal format writing function `write_str` in that the `suffix` is
+    /// always put at the end. The only case where this will not happen is when `suffix.len()` is less than the size of
+    /// the buffer originally provided. In this case, the last bit of `suffix` will be copied (starting at a valid UTF-8
+    /// sequence start; e.g.: writing `"🚀..."` to a 5 byte buffer will leave you with just `"..."`, no matter what was
+    /// written before).
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    ///
+    /// let mut buf: [u8; 4] = [0xff; 4];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    ///
+    /// // Finish writing with too many bytes:
+    /// let write_len = writer.finish_with("12345").unwrap_err();
+    /// assert_eq!(write_len, 4);
+    /// let buf_str = std::str::from_utf8(&buf).unwrap();
+    /// assert_eq!(buf_str, "2345");
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`].
+    pub fn finish_with(self, suffix: &str) -> Result<usize, usize> {
+        self.finish_report(suffix, suffix).result()
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with`], but with a suffix which is not known to be UTF-8,
+    /// such as one which came from a C API. The suffix is validated first, since placing invalid UTF-8 would break the
+    /// guarantee that the written content is valid UTF-8.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// writer.write_str("abc").unwrap();
+    /// assert_eq!(Err(3), writer.finish_with_bytes(b"\x80\0"));
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`]. If `suffix` is not valid UTF-8, it is not
+    /// placed at all, [`WriteBuf::suffix_degraded`] is set and the `Err` case is returned.
+    pub fn finish_with_bytes(mut self, suffix: impl AsRef<[u8]>) -> Result<usize, usize> {
+        let suffix = suffix.as_ref();
+        match core::str::from_utf8(suffix) {
+            Ok(suffix) => self.finish_with(suffix),
+            Err(_) => {
+                self._degrade_suffix(suffix.len());
+                self._result()
+            },
+        }
+    }
+
+    /// Finish the buffer by adding `normal_suffix` if not truncated or `truncated_suffix` if the buffer will be
+    /// truncated. This operates the same as [`WriteBuf::finish_with`] in every other way.
+    pub fn finish_with_or(self, normal_suffix: &str, truncated_suffix: &str) -> Result<usize, usize> {
+        self.finish_report(normal_suffix, truncated_suffix).result()
+    }
+
+    /// Finish writing to the buffer the same way as [`WriteBuf::finish`], but remove trailing whitespace from the written
+    /// content first. Whitespace is anything matched by [`char::is_whitespace`].
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`]. Trimming does not clear [`WriteBuf::truncated`].
+    pub fn finish_trimmed(self) -> Result<usize, usize> {
+        self.finish_trimmed_with("")
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with`], but remove trailing whitespace from the written
+    /// content before adding `suffix`. If written content has to be rolled back to make room for `suffix`, whitespace
+    /// left at the end of the rolled back content is removed as well, so the suffix never follows a gap.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 23] = [0xff; 23];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "error while reading the configuration file").unwrap_err();
+    /// let write_len = writer.finish_trimmed_with("…").unwrap_err();
+    /// assert_eq!("error while reading…", std::str::from_utf8(&buf[..write_len]).unwrap());
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`]. Trimming does not clear [`WriteBuf::truncated`].
+    pub fn finish_trimmed_with(mut self, suffix: &str) -> Result<usize, usize> {
+        self._finish_with_trim(suffix, suffix, true);
+        self._result()
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with_or`], but when the truncated suffix is used, remove
+    /// combining marks (such as the U+0301 of a decomposed `"é"`) from the end of the content in front of it, so no
+    /// accent is drawn onto the suffix.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 9] = [0xff; 9];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "cafe\u{301} au lait").unwrap_err();
+    /// let write_len = writer.finish_tidy_with_or("", "…").unwrap_err();
+    /// assert_eq!("cafe…", std::str::from_utf8(&buf[..write_len]).unwrap());
+    /// ```
+    ///
+    /// Only marks in a small table of the common combining blocks are removed, and characters which combine in other
+    /// ways, like Hangul jamo and emoji modifiers, are left alone. The removed marks count toward
+    /// [`WriteBuf::dropped_bytes`].
+    ///
+    /// # Returns
+    ///
+    /// The same as [`WriteBuf::finish`].
+    pub fn finish_tidy_with_or(mut self, normal_suffix: &str, truncated_suffix: &str) -> Result<usize, usize> {
+        let placed = self._finish_with(normal_suffix, truncated_suffix);
+        if placed == SuffixKind::Truncated || placed == SuffixKind::RolledBack {
+            let position = self.position();
+            let suffix_start = position - truncated_suffix.len();
+            let content = written_str(self.target.read_back(suffix_start));
+            let content_end = content.trim_end_matches(utf8::is_combining_mark).len();
+            if content_end < suffix_start {
+                self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(suffix_start - content_end);
+                self.position.set(content_end);
+                self.target.copy_within(suffix_start..position, content_end);
+                self.position.set(content_end + truncated_suffix.len());
+            }
+        }
+        self._result()
+    }
+
+    /// Finish the buffer by filling everything after the written content with `fill`, so the entire target buffer is
+    /// used. This is useful for fixed-width text fields. The reserve is filled as well.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut field: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut field);
+    /// write!(writer, "{}", 42).unwrap();
+    /// assert_eq!(Ok(8), writer.finish_padded(' '));
+    /// assert_eq!(b"42      ", &field);
+    /// ```
+    ///
+    /// If `fill` is a multibyte character and the remaining space is not a multiple of its encoded length, the last few
+    /// bytes are filled with ASCII spaces.
+    ///
+    /// # Returns
+    ///
+    /// The length of the target buffer. The `Ok` case indicates truncation did not occur, while `Err` indicates that it
+    /// did.
+    pub fn finish_padded(self, fill: char) -> Result<usize, usize> {
+        self.finish_with_padded("", fill)
+    }
+
+    /// Finish the buffer by placing `suffix` flush with the end of the target buffer and filling the space between the
+    /// written content and it with `fill`. The `suffix` is placed the same way as [`WriteBuf::finish_with`], so it
+    /// might roll back written content. See [`WriteBuf::finish_padded`].
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut field: [u8; 10] = [0xff; 10];
+    /// let mut writer = WriteBuf::new(&mut field);
+    /// write!(writer, "total").unwrap();
+    /// assert_eq!(Ok(10), writer.finish_with_padded("|\n", '.'));
+    /// assert_eq!(b"total...|\n", &field);
+    /// ```
+    pub fn finish_with_padded(mut self, suffix: &str, fill: char) -> Result<usize, usize> {
+        let placed = self._finish_with(suffix, suffix);
+
+        // A suffix larger than the target only has its end placed
+        let placed_len = if placed == SuffixKind::Omitted {
+            0
+        } else if suffix.len() <= self.target.capacity() {
+            suffix.len()
+        } else {
+            rollback_suffix(&[], self.target.capacity(), suffix, &CodePoint).1.len()
+        };
+        let content_end = self.position() - placed_len;
+        let suffix_start = self.target.capacity() - placed_len;
+
+        // Fill up to where the suffix goes, then place it again there, so nothing is written past a gap
+        self.position.set(content_end);
+        fill_repeated(&mut self.target, content_end..suffix_start, fill);
+        self.target
+            .write_at(suffix_start, &suffix.as_bytes()[suffix.len() - placed_len..]);
+        self.position.set(self.target.capacity());
+        self._result()
+    }
+
+    /// Finish the buffer so it ends with exactly one `\n`. This is the same as [`WriteBuf::finish_with_line_ending`]
+    /// with [`LineEnding::Lf`].
+    pub fn finish_with_newline(self) -> Result<usize, usize> {
+        self.finish_with_line_ending(LineEnding::Lf)
+    }
+
+    /// Finish the buffer so it ends with exactly one `ending`, even if the content was truncated. This is the same as
+    /// [`WriteBuf::finish_with_or_line`] with no truncation marker.
+    pub fn finish_with_line_ending(self, ending: LineEnding) -> Result<usize, usize> {
+        self.finish_with_or_line("", ending)
+    }
+
+    /// Finish the buffer so it ends with exactly one `ending`, putting `truncated_marker` in front of it if the buffer
+    /// will be truncated. This is useful for line-oriented sinks, which need every record to be terminated.
+    ///
+    /// ```
+    /// use fmtbuf::{LineEnding, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "request failed: timeout").unwrap_err();
+    /// let write_len = writer.finish_with_or_line("…", LineEnding::CrLf).unwrap_err();
+    /// assert_eq!("request…\r\n", std::str::from_utf8(&buf[..write_len]).unwrap());
+    /// ```
+    ///
+    /// A single line ending (`"\r\n"`, `"\n"` or a lone `"\r"`) at the end of the written content is replaced by
+    /// `ending` instead of being doubled. Written content is rolled back the same way as [`WriteBuf::finish_with`] if
+    /// needed, and if the rolled back content ends in a line ending or a lone `'\r'`, it is removed as well. If the
+    /// target buffer is too small to hold the suffix, only the end of it is kept.
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`].
+    pub fn finish_with_or_line(mut self, truncated_marker: &str, ending: LineEnding) -> Result<usize, usize> {
+        let original_len = self.position();
+        let trimmed_len = strip_line_ending(self.written()).len();
+        self.position.set(trimmed_len);
+
+        let ending = ending.as_str();
+        let placed = self._finish_with_fmt(format_args!("{ending}"), format_args!("{truncated_marker}{ending}"));
+        if placed == SuffixKind::Omitted {
+            // Stripping the line ending only moved the position, so the strict writer's content is still there
+            self.position.set(original_len);
+            return self._result();
+        }
+
+        // Rolling back can leave the content ending in a line ending or a piece of one, so slide the suffix over it
+        let suffix_len = if self.truncated() {
+            truncated_marker.len() + ending.len()
+        } else {
+            ending.len()
+        };
+        let position = self.position();
+        if suffix_len > position || position - suffix_len >= trimmed_len {
+            return self._result();
+        }
+        let suffix_start = position - suffix_len;
+        let content_end = strip_line_ending(written_str(self.target.read_back(suffix_start))).len();
+        self.position.set(content_end);
+        self.target.copy_within(suffix_start..position, content_end);
+        self.position.set(content_end + suffix_len);
+        self._result()
+    }
+
+    /// Finish the buffer normally if nothing was truncated, but replace all the written content with `fallback` if
+    /// something was. This is useful when a partial message is worse than a generic one, such as when a cut-off number
+    /// could be misread.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "balance={}", 1234567.89).unwrap_err();
+    /// let write_len = writer.finish_or_replace("<too long>").unwrap_err();
+    /// assert_eq!("<too long>", std::str::from_utf8(&buf[..write_len]).unwrap());
+    /// ```
+    ///
+    /// The `fallback` is written from the start of the target buffer and ignores the reserve. If it does not fit in the
+    /// target, it is cut the same way as a suffix given to [`WriteBuf::finish_with`]: only the end of it is kept,
+    /// starting at a valid UTF-8 sequence start.
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`]. When `fallback` replaced the content, this is
+    /// always `Err`.
+    pub fn finish_or_replace(mut self, fallback: &str) -> Result<usize, usize> {
+        if !self.truncated() {
+            return self._result();
+        }
+
+        self.position.set(0);
+        self._finish_with(fallback, fallback);
+        self._result()
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with_or`] with no normal suffix, but if content was
+    /// truncated, follow `truncated_suffix` with whatever closes the brackets and quotes left open by the truncated
+    /// content. This keeps cut-off bracketed or quoted fragments parseable downstream.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 28] = [0xff; 28];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "request{{id=42, body=\"{}\"}}", "x".repeat(32)).unwrap_err();
+    /// let write_len = writer.finish_balanced("...").unwrap_err();
+    /// assert_eq!(r#"request{id=42, body="xx..."}"#, std::str::from_utf8(&buf[..write_len]).unwrap());
+    /// ```
+    ///
+    /// The `(`, `[` and `{` brackets are closed by any of `)`, `]` or `}`, and `"` or `'` quote until the same quote,
+    /// with `\` escaping the next character inside of quotes. Brackets inside of quotes are ignored. Written content is
+    /// rolled back as needed for the suffix and closers to fit; since rolling back can change what is left open, the
+    /// closers always match the content which is kept. If `truncated_suffix` does not fit in the target buffer, it is
+    /// cut the same way as in [`WriteBuf::finish_with`] and no closers are added.
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`].
+    pub fn finish_balanced(mut self, truncated_suffix: &str) -> Result<usize, usize> {
+        if !self.truncated() {
+            return self._result();
+        }
+
+        let marker = truncated_suffix;
+        let len = self.target.capacity();
+        if marker.len() > len {
+            self._finish_with(marker, marker);
+            return self._result();
+        }
+
+        // Rolling back to make room for the closers can change the closers, so look for the longest content that fits
+        let position = self.position();
+        let (end, closers_len) = balance::fit(self.written_bytes(), len - marker.len());
+        if end < position {
+            self._degrade_suffix(marker.len() + closers_len);
+            if self.strict {
+                return self._result();
+            }
+        }
+
+        self.position.set(end);
+        let (content, tail) = self
+            .target
+            .read_back_mut(end + marker.len() + closers_len)
+            .split_at_mut(end);
+        tail[..marker.len()].copy_from_slice(marker.as_bytes());
+        balance::write_closers(content, &mut tail[marker.len()..marker.len() + closers_len]);
+        self.position.set(end + marker.len() + closers_len);
+        self._result()
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with_or`], but first close every bidirectional embedding and
+    /// isolate left open by the written content, so text placed after the buffer can not be reordered by it. This is
+    /// the "Trojan Source" hazard of building log lines out of untrusted input.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 16] = [0xff; 16];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// // A user name with a right-to-left override, which is closed later on in the input
+    /// write!(writer, "user=\u{202E}nimda\u{202C} logged in").unwrap_err();
+    /// let write_len = writer.finish_bidi_safe_with_or("", "…").unwrap_err();
+    /// assert_eq!("user=\u{202E}ni\u{202C}…", std::str::from_utf8(&buf[..write_len]).unwrap());
+    /// ```
+    ///
+    /// The embeddings (U+202A, U+202B, U+202D and U+202E) are closed with a PDF (U+202C) and the isolates (U+2066 to
+    /// U+2068) with a PDI (U+2069), innermost first, following the matching rules of the Unicode Bidirectional
+    /// Algorithm. The closers go right after the content, before the suffix, so the suffix is not affected by the
+    /// direction of the content. This closes what is left open even if nothing was truncated, and content with nothing
+    /// left open is not changed.
+    ///
+    /// Written content is rolled back as needed for the closers and the suffix to fit; since rolling back can change
+    /// what is left open, the closers always match the content which is kept. If `truncated_suffix` does not fit in the
+    /// target buffer, it is cut the same way as in [`WriteBuf::finish_with`], which leaves no room for closers, so no
+    /// content is kept either.
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`].
+    pub fn finish_bidi_safe_with_or(mut self, normal_suffix: &str, truncated_suffix: &str) -> Result<usize, usize> {
+        let len = self.target.capacity();
+        let position = self.position();
+        let mut suffix = normal_suffix;
+        let (mut end, mut closers_len) = bidi::fit(self.written_bytes(), len.saturating_sub(suffix.len()));
+        if self.truncated() || end < position || len < suffix.len() {
+            if !self.truncated() {
+                self._degrade_suffix(suffix.len() + closers_len);
+            }
+            suffix = truncated_suffix;
+            if suffix.len() > len {
+                self._finish_with(suffix, suffix);
+                return self._result();
+            }
+            (end, closers_len) = bidi::fit(self.written_bytes(), len - suffix.len());
+            if end < position {
+                self._degrade_suffix(suffix.len() + closers_len);
+                if self.strict {
+                    return self._result();
+                }
+            }
+        }
+
+        self._drop_content_after(end);
+        let (content, tail) = self
+            .target
+            .read_back_mut(end + closers_len + suffix.len())
+            .split_at_mut(end);
+        bidi::write_closers(content, &mut tail[..closers_len]);
+        tail[closers_len..].copy_from_slice(suffix.as_bytes());
+        self.position.set(end + closers_len + suffix.len());
+        self._result()
+    }
+
+    /// Finish the buffer by adding the character `c` to the end. This operates the same as [`WriteBuf::finish_with`]
+    /// with `c` encoded as UTF-8, including rolling back written content to make room for a multibyte `c`.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 6] = [0xff; 6];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "abcde").unwrap();
+    /// assert_eq!(Err(6), writer.finish_with_char('…'));
+    /// assert_eq!("abc…", std::str::from_utf8(&buf).unwrap());
+    /// ```
+    pub fn finish_with_char(self, c: char) -> Result<usize, usize> {
+        self.finish_with_or_char(c, c)
+    }
+
+    /// Finish the buffer by adding the character `normal` if not truncated or `truncated` if the buffer will be
+    /// truncated. This operates the same as [`WriteBuf::finish_with_or`] in every other way.
+    pub fn finish_with_or_char(self, normal: char, truncated: char) -> Result<usize, usize> {
+        let mut normal_buf = [0u8; 4];
+        let mut truncated_buf = [0u8; 4];
+        self.finish_with_or(
+            normal.encode_utf8(&mut normal_buf),
+            truncated.encode_utf8(&mut truncated_buf),
+        )
+    }
+
+    /// Finish the buffer with a suffix chosen by `f`, which is given a [`FinishContext`] describing the state of the
+    /// buffer. This is useful when neither of two fixed suffixes is right for every buffer size. The returned suffix is
+    /// placed the same way as [`WriteBuf::finish_with`], so it still might roll back written content.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// fn marker(ctx: fmtbuf::FinishContext) -> &'static str {
+    ///     match (ctx.truncated(), ctx.remaining()) {
+    ///         (false, _) => "",
+    ///         (true, 0) => "",
+    ///         (true, 1..=2) => ".",
+    ///         (true, _) => "…",
+    ///     }
+    /// }
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::with_reserve(&mut buf, 1);
+    /// write!(writer, "overflow").unwrap_err();
+    /// assert_eq!(Err(8), writer.finish_with_fn(marker));
+    /// assert_eq!(b"overflo.", &buf);
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`].
+    pub fn finish_with_fn<'s, F>(mut self, f: F) -> Result<usize, usize>
+    where
+        F: FnOnce(FinishContext) -> &'s str,
+    {
+        let suffix = f(FinishContext {
+            position: self.position(),
+            remaining: self._unwritten_len(),
+            truncated: self.truncated(),
+        });
+        self._finish_with(suffix, suffix);
+        self._result()
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with_or`], but return a [`FinishReport`] describing what
+    /// happened instead of a plain `Result`. This allows a caller to tell content truncation apart from the suffix not
+    /// fitting where it was supposed to.
+    ///
+    /// ```
+    /// use fmtbuf::{SuffixKind, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "finished").unwrap();
+    ///
+    /// // All the content fit, but there is no space left for the suffix
+    /// let report = writer.finish_report("\0", "...\0");
+    /// assert!(report.suffix_degraded);
+    /// assert!(!report.content_truncated);
+    /// assert_eq!(report.written_len, 8);
+    /// assert_eq!(report.suffix_applied, SuffixKind::RolledBack);
+    /// assert_eq!(&buf, b"fini...\0");
+    /// ```
+    ///
+    /// The report also counts the `write_str` calls made and the input bytes dropped by them, which is useful for
+    /// metrics about how much output is being lost:
+    ///
+    /// ```
+    /// use fmtbuf::{SuffixKind, WriteBuf};
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::with_reserve(&mut buf, 1);
+    /// let _ = writer.write_str("abc");
+    /// let _ = writer.write_str("defghi");
+    /// let _ = writer.write_str("jkl");
+    /// let report = writer.finish_report("", "~");
+    /// assert_eq!(report.write_calls, 3);
+    /// assert_eq!(report.dropped_input_bytes, 5);
+    /// assert_eq!(report.suffix_applied, SuffixKind::Truncated);
+    /// assert_eq!(&buf, b"abcdefg~");
+    /// ```
+    pub fn finish_report(mut self, normal_suffix: &str, truncated_suffix: &str) -> FinishReport {
+        let required_len = self
+            .front
+            .len()
+            .saturating_add(self.required_len())
+            .saturating_add(normal_suffix.len());
+        let suffix_applied = self._finish_with(normal_suffix, truncated_suffix);
+        self._report(suffix_applied, required_len)
+    }
+
+    /// Finish the buffer by formatting `normal` if not truncated or `truncated` if the buffer will be truncated. The
+    /// chosen suffix is rendered directly into the end of the target buffer, so a marker like `"(+{} more)"` does not
+    /// need to be formatted into a temporary buffer first. Placement works the same as [`WriteBuf::finish_with_or`]: as
+    /// much of the end of the suffix as fits is kept, never splitting a UTF-8 sequence.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 12] = [0xff; 12];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let items = ["alpha", "beta", "gamma", "delta"];
+    /// let mut count = 0;
+    /// for item in items.iter() {
+    ///     if write!(writer, "{item} ").is_err() {
+    ///         break;
+    ///     }
+    ///     count += 1;
+    /// }
+    /// let more = items.len() - count;
+    /// let write_len = writer
+    ///     .finish_with_fmt(format_args!(""), format_args!("(+{more} more)"))
+    ///     .unwrap_err();
+    /// assert_eq!(std::str::from_utf8(&buf[..write_len]).unwrap(), "alp(+2 more)");
+    /// ```
+    ///
+    /// Note that the suffix might be formatted more than once: once to find out it does not fit in the remaining space
+    /// and again to write it at the correct position. Suffixes whose output changes between formatting calls will lead
+    /// to surprising (but still valid UTF-8) results.
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`].
+    pub fn finish_with_fmt(mut self, normal: fmt::Arguments, truncated: fmt::Arguments) -> Result<usize, usize> {
+        self._finish_with_fmt(normal, truncated);
+        self._result()
+    }
+
+    /// Finish writing to the buffer the same way as [`WriteBuf::finish`], but return the written contents of the target
+    /// buffer instead of its length. This saves re-validating the UTF-8 the writer already guarantees.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "x={}", 1.5).unwrap();
+    /// assert_eq!(Ok("x=1.5"), writer.finish_str());
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The `Ok` case indicates truncation did not occur, while `Err` indicates that it did.
+    pub fn finish_str(self) -> Result<&'a str, &'a str> {
+        self.finish_with_or_str("", "")
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with`], but return the written contents. See
+    /// [`WriteBuf::finish_str`].
+    pub fn finish_with_str(self, suffix: &str) -> Result<&'a str, &'a str> {
+        self.finish_with_or_str(suffix, suffix)
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with_or`], but return the written contents. See
+    /// [`WriteBuf::finish_str`].
+    pub fn finish_with_or_str(mut self, normal_suffix: &str, truncated_suffix: &str) -> Result<&'a str, &'a str> {
+        self._finish_with(normal_suffix, truncated_suffix);
+        let truncated = self.truncated();
+        let written = written_str(self.target.into_read_back(self.position.get()));
+        if truncated {
+            Err(written)
+        } else {
+            Ok(written)
+        }
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_str`], but also return the unwritten rest of the target
+    /// buffer. This is useful for appending data which is not text, such as a binary payload after a text header, without
+    /// having to slice the original buffer at the right place.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 16] = [0; 16];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// write!(writer, "len={};", 3).unwrap();
+    /// let (header, tail) = writer.finish_split();
+    /// assert_eq!(Ok("len=3;"), header);
+    /// tail[..3].copy_from_slice(&[1, 2, 3]);
+    /// assert_eq!(b"len=3;\x01\x02\x03", &buf[..9]);
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The written contents, with the same meaning as [`WriteBuf::finish_str`], and the bytes after them. The tail
+    /// includes the reserve, since nothing else will be written to it. For a writer made by [`WriteBuf::new_uninit`],
+    /// the part of the tail which was never written is zeroed.
+    pub fn finish_split(self) -> (Result<&'a str, &'a str>, &'a mut [u8]) {
+        let truncated = self.truncated();
+        let (written, tail) = self.target.into_split(self.position.get());
+        let written = written_str(written);
+        if truncated {
+            (Err(written), tail)
+        } else {
+            (Ok(written), tail)
+        }
+    }
+
+    /// Finish the buffer with a single NUL terminator and return the written contents as a `CStr`. This is useful for
+    /// filling in `char[]` fields of C structures. If the buffer is full, written content is rolled back to the last
+    /// complete UTF-8 sequence that leaves room for the terminator, so the result is always terminated.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut name: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new_cstr(&mut name);
+    /// write!(writer, "eth{}", 0).unwrap();
+    /// assert_eq!(b"eth0", writer.finish_cstr().unwrap().to_bytes());
+    /// assert_eq!(b"eth0\0", &name[..5]);
+    ///
+    /// let mut writer = WriteBuf::new_cstr(&mut name);
+    /// write!(writer, "wlp0s20f3").unwrap_err();
+    /// assert_eq!(b"wlp0s20", writer.finish_cstr().unwrap_err().to_bytes());
+    /// ```
+    ///
+    /// Creating the writer with [`WriteBuf::new_cstr`] keeps the last byte free for the terminator, so content does not
+    /// need to be rolled back to make room for it.
+    ///
+    /// # Returns
+    ///
+    /// The `Ok` case indicates truncation did not occur, while `Err` indicates that it did. If content containing a NUL
+    /// was written, the returned `CStr` ends at the first one, although everything written is still in the buffer. If
+    /// the target buffer is empty, there is no room for a terminator, so this returns `Err` with an empty `CStr` which
+    /// does not point into the target buffer. The same happens with a strict writer (see [`WriteBuf::new_strict`])
+    /// which has no room for the terminator after the written content.
+    pub fn finish_cstr(mut self) -> Result<&'a core::ffi::CStr, &'a core::ffi::CStr> {
+        self._finish_with("\0", "\0");
+        let truncated = self.truncated();
+        let written = self.target.into_read_back(self.position.get());
+        let cstr = match written.iter().position(|cu| *cu == 0) {
+            Some(nul_idx) => core::ffi::CStr::from_bytes_with_nul(&written[..=nul_idx]).ok(),
+            None => None,
+        };
+        match cstr {
+            Some(cstr) if !truncated => Ok(cstr),
+            Some(cstr) => Err(cstr),
+            // only an empty target has no room for the terminator
+            None => Err(Default::default()),
+        }
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish`], pass the finished contents to `f`, then reset this writer
+    /// so it can be reused. This is useful for a long-lived writer, such as one stored in a struct, that emits one
+    /// message at a time.
+    ///
+    /// ```
+    /// use fmtbuf::WriteBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 8] = [0xff; 8];
+    /// let mut writer = WriteBuf::new(&mut buf);
+    /// let mut lines = Vec::new();
+    /// for i in [1, 22, 333] {
+    ///     write!(writer, "#{i}").unwrap();
+    ///     writer.finish_and_reset(|line| lines.push(line.to_vec())).unwrap();
+    /// }
+    /// assert_eq!(lines, [b"#1".to_vec(), b"#22".to_vec(), b"#333".to_vec()]);
+    /// ```
+    ///
+    /// Resetting sets the [`WriteBuf::position`] back to 0 and clears [`WriteBuf::truncated`]. The
+    /// [`WriteBuf::reserve`] is kept.
+    ///
+    /// # Returns
+    ///
+    /// The returned value has the same meaning as [`WriteBuf::finish`].
+    pub fn finish_and_reset<F>(&mut self, f: F) -> Result<usize, usize>
+    where
+        F: FnOnce(&[u8]),
+    {
+        self.finish_with_or_and_reset("", "", f)
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with`], then pass the finished contents to `f` and reset
+    /// this writer. See [`WriteBuf::finish_and_reset`].
+    pub fn finish_with_and_reset<F>(&mut self, suffix: &str, f: F) -> Result<usize, usize>
+    where
+        F: FnOnce(&[u8]),
+    {
+        self.finish_with_or_and_reset(suffix, suffix, f)
+    }
+
+    /// Finish the buffer the same way as [`WriteBuf::finish_with_or`], then pass the finished contents to `f` and reset
+    /// this writer. See [`WriteBuf::finish_and_reset`].
+    pub fn finish_with_or_and_reset<F>(
+        &mut self,
+        normal_suffix: &str,
+        truncated_suffix: &str,
+        f: F,
+    ) -> Result<usize, usize>
+    where
+        F: FnOnce(&[u8]),
+    {
+        self._finish_with(normal_suffix, truncated_suffix);
+        let result = self._result();
+        f(self.written_bytes());
+        self._reset();
+        result
+    }
+
+    /// Forget all written contents, truncation state and counters, keeping the reserve.
+    fn _reset(&mut self) {
+        self.position.set(0);
+        self.content_truncated = false;
+        self.capacity_truncated = false;
+        self.suffix_degraded = false;
+        self.segment_start = NO_SEGMENT;
+        self.dropped_input_bytes = 0;
+        self.write_calls = 0;
+    }
+
+    fn _result(&self) -> Result<usize, usize> {
+        let len = self.front.len() + self.position();
+        if self.truncated() {
+            Err(len)
+        } else {
+            Ok(len)
+        }
+    }
+
+    fn _report(&self, suffix_applied: SuffixKind, required_len: usize) -> FinishReport {
+        FinishReport {
+            written_len: self.front.len() + self.position(),
+            content_truncated: self.content_truncated,
+            suffix_degraded: self.suffix_degraded,
+            dropped_input_bytes: self.dropped_input_bytes,
+            suffix_applied,
+            write_calls: self.write_calls,
+            required_len,
+        }
+    }
+
+    fn _finish_with_fmt(&mut self, normal: fmt::Arguments, truncated: fmt::Arguments) -> SuffixKind {
+        // Attempt to render the suffix into the unwritten tail of the buffer
+        for (suffix, should_test, kind) in [
+            (normal, !self.truncated(), SuffixKind::Normal),
+            (truncated, true, SuffixKind::Truncated),
+        ] {
+            if !should_test {
+                continue;
+            }
+
+            let position = self.position();
+            let mut tail = TailWriter::new(&mut self.target, position, 0);
+            let _ = fmt::write(&mut tail, suffix);
+            if !tail.overflowed {
+                self.position.set(position + tail.position);
+                return kind;
+            }
+
+            // we attempted to perform a write, but rejected it; the suffix is only measured if the hook needs it
+            let needed = if self.on_truncate.is_some() { measure(suffix) } else { 0 };
+            self._degrade_suffix(needed);
+        }
+
+        if self.strict {
+            return SuffixKind::Omitted;
+        }
+
+        // The suffix did not fit, so measure it to figure out where it should be placed
+        let suffix_len = measure(truncated);
+
+        // if the suffix is larger than the entire target buffer, keep the last N
+        if self.target.capacity() < suffix_len {
+            self._drop_content_after(0);
+            let skip = suffix_len - self.target.capacity();
+            let mut tail = TailWriter::new(&mut self.target, 0, skip);
+            let _ = fmt::write(&mut tail, truncated);
+            self.position.set(tail.position);
+            return SuffixKind::Cut;
+        }
+
+        // Only scan the written content, even if the suffix rendered shorter this time
+        let potential_end_idx = (self.target.capacity() - suffix_len).min(self.position());
+        let write_idx = policy::cut_point(self.policy, self.written_bytes(), potential_end_idx);
+        self._drop_content_after(write_idx);
+        let mut tail = TailWriter::new(&mut self.target, write_idx, 0);
+        let _ = fmt::write(&mut tail, truncated);
+        self.position.set(write_idx + tail.position);
+        SuffixKind::RolledBack
+    }
+
+    /// Apply the suffix logic for the [`WriteBuf::finish_with`] family of functions. After this is called, the
+    /// [`WriteBuf::position`] is the end of the suffix.
+    fn _finish_with(&mut self, normal: &str, truncated: &str) -> SuffixKind {
+        self._finish_with_trim(normal, truncated, false)
+    }
+
+    /// Apply the suffix logic the same way as [`WriteBuf::_finish_with`]. If `trim` is set, trailing whitespace is
+    /// removed from the written content before placing the suffix and again after rolling back to make room for it.
+    fn _finish_with_trim(&mut self, normal: &str, truncated: &str, trim: bool) -> SuffixKind {
+        debug_assert!(
+            self.suffixes.is_none() || normal.len().max(truncated.len()) <= self.reserve,
+            "suffix is longer than the reserve set by WriteBuf::with_reserve_for"
+        );
+        if trim {
+            let trimmed_len = self.written().trim_end().len();
+            self.position.set(trimmed_len);
+        }
+
+        let remaining = self._unwritten_len();
+
+        // If the truncated case is shorter than the normal case, then writing it might still work
+        for (suffix, should_test, kind) in [
+            (normal, !self.truncated(), SuffixKind::Normal),
+            (truncated, true, SuffixKind::Truncated),
+        ] {
+            if !should_test {
+                continue;
+            }
+
+            // enough room in the buffer to write entire suffix, so just write it
+            if suffix.len() <= remaining {
+                let position = self.position();
+                self.target.write_at(position, suffix.as_bytes());
+                self.position.set(position + suffix.len());
+                return kind;
+            }
+
+            // we attempted to perform a write, but rejected it
+            self._degrade_suffix(suffix.len());
+        }
+
+        if self.strict {
+            return SuffixKind::Omitted;
+        }
+
+        // Move the position back before overwriting content, so readers never see partially-copied bytes
+        let (mut write_idx, suffix) =
+            rollback_suffix(self.written_bytes(), self.target.capacity(), truncated, self.policy);
+        if trim {
+            write_idx = written_str(self.target.read_back(write_idx)).trim_end().len();
+        }
+        self._drop_content_after(write_idx);
+        self.target.write_at(write_idx, suffix.as_bytes());
+        self.position.set(write_idx + suffix.len());
+        if suffix.len() < truncated.len() {
+            SuffixKind::Cut
+        } else {
+            SuffixKind::RolledBack
+        }
+    }
+
+    /// Roll the [`WriteBuf::position`] back to `idx` to make room for a suffix, counting the written content after it as
+    /// dropped.
+    fn _drop_content_after(&mut self, idx: usize) {
+        let position = self.position();
+        self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(position.saturating_sub(idx));
+        self.position.set(idx);
+    }
+
+    /// Get the number of bytes after the written content, including the reserve. The position never passes the end of
+    /// the target buffer, but this saturates instead of relying on it, so it can never panic.
+    fn _unwritten_len(&self) -> usize {
+        self.target.capacity().saturating_sub(self.position())
+    }
+
+    /// Get the error for a write of `needed` bytes which does not fit before the reserve.
+    fn _capacity_error(&self, needed: usize) -> WriteError {
+        let available = self.remaining();
+        if needed <= self._unwritten_len() {
+            WriteError::ReserveBlocked { needed, available }
+        } else {
+            WriteError::Capacity { needed, available }
+        }
+    }
+
+    /// Write all of `s` or none of it, for the [`Extend`] implementations. If `s` does not fit, the buffer is marked as
+    /// truncated and `false` is returned, so the caller stops taking items.
+    fn _extend_one(&mut self, s: &str) -> bool {
+        if s.is_empty() {
+            return true;
+        }
+        if self._latched() || s.len() > self.remaining() {
+            self._mark_truncated(s.len());
+            self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(s.len());
+            return false;
+        }
+        self._write(s.as_bytes()).is_ok()
+    }
+
+    /// Copy `s` into room which was kept in the reserve, even if the buffer is truncated and truncation latches. If the
+    /// room was taken anyway, such as by changing the reserve while it was kept, nothing is written.
+    fn _write_reserved(&mut self, s: &str) {
+        let position = self.position();
+        if position.saturating_add(s.len()) <= self.target.capacity() {
+            self.target.write_at(position, s.as_bytes());
+            self.position.set(position + s.len());
+        }
+    }
+
+    /// Mark the content as truncated by a write of `needed` bytes, remembering if it would not have fit even without the
+    /// reserve.
+    fn _mark_truncated(&mut self, needed: usize) {
+        let was_truncated = self.truncated();
+        self.content_truncated = true;
+        if needed > self._unwritten_len() {
+            self.capacity_truncated = true;
+        }
+        self._notify_truncated(was_truncated, TruncationSource::Write, needed, self.remaining());
+    }
+
+    /// Mark the suffix as degraded because a suffix of `needed` bytes, along with the written content, did not fit.
+    fn _degrade_suffix(&mut self, needed: usize) {
+        let was_truncated = self.truncated();
+        self.suffix_degraded = true;
+        self._notify_truncated(was_truncated, TruncationSource::Finish, needed, self._unwritten_len());
+    }
+
+    /// Call the hook set by [`WriteBuf::on_truncate`] for `needed` bytes which did not fit in `available`, unless the
+    /// buffer `was_truncated` and the hook only wants the first truncation.
+    fn _notify_truncated(&self, was_truncated: bool, source: TruncationSource, needed: usize, available: usize) {
+        if let Some(hook) = self.on_truncate {
+            if !was_truncated || self.truncate_hook_repeats {
+                hook(&TruncationEvent {
+                    position: self.position(),
+                    len: needed,
+                    overflow: needed.saturating_sub(available),
+                    source,
+                });
+            }
+        }
+    }
+
+    fn _write(&mut self, input: &[u8]) -> fmt::Result {
+        self._write_partial(input).map_err(|_| fmt::Error)
+    }
+
+    /// Perform a write like [`WriteBuf::_write`], but return the number of bytes of `input` which were copied if it did
+    /// not entirely fit.
+    fn _write_partial(&mut self, input: &[u8]) -> Result<(), usize> {
+        self.write_calls = self.write_calls.saturating_add(1);
+        // Nothing was asked for, so nothing can be lost, even if the buffer is already truncated or full
+        if input.is_empty() {
+            return Ok(());
+        }
+
+        // The room before the reserve, or `None` if the written content already encroaches on the reserve
+        let position = self.position();
+        let available = self
+            .target
+            .capacity()
+            .saturating_sub(position)
+            .checked_sub(self.reserve);
+        match available {
+            Some(available) if input.len() <= available && !self._latched() => {
+                // Copy the bytes before publishing the new position, so a reader never sees a length covering uncopied
+                // bytes
+                self.target.write_at(position, input);
+                self.position.set(position + input.len());
+                Ok(())
+            },
+            _ => self._write_truncated(input, position, available),
+        }
+    }
+
+    /// The slow path of [`WriteBuf::_write_partial`], for an `input` which does not entirely fit.
+    #[cold]
+    fn _write_truncated(&mut self, input: &[u8], position: usize, available: Option<usize>) -> Result<(), usize> {
+        if self._latched() {
+            self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(input.len());
+            return Err(0);
+        }
+
+        self._mark_truncated(input.len());
+        let copied = match available {
+            Some(available) if !self.strict => policy::cut_point(self.policy, input, available),
+            _ => 0,
+        };
+        self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(input.len() - copied);
+        self.target.write_at(position, &input[..copied]);
+        self.position.set(position + copied);
+        Err(copied)
+    }
+
+    /// Write `c` `count` times as a single write, cutting only between whole copies of `c`.
+    fn _write_repeated(&mut self, c: char, count: usize) -> fmt::Result {
+        self._start_segment();
+        self._fill(c, count)
+    }
+
+    /// Write `c` `count` times like [`WriteBuf::_write_repeated`], as part of the current segment.
+    fn _fill(&mut self, c: char, count: usize) -> fmt::Result {
+        self.write_calls = self.write_calls.saturating_add(1);
+        let len = c.len_utf8();
+        let needed = len.saturating_mul(count);
+        let latched = self._latched();
+        let fits = if latched || (self.strict && needed > self.remaining()) {
+            0
+        } else {
+            count.min(self.remaining() / len)
+        };
+        if fits < count && !latched {
+            self._mark_truncated(needed);
+        }
+
+        let position = self.position();
+        fill_repeated(&mut self.target, position..position + fits * len, c);
+        self.position.set(position + fits * len);
+        if fits < count {
+            self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(needed - fits * len);
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+
+    /// Write `bytes` in hexadecimal for [`WriteBuf::write_hex_bytes`] and [`WriteBuf::write_hex_bytes_sep`], cutting
+    /// after a whole byte if it does not fit.
+    fn _write_hex_bytes(&mut self, bytes: &[u8], upper: bool, separator: Option<char>) -> fmt::Result {
+        self._start_segment();
+        self.write_calls = self.write_calls.saturating_add(1);
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        let mut encoded_separator = [0u8; 4];
+        let separator = separator.map_or(&[][..], |c| c.encode_utf8(&mut encoded_separator).as_bytes());
+        // Every byte after the first one takes a separator as well as its digits
+        let step = 2 + separator.len();
+        let needed = step.saturating_mul(bytes.len()) - separator.len();
+        let latched = self._latched();
+        let remaining = self.remaining();
+        let fits = if latched || (needed > remaining && (self.strict || remaining < 2)) {
+            0
+        } else {
+            bytes.len().min(1 + (remaining - 2) / step)
+        };
+        if fits < bytes.len() && !latched {
+            self._mark_truncated(needed);
+        }
+
+        let digit_chars: &[u8; 16] = if upper {
+            b"0123456789ABCDEF"
+        } else {
+            b"0123456789abcdef"
+        };
+        let mut chunk = [0u8; 128];
+        let mut used = 0;
+        let mut position = self.position();
+        for (idx, byte) in bytes[..fits].iter().enumerate() {
+            if used + step > chunk.len() {
+                self.target.write_at(position, &chunk[..used]);
+                position += used;
+                used = 0;
+            }
+            if idx > 0 {
+                chunk[used..used + separator.len()].copy_from_slice(separator);
+                used += separator.len();
+            }
+            chunk[used] = digit_chars[usize::from(byte >> 4)];
+            chunk[used + 1] = digit_chars[usize::from(byte & 0xf)];
+            used += 2;
+        }
+        self.target.write_at(position, &chunk[..used]);
+        self.position.set(position + used);
+
+        if fits < bytes.len() {
+            let written = if fits == 0 { 0 } else { step * fits - separator.len() };
+            self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(needed - written);
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+
+    /// Write `bytes` in base64 for [`WriteBuf::write_base64`] and [`Base64Writer`], cutting after a whole group of 3
+    /// bytes if it does not fit.
+    fn _write_base64(&mut self, bytes: &[u8], config: Base64Config) -> fmt::Result {
+        self._start_segment();
+        self.write_calls = self.write_calls.saturating_add(1);
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        let needed = config.encoded_len(bytes.len());
+        let latched = self._latched();
+        let room = if latched || (self.strict && needed > self.remaining()) {
+            0
+        } else {
+            self.remaining()
+        };
+        let full_groups = bytes.len() / 3;
+        let fit_groups = full_groups.min(room / 4);
+        // The last group of fewer than 3 bytes is written entirely or not at all, like the full groups
+        let tail = &bytes[full_groups * 3..];
+        let fits = fit_groups == full_groups && config.tail_len(tail.len()) <= room - 4 * fit_groups;
+        if !fits && !latched {
+            self._mark_truncated(needed);
+        }
+
+        let mut chunk = [0u8; 128];
+        let mut used = 0;
+        let mut position = self.position();
+        let groups = bytes[..fit_groups * 3]
+            .chunks(3)
+            .chain(if fits { Some(tail) } else { None });
+        for group in groups.filter(|group| !group.is_empty()) {
+            if used + 4 > chunk.len() {
+                self.target.write_at(position, &chunk[..used]);
+                position += used;
+                used = 0;
+            }
+            used += config.encode_group(group, &mut chunk[used..]);
+        }
+        self.target.write_at(position, &chunk[..used]);
+        self.position.set(position + used);
+
+        if !fits {
+            self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(needed - 4 * fit_groups);
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+
+    /// Write `value` in decimal for the `write_u32` family of functions.
+    fn _write_decimal(&mut self, value: u64, negative: bool) -> fmt::Result {
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        let formatted = int::decimal_u64(value, negative, &mut scratch);
+        self._write_whole(formatted)
+    }
+
+    /// Write all of `bytes` as a single write or none of it, marking the buffer as truncated if it does not fit. This is
+    /// for content which is meaningless if it is cut, such as a number. `bytes` must be valid UTF-8.
+    fn _write_whole(&mut self, bytes: &[u8]) -> fmt::Result {
+        let position = self._claim_whole(bytes.len())?;
+        self.target.write_at(position, bytes);
+        self.position.set(position + bytes.len());
+        Ok(())
+    }
+
+    /// Start a write of `len` bytes which must be written entirely or not at all, like [`WriteBuf::_write_whole`]. If
+    /// they fit, the position to write them at is returned, and the caller copies them and moves the position past them.
+    fn _claim_whole(&mut self, len: usize) -> Result<usize, fmt::Error> {
+        self._start_segment();
+        self.write_calls = self.write_calls.saturating_add(1);
+        let latched = self._latched();
+        if !latched && len <= self.remaining() {
+            return Ok(self.position());
+        }
+
+        if !latched {
+            self._mark_truncated(len);
+        }
+        self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(len);
+        Err(fmt::Error)
+    }
+
+    /// Write `value` for [`WriteBuf::write_uint_grouped`] and [`WriteBuf::write_int_grouped`].
+    fn _write_grouped(&mut self, value: u64, negative: bool, separator: char, grouping: Grouping) -> fmt::Result {
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        let digits = int::decimal_u64(value, false, &mut scratch);
+        let len = grouping.len(digits.len(), separator) + usize::from(negative);
+        self._write_rendered(len, |out| grouping.render(digits, negative, separator, out))
+    }
+
+    /// Write `value` with `bits` bits per digit for [`WriteBuf::write_hex`] and friends.
+    fn _write_radix(&mut self, value: u64, bits: u32, prefix: &str, format: IntFormat) -> fmt::Result {
+        self._write_rendered(format.len(value, bits), |out| format.render(value, bits, prefix, out))
+    }
+
+    /// Write `len` bytes entirely or not at all, like [`WriteBuf::_write_whole`], where the bytes are passed in pieces
+    /// by `render` instead of all at once.
+    fn _write_rendered(&mut self, len: usize, render: impl FnOnce(&mut dyn FnMut(&[u8]))) -> fmt::Result {
+        let position = self._claim_whole(len)?;
+        let mut end = position;
+        let target = &mut self.target;
+        render(&mut |piece| {
+            target.write_at(end, piece);
+            end += piece.len();
+        });
+        debug_assert_eq!(position + len, end, "rendered a different length than claimed");
+        self.position.set(end);
+        Ok(())
+    }
+
+    /// Encode `c` at the position and move past it, without checking that it fits.
+    fn _write_char_unchecked(&mut self, c: char) {
+        let mut encoded = [0u8; 4];
+        let encoded = c.encode_utf8(&mut encoded).as_bytes();
+        let position = self.position();
+        self.target.write_at(position, encoded);
+        self.position.set(position + encoded.len());
+    }
+}
+
+/// Convert contents written by a [`WriteBuf`] to a `&str`.
+fn written_str(written: &[u8]) -> &str {
+    #[cfg(debug_assertions)]
+    return core::str::from_utf8(written).expect("contents of buffer should have been UTF-8 encoded");
+
+    // safety: The only way to write into the buffer is with valid UTF-8, so there is no reason to check the contents
+    // for validity. They're still checked in debug builds just in case, though.
+    #[cfg(not(debug_assertions))]
+    unsafe {
+        core::str::from_utf8_unchecked(written)
+    }
+}
+
+/// Remove a single `"\r\n"`, `"\n"` or `"\r"` from the end of `s`.
+fn strip_line_ending(s: &str) -> &str {
+    s.strip_suffix("\r\n")
+        .or_else(|| s.strip_suffix('\n'))
+        .or_else(|| s.strip_suffix('\r'))
+        .unwrap_or(s)
+}
+
+/// Fill `range` of `target` with copies of `fill`. If the length of `range` is not a multiple of the encoded length of
+/// `fill`, the remainder is filled with ASCII spaces.
+fn fill_repeated<'a, T: Target<'a>>(target: &mut T, range: core::ops::Range<usize>, fill: char) {
+    // Copy a chunk of whole `fill` characters at a time instead of one character at a time
+    let len = fill.len_utf8();
+    let mut chunk = [0u8; 64];
+    let chunk_len = chunk.len() / len * len;
+    for start in (0..chunk_len).step_by(len) {
+        fill.encode_utf8(&mut chunk[start..start + len]);
+    }
+    let mut idx = range.start;
+    while idx + len <= range.end {
+        let copied = ((range.end - idx) / len * len).min(chunk_len);
+        target.write_at(idx, &chunk[..copied]);
+        idx += copied;
+    }
+    for idx in idx..range.end {
+        target.write_at(idx, b" ");
+    }
+}
+
+/// Shorten the valid UTF-8 content at the start of `buf` to at most `max_len` bytes in place, ending it with `marker`
+/// if shortening was needed. This uses the same logic as [`WriteBuf::finish_with`] to place the marker, without
+/// needing to copy the content through a [`WriteBuf`].
+///
+/// ```
+/// let mut buf = *b"connection refused by peer";
+/// let len = buf.len();
+/// let new_len = fmtbuf::truncate_in_place(&mut buf, len, 16, "...");
+/// assert_eq!(&buf[..new_len], b"connection re...");
+/// ```
+///
+/// # Parameters
+///
+/// * `buf`: The buffer holding the content. Bytes after `content_len` are ignored.
+/// * `content_len`: The length of the valid UTF-8 content at the start of `buf`.
+/// * `max_len`: The maximum length the content is allowed to have. This is clamped to `buf.len()`.
+/// * `marker`: The marker to end the content with if it had to be shortened. If `marker` is longer than `max_len`, then
+///   only the end of it is kept (starting at a valid UTF-8 sequence start), just like a suffix passed to
+///   [`WriteBuf::finish_with`].
+///
+/// # Returns
+///
+/// The new length of the content. If `content_len <= max_len`, the buffer is not modified and `content_len` is returned.
+pub fn truncate_in_place(buf: &mut [u8], content_len: usize, max_len: usize, marker: &str) -> usize {
+    let content_len = content_len.min(buf.len());
+    if content_len <= max_len {
+        return content_len;
+    }
+
+    // The content can go past `max_len`, which lets the cut see what comes after it
+    let (write_idx, marker) = rollback_suffix(&buf[..content_len], max_len, marker, &CodePoint);
+    buf[write_idx..write_idx + marker.len()].copy_from_slice(marker.as_bytes());
+    write_idx + marker.len()
+}
+
+/// Figure out where `suffix` should be placed so that it ends before the end of a target of `target_len` bytes, assuming
+/// it does not fit after the `written` content at the start of the target. If `suffix` fits into the target, it is
+/// placed after the cut point `policy` picks in front of it. If it is larger than the entire target, only the end of it
+/// is kept, starting at a `char` boundary.
+///
+/// Only `written` is scanned, never the rest of the target, which might not be initialized.
+///
+/// # Returns
+///
+/// The index of the target the suffix should be written to and the portion of `suffix` to write there.
+fn rollback_suffix<'s>(
+    written: &[u8],
+    target_len: usize,
+    suffix: &'s str,
+    policy: &dyn TruncationPolicy,
+) -> (usize, &'s str) {
+    // if the suffix is larger than the entire target buffer, copy the last N
+    if target_len < suffix.len() {
+        let mut start = suffix.len() - target_len;
+        while !suffix.is_char_boundary(start) {
+            start += 1;
+        }
+        return (0, &suffix[start..]);
+    }
+
+    // Scan backwards to find the position we should write to (can't interrupt a UTF-8 multibyte sequence)
+    let potential_end_idx = (target_len - suffix.len()).min(written.len());
+    (policy::cut_point(policy, written, potential_end_idx), suffix)
+}
+
+/// Split `s` into the pieces [`WriteBuf::write_middle_truncated_with`] writes to keep it within `max_bytes`: the start of
+/// it, the marker and the end of it. When `s` fits, it is the first piece and the others are empty.
+fn split_middle<'s>(s: &'s str, max_bytes: usize, marker: &'s str, head_percent: u8) -> [&'s str; 3] {
+    if s.len() <= max_bytes {
+        return [s, "", ""];
+    }
+    if marker.len() > max_bytes {
+        let end = utf8::floor_char_boundary(marker.as_bytes(), max_bytes);
+        return ["", &marker[..end], ""];
+    }
+
+    let room = max_bytes - marker.len();
+    let head_room = room * usize::from(head_percent.min(100)) / 100;
+    let head = &s[..utf8::floor_char_boundary(s.as_bytes(), head_room)];
+    let mut tail_start = s.len() - (room - head.len());
+    while !s.is_char_boundary(tail_start) {
+        tail_start += 1;
+    }
+    [head, marker, &s[tail_start..]]
+}
+
+/// Split `path` into the pieces [`WriteBuf::write_path_truncated`] writes to keep it within `room` bytes, along with if
+/// the last component was cut. When `path` fits, it is the first piece and the others are empty.
+fn split_path<'s>(path: &'s str, sep: char, sep_str: &'s str, room: usize) -> ([&'s str; 3], bool) {
+    const MARKER: &str = "…";
+    if path.len() <= room {
+        return ([path, "", ""], false);
+    }
+
+    // Look for the first component after a run of separators which fits along with the marker
+    let mut last = 0;
+    let mut prev = None;
+    for (idx, c) in path.char_indices() {
+        if prev == Some(sep) && c != sep {
+            if MARKER.len() + sep_str.len() + (path.len() - idx) <= room {
+                return ([MARKER, sep_str, &path[idx..]], false);
+            }
+            last = idx;
+        }
+        prev = Some(c);
+    }
+
+    // Leave out at least one byte of the last component, so it is always cut and gets the marker
+    let component = &path[last..];
+    let max_bytes = room.min(component.len() - 1);
+    if max_bytes < MARKER.len() {
+        let end = utf8::floor_char_boundary(MARKER.as_bytes(), room);
+        return (["", &MARKER[..end], ""], true);
+    }
+    (split_middle(component, max_bytes, MARKER, 50), true)
+}
+
+/// The state of a [`WriteBuf`] at some point in time, created by [`WriteBuf::checkpoint`] and restored by
+/// [`WriteBuf::rollback_to`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    target: usize,
+    position: usize,
+    content_truncated: bool,
+    capacity_truncated: bool,
+    suffix_degraded: bool,
+    dropped_input_bytes: usize,
+}
+
+impl Checkpoint {
+    /// Get the [`WriteBuf::position`] at the time this checkpoint was taken.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// A [`WriteBuf`] lent out by [`WriteBuf::reborrow`].
+///
+/// This dereferences to the borrowed [`WriteBuf`], so all non-consuming operations are available, and writes through
+/// [`fmt::Write`] behave exactly as they do on the original.
+pub struct WriteBufRef<'b, 'a, T = SliceTarget<'a>>(&'b mut WriteBuf<'a, T>);
+
+impl<'b, 'a, T: Target<'a>> WriteBufRef<'b, 'a, T> {
+    /// Lend this writer out again, so the helper holding it can pass it on and keep using it afterward.
+    pub fn reborrow(&mut self) -> WriteBufRef<'_, 'a, T> {
+        WriteBufRef(self.0)
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> core::ops::Deref for WriteBufRef<'b, 'a, T> {
+    type Target = WriteBuf<'a, T>;
+
+    fn deref(&self) -> &WriteBuf<'a, T> {
+        self.0
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> core::ops::DerefMut for WriteBufRef<'b, 'a, T> {
+    fn deref_mut(&mut self) -> &mut WriteBuf<'a, T> {
+        self.0
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for WriteBufRef<'b, 'a, T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.0.write_char(c)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        self.0.write_fmt(args)
+    }
+}
+
+/// A [`WriteBuf`] lent out by [`WriteBuf::lossy`].
+///
+/// Writes through [`fmt::Write`] are passed on to the borrowed [`WriteBuf`], but always return `Ok(())`, even if they
+/// were truncated or rejected. Content which does not fit is dropped just as it would be on the original, so a `write!`
+/// whose earlier pieces were cut still drops the pieces after them.
+pub struct Lossy<'b, 'a, T = SliceTarget<'a>>(&'b mut WriteBuf<'a, T>);
+
+impl<'b, 'a, T: Target<'a>> core::ops::Deref for Lossy<'b, 'a, T> {
+    type Target = WriteBuf<'a, T>;
+
+    fn deref(&self) -> &WriteBuf<'a, T> {
+        self.0
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> core::ops::DerefMut for Lossy<'b, 'a, T> {
+    fn deref_mut(&mut self) -> &mut WriteBuf<'a, T> {
+        self.0
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for Lossy<'b, 'a, T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let _ = self.0.write_str(s);
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        let _ = self.0.write_char(c);
+        Ok(())
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        let _ = self.0.write_fmt(args);
+        Ok(())
+    }
+}
+
+/// The state of a [`WriteBuf`] when it is being finished, given to the suffix function of [`WriteBuf::finish_with_fn`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FinishContext {
+    position: usize,
+    remaining: usize,
+    truncated: bool,
+}
+
+impl FinishContext {
+    /// Get the [`WriteBuf::position`] at the end of the written content.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Get the count of bytes after the written content, including the reserve. A suffix no longer than this will be
+    /// placed without rolling back written content.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Get if a truncated write has happened. See [`WriteBuf::truncated`].
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+/// The line terminator used by [`WriteBuf::finish_with_line_ending`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    /// A Unix-style `"\n"`.
+    Lf,
+    /// A Windows-style or network protocol `"\r\n"`.
+    CrLf,
+}
+
+impl LineEnding {
+    /// Get the text of this line ending.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Where [`WriteBuf::write_padded`] puts content in a field which is wider than it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Align {
+    /// At the start of the field, with the fill after it, like `{:<}`. This is the default.
+    #[default]
+    Left,
+    /// At the end of the field, with the fill before it, like `{:>}`.
+    Right,
+    /// In the middle of the field, like `{:^}`. If the fill can not be split evenly, the extra one goes after the
+    /// content.
+    Center,
+}
+
+impl Align {
+    /// Split `gap` fill characters into the ones before the content and after it.
+    fn split(self, gap: usize) -> (usize, usize) {
+        match self {
+            Align::Left => (0, gap),
+            Align::Right => (gap, 0),
+            Align::Center => (gap / 2, gap - gap / 2),
+        }
+    }
+}
+
+/// A writer which uses a limited part of a [`WriteBuf`], made by [`WriteBuf::sub_writer`].
+///
+/// Written content goes directly into the parent buffer, so it is part of the parent's [`WriteBuf::written`] content
+/// as soon as it is written.
+pub struct SubWriteBuf<'b, 'a, T = SliceTarget<'a>> {
+    buf: &'b mut WriteBuf<'a, T>,
+    /// The position in the parent where this writer started.
+    start: usize,
+    /// The position in the parent this writer can not write past.
+    end: usize,
+    /// The number of bytes the parent had left before its reserve when this writer was made.
+    available: usize,
+    truncated: bool,
+}
+
+impl<'b, 'a, T: Target<'a>> SubWriteBuf<'b, 'a, T> {
+    /// Get the number of bytes written by this writer.
+    pub fn position(&self) -> usize {
+        self.buf.position() - self.start
+    }
+
+    /// Get the number of bytes this writer can still write.
+    pub fn remaining(&self) -> usize {
+        self.end - self.buf.position()
+    }
+
+    /// Get if a write to this writer was truncated, either from reaching its budget or the end of the parent.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Get the content written by this writer.
+    pub fn written(&self) -> &str {
+        &self.buf.written()[self.start..]
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for SubWriteBuf<'b, 'a, T> {
+    /// Append `s` to the parent buffer, truncating it in the same way as [`WriteBuf`] does if it goes past the budget.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        if self.buf._latched() || (self.truncated && self.buf.latch) {
+            return Err(fmt::Error);
+        }
+
+        let position = self.buf.position();
+        let room = self.end - position;
+        let mut parent_truncated = false;
+        let to_copy = if s.len() <= room {
+            s.as_bytes()
+        } else {
+            self.truncated = true;
+            // Only running out of room in the parent truncates it, not reaching the budget
+            let needed = position - self.start + s.len();
+            if needed > self.available {
+                self.buf._mark_truncated(s.len());
+                parent_truncated = true;
+            }
+            if self.buf.strict {
+                &s.as_bytes()[..0]
+            } else {
+                &s.as_bytes()[..policy::cut_point(self.buf.policy, s.as_bytes(), room)]
+            }
+        };
+        if parent_truncated {
+            self.buf.dropped_input_bytes = self.buf.dropped_input_bytes.saturating_add(s.len() - to_copy.len());
+        }
+        self.buf.target.write_at(position, to_copy);
+        self.buf.position.set(position + to_copy.len());
+
+        if to_copy.len() == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+/// A description of how a [`WriteBuf`] was finished. See [`WriteBuf::finish_report`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FinishReport {
+    /// The number of bytes written to the target buffer, including the suffix.
+    pub written_len: usize,
+    /// Content passed to `write_str` did not fit into the buffer. See [`WriteBuf::content_truncated`].
+    pub content_truncated: bool,
+    /// The suffix could not be placed after the written content. See [`WriteBuf::suffix_degraded`].
+    pub suffix_degraded: bool,
+    /// The number of bytes passed to `write_str` which are not in the target buffer, including bytes of writes rejected
+    /// because the buffer was already truncated and written content the suffix was placed over. See
+    /// [`WriteBuf::dropped_bytes`].
+    pub dropped_input_bytes: usize,
+    /// How the suffix was placed.
+    pub suffix_applied: SuffixKind,
+    /// The number of `write_str` calls made. Note that a single `write!` makes a call for each piece of the format.
+    pub write_calls: usize,
+    /// The length of a target buffer which would have fit all of the content and the normal suffix. See
+    /// [`WriteBuf::required_len`].
+    pub required_len: usize,
+}
+
+/// How far [`WriteBuf::write_iter`] or [`WriteBuf::write_iter_str`] got through an iterator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriteIterResult {
+    /// The number of items taken from the iterator and written.
+    pub consumed: usize,
+    /// The iterator has no items left. If this is `false`, the next item did not fit and is still in the iterator.
+    pub exhausted: bool,
+}
+
+/// How the [`WriteBuf::finish_with`] family of functions placed the suffix. See [`FinishReport::suffix_applied`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SuffixKind {
+    /// The normal suffix was placed after the written content.
+    Normal,
+    /// The truncated suffix was placed after the written content.
+    Truncated,
+    /// Written content was rolled back to make room for the truncated suffix.
+    RolledBack,
+    /// The truncated suffix is larger than the target buffer, so only the end of it was placed.
+    Cut,
+    /// The suffix did not fit after the written content, so it was left off. This only happens with a strict writer,
+    /// since it does not roll back written content. See [`WriteBuf::new_strict`].
+    Omitted,
+}
+
+/// What did not fit when a [`WriteBuf`] was truncated. See [`WriteBuf::on_truncate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TruncationEvent {
+    /// The position of the buffer when the content was dropped, before any of the write was copied or any content was
+    /// rolled back.
+    pub position: usize,
+    /// The length of the write, or of the suffix along with anything added to close the content before it.
+    pub len: usize,
+    /// The number of bytes of `len` which did not fit. For a suffix, this is the number of bytes of written content
+    /// which had to be rolled back. It is 0 when content was dropped for another reason, such as an atomic write of a
+    /// value which changed since it was measured or a suffix which is not valid UTF-8.
+    pub overflow: usize,
+    /// Where the content was dropped.
+    pub source: TruncationSource,
+}
+
+/// Where content was dropped from a [`WriteBuf`]. See [`TruncationEvent::source`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TruncationSource {
+    /// A write did not fit, such as through `write_str`.
+    Write,
+    /// The suffix did not fit after the written content in the [`WriteBuf::finish_with`] family of functions.
+    Finish,
+}
+
+impl FinishReport {
+    /// Get if any truncation happened. This is true if either `content_truncated` or `suffix_degraded` is.
+    pub fn truncated(&self) -> bool {
+        self.content_truncated || self.suffix_degraded
+    }
+
+    /// Convert this report into the `Result` returned by [`WriteBuf::finish_with_or`].
+    pub fn result(&self) -> Result<usize, usize> {
+        if self.truncated() {
+            Err(self.written_len)
+        } else {
+            Ok(self.written_len)
+        }
+    }
+}
+
+impl<'a, T: Target<'a>> fmt::Write for WriteBuf<'a, T> {
+    /// Append `s` to the target buffer.
+    ///
+    /// # Error
+    ///
+    /// An error is returned if the entirety of `s` can not fit in the target buffer or if a previous `write_str`
+    /// operation failed. If this occurs, as much as `s` that can fit into the buffer will be written up to the last
+    /// valid Unicode code point. In other words, if the target buffer have 6 writable bytes left and `s` is the two
+    /// code points `"♡🐶"` (a.k.a.: the 7 byte `b"\xe2\x99\xa1\xf0\x9f\x90\xb6"`), then only `♡` will make it to the
+    /// output buffer, making the target of your ♡ ambiguous. Writing an empty `s` always succeeds, since nothing can be
+    /// lost.
+    ///
+    /// Truncation marks this buffer as truncated, which can be observed with [`WriteBuf::truncated`]. Future write
+    /// attempts will immediately return in `Err`. This also affects the behavior of [`WriteBuf::finish`] family of
+    /// functions, which will always return the `Err` case to indicate truncation. For [`WriteBuf::finish_with_or`],
+    /// the `normal_suffix` will not be attempted.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self._start_segment();
+        self._write(s.as_bytes())
+    }
+
+    /// Append `c` to the target buffer. This behaves exactly like writing the encoded `c` with `write_str`, but `c` is
+    /// copied into the target buffer without going through the `str` machinery. A `char` is never split, so if it does
+    /// not entirely fit, nothing is written and this buffer is marked as truncated.
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self._start_segment();
+        self.write_calls = self.write_calls.saturating_add(1);
+        let len = c.len_utf8();
+        let latched = self._latched();
+        if !latched && len <= self.remaining() {
+            self._write_char_unchecked(c);
+            return Ok(());
+        }
+
+        if !latched {
+            self._mark_truncated(len);
+        }
+        self.dropped_input_bytes = self.dropped_input_bytes.saturating_add(len);
+        Err(fmt::Error)
+    }
+
+    /// Format `args` into the target buffer. This behaves like the provided implementation, but the entire format is
+    /// treated as a single write for [`WriteBuf::undo_last_write`]. With a strict writer, the pieces written before one
+    /// which did not fit are rolled back, so the entire format is all-or-nothing. If truncation does not latch (see
+    /// [`WriteBuf::set_latch`]), every piece of the format is attempted and `Err` is returned if any of them were cut.
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        let start = self.position();
+        self._start_segment();
+        let dropped_input_bytes = self.dropped_input_bytes;
+        let mut result = fmt::write(&mut Segment(self), args);
+        if self.dropped_input_bytes != dropped_input_bytes {
+            result = Err(fmt::Error);
+        }
+        if result.is_err() && self.strict {
+            self.position.set(start);
+        }
+        result
+    }
+}
+
+impl<'a, T: Target<'a>> fmt::Debug for WriteBuf<'a, T> {
+    /// Shows the state of the buffer along with the start of the written content. Only the first 64 characters are
+    /// shown, so printing a huge buffer does not flood the output.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteBuf")
+            .field("position", &self.position())
+            .field("capacity", &self.capacity())
+            .field("reserve", &self.reserve())
+            .field("truncated", &self.truncated())
+            .field("written", &Preview(self.written()))
+            .finish()
+    }
+}
+
+/// The number of characters of written content shown by the [`fmt::Debug`] implementation of [`WriteBuf`].
+const DEBUG_PREVIEW_CHARS: usize = 64;
+
+/// Debug-formats the start of a string, noting how many bytes were left out.
+struct Preview<'s>(&'s str);
+
+impl<'s> fmt::Debug for Preview<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let end = self
+            .0
+            .char_indices()
+            .nth(DEBUG_PREVIEW_CHARS)
+            .map_or(self.0.len(), |(idx, _)| idx);
+        fmt::Debug::fmt(&self.0[..end], f)?;
+        if end < self.0.len() {
+            write!(f, "... ({} more bytes)", self.0.len() - end)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T: Target<'a>> fmt::Display for WriteBuf<'a, T> {
+    /// Writes the written content exactly as [`WriteBuf::written`] returns it. Truncation is not marked, since the
+    /// suffix is only chosen when finishing.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.written())
+    }
+}
+
+impl<'a, T: Target<'a>> AsRef<str> for WriteBuf<'a, T> {
+    fn as_ref(&self) -> &str {
+        self.written()
+    }
+}
+
+impl<'a, T: Target<'a>> core::borrow::Borrow<str> for WriteBuf<'a, T> {
+    fn borrow(&self) -> &str {
+        self.written()
+    }
+}
+
+impl<'a, T: Target<'a>> PartialEq<str> for WriteBuf<'a, T> {
+    /// Compares the written content to `other`.
+    fn eq(&self, other: &str) -> bool {
+        self.written() == other
+    }
+}
+
+impl<'a, 's, T: Target<'a>> PartialEq<&'s str> for WriteBuf<'a, T> {
+    /// Compares the written content to `other`.
+    fn eq(&self, other: &&'s str) -> bool {
+        self.written() == *other
+    }
+}
+
+/// Extending a buffer writes each item whole; it stops silently at the first item which does not fit and marks the
+/// buffer as truncated, since [`Extend`] has no way to report an error. The items after it are not taken from the
+/// iterator. A multi-byte `char` is never split: it is either written or dropped.
+///
+/// ```
+/// use fmtbuf::WriteBuf;
+///
+/// let mut buf: [u8; 8] = [0; 8];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// writer.extend("a🚀b🚀c".chars());
+/// assert_eq!("a🚀b", writer.written());
+/// assert!(writer.truncated());
+/// ```
+impl<'a, T: Target<'a>> Extend<char> for WriteBuf<'a, T> {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        self._start_segment();
+        let mut encoded = [0u8; 4];
+        for c in iter {
+            if !self._extend_one(c.encode_utf8(&mut encoded)) {
+                break;
+            }
+        }
+    }
+}
+
+/// Writes each `&str` whole, with the same semantics as extending with `char`s.
+impl<'a, 's, T: Target<'a>> Extend<&'s str> for WriteBuf<'a, T> {
+    fn extend<I: IntoIterator<Item = &'s str>>(&mut self, iter: I) {
+        self._start_segment();
+        for s in iter {
+            if !self._extend_one(s) {
+                break;
+            }
+        }
+    }
+}
+
+/// Writes each `String` whole, with the same semantics as extending with `char`s.
+#[cfg(feature = "std")]
+impl<'a, T: Target<'a>> Extend<String> for WriteBuf<'a, T> {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
+        self._start_segment();
+        for s in iter {
+            if !self._extend_one(&s) {
+                break;
+            }
+        }
+    }
+}
+
+/// Writes the pieces of a single `write_fmt` call without starting a new segment for each of them. When truncation
+/// does not latch, errors are not passed on, so formatting continues to the later pieces.
+struct Segment<'b, 'a, T>(&'b mut WriteBuf<'a, T>);
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for Segment<'b, 'a, T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let result = self.0._write(s.as_bytes());
+        if self.0.latch {
+            result
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Writes to a [`Segment`] of a buffer, cutting the output after `left` bytes, which is used by
+/// [`WriteBuf::write_display_truncated`]. Once the output was cut, formatting is stopped by returning `Err`.
+struct Capped<'b, 'a, T> {
+    buf: &'b mut WriteBuf<'a, T>,
+    left: usize,
+    exceeded: bool,
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for Capped<'b, 'a, T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.exceeded {
+            return Err(fmt::Error);
+        }
+        let s = if s.len() > self.left {
+            let mut end = self.left;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            self.exceeded = true;
+            &s[..end]
+        } else {
+            s
+        };
+        self.left -= s.len();
+
+        let result = Segment(self.buf).write_str(s);
+        if self.exceeded {
+            Err(fmt::Error)
+        } else {
+            result
+        }
+    }
+}
+
+/// Counts the `char`s of formatted output for [`WriteBuf::write_display_padded`].
+struct CharCounter(usize);
+
+impl fmt::Write for CharCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 = self.0.saturating_add(s.chars().count());
+        Ok(())
+    }
+}
+
+/// Storage for [`WriteBuf::position`].
+///
+/// With the `signal-safe` feature, this is an atomic and updates are published with release ordering. Since the writer
+/// always copies bytes before publishing a position covering them, a reader using [`WriteBuf::written_bytes_relaxed`]
+/// will never see a length that includes bytes which have not been copied yet.
+#[cfg(not(feature = "signal-safe"))]
+struct Position(usize);
+
+#[cfg(not(feature = "signal-safe"))]
+impl Position {
+    fn new(value: usize) -> Self {
+        Self(value)
+    }
+
+    fn get(&self) -> usize {
+        self.0
+    }
+
+    fn set(&mut self, value: usize) {
+        self.0 = value;
+    }
+}
+
+#[cfg(feature = "signal-safe")]
+struct Position(core::sync::atomic::AtomicUsize);
+
+#[cfg(feature = "signal-safe")]
+impl Position {
+    fn new(value: usize) -> Self {
+        Self(core::sync::atomic::AtomicUsize::new(value))
+    }
+
+    fn get(&self) -> usize {
+        // The only writer is the owner of the `WriteBuf`, so it always sees its own stores
+        self.0.load(core::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn get_published(&self) -> usize {
+        self.0.load(core::sync::atomic::Ordering::Acquire)
+    }
+
+    fn set(&mut self, value: usize) {
+        self.0.store(value, core::sync::atomic::Ordering::Release);
+    }
+}
+
+/// A bounded writer used to render suffixes directly into the end of a target buffer.
+///
+/// The first `skip` bytes of output are discarded, along with any UTF-8 continuation bytes immediately following them.
+/// This is used to keep only the end of a suffix which is larger than the target buffer.
+struct TailWriter<'b, T> {
+    target: &'b mut T,
+    /// The index of `target` the output starts at.
+    start: usize,
+    skip: usize,
+    aligned: bool,
+    position: usize,
+    overflowed: bool,
+}
+
+impl<'b, T> TailWriter<'b, T> {
+    fn new(target: &'b mut T, start: usize, skip: usize) -> Self {
+        Self {
+            target,
+            start,
+            skip,
+            aligned: skip == 0,
+            position: 0,
+            overflowed: false,
+        }
+    }
+}
+
+impl<'a, 'b, T: Target<'a>> fmt::Write for TailWriter<'b, T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut input = s.as_bytes();
+
+        let skipped = self.skip.min(input.len());
+        self.skip -= skipped;
+        input = &input[skipped..];
+        if self.skip > 0 {
+            return Ok(());
+        }
+
+        // The skip might have stopped in the middle of a multibyte sequence, so drop the rest of it
+        if !self.aligned {
+            let start = input
+                .iter()
+                .position(|cu| utf8::utf8_char_width(*cu).is_some())
+                .unwrap_or(input.len());
+            input = &input[start..];
+            self.aligned = !input.is_empty();
+        }
+
+        let remaining = self.target.capacity() - self.start - self.position;
+        if remaining < input.len() {
+            self.overflowed = true;
+            return Err(fmt::Error);
+        }
+        self.target.write_at(self.start + self.position, input);
+        self.position += input.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+// The tests over `TEST_CASES` compare `input.len()` to the cut positions, so a zero length is written the same way
+#[allow(clippy::len_zero)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// * `.0`: Input string
+    /// * `.1`: The end position if the last byte was chopped off
+    static TEST_CASES: &[(&str, usize)] = &[
+        ("", 0),
+        ("James", 4),
+        ("_ø", 1),
+        ("磨", 0),
+        ("here: 见/見", 10),
+        ("𨉟呐㗂越", 10),
+        ("🚀", 0),
+        ("🚀🚀🚀", 8),
+        ("rocket: 🚀", 8),
+    ];
+
+    #[test]
     fn rfind_utf8_end_test() {
         for (input, last_valid_idx_after_cut) in TEST_CASES.iter() {
-            let result = rfind_utf8_end(input.as_bytes());
-            assert_eq!(result, input.len(), "input=\"{input}\"");
+            let result = rfind_utf8_end(input.as_bytes());
+            assert_eq!(result, input.len(), "input=\"{input}\"");
+            if input.len() == 0 {
+                continue;
+            }
+            let input_truncated = &input.as_bytes()[..input.len() - 1];
+            let result = rfind_utf8_end(input_truncated);
+            assert_eq!(
+                result, *last_valid_idx_after_cut,
+                "input=\"{input}\" truncated={input_truncated:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn format_enough_space() {
+        for (input, _) in TEST_CASES.iter() {
+            let mut buf: [u8; 128] = [0xff; 128];
+            let mut writer = WriteBuf::new(&mut buf);
+
+            writer.write_str(input).unwrap();
+            assert_eq!(input.len(), writer.position());
+            let last_idx = writer.finish().unwrap();
+            assert_eq!(input.len(), last_idx);
+        }
+    }
+
+    #[test]
+    fn format_enough_space_just_enough_reserved() {
+        for (input, _) in TEST_CASES.iter() {
+            let mut buf: [u8; 128] = [0xff; 128];
+            let mut writer = WriteBuf::with_reserve(&mut buf[..input.len() + 1], 1);
+
+            writer.write_str(input).unwrap();
+            assert_eq!(input.len(), writer.position());
+            let last_idx = writer.finish().unwrap();
+            assert_eq!(input.len(), last_idx);
+        }
+    }
+
+    #[test]
+    fn format_truncation() {
+        for (input, last_valid_idx_after_cut) in TEST_CASES.iter() {
+            if input.len() == 0 {
+                continue;
+            }
+
+            let mut buf: [u8; 128] = [0xff; 128];
+            let mut writer = WriteBuf::new(&mut buf[..input.len() - 1]);
+
+            writer.write_str(input).unwrap_err();
+            assert_eq!(*last_valid_idx_after_cut, writer.position());
+            assert!(writer.truncated());
+            write!(writer, "!!!").expect_err("writes should fail here");
+
+            let last_idx = writer.finish().unwrap_err();
+            assert_eq!(*last_valid_idx_after_cut, last_idx);
+        }
+    }
+
+    struct SimpleString {
+        storage: [u8; 128],
+        size: usize,
+    }
+
+    impl SimpleString {
+        pub fn from_segments(segments: &[&str]) -> Self {
+            let mut out = Self {
+                storage: [0; 128],
+                size: 0,
+            };
+            for segment in segments {
+                out.append(segment);
+            }
+            out
+        }
+
+        pub fn append(&mut self, value: &str) {
+            let value = value.as_bytes();
+            self.storage[self.size..self.size + value.len()].copy_from_slice(value);
+            self.size += value.len();
+        }
+
+        pub fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.storage[..self.size]).unwrap()
+        }
+    }
+
+    impl From<&str> for SimpleString {
+        fn from(value: &str) -> Self {
+            Self::from_segments(&[value])
+        }
+    }
+
+    #[test]
+    fn finish_with_bytes_rejects_invalid_utf8() {
+        let hostile: [&[u8]; 5] = [b"\xff\0", b"\x80abc", b"abc\xf0\x9f", b"\xed\xa0\x80", b"\xc0\x80"];
+        for (input, _) in TEST_CASES.iter() {
+            for buf_len in 0..input.len() + 6 {
+                for suffix in hostile.iter() {
+                    let mut buf: [u8; 128] = [0xff; 128];
+                    let mut writer = WriteBuf::new(&mut buf[..buf_len]);
+                    let _ = writer.write_str(input);
+                    let position = writer.position();
+                    let written_len = writer.finish_with_bytes(suffix).unwrap_err();
+                    assert_eq!(position, written_len);
+                    assert!(core::str::from_utf8(&buf[..written_len]).is_ok());
+                }
+            }
+        }
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abc").unwrap();
+        assert_eq!(Ok(5), writer.finish_with_bytes(b"\xc3\xb8"));
+        assert_eq!("abcø", core::str::from_utf8(&buf[..5]).unwrap());
+    }
+
+    #[test]
+    fn finish_with_cut_suffix_starts_at_char_boundary() {
+        for buf_len in 0..9 {
+            let mut buf: [u8; 8] = [0xff; 8];
+            let mut writer = WriteBuf::new(&mut buf[..buf_len]);
+            writer.write_str("abcdefghijk").unwrap_err();
+            let written_len = writer.finish_with("é🚀é").unwrap_err();
+            let written = core::str::from_utf8(&buf[..written_len]).unwrap();
+            assert!("é🚀é".ends_with(written) || written.ends_with("é🚀é"), "{:?}", written);
+        }
+    }
+
+    #[test]
+    fn from_raw_parts_writes_through_pointer() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let ptr = buf.as_mut_ptr();
+        let mut writer = unsafe { WriteBuf::from_raw_parts_with_reserve(ptr, buf.len(), 1) };
+        assert_eq!(ptr, writer.as_mut_ptr());
+        assert_eq!(8, writer.capacity());
+        assert_eq!(1, writer.reserve());
+        writer.write_str("abcdefgh").unwrap_err();
+        assert_eq!(Err(8), writer.finish_with("\0"));
+        assert_eq!(b"abcdefg\0", &buf);
+
+        let mut writer = unsafe { WriteBuf::from_raw_parts(core::ptr::null_mut(), 0) };
+        assert_eq!(0, writer.capacity());
+        writer.write_str("").unwrap();
+        writer.write_str("a").unwrap_err();
+        assert_eq!(Err(0), writer.finish());
+    }
+
+    #[test]
+    fn finish_split_reassembles_frame() {
+        let payload = [0u8, 0xff, 0x80, 7];
+        let mut buf: [u8; 12] = [0xee; 12];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 4);
+        write!(writer, "bin:{}|", payload.len()).unwrap();
+        let (header, tail) = writer.finish_split();
+        let header_len = header.unwrap().len();
+        assert_eq!(6, header_len);
+        assert_eq!(6, tail.len());
+        tail[..payload.len()].copy_from_slice(&payload);
+        let frame = &buf[..header_len + payload.len()];
+        assert_eq!(b"bin:4|\x00\xff\x80\x07", frame);
+
+        let mut buf: [u8; 4] = [0xee; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abcdef").unwrap_err();
+        let (written, tail) = writer.finish_split();
+        assert_eq!(Err("abcd"), written);
+        assert!(tail.is_empty());
+    }
+
+    #[test]
+    fn debug_shows_state_and_preview() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 2);
+        writer.write_str("say \"hi\"").unwrap();
+
+        let mut out: [u8; 256] = [0; 256];
+        let mut out_writer = WriteBuf::new(&mut out);
+        write!(out_writer, "{:?}", writer).unwrap();
+        assert_eq!(
+            r#"WriteBuf { position: 8, capacity: 16, reserve: 2, truncated: false, written: "say \"hi\"" }"#,
+            out_writer.written()
+        );
+    }
+
+    #[test]
+    fn debug_preview_is_capped() {
+        let mut buf: [u8; 512] = [0xff; 512];
+        let mut writer = WriteBuf::new(&mut buf);
+        for _ in 0..100 {
+            writer.write_str("é").unwrap();
+        }
+
+        let mut out: [u8; 512] = [0; 512];
+        let mut out_writer = WriteBuf::new(&mut out);
+        write!(out_writer, "{:?}", Preview(writer.written())).unwrap();
+        let preview = out_writer.written();
+        assert!(preview.starts_with("\"éé"));
+        assert_eq!(1 + 64 * 2 + 1, preview.find("...").unwrap());
+        assert!(preview.ends_with("\"... (72 more bytes)"));
+
+        let mut out_writer = WriteBuf::new(&mut out);
+        write!(out_writer, "{:?}", Preview("short")).unwrap();
+        assert_eq!("\"short\"", out_writer.written());
+    }
+
+    #[test]
+    fn display_and_comparisons_use_written() {
+        fn as_str<S: AsRef<str>>(value: &S) -> &str {
+            value.as_ref()
+        }
+
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abcdef").unwrap_err();
+        assert!(writer == "abcd");
+        assert!(writer == *"abcd");
+        assert!(writer != "abcdef");
+        assert_eq!("abcd", as_str(&writer));
+        assert_eq!("abcd", core::borrow::Borrow::<str>::borrow(&writer));
+
+        let mut out: [u8; 8] = [0; 8];
+        let mut out_writer = WriteBuf::new(&mut out);
+        write!(out_writer, "[{}]", writer).unwrap();
+        assert_eq!("[abcd]", out_writer.written());
+    }
+
+    #[test]
+    fn write_iter_resumes_into_next_buffer() {
+        let text = "chunks of 🚀 content";
+        let mut chars = text.chars().peekable();
+        let mut out: [u8; 32] = [0; 32];
+        let mut out_writer = WriteBuf::new(&mut out);
+        let mut total = 0;
+        loop {
+            let mut buf: [u8; 6] = [0xff; 6];
+            let mut writer = WriteBuf::with_reserve(&mut buf, 1);
+            let result = writer.write_iter(&mut chars);
+            assert!(!writer.truncated());
+            assert!(writer.position() <= 5);
+            total += result.consumed;
+            out_writer.write_str(writer.written()).unwrap();
+            if result.exhausted {
+                break;
+            }
+            assert!(result.consumed > 0);
+        }
+        assert_eq!(text.chars().count(), total);
+        assert_eq!(text, out_writer.written());
+    }
+
+    #[test]
+    fn write_iter_str_leaves_unfit_item() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        let mut parts = ["ab", "", "cdefghi", "j"].iter().copied().peekable();
+        let result = writer.write_iter_str(&mut parts);
+        assert_eq!(
+            WriteIterResult {
+                consumed: 2,
+                exhausted: false
+            },
+            result
+        );
+        assert_eq!("ab", writer.written());
+        assert_eq!(Some(&"cdefghi"), parts.peek());
+        assert!(!writer.truncated());
+
+        // A latched buffer does not take anything
+        writer.write_str("cdefghij").unwrap_err();
+        let result = writer.write_iter_str(&mut parts);
+        assert_eq!(
+            WriteIterResult {
+                consumed: 0,
+                exhausted: false
+            },
+            result
+        );
+        let result = writer.write_iter(&mut "".chars().peekable());
+        assert_eq!(
+            WriteIterResult {
+                consumed: 0,
+                exhausted: true
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn write_char_matches_write_str() {
+        for c in ['a', 'ø', '見', '🚀'].iter().copied() {
+            let len = c.len_utf8();
+            for short in [0, 1].iter().copied() {
+                let mut encoded = [0u8; 4];
+                let encoded = c.encode_utf8(&mut encoded);
+
+                let mut buf: [u8; 8] = [0xff; 8];
+                let mut writer = WriteBuf::with_reserve(&mut buf, 8 - len + short);
+                let mut by_str_buf: [u8; 8] = [0xff; 8];
+                let mut by_str = WriteBuf::with_reserve(&mut by_str_buf, 8 - len + short);
+
+                let result = writer.write_char(c);
+                assert_eq!(by_str.write_str(encoded), result);
+                assert_eq!(short == 0, result.is_ok(), "c={:?} short={}", c, short);
+                assert_eq!(by_str.written(), writer.written());
+                assert_eq!(by_str.truncated(), writer.truncated());
+                assert_eq!(by_str.required_len(), writer.required_len());
+
+                // Once latched, both reject the char without touching the target
+                writer.write_char(c).unwrap_err();
+                by_str.write_str(encoded).unwrap_err();
+                assert_eq!(by_str.dropped_bytes(), writer.dropped_bytes());
+                assert_eq!(by_str_buf, buf);
+                if short == 1 {
+                    assert_eq!([0xff; 8], buf);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sub_writers_split_remaining_space() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 2);
+        writer.write_str("ab").unwrap();
+        let half = writer.remaining() / 2;
+        assert_eq!(6, half);
+
+        let digits = "0123456789";
+        let mut first = writer.sub_writer(half);
+        write!(first, "{}", digits).unwrap_err();
+        assert!(first.truncated());
+        assert_eq!("012345", first.written());
+        assert_eq!(0, first.remaining());
+        // The sub-writer hit its budget, so it latches like its parent would
+        first.write_str("x").unwrap_err();
+
+        let mut second = writer.sub_writer(half);
+        second.write_str("xyz").unwrap();
+        assert!(!second.truncated());
+        assert_eq!(3, second.position());
+        assert_eq!(3, second.remaining());
+
+        assert_eq!("ab012345xyz", writer.written());
+        assert!(!writer.truncated());
+        writer.write_str("!").unwrap();
+        assert_eq!("ab012345xyz!~~", writer.finish_with_or_str("~~", "..").unwrap());
+    }
+
+    #[test]
+    fn sub_writer_truncates_parent_when_it_runs_out() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("ab").unwrap();
+        {
+            let mut sub = writer.sub_writer(100);
+            assert_eq!(6, sub.remaining());
+            sub.write_str("cd🚀!").unwrap_err();
+            assert_eq!("cd🚀", sub.written());
+        }
+        assert_eq!("abcd🚀", writer.written());
+        assert!(writer.truncated());
+        assert_eq!(1, writer.dropped_bytes());
+        writer.write_str("e").unwrap_err();
+        writer.sub_writer(2).write_str("e").unwrap_err();
+        assert_eq!("abcd🚀", writer.written());
+    }
+
+    #[test]
+    fn write_display_truncated_cuts_value() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        let value = "abcdéfgh";
+        writer.write_display_truncated(&value, 5, "…").unwrap();
+        writer.write_display_truncated(&value, 9, "…").unwrap();
+        writer.write_display_truncated(&value, 5, "").unwrap();
+        writer.write_display_truncated(&42, 0, "#").unwrap();
+        assert_eq!("abcd…abcdéfghabcd#", writer.written());
+        assert!(!writer.truncated());
+        assert_eq!(0, writer.dropped_bytes());
+    }
+
+    #[test]
+    fn write_display_truncated_outer_buffer_full() {
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("user=").unwrap();
+        writer.write_display_truncated(&"0123456789", 32, "...").unwrap_err();
+        assert_eq!("user=0123456", writer.written());
+        assert!(writer.truncated());
+
+        // The value is cut by its budget, but the marker does not fit
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_display_truncated(&"0123456789", 6, "...").unwrap_err();
+        assert_eq!("012345..", writer.written());
+        assert!(writer.truncated());
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_str("ab").unwrap();
+        writer.write_display_truncated(&"0123456789", 6, "...").unwrap_err();
+        assert_eq!("ab", writer.written());
+    }
+
+    #[test]
+    fn write_middle_truncated_cut_points() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        let mut check = |s: &str, max_bytes: usize, head_percent: u8, expected: &str| {
+            let start = writer.position();
+            writer
+                .write_middle_truncated_with(s, max_bytes, ".", head_percent)
+                .unwrap();
+            assert_eq!(
+                expected,
+                &writer.written()[start..],
+                "s={:?} max_bytes={} head_percent={}",
+                s,
+                max_bytes,
+                head_percent
+            );
+        };
+        // Both cuts land exactly on the boundaries around a "見"
+        check("見見見見", 7, 50, "見.見");
+        // Both cuts land in the middle of a "見", and the room the start does not use goes to the end
+        check("見見見見", 9, 50, "見.見");
+        check("ab見cd見ef", 9, 50, "ab.d見ef");
+        check("見見見見", 9, 100, "見見.");
+        check("見見見見", 12, 50, "見見見見");
+    }
+
+    #[test]
+    fn write_middle_truncated_split() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_middle_truncated("abcdefghij", 6, "-").unwrap();
+        writer.write_middle_truncated_with("abcdefghij", 6, "-", 0).unwrap();
+        writer.write_middle_truncated_with("abcdefghij", 6, "-", 100).unwrap();
+        writer.write_middle_truncated_with("abcdefghij", 6, "-", 250).unwrap();
+        assert_eq!("ab-hij-fghijabcde-abcde-", writer.written());
+        assert!(!writer.truncated());
+    }
+
+    #[test]
+    fn write_middle_truncated_small_budget() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        // Only the marker and a single char fit
+        writer.write_middle_truncated("abcdefghij", 4, "…").unwrap();
+        writer.write_str("|").unwrap();
+        writer.write_middle_truncated("abcdefghij", 3, "…").unwrap();
+        writer.write_str("|").unwrap();
+        // Not even the marker fits
+        writer.write_middle_truncated("abcdefghij", 2, "…").unwrap();
+        writer.write_str("|").unwrap();
+        writer.write_middle_truncated("abcdefghij", 2, "...").unwrap();
+        writer.write_str("|").unwrap();
+        writer.write_middle_truncated("abcdefghij", 0, "...").unwrap();
+        writer.write_middle_truncated("", 0, "...").unwrap();
+        assert_eq!("…j|…||..|", writer.written());
+        assert_eq!(0, writer.dropped_bytes());
+    }
+
+    #[test]
+    fn write_middle_truncated_outer_buffer_full() {
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_middle_truncated("abcdefghij", 8, "…").unwrap_err();
+        assert_eq!("ab…h", writer.written());
+        assert!(writer.truncated());
+        // The start, the marker and the end are undone together
+        assert!(writer.undo_last_write());
+        assert_eq!("", writer.written());
+
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_str("x").unwrap();
+        writer.write_middle_truncated("abcdefghij", 8, "…").unwrap_err();
+        assert_eq!("x", writer.written());
+    }
+
+    #[test]
+    fn write_path_truncated_drops_directories() {
+        for (path, sep, room, expected) in [
+            ("src/lib.rs", '/', 10, "src/lib.rs"),
+            ("/home/build/src/lib.rs", '/', 18, "…/src/lib.rs"),
+            ("/home/build/src/lib.rs", '/', 20, "…/build/src/lib.rs"),
+            // Repeated separators are a single boundary
+            ("a//b//c.txt", '/', 10, "…/c.txt"),
+            // Trailing separators stay with the last component
+            ("var/log/app/", '/', 10, "…/app/"),
+            ("C:\\Users\\me\\notes.txt", '\\', 16, "…\\me\\notes.txt"),
+        ] {
+            let mut buf = [0xffu8; 64];
+            let mut writer = WriteBuf::new(&mut buf[..room]);
+            writer.write_path_truncated(path, sep).unwrap();
+            assert_eq!(expected, writer.written(), "path={:?} room={}", path, room);
+            assert!(!writer.truncated());
+        }
+    }
+
+    #[test]
+    fn write_path_truncated_cuts_last_component() {
+        for (path, room, expected) in [
+            // The last component does not fit on its own
+            ("logs/very_long_file_name.txt", 12, "very…e.txt"),
+            ("abcdefgh", 6, "a…gh"),
+            // The last component fits on its own, but not along with the marker and separator
+            ("/a/b/c/file.txt", 8, "fi…xt"),
+            ("/a/b/c/", 4, "…"),
+            ("a/b/c", 2, ""),
+            ("a/b/c", 0, ""),
+        ] {
+            let mut buf = [0xffu8; 64];
+            let mut writer = WriteBuf::new(&mut buf[..room]);
+            writer.write_path_truncated(path, '/').unwrap_err();
+            assert_eq!(expected, writer.written(), "path={:?} room={}", path, room);
+            assert!(writer.truncated(), "path={:?} room={}", path, room);
+            assert_eq!(path.len() - expected.len(), writer.dropped_bytes(), "path={:?}", path);
+        }
+
+        // A strict writer writes none of it
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_path_truncated("/a/b/c/file.txt", '/').unwrap_err();
+        assert_eq!("", writer.written());
+        assert_eq!(15, writer.dropped_bytes());
+    }
+
+    #[test]
+    fn write_path_truncated_after_content() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("rm ").unwrap();
+        writer.write_path_truncated("/tmp/build/out.o", '/').unwrap();
+        assert_eq!("rm …/out.o", writer.written());
+        // The whole path is a single write
+        assert!(writer.undo_last_write());
+        assert_eq!("rm ", writer.written());
+    }
+
+    #[test]
+    fn write_separated_first_item_does_not_fit() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        assert_eq!(Ok(0), writer.write_separated(["abcdef", "g"], ", "));
+        assert_eq!("", writer.written());
+        assert!(writer.truncated());
+        assert_eq!(6, writer.dropped_bytes());
+        assert_eq!(Err(WriteError::AlreadyTruncated), writer.write_separated(["a"], ", "));
+    }
+
+    #[test]
+    fn write_separated_truncated_inside_separator() {
+        // The separator after "ab" only fits its first byte, which is rolled back with the rest of it
+        let mut buf: [u8; 3] = [0xff; 3];
+        let mut writer = WriteBuf::new(&mut buf);
+        assert_eq!(Ok(1), writer.write_separated(["ab", "c"], ", "));
+        assert_eq!("ab", writer.written());
+        assert!(writer.truncated());
+        assert_eq!("ab~", writer.finish_with_or_str("", "~").unwrap_err());
+
+        // The whole list is one segment, so undoing the last write removes all of it
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("x=").unwrap();
+        assert_eq!(Ok(3), writer.write_separated(1..4, "|"));
+        assert_eq!("x=1|2|3", writer.written());
+        assert!(writer.undo_last_write());
+        assert_eq!("x=", writer.written());
+    }
+
+    #[test]
+    fn write_list_always_closes() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        assert_eq!(Ok(2), writer.write_list(['a', 'b', 'c'], "[", ", ", "]"));
+        assert_eq!("[a, b]", writer.written());
+        assert!(writer.truncated());
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        assert_eq!(Ok(0), writer.write_list(["long item"], "{ ", ", ", " }"));
+        assert_eq!("{  }", writer.written());
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 7);
+        assert_eq!(
+            Err(WriteError::ReserveBlocked {
+                needed: 2,
+                available: 1
+            }),
+            writer.write_list([1], "(", ",", ")")
+        );
+        assert_eq!("", writer.written());
+        assert!(writer.truncated());
+    }
+
+    #[test]
+    fn extend_drops_chars_whole() {
+        // Room for two and a half rockets
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::new(&mut buf);
+        let mut rockets = core::iter::repeat('🚀').take(5);
+        writer.extend(rockets.by_ref());
+        assert_eq!("🚀🚀", writer.written());
+        assert!(writer.truncated());
+        // The rocket which did not fit was taken, but the ones after it were not
+        assert_eq!(2, rockets.count());
+
+        // Only complete rockets fit, so a short item after the cut is not written either
+        writer.extend(['x'].iter().copied());
+        assert_eq!("🚀🚀", writer.written());
+    }
+
+    #[test]
+    fn extend_writes_strs_whole() {
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 2);
+        writer.extend("ab,🚀,cd,e".split(','));
+        assert_eq!("ab🚀cd", writer.written());
+        assert!(writer.truncated());
+        assert_eq!("ab🚀cd~", writer.finish_with_or_str("", "~").unwrap_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn extend_writes_strings_whole() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.extend((1..10).map(|x| format!("{}-", x * 10)));
+        assert_eq!("10-20-", writer.written());
+        assert!(writer.truncated());
+    }
+
+    #[test]
+    fn finish_with_enough_space() {
+        for (input, _) in TEST_CASES.iter() {
+            let mut buf: [u8; 128] = [0xff; 128];
+            let mut writer = WriteBuf::new(&mut buf);
+
+            writer.write_str(input).unwrap();
+            let position = writer.finish_with(".123").unwrap();
+            assert_eq!(position, input.len() + 4);
+            let expected_written = SimpleString::from_segments(&[input, ".123"]);
+            let actually_wriiten = core::str::from_utf8(&buf[..position]).unwrap();
+            assert_eq!(expected_written.as_str(), actually_wriiten);
+        }
+    }
+
+    #[test]
+    fn finish_with_overwrite() {
+        for (input, last_valid_idx_after_cut) in TEST_CASES.iter() {
             if input.len() == 0 {
                 continue;
             }
-            let input_truncated = &input.as_bytes()[..input.len() - 1];
-            let result = rfind_utf8_end(input_truncated);
-            assert_eq!(
-                result, *last_valid_idx_after_cut,
-                "input=\"{input}\" truncated={input_truncated:?}"
-            );
+
+            let mut buf: [u8; 128] = [0xff; 128];
+            let mut writer = WriteBuf::new(&mut buf[..input.len()]);
+
+            writer.write_str(input).unwrap();
+            let position = writer.finish_with("?").unwrap_err();
+            assert_eq!(position, last_valid_idx_after_cut + 1);
+            let expected_written = SimpleString::from_segments(&[
+                core::str::from_utf8(&input.as_bytes()[..*last_valid_idx_after_cut]).unwrap(),
+                "?",
+            ]);
+            let actually_wriiten = core::str::from_utf8(&buf[..position]).unwrap();
+            assert_eq!(expected_written.as_str(), actually_wriiten);
+        }
+    }
+
+    #[test]
+    fn finish_with_or_with_longer_normal_closer() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let writer = WriteBuf::new(&mut buf);
+
+        let written = writer.finish_with_or("0123456789", "abc").unwrap_err();
+        assert_eq!(written, 3);
+        assert_eq!("abc", core::str::from_utf8(&buf[..written]).unwrap());
+    }
+
+    #[test]
+    fn finish_with_full_overwrite_utf8() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let writer = WriteBuf::new(&mut buf);
+
+        let written = writer.finish_with("🚀12").unwrap_err();
+        assert_eq!(written, 2);
+        assert_eq!("12", core::str::from_utf8(&buf[..written]).unwrap());
+    }
+
+    #[test]
+    fn set_reserve_should_not_change_written() {
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        write!(writer, "0123456789").unwrap();
+        assert_eq!("0123456789", writer.written());
+
+        writer.set_reserve(4);
+        assert_eq!("0123456789", writer.written());
+
+        writer.finish_with_or("", "!").unwrap();
+        assert_eq!("0123456789", core::str::from_utf8(&buf).unwrap());
+    }
+
+    #[test]
+    fn pop_multibyte() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("aé🚀").unwrap();
+        assert_eq!(Some('🚀'), writer.pop());
+        assert_eq!(3, writer.position());
+        assert_eq!(Some('é'), writer.pop());
+        assert_eq!(1, writer.position());
+        assert_eq!(Some('a'), writer.pop());
+        assert_eq!(0, writer.position());
+        assert_eq!(None, writer.pop());
+        assert_eq!(0, writer.position());
+    }
+
+    #[test]
+    fn pop_then_write() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        write!(writer, "{}, {}, ", 1, 2).unwrap();
+        assert_eq!(2, writer.pop_n(2));
+        write!(writer, ".").unwrap();
+        assert_eq!("1, 2.", writer.written());
+        assert_eq!(5, writer.pop_n(10));
+        assert_eq!("", writer.written());
+    }
+
+    #[test]
+    fn pop_does_not_reset_truncated() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("12345").unwrap_err();
+        assert_eq!(Some('4'), writer.pop());
+        assert!(writer.truncated());
+        writer.write_str("!").unwrap_err();
+        assert_eq!("123", writer.written());
+    }
+
+    #[test]
+    fn as_mut_str_then_write() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        write!(writer, "content-type: ").unwrap();
+        writer.as_mut_str().make_ascii_uppercase();
+        write!(writer, "text/plain; é").unwrap();
+        let view = writer.as_mut_str();
+        // safety: replacing ASCII with ASCII keeps the contents valid UTF-8
+        for b in unsafe { view.as_bytes_mut() } {
+            if *b == b' ' {
+                *b = b'_';
+            }
+        }
+        write!(writer, "!").unwrap();
+
+        let len = writer.finish().unwrap();
+        assert_eq!(
+            "CONTENT-TYPE:_text/plain;_é!",
+            core::str::from_utf8(&buf[..len]).unwrap()
+        );
+    }
+
+    #[test]
+    fn as_mut_str_empty() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        assert_eq!("", writer.as_mut_str());
+        writer.write_str("12345").unwrap_err();
+        writer.as_mut_str().make_ascii_lowercase();
+        assert_eq!(Err(4), writer.finish());
+    }
+
+    /// Write the decimal digits of `value` to the start of `out`, returning the number of bytes used, like an `itoa`
+    /// style formatter would.
+    fn write_digits(mut value: u32, out: &mut [u8]) -> usize {
+        let mut digits = [0u8; 10];
+        let mut len = 0;
+        loop {
+            digits[len] = b'0' + (value % 10) as u8;
+            len += 1;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        for (dst, src) in out.iter_mut().zip(digits[..len].iter().rev()) {
+            *dst = *src;
+        }
+        len
+    }
+
+    #[test]
+    fn spare_capacity_direct_digits() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 1);
+
+        write!(writer, "id=").unwrap();
+        assert_eq!(28, writer.spare_capacity_mut().len());
+        let used = write_digits(4_294_967, writer.spare_capacity_mut());
+        writer.advance(used).unwrap();
+        write!(writer, ", next={}", 1).unwrap();
+
+        let len = writer.finish_with("\0").unwrap();
+        assert_eq!("id=4294967, next=1\0", core::str::from_utf8(&buf[..len]).unwrap());
+    }
+
+    #[test]
+    fn advance_rejects_invalid_utf8() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.spare_capacity_mut()[..2].copy_from_slice(b"\xf0\x9f");
+        writer.advance(2).unwrap_err();
+        assert_eq!(0, writer.position());
+        assert!(!writer.truncated());
+        writer.write_str("ok").unwrap();
+    }
+
+    #[test]
+    fn advance_past_reserve_truncates() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 3);
+
+        assert_eq!(5, writer.spare_capacity_mut().len());
+        writer.spare_capacity_mut().copy_from_slice(b"12345");
+        writer.advance(6).unwrap_err();
+        assert_eq!(0, writer.position());
+        assert!(writer.content_truncated());
+        writer.advance(5).unwrap_err();
+    }
+
+    #[test]
+    fn advance_after_truncation_without_latch() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.set_latch(false);
+
+        // "🚀" does not fit, which leaves 3 bytes of spare capacity
+        writer.write_str("12345🚀").unwrap_err();
+        writer.spare_capacity_mut()[..2].copy_from_slice(b"ab");
+        writer.advance(2).unwrap();
+        assert_eq!("12345ab", writer.written());
+        assert!(writer.truncated());
+
+        writer.set_latch(true);
+        assert_eq!(Err(WriteError::AlreadyTruncated), writer.advance(1));
+    }
+
+    #[test]
+    fn spare_capacity_reserve_larger_than_buffer() {
+        let mut buf: [u8; 2] = [0xff; 2];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 4);
+
+        assert!(writer.spare_capacity_mut().is_empty());
+        writer.advance(0).unwrap();
+    }
+
+    #[test]
+    fn truncate_in_place_fits() {
+        let mut buf = *b"exactly16bytes!!";
+        let len = buf.len();
+        assert_eq!(16, truncate_in_place(&mut buf, len, 16, "..."));
+        assert_eq!(b"exactly16bytes!!", &buf);
+        assert_eq!(10, truncate_in_place(&mut buf, 10, 16, "..."));
+        assert_eq!(b"exactly16bytes!!", &buf);
+    }
+
+    #[test]
+    fn truncate_in_place_multibyte_straddles_max_len() {
+        let content = "ab🚀cd";
+        let mut buf: [u8; 16] = [0xff; 16];
+        buf[..content.len()].copy_from_slice(content.as_bytes());
+
+        // max_len lands inside the 🚀, and so does the marker's write position
+        let new_len = truncate_in_place(&mut buf, content.len(), 5, "…");
+        assert_eq!("ab…", core::str::from_utf8(&buf[..new_len]).unwrap());
+    }
+
+    #[test]
+    fn truncate_in_place_matches_finish_with() {
+        for (input, _) in TEST_CASES.iter() {
+            for max_len in 0..input.len() {
+                let mut in_place: [u8; 128] = [0xff; 128];
+                in_place[..input.len()].copy_from_slice(input.as_bytes());
+                let new_len = truncate_in_place(&mut in_place, input.len(), max_len, "…");
+
+                let mut expected: [u8; 128] = [0xff; 128];
+                let mut writer = WriteBuf::new(&mut expected[..max_len]);
+                writer.write_str(input).unwrap_err();
+                let expected_len = writer.finish_with("…").unwrap_err();
+
+                assert_eq!(expected_len, new_len, "input={input:?} max_len={max_len}");
+                assert_eq!(
+                    expected[..new_len],
+                    in_place[..new_len],
+                    "input={input:?} max_len={max_len}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn truncate_in_place_marker_longer_than_max_len() {
+        let mut buf = *b"0123456789";
+        assert_eq!(2, truncate_in_place(&mut buf, 10, 2, "[cut]"));
+        assert_eq!(b"t]", &buf[..2]);
+
+        let mut buf = *b"0123456789";
+        assert_eq!(0, truncate_in_place(&mut buf, 10, 3, "🚀"));
+    }
+
+    #[test]
+    fn guarantees_default() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        let expected = Guarantees::VALID_UTF8 | Guarantees::CHAR_BOUNDARY;
+        assert_eq!(expected, writer.guarantees());
+        assert!(!writer.guarantees().contains(Guarantees::GRAPHEME_BOUNDARY));
+
+        // neither reserve nor truncation change what the output is guaranteed to be
+        writer.set_reserve(2);
+        writer.write_str("🚀").unwrap_err();
+        assert_eq!(expected, writer.guarantees());
+    }
+
+    #[test]
+    fn guarantees_do_not_depend_on_configuration() {
+        let expected = Guarantees::VALID_UTF8 | Guarantees::CHAR_BOUNDARY;
+        let policies: [&dyn TruncationPolicy; 3] = [&CodePoint, &WordBoundary::DEFAULT, &LineBoundary];
+        for policy in policies {
+            for latch in [true, false] {
+                for reserve in [0, 3, 16] {
+                    let mut buf: [u8; 8] = [0xff; 8];
+                    let mut writer = WriteBuf::new(&mut buf);
+                    writer.set_policy(policy);
+                    writer.set_latch(latch);
+                    writer.set_reserve(reserve);
+                    assert_eq!(expected, writer.guarantees(), "latch={} reserve={}", latch, reserve);
+
+                    let _ = writer.write_str("a 🚀\n🚀🚀");
+                    assert_eq!(expected, writer.guarantees(), "latch={} reserve={}", latch, reserve);
+                    assert!(core::str::from_utf8(writer.written_bytes()).is_ok());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rollback_clears_truncation() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("key").unwrap();
+        let checkpoint = writer.checkpoint();
+        assert_eq!(3, checkpoint.position());
+        writer.write_str("=value").unwrap_err();
+        assert!(writer.truncated());
+        writer.rollback_to(checkpoint).unwrap();
+        assert!(!writer.truncated());
+        assert_eq!("key", writer.written());
+        writer.write_str("=v").unwrap();
+        assert_eq!(Ok(5), writer.finish());
+    }
+
+    #[test]
+    fn rollback_keeps_earlier_truncation() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("12345").unwrap_err();
+        let checkpoint = writer.checkpoint();
+        writer.write_str("6").unwrap_err();
+        writer.rollback_to(checkpoint).unwrap();
+        assert!(writer.content_truncated());
+        assert_eq!("1234", writer.written());
+    }
+
+    #[test]
+    fn rollback_rejects_foreign_checkpoint() {
+        let mut buf_a: [u8; 8] = [0xff; 8];
+        let mut buf_b: [u8; 8] = [0xff; 8];
+        let mut writer_a = WriteBuf::new(&mut buf_a);
+        let mut writer_b = WriteBuf::new(&mut buf_b);
+
+        let checkpoint_a = writer_a.checkpoint();
+        writer_a.write_str("a").unwrap();
+        writer_b.write_str("bb").unwrap();
+        writer_b.rollback_to(checkpoint_a).unwrap_err();
+        assert_eq!("bb", writer_b.written());
+        writer_a.rollback_to(checkpoint_a).unwrap();
+        assert_eq!("", writer_a.written());
+    }
+
+    #[test]
+    fn rollback_rejects_checkpoint_beyond_position() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        let start = writer.checkpoint();
+        writer.write_str("abcd").unwrap();
+        let later = writer.checkpoint();
+        writer.rollback_to(start).unwrap();
+        writer.rollback_to(later).unwrap_err();
+        assert_eq!(0, writer.position());
+    }
+
+    #[test]
+    fn transaction_commits() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.transaction(|w| write!(w, "a={}", 1)).unwrap();
+        writer.transaction(|w| write!(w, " b={}", 2)).unwrap();
+        assert_eq!("a=1 b=2", writer.written());
+    }
+
+    #[test]
+    fn transaction_rolls_back_on_closure_error() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer
+            .transaction(|w| {
+                write!(w, "partial")?;
+                Err(fmt::Error)
+            })
+            .unwrap_err();
+        assert_eq!("", writer.written());
+        assert!(!writer.truncated());
+    }
+
+    #[test]
+    fn transaction_rolls_back_ignored_truncation() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("abc").unwrap();
+        // the closure swallows the error, but the truncation is still noticed
+        writer
+            .transaction(|w| {
+                let _ = w.write_str("defghijk");
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!("abc", writer.written());
+        assert_eq!(Ok(3), writer.finish());
+    }
+
+    #[test]
+    fn transaction_nested() {
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer
+            .transaction(|outer| {
+                write!(outer, "[")?;
+                // inner transaction does not fit, so only it is rolled back
+                let inner = outer.transaction(|inner| write!(inner, "too long to fit"));
+                assert!(inner.is_err());
+                outer.transaction(|inner| write!(inner, "ok"))?;
+                write!(outer, "]")
+            })
+            .unwrap();
+        assert_eq!("[ok]", writer.written());
+
+        writer
+            .transaction(|outer| {
+                write!(outer, " (")?;
+                outer.transaction(|inner| write!(inner, "fits"))?;
+                write!(outer, " and this does not)")
+            })
+            .unwrap_err();
+        assert_eq!("[ok]", writer.written());
+        assert!(!writer.truncated());
+    }
+
+    #[test]
+    fn undo_last_write_format_is_one_segment() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("a").unwrap();
+        let (b, cd) = ('b', "cd");
+        write!(writer, "{b}{cd}{}", 3).unwrap();
+        assert_eq!("abcd3", writer.written());
+        assert!(writer.undo_last_write());
+        assert_eq!("a", writer.written());
+        assert!(!writer.undo_last_write());
+        assert_eq!("a", writer.written());
+    }
+
+    #[test]
+    fn undo_last_write_clears_truncation() {
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("abc").unwrap();
+        write!(writer, "-{}", 12345).unwrap_err();
+        assert!(writer.truncated());
+        assert_eq!(3, writer.dropped_bytes());
+        assert!(writer.undo_last_write());
+        assert!(!writer.truncated());
+        assert_eq!(0, writer.dropped_bytes());
+        assert_eq!(3, writer.required_len());
+        assert_eq!("abc", writer.written());
+        writer.write_char('!').unwrap();
+        assert_eq!(Ok(4), writer.finish());
+    }
+
+    #[test]
+    fn undo_last_write_after_truncation_is_noop() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("abcde").unwrap_err();
+        writer.write_str("f").unwrap_err();
+        assert!(!writer.undo_last_write());
+        assert!(writer.truncated());
+        assert_eq!("abcd", writer.written());
+    }
+
+    #[test]
+    fn undo_last_write_after_rollback_is_noop() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("ab").unwrap();
+        let checkpoint = writer.checkpoint();
+        writer.write_str("cd").unwrap();
+        writer.rollback_to(checkpoint).unwrap();
+        assert!(!writer.undo_last_write());
+        assert_eq!("ab", writer.written());
+    }
+
+    #[test]
+    fn insert_str_positions() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("bodyé").unwrap();
+        writer.insert_str(0, "<").unwrap();
+        assert_eq!("<bodyé", writer.written());
+        writer.insert_str(3, "-").unwrap();
+        assert_eq!("<bo-dyé", writer.written());
+        // adjacent to a multibyte character, on both sides
+        writer.insert_str(6, "[").unwrap();
+        writer.insert_str(9, "]").unwrap();
+        assert_eq!("<bo-dy[é]", writer.written());
+        writer.insert_str(writer.position(), ">").unwrap();
+        assert_eq!("<bo-dy[é]>", writer.written());
+        assert_eq!(Ok(11), writer.finish());
+    }
+
+    #[test]
+    fn insert_str_rejects_non_boundary() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("a🚀").unwrap();
+        assert_eq!(Err(WriteError::InvalidInput), writer.insert_str(2, "x"));
+        writer.insert_str(6, "x").unwrap_err();
+        assert_eq!("a🚀", writer.written());
+    }
+
+    #[test]
+    fn insert_str_rejects_overflow() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 1);
+
+        writer.write_str("12345").unwrap();
+        assert_eq!(
+            Err(WriteError::ReserveBlocked {
+                needed: 3,
+                available: 2
+            }),
+            writer.insert_str(0, "abc")
+        );
+        assert_eq!(
+            Err(WriteError::Capacity {
+                needed: 4,
+                available: 2
+            }),
+            writer.insert_str(0, "abcd")
+        );
+        assert_eq!("12345", writer.written());
+        assert!(!writer.truncated());
+        writer.insert_str(0, "ab").unwrap();
+        assert_eq!("ab12345", writer.written());
+        assert!(!writer.undo_last_write());
+    }
+
+    #[test]
+    fn split_at_frames() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let (mut header, mut body) = WriteBuf::new(&mut buf).split_at(4).ok().unwrap();
+
+        body.write_str("payload").unwrap();
+        let body_len = body.finish().unwrap();
+        write!(header, "{body_len:>3}|").unwrap();
+        assert_eq!(Ok(4), header.finish());
+        assert_eq!("  7|payload", core::str::from_utf8(&buf[..4 + body_len]).unwrap());
+    }
+
+    #[test]
+    fn split_at_header_truncates_independently() {
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 1);
+        writer.write_str("ab").unwrap();
+        let (mut header, mut body) = writer.split_at(4).ok().unwrap();
+        assert_eq!(2, header.position());
+        assert_eq!(0, header.reserve());
+        assert_eq!(1, body.reserve());
+
+        header.write_str("cdef").unwrap_err();
+        body.write_str("body").unwrap();
+        assert_eq!(Err(4), header.finish_with_or("", "~"));
+        assert_eq!(Ok(5), body.finish_with("\n"));
+        assert_eq!(b"abc~body\n", &buf[..9]);
+    }
+
+    #[test]
+    fn split_at_rejects_written_content() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abc").unwrap();
+
+        let writer = writer.split_at(2).err().unwrap();
+        let writer = writer.split_at(9).err().unwrap();
+        assert_eq!("abc", writer.written());
+        let (front, back) = writer.split_at(8).ok().unwrap();
+        assert_eq!("abc", front.written());
+        assert_eq!(0, back.capacity());
+    }
+
+    fn write_greeting(mut buf: WriteBufRef, name: &str) -> fmt::Result {
+        write!(buf, "hello, {name}")
+    }
+
+    fn write_receipt(mut buf: WriteBufRef) -> fmt::Result {
+        buf.write_str("[")?;
+        write_greeting(buf.reborrow(), "world")?;
+        buf.write_str("]")
+    }
+
+    #[test]
+    fn reborrow_shares_state() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 1);
+
+        write_receipt(writer.reborrow()).unwrap();
+        assert_eq!("[hello, world]", writer.written());
+        // The single `write!` in `write_greeting` is still one segment
+        assert!(writer.reborrow().undo_last_write());
+        assert_eq!("[hello, world", writer.written());
+
+        write_greeting(writer.reborrow(), "again").unwrap_err();
+        assert!(writer.content_truncated());
+        assert_eq!(Err(16), writer.finish_with("."));
+        assert_eq!(b"[hello, worldhe.", &buf);
+    }
+
+    #[test]
+    fn finish_trimmed_removes_whitespace() {
+        for (input, buf_size, suffix, expected) in [
+            ("ok  \t\n", 16, "", Ok("ok")),
+            ("ok\u{3000}\u{a0}", 16, "!", Ok("ok!")),
+            ("   \n", 16, "", Ok("")),
+            ("   \n", 16, ".", Ok(".")),
+            ("no trailing", 16, "", Ok("no trailing")),
+            // trimming before the suffix makes room for it
+            ("abc   ", 7, "...", Ok("abc...")),
+            ("abc   defg", 8, "", Err("abc   de")),
+            // the rollback lands after a space
+            ("abc   defg", 9, "...", Err("abc...")),
+            ("abc 🚀defg", 10, "…", Err("abc…")),
+            ("    defg", 6, "..", Err("..")),
+        ] {
+            let mut buf = [0xffu8; 16];
+            let mut writer = WriteBuf::new(&mut buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let truncated = writer.truncated();
+            let result = writer.finish_trimmed_with(suffix);
+            assert_eq!(truncated, result.is_err(), "input={input:?}");
+            let as_str = |len: usize| core::str::from_utf8(&buf[..len]).unwrap();
+            assert_eq!(expected, result.map(as_str).map_err(as_str), "input={input:?}");
+        }
+    }
+
+    #[test]
+    fn finish_trimmed_keeps_reserve() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 2);
+        writer.write_str("a b    c").unwrap_err();
+        assert_eq!(Err(3), writer.finish_trimmed());
+        assert_eq!(b"a b   \xff\xff", &buf);
+    }
+
+    #[test]
+    fn finish_tidy_removes_trailing_marks() {
+        fn check(input: &str, normal: &str, truncated: &str, expected: Result<&str, &str>) {
+            let mut buf: [u8; 8] = [0xff; 8];
+            let mut writer = WriteBuf::new(&mut buf);
+            let _ = writer.write_str(input);
+            let result = writer.finish_tidy_with_or(normal, truncated);
+            let as_str = |len: usize| core::str::from_utf8(&buf[..len]).unwrap();
+            assert_eq!(expected, result.map(as_str).map_err(as_str), "input={:?}", input);
+        }
+
+        // Rolling back for the suffix stops right after a mark, which is removed
+        check("abcde\u{301}xy", "", ".", Err("abcde."));
+        // A composed "é" is not a mark, so it stays
+        check("abcdeéxy", "", ".", Err("abcdeé."));
+        // The write was cut after the marks, and the suffix fits after them
+        check("abc\u{301}🚀", "", ".", Err("abc."));
+        check("ae\u{301}\u{302}🚀", "!", "..", Err("ae.."));
+        // Nothing is removed when the content was not truncated
+        check("e\u{301}", "!", ".", Ok("e\u{301}!"));
+    }
+
+    #[test]
+    fn finish_padded_fills_target() {
+        for (input, buf_size, reserve, suffix, fill, expected) in [
+            ("ab", 6, 0, "", ' ', Ok("ab    ")),
+            ("ab", 6, 2, "", '-', Ok("ab----")),
+            ("abcdefg", 6, 0, "", ' ', Err("abcdef")),
+            ("ab", 9, 0, "", '…', Ok("ab…… ")),
+            ("ab", 8, 0, "", '…', Ok("ab……")),
+            ("ab", 8, 2, "\r\n", '.', Ok("ab....\r\n")),
+            ("abcdefg", 8, 2, "\r\n", '.', Err("abcdef\r\n")),
+            ("abcdefg", 8, 0, "]", '.', Ok("abcdefg]")),
+            // rolling back to a char boundary leaves a gap before the suffix
+            ("ab🚀🚀", 11, 0, "…", '_', Err("ab🚀__…")),
+            ("ab", 3, 0, "🚀...", '.', Err("...")),
+            ("", 0, 0, "", '.', Ok("")),
+        ] {
+            let mut buf = [0xffu8; 12];
+            let mut writer = WriteBuf::with_reserve(&mut buf[..buf_size], reserve);
+            let _ = writer.write_str(input);
+            let result = writer.finish_with_padded(suffix, fill);
+            assert_eq!(buf_size, result.unwrap_or_else(|len| len), "input={input:?}");
+            let written = core::str::from_utf8(&buf[..buf_size]).unwrap();
+            assert_eq!(
+                expected,
+                result.map(|_| written).map_err(|_| written),
+                "input={input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn finish_with_line_ending_terminates_once() {
+        for (input, buf_size, marker, ending, expected) in [
+            ("abc", 8, "", LineEnding::Lf, Ok("abc\n")),
+            ("abc\n", 8, "", LineEnding::Lf, Ok("abc\n")),
+            ("abc\r\n", 8, "", LineEnding::Lf, Ok("abc\n")),
+            ("abc\r\n", 8, "", LineEnding::CrLf, Ok("abc\r\n")),
+            ("abc\n", 8, "", LineEnding::CrLf, Ok("abc\r\n")),
+            ("abc\r", 8, "", LineEnding::CrLf, Ok("abc\r\n")),
+            ("abc\n\n", 8, "", LineEnding::Lf, Ok("abc\n\n")),
+            // 2 bytes remaining
+            ("abcdef", 8, "…", LineEnding::CrLf, Ok("abcdef\r\n")),
+            ("abcdef", 8, "~", LineEnding::CrLf, Ok("abcdef\r\n")),
+            // 1 byte remaining
+            ("abcdefg", 8, "", LineEnding::Lf, Ok("abcdefg\n")),
+            ("abcdefg", 8, "", LineEnding::CrLf, Err("abcdef\r\n")),
+            ("abcdefg", 8, "~", LineEnding::CrLf, Err("abcde~\r\n")),
+            // 0 bytes remaining
+            ("abcdefgh", 8, "", LineEnding::Lf, Err("abcdefg\n")),
+            ("abcdefghij", 8, "…", LineEnding::CrLf, Err("abc…\r\n")),
+            ("abcdefgh\r\n", 8, "~", LineEnding::CrLf, Err("abcde~\r\n")),
+            // rolling back splits a "\r\n" in the content
+            ("ab\r\ncdef", 6, "", LineEnding::CrLf, Err("ab\r\n")),
+            ("ab\r\ncdef", 5, "", LineEnding::Lf, Err("ab\n")),
+            ("a\r\ncdef", 6, "~", LineEnding::CrLf, Err("a~\r\n")),
+            // tiny buffers keep the end of the suffix
+            ("abc", 2, "", LineEnding::CrLf, Err("\r\n")),
+            ("abc", 1, "", LineEnding::CrLf, Err("\n")),
+            ("abc", 1, "…", LineEnding::Lf, Err("\n")),
+            ("abc", 0, "…", LineEnding::Lf, Err("")),
+        ] {
+            let mut buf = [0xffu8; 16];
+            let mut writer = WriteBuf::new(&mut buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let result = writer.finish_with_or_line(marker, ending);
+            let as_str = |len: usize| core::str::from_utf8(&buf[..len]).unwrap();
+            assert_eq!(
+                expected,
+                result.map(as_str).map_err(as_str),
+                "input={input:?} buf_size={buf_size} marker={marker:?} ending={ending:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn finish_with_newline_is_lf() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("ok").unwrap();
+        assert_eq!(Ok(3), writer.finish_with_newline());
+        assert_eq!(b"ok\n", &buf[..3]);
+    }
+
+    #[test]
+    fn finish_or_replace_replaces_truncated() {
+        for (input, buf_size, reserve, fallback, expected) in [
+            ("12345", 8, 0, "<long>", Ok("12345")),
+            ("12345", 8, 3, "<long>", Ok("12345")),
+            ("123456789", 8, 0, "<long>", Err("<long>")),
+            ("123456", 8, 3, "<too long>", Err("oo long>")),
+            ("123456789", 8, 0, "", Err("")),
+            // the fallback is cut like a giant suffix
+            ("123456789", 8, 0, "<very long>", Err("ry long>")),
+            ("🚀🚀", 6, 0, "見見見", Err("見見")),
+            ("🚀🚀", 7, 0, "見見見", Err("見見")),
+            ("🚀🚀", 2, 0, "見見見", Err("")),
+            ("🚀🚀", 3, 0, "見見見", Err("見")),
+        ] {
+            let mut buf = [0xffu8; 16];
+            let mut writer = WriteBuf::with_reserve(&mut buf[..buf_size], reserve);
+            let _ = writer.write_str(input);
+            let result = writer.finish_or_replace(fallback);
+            let as_str = |len: usize| core::str::from_utf8(&buf[..len]).unwrap();
+            assert_eq!(
+                expected,
+                result.map(as_str).map_err(as_str),
+                "input={input:?} fallback={fallback:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn finish_balanced_closes_delimiters() {
+        for (input, buf_size, marker, expected) in [
+            ("f(a, [b]) + \"c\"", 16, "…", Ok("f(a, [b]) + \"c\"")),
+            // unbalanced content which was not truncated is left alone
+            ("f(a, [b", 16, "…", Ok("f(a, [b")),
+            ("plain text here", 8, "…", Err("plain…")),
+            ("f(a, [b, c], d)", 12, "…", Err("f(a, [b…])")),
+            ("f(a, [b, c], d)", 10, "…", Err("f(a, …)")),
+            // rolling back past a closer needs a longer suffix
+            ("f(a, [b], c)", 10, "..", Err("f(a, [..])")),
+            ("{\"k\": \"v(al\"}", 12, "~", Err("{\"k\": \"v(~\"}")),
+            ("'it\\'s' (x)", 9, "~", Err("'it\\'s' ~")),
+            // the closers themselves force the content to be rolled back to nothing
+            ("(((((", 4, "", Err("(())")),
+            ("((((((((", 4, ".", Err("(.)")),
+            ("abcdef", 2, "...", Err("..")),
+        ] {
+            let mut buf = [0xffu8; 16];
+            let mut writer = WriteBuf::new(&mut buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let result = writer.finish_balanced(marker);
+            let as_str = |len: usize| core::str::from_utf8(&buf[..len]).unwrap();
+            assert_eq!(
+                expected,
+                result.map(as_str).map_err(as_str),
+                "input={input:?} buf_size={buf_size}"
+            );
+        }
+    }
+
+    #[test]
+    fn finish_balanced_without_closers_matches_finish_with_or() {
+        for (input, _) in TEST_CASES.iter() {
+            for buf_len in 0..input.len() + 4 {
+                let mut expected_buf = [0xffu8; 128];
+                let mut writer = WriteBuf::new(&mut expected_buf[..buf_len]);
+                let _ = writer.write_str(input);
+                let expected = writer.finish_with_or("", "…");
+
+                let mut buf = [0xffu8; 128];
+                let mut writer = WriteBuf::new(&mut buf[..buf_len]);
+                let _ = writer.write_str(input);
+                assert_eq!(
+                    expected,
+                    writer.finish_balanced("…"),
+                    "input={input:?} buf_len={buf_len}"
+                );
+                assert_eq!(expected_buf, buf, "input={input:?} buf_len={buf_len}");
+            }
+        }
+    }
+
+    #[test]
+    fn finish_bidi_safe_closes_embeddings() {
+        // RLO, RLI, PDF and PDI are 3 bytes each
+        for (input, buf_size, expected) in [
+            // Balanced content is left alone
+            ("a\u{202E}b\u{202C}c", 16, Ok("a\u{202E}b\u{202C}c!")),
+            ("abc", 4, Ok("abc!")),
+            // Content left open by the input itself is closed, even when nothing was truncated
+            ("a\u{202E}bc", 16, Ok("a\u{202E}bc\u{202C}!")),
+            ("a\u{202E}bc", 9, Err("a\u{202E}b\u{202C}.")),
+            // Nested controls are closed innermost first
+            ("x\u{2067}y\u{202E}z", 32, Ok("x\u{2067}y\u{202E}z\u{202C}\u{2069}!")),
+            (
+                "x\u{2067}y\u{202E}z\u{202C}\u{2069}",
+                15,
+                Err("x\u{2067}y\u{202E}\u{202C}\u{2069}."),
+            ),
+            // Rolling back past a control means it no longer needs closing
+            ("a\u{202E}\u{202E}b", 5, Err("a.")),
+            // The suffix does not fit along with any content
+            ("\u{202E}a", 3, Err("...")),
+            ("\u{202E}a", 2, Err("..")),
+        ] {
+            let mut buf = [0xffu8; 32];
+            let mut writer = WriteBuf::new(&mut buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let suffix = if buf_size < 4 { "..." } else { "." };
+            let result = writer.finish_bidi_safe_with_or("!", suffix);
+            let as_str = |len: usize| core::str::from_utf8(&buf[..len]).unwrap();
+
+            assert_eq!(
+                expected,
+                result.map(as_str).map_err(as_str),
+                "input={:?} buf_size={}",
+                input,
+                buf_size
+            );
+        }
+    }
+
+    #[test]
+    fn finish_bidi_safe_strict_does_not_roll_back() {
+        let mut buf = [0xffu8; 8];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_str("ab\u{202E}c").unwrap();
+        // The PDF and suffix only fit by giving up content
+        assert_eq!(Err(6), writer.finish_bidi_safe_with_or("!", "."));
+    }
+
+    #[test]
+    fn finish_bidi_safe_without_controls_matches_finish_with_or() {
+        for (input, _) in TEST_CASES.iter() {
+            for buf_len in 0..input.len() + 4 {
+                let mut expected_buf = [0xffu8; 128];
+                let mut writer = WriteBuf::new(&mut expected_buf[..buf_len]);
+                let _ = writer.write_str(input);
+                let expected = writer.finish_with_or("!", "…");
+
+                let mut buf = [0xffu8; 128];
+                let mut writer = WriteBuf::new(&mut buf[..buf_len]);
+                let _ = writer.write_str(input);
+                assert_eq!(
+                    expected,
+                    writer.finish_bidi_safe_with_or("!", "…"),
+                    "input={:?} buf_len={}",
+                    input,
+                    buf_len
+                );
+                assert_eq!(expected_buf, buf, "input={:?} buf_len={}", input, buf_len);
+            }
+        }
+    }
+
+    #[test]
+    fn finish_with_char_matches_finish_with() {
+        for (input, buf_size, normal, truncated) in [
+            ("abc", 4, '!', '…'),
+            ("abcde", 6, '!', '…'),
+            ("abcdef", 6, '!', '…'),
+            ("🚀", 5, '!', '…'),
+            ("🚀", 6, '!', '…'),
+            ("a", 2, '!', '…'),
+            ("", 1, '🚀', '🚀'),
+        ] {
+            let mut expected_buf = [0xffu8; 8];
+            let mut writer = WriteBuf::new(&mut expected_buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let (mut normal_buf, mut truncated_buf) = ([0u8; 4], [0u8; 4]);
+            let expected = writer.finish_with_or(
+                normal.encode_utf8(&mut normal_buf),
+                truncated.encode_utf8(&mut truncated_buf),
+            );
+
+            let mut buf = [0xffu8; 8];
+            let mut writer = WriteBuf::new(&mut buf[..buf_size]);
+            let _ = writer.write_str(input);
+            assert_eq!(
+                expected,
+                writer.finish_with_or_char(normal, truncated),
+                "input={input:?}"
+            );
+            assert_eq!(expected_buf, buf, "input={input:?}");
+        }
+    }
+
+    #[test]
+    fn finish_with_fn_sees_state() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 2);
+        writer.write_str("abc").unwrap();
+        let result = writer.finish_with_fn(|ctx| {
+            assert_eq!(3, ctx.position());
+            assert_eq!(5, ctx.remaining());
+            assert!(!ctx.truncated());
+            "\r\n"
+        });
+        assert_eq!(Ok(5), result);
+        assert_eq!(b"abc\r\n", &buf[..5]);
+
+        let mut writer = WriteBuf::with_reserve(&mut buf, 2);
+        writer.write_str("abcdefgh").unwrap_err();
+        let result = writer.finish_with_fn(|ctx| {
+            assert_eq!(6, ctx.position());
+            assert_eq!(2, ctx.remaining());
+            assert!(ctx.truncated());
+            "(more)"
+        });
+        // a suffix longer than the remaining space rolls back like finish_with
+        assert_eq!(Err(8), result);
+        assert_eq!(b"ab(more)", &buf);
+    }
+
+    #[test]
+    fn finish_str_matches_finish() {
+        for (input, buf_size, normal, truncated) in [
+            ("0123456789", 8, "", ""),
+            ("0123", 12, "!", "..."),
+            ("🚀🚀🚀", 10, "!", "…"),
+            ("🚀🚀🚀", 12, "!", "…"),
+            ("🚀", 2, "", "…"),
+        ] {
+            let mut expected_buf = [0xffu8; 12];
+            let mut writer = WriteBuf::new(&mut expected_buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let expected = writer.finish_with_or(normal, truncated);
+
+            let mut buf = [0xffu8; 12];
+            let mut writer = WriteBuf::new(&mut buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let result = writer.finish_with_or_str(normal, truncated);
+            let as_str = |len: usize| core::str::from_utf8(&expected_buf[..len]).unwrap();
+            assert_eq!(expected.map(as_str).map_err(as_str), result);
+        }
+    }
+
+    #[test]
+    fn finish_str_variants() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abc").unwrap();
+        assert_eq!(Ok("abc"), writer.finish_str());
+
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abc").unwrap();
+        assert_eq!(Ok("abc\0"), writer.finish_with_str("\0"));
+
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abcdef").unwrap_err();
+        assert_eq!(Err("abcd"), writer.finish_str());
+    }
+
+    #[test]
+    fn finish_cstr_always_terminates() {
+        for (input, buf_size, expected) in [
+            ("", 1, Ok("")),
+            ("a", 1, Err("")),
+            ("abc", 4, Ok("abc")),
+            ("abcd", 4, Err("abc")),
+            ("ab🚀", 6, Err("ab")),
+            ("ab🚀", 7, Ok("ab🚀")),
+        ] {
+            let mut buf = [0xffu8; 8];
+            let mut writer = WriteBuf::new(&mut buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let result = writer
+                .finish_cstr()
+                .map(|s| s.to_str().unwrap())
+                .map_err(|s| s.to_str().unwrap());
+            assert_eq!(expected, result, "input={input:?} buf_size={buf_size}");
+
+            // creating with new_cstr avoids the rollback
+            let mut writer = WriteBuf::new_cstr(&mut buf[..buf_size]);
+            let _ = writer.write_str(input);
+            let truncated = writer.truncated();
+            let result = writer.finish_cstr();
+            assert_eq!(truncated, result.is_err());
+            let cstr = match result {
+                Ok(s) | Err(s) => s,
+            };
+            assert!(input.starts_with(cstr.to_str().unwrap()));
+        }
+    }
+
+    #[test]
+    fn finish_cstr_degenerate() {
+        let mut buf: [u8; 0] = [];
+        let writer = WriteBuf::new_cstr(&mut buf);
+        assert_eq!(b"", writer.finish_cstr().unwrap_err().to_bytes());
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new_cstr(&mut buf);
+        writer.write_str("ab\0cd").unwrap();
+        assert_eq!(b"ab", writer.finish_cstr().unwrap().to_bytes());
+        assert_eq!(b"ab\0cd\0", &buf[..6]);
+    }
+
+    #[test]
+    fn finish_and_reset_matches_finish() {
+        for (input, buf_size, reserve, normal, truncated) in [
+            ("0123456789", 8, 0, "", ""),
+            ("0123456789", 12, 1, "!", "..."),
+            ("0123", 12, 2, "!\0", "...\0"),
+            ("🚀🚀🚀", 10, 0, "!", "…"),
+            ("🚀🚀🚀", 10, 3, "!", "…"),
+            ("🚀", 2, 0, "", "…"),
+        ] {
+            let mut expected_buf = [0xffu8; 12];
+            let mut writer = WriteBuf::with_reserve(&mut expected_buf[..buf_size], reserve);
+            let _ = writer.write_str(input);
+            let expected = writer.finish_with_or(normal, truncated);
+            let expected_buf = &expected_buf[..buf_size];
+
+            let mut buf = [0xffu8; 12];
+            let mut writer = WriteBuf::with_reserve(&mut buf[..buf_size], reserve);
+            for _ in 0..3 {
+                let _ = writer.write_str(input);
+                let mut finished = SimpleString::from("");
+                let result = writer.finish_with_or_and_reset(normal, truncated, |written| {
+                    finished.append(core::str::from_utf8(written).unwrap())
+                });
+                assert_eq!(
+                    expected, result,
+                    "input={input:?} buf_size={buf_size} reserve={reserve}"
+                );
+                let len = match expected {
+                    Ok(len) | Err(len) => len,
+                };
+                assert_eq!(&expected_buf[..len], finished.as_str().as_bytes());
+                assert_eq!(0, writer.position());
+                assert!(!writer.truncated());
+                assert_eq!(reserve, writer.reserve());
+            }
+        }
+    }
+
+    #[test]
+    fn finish_and_reset_clears_segment() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("first").unwrap();
+        assert_eq!(Ok(6), writer.finish_with_and_reset("\n", |_| ()));
+        assert!(!writer.undo_last_write());
+        writer.write_str("second").unwrap();
+        assert_eq!(Ok(6), writer.finish_and_reset(|_| ()));
+        assert_eq!(b"second\xff\xff", &buf);
+    }
+
+    /// A small xorshift generator, so property-style tests are reproducible without extra dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// Run random operations with adversarial lengths, reserves and indices against writers of every kind. Any panic
+    /// fails the test, and the finished content must always be valid UTF-8 inside the target buffer.
+    #[test]
+    fn adversarial_operations_never_panic() {
+        const PIECES: &[&str] = &["", "a", "bc", "ø", "見", "🚀", "0123456789", "(x[y", "🚀🚀🚀🚀🚀🚀🚀"];
+        let mut rng = Rng(0xbad_5eed);
+        for _ in 0..20_000 {
+            let mut buf = [0xffu8; 24];
+            let buf_len = rng.below(buf.len() + 1);
+            let reserve = rng.below(2 * buf_len + 3);
+            let target = &mut buf[..buf_len];
+            let mut writer = match rng.below(5) {
+                0 => WriteBuf::new(target),
+                1 => WriteBuf::with_reserve(target, reserve),
+                2 => WriteBuf::with_front_reserve(target, reserve),
+                3 => WriteBuf::new_strict(target),
+                _ => WriteBuf::with_reserve_strict(target, reserve),
+            };
+            writer.set_latch(rng.below(2) == 0);
+            writer.set_atomic_truncates(rng.below(2) == 0);
+
+            for _ in 0..rng.below(12) {
+                let piece = PIECES[rng.below(PIECES.len())];
+                let idx = rng.below(2 * buf_len + 2);
+                match rng.below(17) {
+                    0 => drop(writer.write_str(piece)),
+                    1 => drop(writer.write_char(piece.chars().next().unwrap_or('é'))),
+                    2 => writer.set_reserve(idx),
+                    3 => writer.release_reserve(idx),
+                    4 => drop(writer.try_set_reserve(idx)),
+                    5 => drop(writer.advance(idx)),
+                    6 => drop(writer.pop_n(idx)),
+                    7 => drop(writer.insert_str(idx, piece)),
+                    8 => drop(writer.overwrite(idx, piece)),
+                    9 => drop(writer.write_str_atomic(piece)),
+                    10 => drop(writer.write_display_truncated(&piece, idx, "…")),
+                    11 => drop(writer.undo_last_write()),
+                    12 => drop(writer.sub_writer(idx).write_str(piece)),
+                    13 => drop(writer.write_list(piece.split(""), "[", ",", "]")),
+                    14 => drop(writer.debug_struct("S").field("f", &piece).finish()),
+                    15 => {
+                        let checkpoint = writer.checkpoint();
+                        let _ = writer.overwrite(idx, piece);
+                        let _ = writer.rollback_to(checkpoint);
+                        let mut list = writer.debug_list();
+                        list.entry_with(|buf| {
+                            buf.set_reserve(idx);
+                            Ok(())
+                        });
+                        let _ = list.finish();
+                    },
+                    _ => drop(write!(writer, "{}{}", piece, idx)),
+                }
+                assert!(writer.position() <= writer.capacity());
+            }
+
+            let suffix = PIECES[rng.below(PIECES.len())];
+            let len = match rng.below(10) {
+                0 => writer.finish(),
+                1 => writer.finish_with(suffix),
+                2 => writer.finish_with_or("", suffix),
+                3 => writer.finish_trimmed_with(suffix),
+                4 => writer.finish_with_padded(suffix, '.'),
+                5 => writer.finish_balanced(suffix),
+                6 => writer.finish_with_char('🚀'),
+                7 => writer.finish_or_replace(suffix),
+                8 => writer.finish_with_fmt(format_args!("{}", suffix), format_args!("{}{}", suffix, suffix)),
+                _ => writer.finish_report("!", suffix).result(),
+            };
+            let len = len.unwrap_or_else(|len| len);
+            assert!(len <= buf_len);
+            assert!(core::str::from_utf8(&buf[..len]).is_ok());
+        }
+    }
+
+    #[test]
+    fn uninit_target_reads_only_written_bytes() {
+        let mut buf = [MaybeUninit::<u8>::uninit(); 16];
+        let mut writer = WriteBuf::with_reserve_uninit(&mut buf, 1);
+        let (text, n) = ("ab", 12);
+        write!(writer, "{}-{}", text, n).unwrap();
+        writer.insert_str(0, "見").unwrap();
+        writer.overwrite(3, "x").unwrap();
+        assert_eq!(Some('2'), writer.pop());
+        assert_eq!("見xb-1", writer.written());
+        writer.write_str("does not fit").unwrap_err();
+        assert_eq!(Err("見xb-1does n…"), writer.finish_with_str("…"));
+
+        // Splitting keeps track of which side was initialized
+        let mut buf = [MaybeUninit::<u8>::uninit(); 12];
+        let (mut front, mut back) = WriteBuf::new_uninit(&mut buf).split_at(4).ok().unwrap();
+        front.write_str("abcdef").unwrap_err();
+        back.write_str("12").unwrap();
+        assert_eq!(Err("a..."), front.finish_with_str("..."));
+        let (written, tail) = back.finish_split();
+        assert_eq!(Ok("12"), written);
+        assert_eq!([0; 6], tail);
+    }
+
+    #[test]
+    fn uninit_spare_capacity_is_zeroed() {
+        let mut buf = [MaybeUninit::<u8>::uninit(); 8];
+        let mut writer = WriteBuf::new_uninit(&mut buf);
+        writer.write_str("ab").unwrap();
+        assert_eq!([0; 6], writer.spare_capacity_mut());
+        writer.spare_capacity_mut()[..2].copy_from_slice(b"cd");
+        writer.advance(2).unwrap();
+        // Zeroing only happens once, so bytes written but not claimed are still there
+        writer.spare_capacity_mut()[0] = b'e';
+        assert_eq!(b"e\0", &writer.spare_capacity_mut()[..2]);
+        assert_eq!(Ok("abcd"), writer.finish_str());
+    }
+
+    /// Run random operations against an uninitialized target and a zeroed one side by side. They must always agree, and
+    /// the finished length must only ever cover initialized bytes, which Miri checks when they are read.
+    #[test]
+    fn uninit_target_matches_initialized() {
+        const PIECES: &[&str] = &["", "a", "bc", " ", "見", "🚀", "0123456789", "(x[y", "\r\n"];
+        let iterations = if cfg!(miri) { 300 } else { 5_000 };
+        let mut rng = Rng(0x0dd_5eed);
+        for _ in 0..iterations {
+            let mut buf = [0u8; 24];
+            let mut uninit_buf = [MaybeUninit::<u8>::uninit(); 24];
+            let buf_len = rng.below(buf.len() + 1);
+            let reserve = rng.below(buf_len + 3);
+            let mut writer = WriteBuf::with_reserve(&mut buf[..buf_len], reserve);
+            let mut uninit = WriteBuf::with_reserve_uninit(&mut uninit_buf[..buf_len], reserve);
+            if rng.below(2) == 0 {
+                writer.set_latch(false);
+                uninit.set_latch(false);
+            }
+
+            for _ in 0..rng.below(10) {
+                let piece = PIECES[rng.below(PIECES.len())];
+                let idx = rng.below(buf_len + 2);
+                for writer in [&mut writer, &mut uninit] {
+                    match rng.0 % 10 {
+                        0 => drop(writer.write_char(piece.chars().next().unwrap_or('é'))),
+                        1 => drop(writer.insert_str(idx, piece)),
+                        2 => drop(writer.overwrite(idx, piece)),
+                        3 => drop(writer.pop_n(idx)),
+                        4 => drop(writer.undo_last_write()),
+                        5 => drop(writer.sub_writer(idx).write_str(piece)),
+                        6 => drop(writer.write_display_truncated(&piece, idx, "…")),
+                        7 => drop(writer.advance(idx.min(2))),
+                        8 => drop(write!(writer, "{}{}", piece, idx)),
+                        _ => drop(writer.write_str(piece)),
+                    }
+                }
+                assert_eq!(writer.written(), uninit.written());
+                assert_eq!(writer.truncated(), uninit.truncated());
+            }
+
+            let suffix = PIECES[rng.below(PIECES.len())];
+            let finish = |writer: WriteBuf| match rng.0 % 9 {
+                0 => writer.finish_with(suffix),
+                1 => writer.finish_with_or("", suffix),
+                2 => writer.finish_trimmed_with(suffix),
+                3 => writer.finish_with_padded(suffix, '.'),
+                4 => writer.finish_balanced(suffix),
+                5 => writer.finish_with_char('🚀'),
+                6 => writer.finish_or_replace(suffix),
+                7 => writer.finish_with_or_line(suffix, LineEnding::CrLf),
+                _ => writer.finish_with_fmt(format_args!("{}", suffix), format_args!("{}{}", suffix, suffix)),
+            };
+            let expected = finish(writer);
+            let result = finish(uninit);
+            assert_eq!(expected, result);
+            let len = result.unwrap_or_else(|len| len);
+            // safety: The writer never reports a length covering bytes it did not write
+            assert_eq!(&buf[..len], unsafe { crate::target::assume_init(&uninit_buf[..len]) });
+        }
+    }
+
+    #[test]
+    fn fits_agrees_with_write() {
+        const PIECES: &[&str] = &["", "a", "bc", "def", "ø", "見", "🚀", "0123456789"];
+        let mut rng = Rng(0x5eed_f175);
+        for _ in 0..2000 {
+            let mut buf = [0xffu8; 24];
+            let buf_len = rng.below(buf.len() + 1);
+            let reserve = rng.below(buf_len + 3);
+            let mut writer = WriteBuf::with_reserve(&mut buf[..buf_len], reserve);
+            for _ in 0..rng.below(10) {
+                let piece = PIECES[rng.below(PIECES.len())];
+                let fits = writer.fits_str(piece);
+                assert_eq!(fits, writer.would_fit(format_args!("{}", piece)));
+                let position = writer.position();
+                if rng.below(2) == 0 {
+                    assert_eq!(fits, writer.write_str(piece).is_ok(), "piece={piece:?}");
+                } else {
+                    let was_truncated = writer.truncated();
+                    assert_eq!(fits, writer.write_str_if_fits(piece), "piece={piece:?}");
+                    assert_eq!(was_truncated, writer.truncated());
+                    if !fits {
+                        assert_eq!(position, writer.position());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn write_str_atomic_all_or_nothing() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 1);
+
+        writer.write_str_atomic("ab").unwrap();
+        assert_eq!(
+            Err(WriteError::ReserveBlocked {
+                needed: 6,
+                available: 5
+            }),
+            writer.write_str_atomic("cdefgh")
+        );
+        assert_eq!(
+            Err(WriteError::Capacity {
+                needed: 7,
+                available: 5
+            }),
+            writer.write_str_atomic("cdefghi")
+        );
+        assert_eq!("ab", writer.written());
+        assert!(!writer.truncated());
+        writer.write_str_atomic("cdefg").unwrap();
+        assert_eq!(
+            Err(WriteError::ReserveBlocked {
+                needed: 1,
+                available: 0
+            }),
+            writer.write_str_atomic("h")
+        );
+        writer.write_str_atomic("").unwrap();
+        assert_eq!(Ok(8), writer.finish_with("!"));
+        assert_eq!(b"abcdefg!", &buf);
+    }
+
+    #[test]
+    fn write_str_atomic_truncates_when_enabled() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.set_atomic_truncates(true);
+
+        writer.write_str_atomic("🚀🚀").unwrap_err();
+        assert!(writer.content_truncated());
+        assert_eq!(0, writer.position());
+        assert_eq!(Err(WriteError::AlreadyTruncated), writer.write_str_atomic("a"));
+        assert_eq!(Err(0), writer.finish());
+    }
+
+    #[test]
+    fn write_fmt_atomic_measures_first() {
+        struct Inner(u32);
+        impl fmt::Display for Inner {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "[{}]", self.0)
+            }
+        }
+        struct Outer(Inner, Inner);
+        impl fmt::Display for Outer {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}-{}", self.0, self.1)
+            }
+        }
+
+        // The target is not touched by a write which does not fit
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::new(&mut buf);
+        let value = Outer(Inner(1), Inner(23456));
+        write_atomic!(writer, "{}", value).unwrap_err();
+        assert_eq!(0, writer.position());
+        assert_eq!(0, writer.dropped_bytes());
+        write_atomic!(writer, "{}", Outer(Inner(1), Inner(2))).unwrap();
+        assert_eq!("[1]-[2]", writer.written());
+        assert_eq!(Ok(7), writer.finish());
+        assert_eq!([0xff; 3], buf[7..]);
+    }
+
+    #[test]
+    fn write_fmt_atomic_output_changes_between_passes() {
+        // Each time this is formatted, the output is one byte longer
+        struct Growing(core::cell::Cell<usize>);
+        impl fmt::Display for Growing {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let len = self.0.get();
+                self.0.set(len + 1);
+                for _ in 0..len {
+                    f.write_str("x")?;
+                }
+                Ok(())
+            }
+        }
+
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("a").unwrap();
+        let growing = Growing(core::cell::Cell::new(3));
+        assert_eq!(
+            Err(WriteError::Capacity {
+                needed: 4,
+                available: 3
+            }),
+            write_atomic!(writer, "{}", growing)
+        );
+        assert_eq!("a", writer.written());
+        assert!(!writer.truncated());
+
+        struct Failing;
+        impl fmt::Display for Failing {
+            fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+        assert_eq!(Err(WriteError::InvalidInput), write_atomic!(writer, "{}", Failing));
+        assert_eq!("a", writer.written());
+    }
+
+    #[test]
+    fn write_atomic_macro_rolls_back() {
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::new(&mut buf);
+        let (temp, unit) = (12345, 'C');
+
+        write!(writer, "temp=").unwrap();
+        assert_eq!(
+            Err(WriteError::Capacity {
+                needed: 6,
+                available: 5
+            }),
+            write_atomic!(writer, "{temp}{unit}")
+        );
+        assert_eq!("temp=", writer.written());
+        assert!(!writer.truncated());
+        write_atomic!(writer, "{}{unit}", temp / 100).unwrap();
+        assert_eq!("temp=123C", writer.written());
+        // the whole macro counts as one write
+        assert!(writer.undo_last_write());
+        assert_eq!("temp=", writer.written());
+    }
+
+    #[test]
+    fn strict_writes_all_or_nothing() {
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        assert!(writer.is_strict());
+
+        writer.write_str("🚀").unwrap();
+        writer.write_str("🚀🚀").unwrap_err();
+        assert_eq!("🚀", writer.written());
+        assert!(writer.content_truncated());
+        // strict writers still latch, so a write that would fit is rejected
+        writer.write_str("a").unwrap_err();
+        assert_eq!(Err(7), writer.finish_with_or("!", "…"));
+        assert_eq!("🚀…", core::str::from_utf8(&buf[..7]).unwrap());
+    }
+
+    #[test]
+    fn strict_readme_example() {
+        let mut buf: [u8; 10] = [0; 10];
+        let mut writer = WriteBuf::new(&mut buf);
+        write!(&mut writer, "🚀🚀🚀").unwrap_err();
+        assert_eq!("🚀🚀", writer.written());
+        assert_eq!(Err(7), writer.finish_with_or("!", "…"));
+        assert_eq!("🚀…", core::str::from_utf8(&buf[..7]).unwrap());
+
+        let mut buf: [u8; 10] = [0; 10];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        write!(&mut writer, "🚀🚀🚀").unwrap_err();
+        assert_eq!("", writer.written());
+        assert_eq!(Err(3), writer.finish_with_or("!", "…"));
+        assert_eq!("…", core::str::from_utf8(&buf[..3]).unwrap());
+    }
+
+    #[test]
+    fn strict_write_fmt_is_all_or_nothing() {
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        let (key, value) = ("name", "rocket🚀");
+        write!(writer, "{}=", key).unwrap();
+        write!(writer, "{}", value).unwrap_err();
+        assert_eq!("name=", writer.written());
+
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        write!(writer, "{}={}", key, value).unwrap_err();
+        assert_eq!("", writer.written());
+        assert!(writer.truncated());
+    }
+
+    #[test]
+    fn policy_decides_where_writes_are_cut() {
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+        writer.write_str("one\n").unwrap();
+        writer.write_str("two\nthree").unwrap_err();
+        assert_eq!("one\ntwo\n", writer.written());
+
+        // The policy carries over to sub-writers
+        let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+        let mut sub = writer.sub_writer(6);
+        sub.write_str("ab\ncdef").unwrap_err();
+        assert_eq!("ab\n", sub.written());
+
+        // The default cuts at the last code point, and it can be set back
+        let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+        writer.set_policy(&CodePoint);
+        writer.write_str("one\ntwo\nthree").unwrap_err();
+        assert_eq!("one\ntwo\nth", writer.written());
+    }
+
+    #[test]
+    fn policy_decides_where_suffix_rolls_back() {
+        let mut buf: [u8; 12] = [0xff; 12];
+        let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+        writer.write_str("one\ntwo\nthre").unwrap();
+        assert_eq!(Err("one\ntwo\n..."), writer.finish_with_str("..."));
+
+        let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+        writer.write_str("one\ntwo\nthre").unwrap();
+        assert_eq!(
+            Err(11),
+            writer.finish_with_fmt(format_args!("{}", "!"), format_args!("{}", "..."))
+        );
+        assert_eq!(b"one\ntwo\n...", &buf[..11]);
+
+        // With no complete line before the suffix, all of the content is rolled back
+        let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+        writer.write_str("abcdefghijkl").unwrap();
+        assert_eq!(Err("..."), writer.finish_with_str("..."));
+
+        // The content stays if the suffix fits after it
+        let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+        writer.write_str("abc").unwrap();
+        assert_eq!(Ok("abc..."), writer.finish_with_str("..."));
+    }
+
+    #[test]
+    fn strict_finish_never_rolls_back() {
+        // Each writer holds "abcdef" with 2 bytes of reserve, so a 3 byte suffix only fits by rolling back
+        fn new(buf: &mut [u8; 8]) -> WriteBuf<'_> {
+            buf.fill(0xff);
+            let mut writer = WriteBuf::with_reserve_strict(buf, 2);
+            writer.write_str("abcdef").unwrap();
+            writer
+        }
+        let mut buf = [0xffu8; 8];
+
+        let report = new(&mut buf).finish_report("", "...");
+        assert_eq!(SuffixKind::Normal, report.suffix_applied);
+
+        let mut writer = new(&mut buf);
+        writer.write_str("g").unwrap_err();
+        let report = writer.finish_report("", "...");
+        assert_eq!(SuffixKind::Omitted, report.suffix_applied);
+        assert!(report.suffix_degraded);
+        assert_eq!(Err(6), report.result());
+        assert_eq!(b"abcdef\xff\xff", &buf);
+
+        assert_eq!(Err(6), new(&mut buf).finish_with("!!!"));
+        assert_eq!(
+            Err(6),
+            new(&mut buf).finish_with_fmt(format_args!("{}", "!!!"), format_args!("..."))
+        );
+        assert_eq!(b"abcdef\xff\xff", &buf);
+        let mut writer = new(&mut buf);
+        writer.write_str("gh").unwrap_err();
+        assert_eq!(Err(6), writer.finish_with_or_line("...", LineEnding::CrLf));
+        assert_eq!(b"abcdef\xff\xff", &buf);
+        assert_eq!(Ok(7), new(&mut buf).finish_with_newline());
+        assert_eq!(b"abcdef\n\xff", &buf);
+        assert_eq!(Err(8), new(&mut buf).finish_with_padded("!!!", '.'));
+        assert_eq!(b"abcdef..", &buf);
+
+        let mut writer = new(&mut buf);
+        writer.write_str("(").unwrap_err();
+        assert_eq!(Err(6), writer.finish_balanced("..."));
+
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_str("abcd").unwrap();
+        assert_eq!(b"", writer.finish_cstr().unwrap_err().to_bytes());
+    }
+
+    #[test]
+    fn write_str_partial_continues_in_another_buffer() {
+        let mut first: [u8; 6] = [0xff; 6];
+        let mut second: [u8; 8] = [0xff; 8];
+        let s = "abc🚀de";
+
+        let mut writer = WriteBuf::new(&mut first);
+        writer.write_str_partial("").unwrap();
+        // the rocket starts at offset 3, so only "abc" fits in the 6 byte buffer
+        let partial = writer.write_str_partial(s).unwrap_err();
+        assert_eq!(
+            PartialWrite {
+                written: 3,
+                required: 9
+            },
+            partial
+        );
+        assert!(writer.content_truncated());
+        assert_eq!(
+            Err(PartialWrite {
+                written: 0,
+                required: 1
+            }),
+            writer.write_str_partial("f")
+        );
+        assert_eq!(Err(3), writer.finish());
+
+        let mut writer = WriteBuf::new(&mut second);
+        assert_eq!(Ok(6), writer.write_str_partial(&s[partial.written..]));
+        assert_eq!(Ok(6), writer.finish());
+        assert_eq!(b"abc", &first[..3]);
+        assert_eq!("🚀de", core::str::from_utf8(&second[..6]).unwrap());
+    }
+
+    #[test]
+    fn lossy_never_fails() {
+        struct Value<'s>(&'s str);
+
+        impl fmt::Display for Value<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut buf: [u8; 6] = [0xff; 6];
+                let mut writer = WriteBuf::new(&mut buf);
+                write!(writer.lossy(), "{}", self.0)?;
+                f.write_str(writer.written())
+            }
+        }
+
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        write!(writer, "[{}]", Value("🚀🚀")).unwrap();
+        assert_eq!("[🚀]", writer.written());
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        let (first, rocket) = ("abc", "🚀🚀");
+        // the '|' would fit after the cut rocket, but truncation latches, so the rest of the pieces are dropped
+        write!(writer.lossy(), "{}{}|", first, rocket).unwrap();
+        writer.lossy().write_str("x").unwrap();
+        writer.lossy().write_char('y').unwrap();
+        assert_eq!("abc🚀", writer.written());
+        assert!(writer.truncated());
+        assert_eq!(Err(6), writer.finish_with_or("", "..."));
+        assert_eq!(b"abc...", &buf[..6]);
+    }
+
+    #[test]
+    fn non_latching_keeps_writing() {
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.set_latch(false);
+        assert!(!writer.is_latching());
+
+        writer.write_str("ab").unwrap();
+        let (rocket, c) = ("🚀", 'c');
+        // the rocket does not fit in the 4 remaining bytes after "ab", but the 'c' after it does
+        write!(writer, "{rocket}{rocket}{c}").unwrap_err();
+        assert_eq!("ab🚀", writer.written());
+        writer.write_str("xy").unwrap_err();
+        assert!(writer.content_truncated());
+
+        let mut buf: [u8; 5] = [0xff; 5];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.set_latch(false);
+        writer.write_str("ab").unwrap();
+        // with only 3 bytes remaining, the rocket is dropped entirely and the 'c' lands right after "ab"
+        write!(writer, "{rocket}{c}").unwrap_err();
+        assert_eq!("abc", writer.written());
+        assert!(writer.truncated());
+        assert!(writer.fits_str("d"));
+        writer.write_str("d").unwrap();
+        writer.write_str_atomic("e").unwrap();
+        assert_eq!(
+            Err(WriteError::Capacity {
+                needed: 1,
+                available: 0
+            }),
+            write_atomic!(writer, "{c}")
+        );
+        assert_eq!(Err(5), writer.finish_with_or("", "~"));
+        assert_eq!("abcd~", core::str::from_utf8(&buf).unwrap());
+    }
+
+    #[test]
+    fn non_latching_transaction_sees_new_truncation() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.set_latch(false);
+
+        writer.write_str("0123456789").unwrap_err();
+        writer.pop_n(6);
+        writer.transaction(|w| w.write_str("ab")).unwrap();
+        writer.transaction(|w| w.write_str("cdefg")).unwrap_err();
+        assert_eq!("01ab", writer.written());
+        assert!(writer.truncated());
+    }
+
+    /// Keeps the last event a hook was called with, since a hook is a plain `fn` and can only reach statics.
+    struct LastEvent {
+        calls: AtomicUsize,
+        position: AtomicUsize,
+        len: AtomicUsize,
+        overflow: AtomicUsize,
+        finish: AtomicBool,
+    }
+
+    impl LastEvent {
+        const fn new() -> Self {
+            Self {
+                calls: AtomicUsize::new(0),
+                position: AtomicUsize::new(0),
+                len: AtomicUsize::new(0),
+                overflow: AtomicUsize::new(0),
+                finish: AtomicBool::new(false),
+            }
+        }
+
+        fn record(&self, event: &TruncationEvent) {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.position.store(event.position, Ordering::Relaxed);
+            self.len.store(event.len, Ordering::Relaxed);
+            self.overflow.store(event.overflow, Ordering::Relaxed);
+            self.finish
+                .store(event.source == TruncationSource::Finish, Ordering::Relaxed);
+        }
+
+        /// Get the number of calls and the last event.
+        fn get(&self) -> (usize, TruncationEvent) {
+            let source = if self.finish.load(Ordering::Relaxed) {
+                TruncationSource::Finish
+            } else {
+                TruncationSource::Write
+            };
+            (
+                self.calls.load(Ordering::Relaxed),
+                TruncationEvent {
+                    position: self.position.load(Ordering::Relaxed),
+                    len: self.len.load(Ordering::Relaxed),
+                    overflow: self.overflow.load(Ordering::Relaxed),
+                    source,
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn on_truncate_is_called_once() {
+        static LAST: LastEvent = LastEvent::new();
+        fn hook(event: &TruncationEvent) {
+            LAST.record(event);
+        }
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.on_truncate(hook);
+        assert!(!writer.is_truncate_hook_repeating());
+        writer.write_str("abc").unwrap();
+        assert_eq!(0, LAST.get().0);
+        writer.write_str("defghijk").unwrap_err();
+        let expected = TruncationEvent {
+            position: 3,
+            len: 8,
+            overflow: 3,
+            source: TruncationSource::Write,
+        };
+        assert_eq!((1, expected), LAST.get());
+
+        // Neither the rejected write nor the suffix which does not fit call it again
+        writer.write_str("x").unwrap_err();
+        assert_eq!(Err(8), writer.finish_with("!"));
+        assert_eq!((1, expected), LAST.get());
+    }
+
+    #[test]
+    fn on_truncate_repeats() {
+        static LAST: LastEvent = LastEvent::new();
+        fn hook(event: &TruncationEvent) {
+            LAST.record(event);
+        }
+
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.on_truncate(hook);
+        writer.set_latch(false);
+        writer.set_truncate_hook_repeats(true);
+        assert!(writer.is_truncate_hook_repeating());
+        writer.write_str("abcdefgh").unwrap_err();
+        writer.write_str("xy").unwrap_err();
+        let expected = TruncationEvent {
+            position: 6,
+            len: 2,
+            overflow: 2,
+            source: TruncationSource::Write,
+        };
+        assert_eq!((2, expected), LAST.get());
+
+        assert_eq!(Err(6), writer.finish_with_or("", "~"));
+        let expected = TruncationEvent {
+            position: 6,
+            len: 1,
+            overflow: 1,
+            source: TruncationSource::Finish,
+        };
+        assert_eq!((3, expected), LAST.get());
+    }
+
+    #[test]
+    fn on_truncate_from_finish() {
+        static LAST: LastEvent = LastEvent::new();
+        fn hook(event: &TruncationEvent) {
+            LAST.record(event);
         }
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.on_truncate(hook);
+        writer.write_str("abcdef").unwrap();
+        assert_eq!(Err(8), writer.finish_with("!!!"));
+        let expected = TruncationEvent {
+            position: 6,
+            len: 3,
+            overflow: 1,
+            source: TruncationSource::Finish,
+        };
+        assert_eq!((1, expected), LAST.get());
+
+        // A formatted suffix is measured for the event
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.on_truncate(hook);
+        writer.write_str("abcdef").unwrap();
+        assert_eq!(
+            Err(8),
+            writer.finish_with_fmt(format_args!("{}", 1234), format_args!("{}", 1234))
+        );
+        let expected = TruncationEvent {
+            len: 4,
+            overflow: 2,
+            ..expected
+        };
+        assert_eq!((2, expected), LAST.get());
+
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.on_truncate(hook);
+        writer.clear_on_truncate();
+        writer.write_str("abcdef").unwrap_err();
+        assert_eq!(2, LAST.get().0);
+    }
+
+    #[test]
+    fn on_truncate_hook_can_format() {
+        static WRITTEN: AtomicUsize = AtomicUsize::new(0);
+        fn hook(event: &TruncationEvent) {
+            let mut buf: [u8; 96] = [0xff; 96];
+            let mut writer = WriteBuf::new(&mut buf);
+            write!(writer, "{:?}", event).unwrap();
+            WRITTEN.store(writer.finish().unwrap(), Ordering::Relaxed);
+        }
+
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.on_truncate(hook);
+        writer.write_str("abcdef").unwrap_err();
+        let mut expected: StackString<96> = StackString::new();
+        write!(
+            expected,
+            "{:?}",
+            TruncationEvent {
+                position: 0,
+                len: 6,
+                overflow: 2,
+                source: TruncationSource::Write,
+            }
+        )
+        .unwrap();
+        assert_eq!(expected.as_str().len(), WRITTEN.load(Ordering::Relaxed));
+    }
+
+    /// Check that the `write_u32` family of functions matches `Display` for each of `values`.
+    macro_rules! check_decimal {
+        ($write:ident, $($value:expr),+) => {
+            $(
+                let mut buf: [u8; 40] = [0xff; 40];
+                let mut writer = WriteBuf::new(&mut buf);
+                writer.$write($value).unwrap();
+                let mut expected: StackString<40> = StackString::new();
+                write!(expected, "{}", $value).unwrap();
+                assert_eq!(expected.as_str(), writer.written(), "{}({})", stringify!($write), $value);
+            )+
+        };
+    }
+
+    #[test]
+    fn write_decimal_extremes() {
+        check_decimal!(write_u8, u8::MIN, u8::MAX, 9, 10, 99, 100);
+        check_decimal!(write_u16, u16::MIN, u16::MAX);
+        check_decimal!(write_u32, u32::MIN, u32::MAX);
+        check_decimal!(write_u64, u64::MIN, u64::MAX);
+        check_decimal!(write_u128, u128::MIN, u128::MAX, u128::from(u64::MAX) + 1);
+        check_decimal!(write_usize, usize::MIN, usize::MAX);
+        check_decimal!(write_i8, i8::MIN, i8::MAX, -1, -10);
+        check_decimal!(write_i16, i16::MIN, i16::MAX);
+        check_decimal!(write_i32, i32::MIN, i32::MAX, 0);
+        check_decimal!(write_i64, i64::MIN, i64::MAX);
+        check_decimal!(write_i128, i128::MIN, i128::MAX, -1);
+        check_decimal!(write_isize, isize::MIN, isize::MAX);
+    }
+
+    #[test]
+    fn value_writers_are_all_or_nothing() {
+        type WriteValue = fn(&mut WriteBuf<'_>) -> fmt::Result;
+        const REGISTER: IntFormat = IntFormat::new().prefix(true).min_digits(8).group('_', 4);
+        let cases: &[(WriteValue, &str)] = &[
+            (|w| w.write_i16(-567), "-567"),
+            (|w| w.write_u128(1_000_000), "1000000"),
+            (|w| w.write_hex(0xbeef, REGISTER), "0x0000_beef"),
+            (
+                |w| w.write_oct(0o17, IntFormat::new().prefix(true).min_digits(3)),
+                "0o017",
+            ),
+            (|w| w.write_f64(-core::f64::consts::PI, 3), "-3.142"),
+            (
+                |w| w.write_uint_grouped(1_000_000, '\u{2009}', Grouping::Thousands),
+                "1\u{2009}000\u{2009}000",
+            ),
+            (|w| w.write_si(2.2e-6, "A", 2), "2.2 µA"),
+            (|w| w.write_bytes_human(1536, ByteStyle::BINARY), "1.5 KiB"),
+            (
+                |w| w.write_duration(Duration::from_secs(75), DurationStyle::Compact),
+                "1m15s",
+            ),
+            (
+                |w| w.write_duration_ticks(3600 * 100, 100, DurationStyle::Compact),
+                "1h0m0s",
+            ),
+            (
+                |w| w.write_rfc3339(0, 0, TimestampOpts::new()),
+                "1970-01-01T00:00:00.000Z",
+            ),
+            (
+                |w| w.write_socket_addr_v6([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1], 443),
+                "[2001:db8::1]:443",
+            ),
+            (
+                |w| w.write_uuid(&[0xab; 16], UuidStyle::HYPHENATED),
+                "abababab-abab-abab-abab-abababababab",
+            ),
+        ];
+        for (write, expected) in cases {
+            let fits = 2 + expected.len();
+            // One byte short, either from the end of the buffer or from the reserve, writes none of the value
+            for (capacity, reserve) in [(fits - 1, 0), (fits, 1)] {
+                let mut buf = [0xffu8; 64];
+                let mut writer = WriteBuf::with_reserve(&mut buf[..capacity], reserve);
+                writer.write_str("> ").unwrap();
+                write(&mut writer).unwrap_err();
+                assert_eq!("> ", writer.written(), "expected={:?}", expected);
+                assert!(writer.truncated(), "expected={:?}", expected);
+                assert_eq!(expected.len(), writer.dropped_bytes(), "expected={:?}", expected);
+            }
+
+            let mut buf = [0xffu8; 64];
+            let mut writer = WriteBuf::new(&mut buf[..fits]);
+            writer.write_str("> ").unwrap();
+            write(&mut writer).unwrap();
+            assert_eq!(expected, &&writer.written()[2..]);
+            assert!(!writer.truncated(), "expected={:?}", expected);
+            // The value is undone as a single write
+            assert!(writer.undo_last_write(), "expected={:?}", expected);
+            assert_eq!("> ", writer.written(), "expected={:?}", expected);
+        }
+    }
+    #[test]
+    #[should_panic]
+    fn write_duration_ticks_needs_a_clock() {
+        let mut buf: [u8; 12] = [0xff; 12];
+        WriteBuf::new(&mut buf)
+            .write_duration_ticks(1, 0, DurationStyle::Compact)
+            .ok();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_socket_addr_matches_display() {
+        use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
+
+        let addresses: [SocketAddr; 3] = [
+            "192.0.2.1:8080".parse().unwrap(),
+            "[2001:db8::1]:443".parse().unwrap(),
+            SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1),
+                8080,
+                0,
+                2,
+            )),
+        ];
+        for address in addresses {
+            let mut buf: [u8; 64] = [0xff; 64];
+            let mut writer = WriteBuf::new(&mut buf);
+            writer.write_socket_addr(address).unwrap();
+            assert_eq!(address.to_string(), writer.written());
+        }
+    }
+
+    #[test]
+    fn pad_to_column() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("id").unwrap();
+        writer.pad_to(6, ' ').unwrap();
+        writer.write_str("name").unwrap();
+        // Already past the column, so nothing is written
+        writer.pad_to(4, ' ').unwrap();
+        writer.pad_to(10, ' ').unwrap();
+        assert_eq!("id    name", writer.written());
+        assert!(!writer.truncated());
+    }
+
+    #[test]
+    fn pad_to_multibyte_fill_stops_short() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("a").unwrap();
+        // A gap of 7 bytes fits two 3-byte characters
+        writer.pad_to(8, '…').unwrap();
+        assert_eq!("a……", writer.written());
+        writer.pad_to(8, '=').unwrap();
+        assert_eq!("a……=", writer.written());
+        assert!(!writer.truncated());
+    }
+
+    #[test]
+    fn pad_to_past_the_end_truncates() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("ab").unwrap();
+        writer.pad_to(20, '─').unwrap_err();
+        assert_eq!("ab──", writer.written());
+        assert_eq!(12, writer.dropped_bytes());
+        assert!(writer.truncated());
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.pad_to(20, ' ').unwrap_err();
+        assert_eq!("", writer.written());
+        assert_eq!(20, writer.dropped_bytes());
+    }
+
+    #[test]
+    fn write_char_repeated_longer_than_a_chunk() {
+        let mut buf: [u8; 256] = [0xff; 256];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_char_repeated('─', 50).unwrap();
+        writer.write_char_repeated('=', 70).unwrap();
+        writer.write_char_repeated('x', 0).unwrap();
+        let written = writer.written();
+        assert_eq!(220, written.len());
+        assert!(written[..150].chars().all(|c| c == '─'));
+        assert!(written[150..].chars().all(|c| c == '='));
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_char_repeated('🚀', 3).unwrap_err();
+        assert_eq!("🚀🚀", writer.written());
+        assert_eq!(4, writer.dropped_bytes());
+    }
+
+    #[test]
+    fn write_padded_counts_chars() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_padded("héllo", 7, Align::Left, ' ').unwrap();
+        writer.write_str("|").unwrap();
+        writer.write_padded("ü", 4, Align::Right, '·').unwrap();
+        writer.write_str("|").unwrap();
+        // A leftover of 3 puts the extra fill after the content
+        writer.write_padded("ab", 5, Align::Center, '─').unwrap();
+        writer.write_str("|").unwrap();
+        writer.write_padded("toolong", 3, Align::Center, ' ').unwrap();
+        assert_eq!("héllo  |···ü|─ab──|toolong", writer.written());
+        assert!(!writer.truncated());
+        assert_eq!(Align::Left, Align::default());
+    }
+
+    #[test]
+    fn write_padded_truncates_fill() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_padded("ab", 6, Align::Left, ' ').unwrap();
+        // Two of the four 3-byte fill characters do not fit
+        writer.write_padded("", 4, Align::Left, '─').unwrap_err();
+        assert_eq!("ab    ", writer.written());
+        assert_eq!(12, writer.dropped_bytes());
+        assert!(writer.truncated());
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_padded("abc", 10, Align::Right, ' ').unwrap_err();
+        assert_eq!("", writer.written());
     }
 
     #[test]
-    fn format_enough_space() {
-        for (input, _) in TEST_CASES.iter() {
-            let mut buf: [u8; 128] = [0xff; 128];
-            let mut writer = WriteBuf::new(&mut buf);
+    fn write_display_padded_measures_value() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_display_padded(&3.5, 6, Align::Right, '0').unwrap();
+        writer.write_str("|").unwrap();
+        writer
+            .write_display_padded(&format_args!("{}→{}", 1, 2), 6, Align::Center, ' ')
+            .unwrap();
+        writer.write_str("|").unwrap();
+        writer.write_display_padded(&123_456, 2, Align::Left, ' ').unwrap();
+        assert_eq!("0003.5| 1→2  |123456", writer.written());
 
-            writer.write_str(input).unwrap();
-            assert_eq!(input.len(), writer.position());
-            let last_idx = writer.finish().unwrap();
-            assert_eq!(input.len(), last_idx);
-        }
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_display_padded(&"value", 10, Align::Left, ' ').unwrap_err();
+        assert_eq!("value   ", writer.written());
+        assert_eq!(2, writer.dropped_bytes());
+        assert!(writer.truncated());
     }
 
     #[test]
-    fn format_enough_space_just_enough_reserved() {
-        for (input, _) in TEST_CASES.iter() {
-            let mut buf: [u8; 128] = [0xff; 128];
-            let mut writer = WriteBuf::with_reserve(&mut buf[..input.len() + 1], 1);
+    fn write_hex_bytes_cuts_at_whole_bytes() {
+        let input: [u8; 1024] = core::array::from_fn(|idx| idx as u8);
+        let mut buf: [u8; 100] = [0xff; 100];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("x").unwrap();
+        // 99 bytes are left, which is the digits of 49 bytes and half of another
+        writer.write_hex_bytes(&input, false).unwrap_err();
+        let written = writer.written();
+        assert_eq!(99, written.len());
+        assert_eq!("x000102", &written[..7]);
+        assert_eq!("2f30", &written[95..]);
+        assert_eq!(2048 - 98, writer.dropped_bytes());
+        assert!(writer.truncated());
+    }
 
-            writer.write_str(input).unwrap();
-            assert_eq!(input.len(), writer.position());
-            let last_idx = writer.finish().unwrap();
-            assert_eq!(input.len(), last_idx);
-        }
+    #[test]
+    fn write_hex_bytes_sep_never_ends_on_a_separator() {
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::new(&mut buf);
+        // "00:11:22:33" needs 11 bytes; the last separator would fit, but not the digits after it
+        writer.write_hex_bytes_sep(&[0x00, 0x11, 0x22, 0x33], ':').unwrap_err();
+        assert_eq!("00:11:22", writer.written());
+        assert_eq!(3, writer.dropped_bytes());
+
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_hex_bytes_sep(&[0xab], '→').unwrap();
+        writer.write_hex_bytes_sep(&[], ':').unwrap();
+        writer.write_hex_bytes_sep(&[0xab, 0xcd, 0xef], '→').unwrap();
+        assert_eq!("abab→cd→ef", writer.written());
+
+        // A chunk of output is copied at a time
+        let input = [0x5a; 100];
+        let mut buf: [u8; 512] = [0xff; 512];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_hex_bytes_sep(&input, '-').unwrap();
+        assert_eq!(299, writer.position());
+        assert!(writer.written().split('-').all(|byte| byte == "5a"));
     }
 
     #[test]
-    fn format_truncation() {
-        for (input, last_valid_idx_after_cut) in TEST_CASES.iter() {
-            if input.len() == 0 {
-                continue;
-            }
+    fn write_hex_bytes_strict_writes_nothing_partial() {
+        let mut buf: [u8; 7] = [0xff; 7];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_hex_bytes(&[1, 2, 3, 4], true).unwrap_err();
+        assert_eq!("", writer.written());
+        writer.write_hex_bytes(&[1], true).unwrap_err();
+        assert_eq!(10, writer.dropped_bytes());
+    }
 
-            let mut buf: [u8; 128] = [0xff; 128];
-            let mut writer = WriteBuf::new(&mut buf[..input.len() - 1]);
+    #[test]
+    fn write_hex_dump_cuts_between_lines() {
+        let bytes: [u8; 40] = core::array::from_fn(|idx| idx as u8);
+        let opts = HexDumpOpts::new().bytes_per_line(8).group(0).ascii(false);
+        // Each full line is 8 + 2 + 23 + 1 bytes, so two of them fit and the third does not
+        let mut buf: [u8; 100] = [0xff; 100];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_hex_dump(&bytes, opts).unwrap_err();
+        assert_eq!(
+            "00000000  00 01 02 03 04 05 06 07\n00000008  08 09 0a 0b 0c 0d 0e 0f\n",
+            writer.written()
+        );
+        assert_eq!(3 * 34, writer.dropped_bytes());
+        assert!(writer.truncated());
 
-            writer.write_str(input).unwrap_err();
-            assert_eq!(*last_valid_idx_after_cut, writer.position());
-            assert!(writer.truncated());
-            write!(writer, "!!!").expect_err("writes should fail here");
+        let mut buf: [u8; 100] = [0xff; 100];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_hex_dump(&bytes, opts).unwrap_err();
+        assert_eq!("", writer.written());
 
-            let last_idx = writer.finish().unwrap_err();
-            assert_eq!(*last_valid_idx_after_cut, last_idx);
+        let mut buf: [u8; 100] = [0xff; 100];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_hex_dump(&[], opts).unwrap();
+        writer.write_hex_dump(&bytes[..3], opts).unwrap();
+        assert_eq!("00000000  00 01 02\n", writer.written());
+    }
+
+    #[test]
+    fn write_hex_dump_huge_line() {
+        let opts = HexDumpOpts::new().bytes_per_line(usize::MAX);
+        // The ASCII column is padded to the width of a full line, which can never fit
+        let mut buf: [u8; 100] = [0xff; 100];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_hex_dump(b"abc", opts).unwrap_err();
+        assert_eq!("", writer.written());
+        assert!(writer.truncated());
+        assert_eq!(usize::MAX, writer.dropped_bytes());
+
+        let mut buf: [u8; 100] = [0xff; 100];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_hex_dump(b"abc", opts.ascii(false)).unwrap();
+        assert_eq!("00000000  61 62 63\n", writer.written());
+    }
+
+    #[test]
+    fn write_base64_cuts_at_groups() {
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("k=").unwrap();
+        // "Zm9vYmFyYg==" is 12 bytes; the 8 left fit two groups
+        writer.write_base64(b"foobarb", Base64Config::STANDARD).unwrap_err();
+        assert_eq!("k=Zm9vYmFy", writer.written());
+        assert_eq!(4, writer.dropped_bytes());
+        assert!(writer.truncated());
+
+        // The last group is not split, even without padding
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer
+            .write_base64(b"foob", Base64Config::STANDARD.padding(false))
+            .unwrap();
+        writer
+            .write_base64(b"fo", Base64Config::STANDARD.padding(false))
+            .unwrap_err();
+        assert_eq!("Zm9vYg", writer.written());
+        assert_eq!(3, writer.dropped_bytes());
+
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_base64(b"foobar", Base64Config::STANDARD).unwrap_err();
+        assert_eq!("", writer.written());
+    }
+
+    #[test]
+    fn overwrite_patches_in_place() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("[-----] é elapsed=??ms").unwrap();
+        writer.overwrite(1, "ERROR").unwrap();
+        writer.overwrite(19, "42").unwrap();
+        // replacing a multibyte character with one of the same encoded length is fine
+        writer.overwrite(8, "è").unwrap();
+        assert_eq!("[ERROR] è elapsed=42ms", writer.written());
+        assert_eq!(23, writer.position());
+    }
+
+    #[test]
+    fn overwrite_rejects_bad_ranges() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("a🚀").unwrap();
+        writer.overwrite(1, "xx").unwrap_err(); // ends inside 🚀
+        writer.overwrite(2, "xyz").unwrap_err(); // starts inside 🚀
+        writer.overwrite(4, "xx").unwrap_err(); // past the end of written
+        writer.overwrite(usize::MAX, "x").unwrap_err();
+        writer.overwrite(0, "b").unwrap();
+        writer.overwrite(5, "").unwrap();
+        assert_eq!("b🚀", writer.written());
+    }
+
+    #[test]
+    fn overwrite_keeps_truncation() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("abcdef").unwrap_err();
+        writer.overwrite(0, "AB").unwrap();
+        assert!(writer.truncated());
+        assert_eq!(Err(4), writer.finish());
+        assert_eq!(b"ABcd", &buf);
+    }
+
+    #[test]
+    fn overwrite_across_undo_point_keeps_utf8() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("ab").unwrap();
+        let checkpoint = writer.checkpoint();
+        writer.write_str("cd").unwrap();
+
+        // "見" covers the position where the last write started, which is now in the middle of a char
+        writer.overwrite(1, "見").unwrap();
+        assert_eq!("a見", writer.written());
+        assert!(!writer.undo_last_write());
+        assert_eq!(Err(WriteError::InvalidInput), writer.rollback_to(checkpoint));
+        assert_eq!("a見", writer.written());
+    }
+
+    #[test]
+    fn last_char_and_ends_with() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        assert_eq!(None, writer.last_char());
+        assert!(writer.ends_with(""));
+        assert!(!writer.ends_with("."));
+
+        writer.write_str("aé").unwrap();
+        assert_eq!(Some('é'), writer.last_char());
+        assert!(writer.ends_with("é"));
+        assert!(writer.ends_with("aé"));
+        assert!(!writer.ends_with("baé"));
+
+        writer.write_str("见🚀").unwrap();
+        assert_eq!(Some('🚀'), writer.last_char());
+        assert!(writer.ends_with("见🚀"));
+        writer.pop();
+        assert_eq!(Some('见'), writer.last_char());
+    }
+
+    #[test]
+    fn truncation_flags_content_only() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 3);
+
+        writer.write_str("0123456789").unwrap_err();
+        assert!(writer.content_truncated());
+        assert!(!writer.suffix_degraded());
+        let report = writer.finish_report("", "...");
+        assert_eq!(report.written_len, 8);
+        assert!(report.content_truncated);
+        assert!(!report.suffix_degraded);
+        assert_eq!(SuffixKind::Truncated, report.suffix_applied);
+        assert_eq!(Err(8), report.result());
+        assert_eq!("01234...", core::str::from_utf8(&buf).unwrap());
+    }
+
+    #[test]
+    fn truncation_flags_suffix_only() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("01234567").unwrap();
+        assert!(!writer.truncated());
+        let report = writer.finish_report("!", "...");
+        assert!(!report.content_truncated);
+        assert!(report.suffix_degraded);
+        assert!(report.truncated());
+        assert_eq!(SuffixKind::RolledBack, report.suffix_applied);
+        assert_eq!("01234...", core::str::from_utf8(&buf[..report.written_len]).unwrap());
+    }
+
+    #[test]
+    fn truncation_flags_suffix_only_shorter_truncated_suffix_fits() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("0123").unwrap();
+        let report = writer.finish_report("456789", "?");
+        assert!(!report.content_truncated);
+        assert!(report.suffix_degraded);
+        assert_eq!(SuffixKind::Truncated, report.suffix_applied);
+        assert_eq!("0123?", core::str::from_utf8(&buf[..report.written_len]).unwrap());
+    }
+
+    #[test]
+    fn truncation_flags_giant_suffix_without_content() {
+        let mut buf: [u8; 2] = [0xff; 2];
+        let writer = WriteBuf::new(&mut buf);
+
+        let report = writer.finish_report("abc", "abc");
+        assert!(!report.content_truncated);
+        assert!(report.suffix_degraded);
+        assert_eq!(2, report.written_len);
+        assert_eq!(SuffixKind::Cut, report.suffix_applied);
+        assert_eq!("bc", core::str::from_utf8(&buf).unwrap());
+    }
+
+    #[test]
+    fn finish_report_counts_writes() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let writer = WriteBuf::new(&mut buf);
+        let report = writer.finish_report("", "");
+        assert_eq!(0, report.write_calls);
+        assert_eq!(0, report.dropped_input_bytes);
+        assert_eq!(SuffixKind::Normal, report.suffix_applied);
+
+        let mut writer = WriteBuf::new(&mut buf);
+        let (a, b) = (12, 34);
+        write!(writer, "{a}-{b}").unwrap();
+        writer.write_str("a🚀").unwrap_err();
+        writer.write_str("later").unwrap_err();
+        let report = writer.finish_report("", "");
+        assert_eq!(5, report.write_calls);
+        assert_eq!(4 + 5, report.dropped_input_bytes);
+        assert_eq!(SuffixKind::Truncated, report.suffix_applied);
+        assert_eq!("12-34a", core::str::from_utf8(&buf[..report.written_len]).unwrap());
+    }
+
+    #[test]
+    fn front_reserve_holds_back_header() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_front_reserve(&mut buf, 3);
+        writer.write_str("ab").unwrap();
+        assert_eq!(2, writer.position());
+        assert_eq!(b"ab", writer.written_bytes());
+        assert_eq!(Some('b'), writer.pop());
+        writer.write_str("cdefg").unwrap_err();
+        assert_eq!("acdef", writer.written());
+        assert_eq!(Err(8), writer.finish_with_or("", "~"));
+        assert_eq!(b"\0\0\0acde~", &buf);
+
+        let mut writer = WriteBuf::with_front_reserve(&mut buf, 3);
+        writer.write_str("xyz").unwrap();
+        assert_eq!(Ok(6), writer.finish());
+        assert_eq!(b"\0\0\0xyz", &buf[..6]);
+
+        let mut writer = WriteBuf::with_front_reserve(&mut buf, 1);
+        writer.write_str("body").unwrap();
+        let result = writer.finish_with_header(|header, body_len| {
+            header[0] = b'0' + body_len as u8;
+        });
+        assert_eq!(Ok(5), result);
+        assert_eq!(b"4body", &buf[..5]);
+
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abc").unwrap();
+        assert_eq!(Ok(3), writer.finish_with_header(|header, _| assert!(header.is_empty())));
+    }
+
+    #[test]
+    fn front_reserve_without_room_for_body() {
+        for reserve in [8, 9] {
+            let mut buf: [u8; 8] = [0xff; 8];
+            let mut writer = WriteBuf::with_front_reserve(&mut buf, reserve);
+            assert!(writer.truncated());
+            writer.write_str("").unwrap();
+            let mut called = false;
+            assert_eq!(Err(8), writer.finish_with_header(|_, body_len| called = body_len == 0));
+            assert!(called);
+            assert_eq!([0u8; 8], buf);
         }
+
+        let writer = WriteBuf::with_front_reserve(&mut [], 0);
+        assert_eq!(Ok(0), writer.finish());
     }
 
-    struct SimpleString {
-        storage: [u8; 128],
-        size: usize,
+    #[test]
+    fn front_reserve_with_saturated_drop_count() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_front_reserve(&mut buf, 2);
+        writer.write_char_repeated('-', usize::MAX).unwrap_err();
+        assert_eq!(usize::MAX, writer.required_len());
+        let report = writer.finish_report("", "~");
+        assert_eq!(usize::MAX, report.required_len);
+        assert_eq!(8, report.written_len);
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_front_reserve(&mut buf, 2);
+        writer.write_char_repeated('-', usize::MAX).unwrap_err();
+        assert_eq!(Err(8), writer.finish_with_or("", "~"));
     }
 
-    impl SimpleString {
-        pub fn from_segments(segments: &[&str]) -> Self {
-            let mut out = Self {
-                storage: [0; 128],
-                size: 0,
-            };
-            for segment in segments {
-                out.append(segment);
+    #[test]
+    fn empty_writes_always_succeed() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abcdef").unwrap_err();
+        writer.write_str("").unwrap();
+        let empty = "";
+        write!(writer, "{}", empty).unwrap();
+        assert!(writer.fits(0));
+        assert!(writer.write_str_if_fits(""));
+        assert_eq!(Ok(()), writer.write_str_atomic(""));
+        assert_eq!(Ok(0), writer.write_str_partial(""));
+        assert_eq!(2, writer.dropped_bytes());
+        assert_eq!(Err(4), writer.finish());
+
+        let mut writer = WriteBuf::new_strict(&mut buf);
+        writer.write_str("abcdef").unwrap_err();
+        writer.write_str("").unwrap();
+        assert_eq!(Err(0), writer.finish());
+    }
+
+    #[test]
+    fn reserve_edge_cases() {
+        // (target length, reserve)
+        let cases = [(0, 0), (0, 1), (1, 0), (1, 1), (1, 2), (4, 4), (4, 5)];
+        for &(len, reserve) in cases.iter() {
+            let mut buf: [u8; 4] = [0xff; 4];
+            let mut writer = WriteBuf::with_reserve(&mut buf[..len], reserve);
+            assert_eq!(
+                len.saturating_sub(reserve),
+                writer.remaining(),
+                "len={} reserve={}",
+                len,
+                reserve
+            );
+
+            writer.write_str("").unwrap();
+            assert!(!writer.truncated(), "len={} reserve={}", len, reserve);
+
+            if writer.remaining() == 0 {
+                writer.write_str("a").unwrap_err();
+                assert_eq!(0, writer.position(), "len={} reserve={}", len, reserve);
+                assert!(writer.content_truncated(), "len={} reserve={}", len, reserve);
+                writer.write_str("").unwrap();
+                let expected = if len == 0 { Err(0) } else { Err(1) };
+                assert_eq!(
+                    expected,
+                    writer.finish_with_or("", "~"),
+                    "len={} reserve={}",
+                    len,
+                    reserve
+                );
+                if len != 0 {
+                    assert_eq!(b'~', buf[0]);
+                }
+            } else {
+                writer.write_str("a").unwrap();
+                assert_eq!(Ok(1), writer.finish(), "len={} reserve={}", len, reserve);
             }
-            out
         }
+    }
 
-        pub fn append(&mut self, value: &str) {
-            let value = value.as_bytes();
-            self.storage[self.size..self.size + value.len()].copy_from_slice(value);
-            self.size += value.len();
-        }
+    #[test]
+    fn finish_reserved_uses_stored_suffixes() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve_for(&mut buf, "\n", "...\n");
+        assert_eq!(4, writer.reserve());
+        writer.write_str("abc").unwrap();
+        assert_eq!(Ok(4), writer.finish_reserved());
+        assert_eq!(b"abc\n", &buf[..4]);
 
-        pub fn as_str(&self) -> &str {
-            core::str::from_utf8(&self.storage[..self.size]).unwrap()
-        }
+        let mut writer = WriteBuf::with_reserve_for(&mut buf, "\n", "...\n");
+        writer.write_str("abcdef").unwrap_err();
+        assert_eq!(Err(8), writer.finish_reserved());
+        assert_eq!(b"abcd...\n", &buf);
+
+        // explicit suffixes which fit in the reserve are still allowed
+        let mut writer = WriteBuf::with_reserve_for(&mut buf, "\n", "...\n");
+        writer.write_str("abcdef").unwrap_err();
+        assert_eq!(Err(6), writer.finish_with_or("", "~~"));
+        assert_eq!(b"abcd~~", &buf[..6]);
+
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abc").unwrap();
+        assert_eq!(Ok(3), writer.finish_reserved());
+
+        let (front, back) = WriteBuf::with_reserve_for(&mut buf, "", "!").split_at(4).ok().unwrap();
+        assert_eq!(Ok(0), front.finish_reserved());
+        let mut back = back;
+        back.write_str("wxyz").unwrap_err();
+        assert_eq!(Err(4), back.finish_reserved());
+        assert_eq!(b"wxy!", &buf[4..]);
     }
 
-    impl From<&str> for SimpleString {
-        fn from(value: &str) -> Self {
-            Self::from_segments(&[value])
-        }
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "suffix is longer than the reserve")]
+    fn finish_with_longer_suffix_than_reserved_panics() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let writer = WriteBuf::with_reserve_for(&mut buf, "", "~");
+        let _ = writer.finish_with_or("", "...");
     }
 
     #[test]
-    fn finish_with_enough_space() {
-        for (input, _) in TEST_CASES.iter() {
-            let mut buf: [u8; 128] = [0xff; 128];
-            let mut writer = WriteBuf::new(&mut buf);
+    fn release_reserve_recovers_from_reserve_wall() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::with_reserve(&mut buf, 4);
+        writer.write_str("abc").unwrap();
+        writer.write_str("defg").unwrap_err();
+        writer.write_str("h").unwrap_err();
+        assert_eq!("abcd", writer.written());
+        assert!(writer.truncated());
 
-            writer.write_str(input).unwrap();
-            let position = writer.finish_with(b".123").unwrap();
-            assert_eq!(position, input.len() + 4);
-            let expected_written = SimpleString::from_segments(&[input, ".123"]);
-            let actually_wriiten = core::str::from_utf8(&buf[..position]).unwrap();
-            assert_eq!(expected_written.as_str(), actually_wriiten);
-        }
+        writer.release_reserve(3);
+        assert_eq!(1, writer.reserve());
+        assert!(!writer.truncated());
+        writer.write_str("efg").unwrap();
+        assert_eq!(Ok(8), writer.finish_with("!"));
+        assert_eq!(b"abcdefg!", &buf);
+
+        // content which did not fit even without the reserve stays truncated
+        let mut writer = WriteBuf::with_reserve(&mut buf, 2);
+        writer.write_str("abc").unwrap();
+        writer.write_str("defghi").unwrap_err();
+        writer.release_reserve(5);
+        assert_eq!(0, writer.reserve());
+        assert!(writer.truncated());
+        writer.write_str("f").unwrap_err();
+
+        let mut writer = WriteBuf::with_reserve(&mut buf, 2);
+        writer.write_str("abcdef").unwrap();
+        writer.set_latch(false);
+        writer.write_str("ghi").unwrap_err();
+        writer.release_reserve(2);
+        assert!(writer.truncated());
     }
 
     #[test]
-    fn finish_with_overwrite() {
-        for (input, last_valid_idx_after_cut) in TEST_CASES.iter() {
-            if input.len() == 0 {
-                continue;
-            }
+    fn try_set_reserve_checks_remaining_space() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abcde").unwrap();
+        assert_eq!(
+            Err(ReserveError {
+                requested: 4,
+                available: 3
+            }),
+            writer.try_set_reserve(4)
+        );
+        assert_eq!(0, writer.reserve());
+        writer.try_set_reserve(3).unwrap();
+        assert_eq!(3, writer.reserve());
+        writer.write_str("f").unwrap_err();
+        writer.try_set_reserve(0).unwrap();
+        assert!(writer.truncated());
+    }
 
-            let mut buf: [u8; 128] = [0xff; 128];
-            let mut writer = WriteBuf::new(&mut buf[..input.len()]);
+    #[test]
+    fn required_len_counts_dropped_bytes() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        assert_eq!(0, writer.required_len());
+        writer.write_str("abc").unwrap();
+        writer.write_str("de🚀").unwrap_err();
+        assert_eq!(5, writer.position());
+        assert_eq!(4, writer.dropped_bytes());
+        // rejected writes still count
+        writer.write_str("fgh").unwrap_err();
+        assert_eq!(7, writer.dropped_bytes());
+        assert_eq!(3 + 6 + 3, writer.required_len());
 
-            writer.write_str(input).unwrap();
-            let position = writer.finish_with("?").unwrap_err();
-            assert_eq!(position, last_valid_idx_after_cut + 1);
-            let expected_written = SimpleString::from_segments(&[
-                core::str::from_utf8(&input.as_bytes()[..*last_valid_idx_after_cut]).unwrap(),
-                "?",
-            ]);
-            let actually_wriiten = core::str::from_utf8(&buf[..position]).unwrap();
-            assert_eq!(expected_written.as_str(), actually_wriiten);
-        }
+        // the written content the suffix is placed over counts as dropped
+        let report = writer.finish_report("!", "[..]");
+        assert_eq!(SuffixKind::RolledBack, report.suffix_applied);
+        assert_eq!(7 + 1, report.dropped_input_bytes);
+        assert_eq!(3 + 6 + 3 + 1, report.required_len);
+        assert_eq!(b"abcd[..]", &buf);
+
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abcd").unwrap();
+        let report = writer.finish_report("!", "!");
+        assert_eq!(Ok(5), report.result());
+        assert_eq!(0, report.dropped_input_bytes);
+        assert_eq!(5, report.required_len);
+
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("abcd").unwrap();
+        writer.write_str("efghijk").unwrap_err();
+        let required_len = writer.required_len();
+        assert_eq!(
+            Err(8),
+            writer.finish_with_fmt(format_args!("{}", "!"), format_args!("{}", "..."))
+        );
+        assert_eq!(11, required_len);
+        assert_eq!(b"abcde...", &buf);
     }
 
     #[test]
-    fn finish_with_or_with_longer_normal_closer() {
+    fn truncation_flags_both() {
         let mut buf: [u8; 4] = [0xff; 4];
-        let writer = WriteBuf::new(&mut buf);
+        let mut writer = WriteBuf::new(&mut buf);
 
-        let written = writer.finish_with_or("0123456789", "abc").unwrap_err();
-        assert_eq!(written, 3);
-        assert_eq!("abc", core::str::from_utf8(&buf[..written]).unwrap());
+        writer.write_str("0123456").unwrap_err();
+        let report = writer.finish_report("", "...");
+        assert!(report.content_truncated);
+        assert!(report.suffix_degraded);
+        assert_eq!("0...", core::str::from_utf8(&buf[..report.written_len]).unwrap());
     }
 
+    #[cfg(all(feature = "signal-safe", feature = "std"))]
     #[test]
-    fn finish_with_full_overwrite_utf8() {
-        let mut buf: [u8; 4] = [0xff; 4];
-        let writer = WriteBuf::new(&mut buf);
+    fn written_bytes_relaxed_concurrent_reader() {
+        use std::sync::atomic::{AtomicBool, Ordering};
 
-        let written = writer.finish_with("🚀12").unwrap_err();
-        assert_eq!(written, 2);
-        assert_eq!("12", core::str::from_utf8(&buf[..written]).unwrap());
+        // This mimics a signal handler observing the buffer through a pointer while the owner is writing to it. Unlike a
+        // signal handler, the reader thread keeps running while the owner writes, so it is only safe for the owner to
+        // append; rolling back and rewriting bytes could change them while the reader is still looking at them.
+        struct SharedWriter(*mut WriteBuf<'static>);
+        unsafe impl Send for SharedWriter {}
+        unsafe impl Sync for SharedWriter {}
+
+        let capacity = 1 << 16;
+        let target: &'static mut [u8] = Box::leak(vec![0xff; capacity].into_boxed_slice());
+        let writer: &'static mut WriteBuf<'static> = Box::leak(Box::new(WriteBuf::with_reserve(target, 3)));
+        let shared = SharedWriter(writer);
+        let done = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let shared = &shared;
+                let mut observations = 0usize;
+                while !done.load(Ordering::Acquire) || observations == 0 {
+                    let observed = unsafe { (*shared.0).written_bytes_relaxed() };
+                    assert!(observed.len() <= capacity);
+                    assert!(std::str::from_utf8(observed).is_ok(), "observed invalid UTF-8");
+                    observations += 1;
+                }
+            });
+
+            let mut idx = 0usize;
+            while !unsafe { (*shared.0).truncated() } {
+                let _ = unsafe { write!(*shared.0, "🚀é{idx}…") };
+                idx += 1;
+            }
+            done.store(true, Ordering::Release);
+        });
+
+        let writer = unsafe { &mut *shared.0 };
+        assert!(writer.written_bytes_relaxed().len() <= capacity - 3);
+        assert_eq!(writer.written_bytes(), writer.written_bytes_relaxed());
     }
 
     #[test]
-    fn set_reserve_should_not_change_written() {
+    fn finish_with_fmt_matches_finish_with_or() {
+        for (input, _) in TEST_CASES.iter() {
+            for buf_len in 0..input.len() + 6 {
+                for (normal, truncated) in [("", "…"), ("!", "?"), ("\0", "🚀..."), ("12345", "🚀12")] {
+                    let mut expected_buf: [u8; 128] = [0xff; 128];
+                    let mut expected_writer = WriteBuf::new(&mut expected_buf[..buf_len]);
+                    let _ = expected_writer.write_str(input);
+                    let expected = expected_writer.finish_with_or(normal, truncated);
+
+                    let mut actual_buf: [u8; 128] = [0xff; 128];
+                    let mut actual_writer = WriteBuf::new(&mut actual_buf[..buf_len]);
+                    let _ = actual_writer.write_str(input);
+                    let actual = actual_writer.finish_with_fmt(format_args!("{normal}"), format_args!("{truncated}"));
+
+                    let context = format_args!("input={input:?} buf_len={buf_len} suffixes={normal:?}/{truncated:?}");
+                    assert_eq!(expected, actual, "{context}");
+                    let len = actual.unwrap_or_else(|e| e);
+                    assert_eq!(expected_buf[..len], actual_buf[..len], "{context}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finish_with_fmt_interpolated_marker_fits() {
+        let mut buf: [u8; 16] = [0xff; 16];
+        let mut writer = WriteBuf::new(&mut buf);
+
+        writer.write_str("lines").unwrap();
+        let written = writer
+            .finish_with_fmt(format_args!(" ({} ok)", 3), format_args!(" (+{} more)", 7))
+            .unwrap();
+        assert_eq!("lines (3 ok)", core::str::from_utf8(&buf[..written]).unwrap());
+    }
+
+    #[test]
+    fn finish_with_fmt_marker_overflows_tail() {
         let mut buf: [u8; 10] = [0xff; 10];
         let mut writer = WriteBuf::new(&mut buf);
 
-        write!(writer, "0123456789").unwrap();
-        assert_eq!("0123456789", writer.written());
+        writer.write_str("0123456789abc").unwrap_err();
+        let written = writer
+            .finish_with_fmt(format_args!(""), format_args!("(+{} more)", 3))
+            .unwrap_err();
+        assert_eq!(written, 10);
+        assert_eq!("0(+3 more)", core::str::from_utf8(&buf[..written]).unwrap());
+    }
 
-        writer.set_reserve(4);
-        assert_eq!("0123456789", writer.written());
+    #[test]
+    fn finish_with_fmt_marker_overflows_buffer() {
+        let mut buf: [u8; 5] = [0xff; 5];
+        let mut writer = WriteBuf::new(&mut buf);
 
-        writer.finish_with_or("", "!").unwrap();
-        assert_eq!("0123456789", core::str::from_utf8(&buf).unwrap());
+        writer.write_str("data").unwrap();
+        let written = writer
+            .finish_with_fmt(format_args!(" (+{} more)", 12), format_args!("({}{} more)", '🚀', 12))
+            .unwrap_err();
+        assert_eq!(written, 5);
+        assert_eq!("more)", core::str::from_utf8(&buf[..written]).unwrap());
+    }
+
+    #[test]
+    fn finish_with_fmt_marker_overflows_buffer_utf8() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let writer = WriteBuf::new(&mut buf);
+
+        // the cut lands in the middle of the 🚀, which must be dropped entirely
+        let written = writer
+            .finish_with_fmt(format_args!("{}{}", '🚀', 12), format_args!("{}{}", '🚀', 12))
+            .unwrap_err();
+        assert_eq!(written, 2);
+        assert_eq!("12", core::str::from_utf8(&buf[..written]).unwrap());
     }
 }
 
@@ -0,0 +1,249 @@
+use core::fmt;
+
+use crate::written_str;
+
+/// A writer which collects output in a scratch buffer and passes each complete line to a callback, so formatted output
+/// can be sent one line at a time without allocating.
+///
+/// ```
+/// use fmtbuf::LineBuf;
+/// use std::fmt::Write;
+///
+/// let mut sent = Vec::new();
+/// let mut scratch: [u8; 32] = [0; 32];
+/// let mut lines = LineBuf::new(&mut scratch, |line: &str| sent.push(line.to_owned()));
+/// write!(lines, "temp={}\nfan=", 41).unwrap();
+/// writeln!(lines, "{}", "on").unwrap();
+/// write!(lines, "partial").unwrap();
+/// lines.flush();
+/// drop(lines);
+/// assert_eq!(sent, ["temp=41\n", "fan=on\n", "partial"]);
+/// ```
+///
+/// A line longer than the scratch buffer is passed to the callback in chunks, each ending with the continuation marker
+/// (see [`LineBuf::set_continuation_marker`]) except for the last. Chunks are cut at a `char` boundary, like truncated
+/// content in a [`crate::WriteBuf`], so every chunk is valid UTF-8.
+pub struct LineBuf<'a, F: FnMut(&str)> {
+    scratch: &'a mut [u8],
+    /// The number of bytes of the current line in `scratch`.
+    len: usize,
+    callback: F,
+    include_newline: bool,
+    continuation_marker: &'a str,
+    dropped_bytes: usize,
+}
+
+impl<'a, F: FnMut(&str)> LineBuf<'a, F> {
+    /// Create an instance which collects lines in `scratch` and passes each one to `callback`, including the `\n` at
+    /// the end of it. The contents of `scratch` do not matter, as they will be overwritten by writing.
+    pub fn new(scratch: &'a mut [u8], callback: F) -> Self {
+        Self {
+            scratch,
+            len: 0,
+            callback,
+            include_newline: true,
+            continuation_marker: "…",
+            dropped_bytes: 0,
+        }
+    }
+
+    /// Set if the `\n` ending each line is passed to the callback along with it. It is by default.
+    pub fn set_include_newline(&mut self, include_newline: bool) {
+        self.include_newline = include_newline;
+    }
+
+    /// Set the text which ends each chunk of a line which did not fit in the scratch buffer. This is `"…"` by default.
+    ///
+    /// Room for the marker is kept in the scratch buffer, so a larger marker leaves less room for each chunk.
+    pub fn set_continuation_marker(&mut self, marker: &'a str) {
+        self.continuation_marker = marker;
+    }
+
+    /// Get the number of bytes of the line which have not been passed to the callback yet.
+    pub fn pending_len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the number of bytes which were dropped because a single `char` did not fit in the scratch buffer along with
+    /// the continuation marker, and the newline if it is included. This only happens with a very small scratch buffer.
+    pub fn dropped_bytes(&self) -> usize {
+        self.dropped_bytes
+    }
+
+    /// Pass the partial line written since the last `\n` to the callback, if there is one. Nothing is added to it.
+    pub fn flush(&mut self) {
+        if self.len > 0 {
+            self._emit("");
+        }
+    }
+
+    /// Get the number of bytes of a line which fit in the scratch buffer, leaving room for what can end it.
+    fn _content_limit(&self) -> usize {
+        let newline_len = if self.include_newline { 1 } else { 0 };
+        let reserve = self.continuation_marker.len().max(newline_len);
+        self.scratch.len().saturating_sub(reserve)
+    }
+
+    /// Add `s`, which does not contain a `\n`, to the current line, passing chunks of it to the callback if it is too
+    /// long.
+    fn _push(&mut self, s: &str) -> fmt::Result {
+        let limit = self._content_limit();
+        let mut result = Ok(());
+        let mut rest = s;
+        while !rest.is_empty() {
+            let mut take = limit.saturating_sub(self.len).min(rest.len());
+            while !rest.is_char_boundary(take) {
+                take -= 1;
+            }
+            self.scratch[self.len..self.len + take].copy_from_slice(&rest.as_bytes()[..take]);
+            self.len += take;
+            rest = &rest[take..];
+            if rest.is_empty() {
+                break;
+            }
+
+            if self.len == 0 {
+                // The next char does not fit even in an empty scratch buffer, so it can never be sent
+                let skipped = rest.chars().next().map_or(0, char::len_utf8);
+                self.dropped_bytes = self.dropped_bytes.saturating_add(skipped);
+                rest = &rest[skipped..];
+                result = Err(fmt::Error);
+            } else {
+                let marker = self.continuation_marker;
+                self._emit(marker);
+            }
+        }
+        result
+    }
+
+    /// Add `end` to the current line, pass it to the callback and start a new line. If `end` does not fit, which can
+    /// only happen with a scratch buffer too small for the content limit, it is left off.
+    fn _emit(&mut self, end: &str) {
+        let mut len = self.len;
+        if let Some(dest) = self.scratch.get_mut(len..len + end.len()) {
+            dest.copy_from_slice(end.as_bytes());
+            len += end.len();
+        }
+        (self.callback)(written_str(&self.scratch[..len]));
+        self.len = 0;
+    }
+}
+
+impl<'a, F: FnMut(&str)> fmt::Write for LineBuf<'a, F> {
+    /// Add `s` to the current line, passing every line it completes to the callback.
+    ///
+    /// # Error
+    ///
+    /// If a `char` of `s` could not be sent at all. See [`LineBuf::dropped_bytes`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut result = Ok(());
+        let mut lines = s.split('\n');
+        if let Some(first) = lines.next() {
+            result = self._push(first);
+        }
+        for line in lines {
+            let end = if self.include_newline { "\n" } else { "" };
+            self._emit(end);
+            result = result.and(self._push(line));
+        }
+        result
+    }
+}
+
+impl<'a, F: FnMut(&str)> fmt::Debug for LineBuf<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineBuf")
+            .field("pending", &written_str(&self.scratch[..self.len]))
+            .field("capacity", &self.scratch.len())
+            .field("include_newline", &self.include_newline)
+            .field("continuation_marker", &self.continuation_marker)
+            .field("dropped_bytes", &self.dropped_bytes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+    use core::fmt::Write;
+
+    /// Write `pieces` through a [`LineBuf`] over `N` bytes, then flush, returning every line the callback got with a
+    /// `|` after each one.
+    fn send<const N: usize>(
+        pieces: &[&str],
+        configure: impl FnOnce(&mut LineBuf<'_, &mut dyn FnMut(&str)>),
+    ) -> StackString<128> {
+        let mut out = StackString::<128>::new();
+        let mut append = |line: &str| {
+            out.write_str(line).unwrap();
+            out.write_str("|").unwrap();
+        };
+        let mut scratch = [0xff; N];
+        let mut lines = LineBuf::new(&mut scratch, &mut append as &mut dyn FnMut(&str));
+        configure(&mut lines);
+        for piece in pieces {
+            let _ = lines.write_str(piece);
+        }
+        lines.flush();
+        out
+    }
+
+    #[test]
+    fn lines_across_writes() {
+        assert_eq!("a\n|bc\n|\n|d|", send::<8>(&["a\nb", "c\n", "\nd"], |_| ()));
+        // A newline at the end of one write and content in the next
+        assert_eq!("ab\n|cd\n|", send::<8>(&["ab\n", "cd\n"], |_| ()));
+        assert_eq!(
+            "ab|cd||e|",
+            send::<8>(&["ab\ncd\n\ne"], |lines| lines.set_include_newline(false))
+        );
+        // Nothing is pending after a newline, so flushing does nothing
+        assert_eq!("", send::<8>(&["", ""], |_| ()));
+    }
+
+    #[test]
+    fn multiple_lines_per_write() {
+        let mut count = 0;
+        let mut scratch = [0xff; 16];
+        let mut lines = LineBuf::new(&mut scratch, |_: &str| count += 1);
+        write!(lines, "{}\n{}\n{}", 1, 2, 3).unwrap();
+        assert_eq!(1, lines.pending_len());
+        writeln!(lines).unwrap();
+        assert_eq!(0, lines.pending_len());
+        assert_eq!(3, count);
+    }
+
+    #[test]
+    fn long_line_chunks() {
+        // With the 3 byte "…", each chunk holds 5 bytes of the line
+        assert_eq!("abcde…|fghij…|k\n|", send::<8>(&["abcdefghijk\n"], |_| ()));
+        // The chunks are cut at char boundaries, carrying the rest of a char to the next chunk
+        assert_eq!("ab見…|見…|見c\n|", send::<8>(&["ab見見", "見c\n"], |_| ()));
+        assert_eq!(
+            "1234+|5678+|9|",
+            send::<5>(&["12345", "6789"], |lines| {
+                lines.set_continuation_marker("+");
+                lines.set_include_newline(false);
+            })
+        );
+        // Exactly filling the scratch buffer does not need a chunk
+        assert_eq!("abcde\n|", send::<8>(&["abcde\n"], |_| ()));
+    }
+
+    #[test]
+    fn char_too_large_for_scratch() {
+        let mut scratch = [0xff; 6];
+        let mut sent = StackString::<32>::new();
+        let mut lines = LineBuf::new(&mut scratch, |line: &str| {
+            sent.write_str(line).unwrap();
+            sent.write_str("|").unwrap();
+        });
+        lines.set_continuation_marker("...");
+        // Only 3 bytes are left for content, so the 🚀 can never be sent
+        lines.write_str("a🚀b\n").unwrap_err();
+        assert_eq!(4, lines.dropped_bytes());
+        lines.write_str("cd\n").unwrap();
+        assert_eq!("a...|b\n|cd\n|", sent);
+    }
+}
@@ -0,0 +1,199 @@
+use core::fmt::{self, Write};
+
+use crate::{SliceTarget, Target, WriteBuf};
+
+/// Writes the first lines of the content to a [`WriteBuf`] and drops the rest, such as to keep a stack trace or a
+/// config dump within the budget of a log record. [`LineLimit::finish`] then writes a marker saying how many lines were
+/// dropped.
+///
+/// ```
+/// use fmtbuf::{LineLimit, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 64] = [0xff; 64];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// let mut out = LineLimit::new(&mut writer, 2, "… +{} more lines");
+/// for frame in 0..6 {
+///     writeln!(out, "  at frame {}", frame).unwrap();
+/// }
+/// out.finish().unwrap();
+/// assert_eq!("  at frame 0\n  at frame 1\n… +4 more lines", writer.written());
+/// ```
+///
+/// A line is counted when its `\n` is written, no matter how many writes it took, so the content passed on is
+/// everything up to and including the `\n` ending the last line allowed. Everything after it is dropped without being
+/// an error, and is counted as lines: each `\n` ends one, and content after the last `\n` is one more.
+pub struct LineLimit<'b, 'a, T = SliceTarget<'a>> {
+    buf: &'b mut WriteBuf<'a, T>,
+    max_lines: usize,
+    marker: &'b str,
+    /// The number of `\n` passed on to the buffer.
+    lines: usize,
+    /// The number of `\n` dropped.
+    dropped_newlines: usize,
+    /// If content was dropped after the last dropped `\n`.
+    dropped_partial: bool,
+}
+
+impl<'b, 'a, T: Target<'a>> LineLimit<'b, 'a, T> {
+    /// Create an instance which writes the first `max_lines` lines to `buf`. When lines are dropped, `marker` is written
+    /// by [`LineLimit::finish`] with the first `{}` in it replaced by the number of lines which were dropped.
+    pub fn new(buf: &'b mut WriteBuf<'a, T>, max_lines: usize, marker: &'b str) -> Self {
+        Self {
+            buf,
+            max_lines,
+            marker,
+            lines: 0,
+            dropped_newlines: 0,
+            dropped_partial: false,
+        }
+    }
+
+    /// Get the number of lines which are written before the rest is dropped.
+    pub fn max_lines(&self) -> usize {
+        self.max_lines
+    }
+
+    /// Get the number of lines dropped so far.
+    pub fn dropped_lines(&self) -> usize {
+        self.dropped_newlines + usize::from(self.dropped_partial)
+    }
+
+    /// Write the marker if any lines were dropped. The marker is written like any other content, as a single write, so
+    /// it is truncated if it does not fit in the buffer.
+    ///
+    /// # Error
+    ///
+    /// If the marker did not fit in the buffer, the same as [`WriteBuf`].
+    pub fn finish(self) -> fmt::Result {
+        let dropped = self.dropped_lines();
+        if dropped == 0 {
+            return Ok(());
+        }
+        match self.marker.split_once("{}") {
+            Some((before, after)) => write!(self.buf, "{}{}{}", before, dropped, after),
+            None => self.buf.write_str(self.marker),
+        }
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for LineLimit<'b, 'a, T> {
+    /// Write the part of `s` which is within the line limit, dropping the rest.
+    ///
+    /// # Error
+    ///
+    /// If the part of `s` within the line limit did not fit in the buffer, the same as [`WriteBuf`]. Dropping content
+    /// past the line limit is not an error.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s;
+        let mut result = Ok(());
+        while self.lines < self.max_lines {
+            match rest.find('\n') {
+                Some(idx) => {
+                    result = result.and(self.buf.write_str(&rest[..=idx]));
+                    self.lines += 1;
+                    rest = &rest[idx + 1..];
+                },
+                None => return result.and(self.buf.write_str(rest)),
+            }
+        }
+
+        if let Some(idx) = rest.rfind('\n') {
+            self.dropped_newlines += rest.matches('\n').count();
+            self.dropped_partial = idx + 1 < rest.len();
+        } else if !rest.is_empty() {
+            self.dropped_partial = true;
+        }
+        result
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Debug for LineLimit<'b, 'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineLimit")
+            .field("buf", &self.buf)
+            .field("max_lines", &self.max_lines)
+            .field("marker", &self.marker)
+            .field("lines", &self.lines)
+            .field("dropped_lines", &self.dropped_lines())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+
+    /// Write `pieces` through a [`LineLimit`] of `max_lines` into a buffer of `N` bytes, then finish it, returning what
+    /// was written and if every write succeeded.
+    fn limit<const N: usize>(max_lines: usize, marker: &str, pieces: &[&str]) -> (StackString<N>, bool) {
+        let mut buf = [0xff; N];
+        let mut writer = WriteBuf::new(&mut buf);
+        let mut out = LineLimit::new(&mut writer, max_lines, marker);
+        let mut ok = true;
+        for piece in pieces {
+            ok &= out.write_str(piece).is_ok();
+        }
+        ok &= out.finish().is_ok();
+        let mut written = StackString::new();
+        written.write_str(writer.written()).unwrap();
+        (written, ok)
+    }
+
+    #[test]
+    fn lines_in_one_write() {
+        assert_eq!("a\nb\n[+2]", limit::<64>(2, "[+{}]", &["a\nb\nc\nd\n"]).0);
+        // The content after the last newline is a line too
+        assert_eq!("a\nb\n[+2]", limit::<64>(2, "[+{}]", &["a\nb\nc\nd"]).0);
+        assert_eq!("a\n[+1]", limit::<64>(1, "[+{}]", &["a\n\n"]).0);
+        // Nothing was dropped, so there is no marker
+        assert_eq!("a\nb\n", limit::<64>(2, "[+{}]", &["a\nb\n"]).0);
+        assert_eq!("a\nb", limit::<64>(2, "[+{}]", &["a\nb"]).0);
+        assert_eq!("[+1]", limit::<64>(0, "[+{}]", &["a"]).0);
+    }
+
+    #[test]
+    fn lines_across_writes() {
+        assert_eq!(
+            "ab\nc\n[+3]",
+            limit::<64>(2, "[+{}]", &["a", "b\nc", "\nd", "e\nf\n", "g"]).0
+        );
+        assert_eq!("a\n[+2]", limit::<64>(1, "[+{}]", &["a\n", "b", "\n", "c"]).0);
+        // The dropped content ends with a newline in one write and continues in the next
+        assert_eq!("a\n[+2]", limit::<64>(1, "[+{}]", &["a\nb\n", "", "c"]).0);
+    }
+
+    #[test]
+    fn marker() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = LineLimit::new(&mut writer, 1, "(more)");
+            assert_eq!(1, out.max_lines());
+            out.write_str("a\nb\nc").unwrap();
+            assert_eq!(2, out.dropped_lines());
+            out.finish().unwrap();
+        }
+        assert_eq!("a\n(more)", writer.written());
+
+        // Only the first placeholder is filled in
+        assert_eq!("a\nx2y{}", limit::<64>(1, "x{}y{}", &["a\nb\nc"]).0);
+    }
+
+    #[test]
+    fn truncated() {
+        // The marker is truncated like other content
+        let (written, ok) = limit::<8>(1, "+{} lines", &["abc\nd\ne\n"]);
+        assert_eq!(("abc\n+2 l", false), (written.as_str(), ok));
+
+        // A line which does not fit still counts toward the limit, and the lines after it are dropped
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut writer = WriteBuf::new(&mut buf);
+        let mut out = LineLimit::new(&mut writer, 1, "+{}");
+        out.write_str("abcdef\ng\n").unwrap_err();
+        assert_eq!(1, out.dropped_lines());
+        out.finish().unwrap_err();
+        assert_eq!("abcd", writer.written());
+    }
+}
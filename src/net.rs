@@ -0,0 +1,251 @@
+//! Writing network addresses, used by [`crate::WriteBuf::write_ipv4`] and friends.
+
+use crate::int;
+
+/// The longest text of an address, which is an IPv6 socket address with a scope ID, such as
+/// `[ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff%4294967295]:65535`.
+const MAX_LEN: usize = 64;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// The text of an address, built on the stack so it can be written entirely or not at all.
+pub(crate) struct AddrText {
+    bytes: [u8; MAX_LEN],
+    len: usize,
+}
+
+impl AddrText {
+    /// Get the text of an IPv4 address in dotted decimal, such as `192.0.2.1`.
+    pub(crate) fn ipv4(octets: [u8; 4]) -> Self {
+        let mut out = Self::empty();
+        out.push_ipv4(octets);
+        out
+    }
+
+    /// Get the text of an IPv6 address in the canonical form of RFC 5952, such as `2001:db8::1`.
+    pub(crate) fn ipv6(segments: [u16; 8]) -> Self {
+        let mut out = Self::empty();
+        out.push_ipv6(segments);
+        out
+    }
+
+    /// Get the text of an IPv4 address and a port, such as `192.0.2.1:8080`.
+    pub(crate) fn socket_v4(octets: [u8; 4], port: u16) -> Self {
+        let mut out = Self::empty();
+        out.push_ipv4(octets);
+        out.push(b":");
+        out.push_decimal(port.into());
+        out
+    }
+
+    /// Get the text of an IPv6 address and a port, such as `[2001:db8::1]:8080`. A `scope_id` other than 0 is written
+    /// after the address, such as `[fe80::1%2]:8080`.
+    pub(crate) fn socket_v6(segments: [u16; 8], scope_id: u32, port: u16) -> Self {
+        let mut out = Self::empty();
+        out.push(b"[");
+        out.push_ipv6(segments);
+        if scope_id != 0 {
+            out.push(b"%");
+            out.push_decimal(scope_id.into());
+        }
+        out.push(b"]:");
+        out.push_decimal(port.into());
+        out
+    }
+
+    /// Get the text of a MAC address in lowercase hexadecimal with `separator` between the bytes, such as
+    /// `00:1a:2b:3c:4d:5e`.
+    pub(crate) fn mac(bytes: [u8; 6], separator: char) -> Self {
+        let mut out = Self::empty();
+        let mut encoded = [0u8; 4];
+        let separator = separator.encode_utf8(&mut encoded).as_bytes();
+        for (idx, byte) in bytes.iter().enumerate() {
+            if idx > 0 {
+                out.push(separator);
+            }
+            out.push(&[HEX_DIGITS[usize::from(byte >> 4)], HEX_DIGITS[usize::from(byte & 0xf)]]);
+        }
+        out
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    fn empty() -> Self {
+        Self {
+            bytes: [0; MAX_LEN],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn push_decimal(&mut self, value: u64) {
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        self.push(int::decimal_u64(value, false, &mut scratch));
+    }
+
+    fn push_ipv4(&mut self, octets: [u8; 4]) {
+        for (idx, octet) in octets.iter().enumerate() {
+            if idx > 0 {
+                self.push(b".");
+            }
+            self.push_decimal(u64::from(*octet));
+        }
+    }
+
+    fn push_ipv6(&mut self, segments: [u16; 8]) {
+        // An IPv4-mapped address ends in the IPv4 address in dotted decimal (RFC 5952 section 5)
+        if segments[..5] == [0; 5] && segments[5] == 0xffff {
+            self.push(b"::ffff:");
+            let [a, b] = segments[6].to_be_bytes();
+            let [c, d] = segments[7].to_be_bytes();
+            self.push_ipv4([a, b, c, d]);
+            return;
+        }
+
+        match longest_zero_run(&segments) {
+            Some((start, len)) => {
+                self.push_segments(&segments[..start]);
+                self.push(b"::");
+                self.push_segments(&segments[start + len..]);
+            },
+            None => self.push_segments(&segments),
+        }
+    }
+
+    /// Write `segments` in hexadecimal without leading zeros, separated by `:`.
+    fn push_segments(&mut self, segments: &[u16]) {
+        for (idx, segment) in segments.iter().enumerate() {
+            if idx > 0 {
+                self.push(b":");
+            }
+            let mut digits = [0u8; 4];
+            let mut start = digits.len();
+            let mut value = *segment;
+            loop {
+                start -= 1;
+                digits[start] = HEX_DIGITS[usize::from(value & 0xf)];
+                value >>= 4;
+                if value == 0 {
+                    break;
+                }
+            }
+            self.push(&digits[start..]);
+        }
+    }
+}
+
+/// Find the start and length of the run of zero segments which is replaced by `::` (RFC 5952 section 4.2). This is the
+/// longest run, or the first of the longest if there is a tie, and a single zero segment is never a run.
+fn longest_zero_run(segments: &[u16; 8]) -> Option<(usize, usize)> {
+    let mut longest: Option<(usize, usize)> = None;
+    let mut idx = 0;
+    while idx < segments.len() {
+        if segments[idx] != 0 {
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        while idx < segments.len() && segments[idx] == 0 {
+            idx += 1;
+        }
+        let len = idx - start;
+        if len >= 2 && longest.map_or(true, |(_, longest)| len > longest) {
+            longest = Some((start, len));
+        }
+    }
+    longest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const IPV6_CASES: &[([u16; 8], &str)] = &[
+        ([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1], "2001:db8::1"),
+        ([0; 8], "::"),
+        ([0, 0, 0, 0, 0, 0, 0, 1], "::1"),
+        ([1, 0, 0, 0, 0, 0, 0, 0], "1::"),
+        (
+            [0xfe80, 0, 0, 0, 0x1ff, 0xfe23, 0x4567, 0x890a],
+            "fe80::1ff:fe23:4567:890a",
+        ),
+        // Hexadecimal is lowercase without leading zeros
+        (
+            [0x2001, 0xDB8, 0xABCD, 0x12, 0xffff, 0x1, 0x10, 0x100],
+            "2001:db8:abcd:12:ffff:1:10:100",
+        ),
+        // A single zero segment is not compressed
+        ([0x2001, 0xdb8, 0, 1, 1, 1, 1, 1], "2001:db8:0:1:1:1:1:1"),
+        ([0x2001, 0xdb8, 1, 1, 1, 1, 1, 0], "2001:db8:1:1:1:1:1:0"),
+        // The longest run is compressed
+        ([0x2001, 0, 0, 1, 0, 0, 0, 1], "2001:0:0:1::1"),
+        ([0, 0, 1, 0, 0, 0, 1, 0], "0:0:1::1:0"),
+        // A tie goes to the first run
+        ([0x2001, 0xdb8, 0, 0, 1, 0, 0, 1], "2001:db8::1:0:0:1"),
+        ([0, 0, 1, 1, 1, 1, 0, 0], "::1:1:1:1:0:0"),
+        // IPv4-mapped addresses end in dotted decimal
+        ([0, 0, 0, 0, 0, 0xffff, 0xc000, 0x0201], "::ffff:192.0.2.1"),
+        ([0, 0, 0, 0, 0, 0xfffe, 0xc000, 0x0201], "::fffe:c000:201"),
+        ([0xffff; 8], "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"),
+    ];
+
+    #[test]
+    fn ipv4() {
+        assert_eq!(b"192.0.2.1", AddrText::ipv4([192, 0, 2, 1]).as_bytes());
+        assert_eq!(b"0.0.0.0", AddrText::ipv4([0; 4]).as_bytes());
+        assert_eq!(b"255.255.255.255", AddrText::ipv4([255; 4]).as_bytes());
+        assert_eq!(b"10.0.0.1:8080", AddrText::socket_v4([10, 0, 0, 1], 8080).as_bytes());
+        assert_eq!(b"127.0.0.1:0", AddrText::socket_v4([127, 0, 0, 1], 0).as_bytes());
+    }
+
+    #[test]
+    fn ipv6() {
+        for (segments, expected) in IPV6_CASES {
+            assert_eq!(
+                expected.as_bytes(),
+                AddrText::ipv6(*segments).as_bytes(),
+                "segments={:x?}",
+                segments
+            );
+        }
+    }
+
+    #[test]
+    fn socket_v6() {
+        let address = [0x2001, 0xdb8, 0, 0, 0, 0, 0, 1];
+        assert_eq!(b"[2001:db8::1]:443", AddrText::socket_v6(address, 0, 443).as_bytes());
+        assert_eq!(
+            b"[fe80::1%2]:8080",
+            AddrText::socket_v6([0xfe80, 0, 0, 0, 0, 0, 0, 1], 2, 8080).as_bytes()
+        );
+        let longest = AddrText::socket_v6([0xffff; 8], u32::MAX, u16::MAX);
+        assert_eq!(
+            b"[ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff%4294967295]:65535",
+            longest.as_bytes()
+        );
+    }
+
+    #[test]
+    fn mac() {
+        let bytes = [0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e];
+        assert_eq!(b"00:1a:2b:3c:4d:5e", AddrText::mac(bytes, ':').as_bytes());
+        assert_eq!(b"00-1a-2b-3c-4d-5e", AddrText::mac(bytes, '-').as_bytes());
+        assert_eq!("ff→ff→ff→ff→ff→ff".as_bytes(), AddrText::mac([0xff; 6], '→').as_bytes());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn ipv6_matches_std() {
+        for (segments, _) in IPV6_CASES {
+            let [a, b, c, d, e, f, g, h] = *segments;
+            let expected = std::net::Ipv6Addr::new(a, b, c, d, e, f, g, h).to_string();
+            assert_eq!(expected.as_bytes(), AddrText::ipv6(*segments).as_bytes());
+        }
+    }
+}
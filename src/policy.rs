@@ -0,0 +1,268 @@
+use crate::{utf8, written_str};
+
+/// Decides where content is cut when it does not entirely fit in a [`crate::WriteBuf`]. This is used both for a write
+/// which only partly fits and for rolling back content to make room for a suffix in the
+/// [`crate::WriteBuf::finish_with`] family of functions.
+///
+/// ```
+/// use fmtbuf::{LineBoundary, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 16] = [0xff; 16];
+/// let mut writer = WriteBuf::with_policy(&mut buf, &LineBoundary);
+/// write!(writer, "booting\nmounting /data\n").unwrap_err();
+/// // The partial line was cut off entirely
+/// assert_eq!("booting\n", writer.written());
+/// ```
+///
+/// The policy has to be [`Sync`], since a [`crate::WriteBuf`] keeps a reference to it and is shared across threads.
+pub trait TruncationPolicy: Sync {
+    /// Find where to cut `bytes` so that at most `limit` bytes are kept.
+    ///
+    /// `bytes` is valid UTF-8 and can go past `limit`, so a policy can tell if `limit` is on a boundary it cares about.
+    /// When a write only partly fits, `bytes` is the input of that write, and when making room for a suffix, it is the
+    /// written content of the buffer.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes of `bytes` to keep. This must be at most `limit` and must be on a `char` boundary of
+    /// `bytes`. This is checked in debug builds, and in release builds the result is moved back to the `char` boundary
+    /// before it, so the content is always valid UTF-8.
+    fn cut_point(&self, bytes: &[u8], limit: usize) -> usize;
+}
+
+/// Cut at the end of the last complete code point which fits. This is the default.
+///
+/// The one exception is a flag emoji, which is a pair of regional indicator code points: a cut between the two would
+/// leave the first one on its own, which shows as a boxed letter instead of a flag, so it is cut before the pair.
+///
+/// ```
+/// use fmtbuf::WriteBuf;
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 12] = [0xff; 12];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// // Each flag is 8 bytes, so only the first half of "🇫🇷" fits
+/// write!(writer, "🇩🇪🇫🇷").unwrap_err();
+/// assert_eq!("🇩🇪", writer.written());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CodePoint;
+
+impl TruncationPolicy for CodePoint {
+    fn cut_point(&self, bytes: &[u8], limit: usize) -> usize {
+        let end = utf8::floor_char_boundary(bytes, limit);
+        let next = written_str(&bytes[end..]).chars().next();
+        if !next.map_or(false, utf8::is_regional_indicator) {
+            return end;
+        }
+
+        // Regional indicators pair up from the start of a run of them, so an odd number before the cut means the last
+        // one is missing its partner
+        let kept = written_str(&bytes[..end]);
+        let run = kept
+            .chars()
+            .rev()
+            .take_while(|c| utf8::is_regional_indicator(*c))
+            .count();
+        if run % 2 == 1 {
+            end - REGIONAL_INDICATOR_LEN
+        } else {
+            end
+        }
+    }
+}
+
+/// The number of bytes a regional indicator code point is encoded in.
+const REGIONAL_INDICATOR_LEN: usize = 4;
+
+/// Cut after the last `\n` which fits, so a line is never partly kept. If there is no `\n` before the limit, nothing is
+/// kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LineBoundary;
+
+impl TruncationPolicy for LineBoundary {
+    fn cut_point(&self, bytes: &[u8], limit: usize) -> usize {
+        let limit = limit.min(bytes.len());
+        if limit == bytes.len() {
+            return limit;
+        }
+        bytes[..limit]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |idx| idx + 1)
+    }
+}
+
+/// Cut after the last whitespace before the limit, so a word is never partly kept: `"connection refused"` cut to 15
+/// bytes becomes `"connection "` instead of `"connection refu"`. Any Unicode whitespace counts, such as the ideographic
+/// space U+3000.
+///
+/// ```
+/// use fmtbuf::{WordBoundary, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 18] = [0xff; 18];
+/// let mut writer = WriteBuf::with_policy(&mut buf, &WordBoundary::DEFAULT);
+/// write!(writer, "connection refused by peer").unwrap_err();
+/// assert_eq!(Err("connection …"), writer.finish_with_str("…"));
+/// ```
+///
+/// To keep a single long token from collapsing to nothing, at most [`WordBoundary::max_rollback`] bytes are given up
+/// to reach the whitespace. If there is no whitespace that close to the limit, this cuts at the last complete code
+/// point like [`CodePoint`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WordBoundary {
+    max_rollback: usize,
+}
+
+impl WordBoundary {
+    /// A policy which gives up at most 16 bytes to cut at whitespace.
+    pub const DEFAULT: Self = Self::new(16);
+
+    /// Create a policy which gives up at most `max_rollback` bytes before the limit to cut at whitespace.
+    pub const fn new(max_rollback: usize) -> Self {
+        Self { max_rollback }
+    }
+
+    /// Get the most bytes before the limit which are given up to cut at whitespace.
+    pub fn max_rollback(&self) -> usize {
+        self.max_rollback
+    }
+}
+
+impl Default for WordBoundary {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl TruncationPolicy for WordBoundary {
+    fn cut_point(&self, bytes: &[u8], limit: usize) -> usize {
+        let end = CodePoint.cut_point(bytes, limit);
+        let (kept, rest) = (written_str(&bytes[..end]), written_str(&bytes[end..]));
+        // The limit already falls between two words
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            return end;
+        }
+
+        for (idx, c) in kept.char_indices().rev() {
+            let cut = idx + c.len_utf8();
+            if end - cut > self.max_rollback {
+                break;
+            }
+            if c.is_whitespace() {
+                return cut;
+            }
+        }
+        end
+    }
+}
+
+/// Ask `policy` where to cut `bytes`, making sure the answer is on a `char` boundary no later than `limit`.
+pub(crate) fn cut_point(policy: &dyn TruncationPolicy, bytes: &[u8], limit: usize) -> usize {
+    let limit = limit.min(bytes.len());
+    let idx = policy.cut_point(bytes, limit);
+    debug_assert!(
+        idx <= limit,
+        "truncation policy cut at {} past the limit of {}",
+        idx,
+        limit
+    );
+    debug_assert!(
+        idx == bytes.len() || (idx < bytes.len() && utf8::utf8_char_width(bytes[idx]).is_some()),
+        "truncation policy cut at {}, which is not a char boundary",
+        idx
+    );
+    utf8::floor_char_boundary(bytes, idx.min(limit))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Always cuts after the second byte, even in the middle of a `char`.
+    struct Bad;
+
+    impl TruncationPolicy for Bad {
+        fn cut_point(&self, _bytes: &[u8], _limit: usize) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn code_point() {
+        let rocket = "a🚀b".as_bytes();
+        assert_eq!(1, cut_point(&CodePoint, rocket, 4));
+        assert_eq!(5, cut_point(&CodePoint, rocket, 5));
+        assert_eq!(6, cut_point(&CodePoint, rocket, 100));
+    }
+
+    #[test]
+    fn code_point_keeps_flags_whole() {
+        let flags = "🇩🇪🇫🇷".as_bytes();
+        // Cut between the two halves of a flag
+        assert_eq!(0, cut_point(&CodePoint, flags, 7));
+        assert_eq!(8, cut_point(&CodePoint, flags, 12));
+        // Cut after a complete flag
+        assert_eq!(8, cut_point(&CodePoint, flags, 8));
+        assert_eq!(16, cut_point(&CodePoint, flags, 16));
+
+        // With an odd number of indicators, the last one is left on its own and the pairs before it stay whole
+        let odd = "a🇩🇪🇫b".as_bytes();
+        assert_eq!(13, cut_point(&CodePoint, odd, 13));
+        assert_eq!(9, cut_point(&CodePoint, odd, 12));
+        assert_eq!(1, cut_point(&CodePoint, odd, 8));
+        let odd = "🇦🇩🇪".as_bytes();
+        assert_eq!(8, cut_point(&CodePoint, odd, 11));
+        assert_eq!(0, cut_point(&CodePoint, odd, 4));
+    }
+
+    #[test]
+    fn line_boundary() {
+        let lines = b"ab\ncd\nef";
+        assert_eq!(0, cut_point(&LineBoundary, lines, 2));
+        assert_eq!(3, cut_point(&LineBoundary, lines, 3));
+        assert_eq!(3, cut_point(&LineBoundary, lines, 5));
+        assert_eq!(6, cut_point(&LineBoundary, lines, 7));
+        // Everything fits, so the partial last line is kept
+        assert_eq!(8, cut_point(&LineBoundary, lines, 8));
+    }
+
+    #[test]
+    fn word_boundary() {
+        let words = "connection refused";
+        let policy = WordBoundary::DEFAULT;
+        // Cut exactly at the space, on either side of it
+        assert_eq!(10, cut_point(&policy, words.as_bytes(), 10));
+        assert_eq!(11, cut_point(&policy, words.as_bytes(), 11));
+        assert_eq!(11, cut_point(&policy, words.as_bytes(), 15));
+        assert_eq!(18, cut_point(&policy, words.as_bytes(), 18));
+        // Inside the first word, there is no whitespace to go back to
+        assert_eq!(7, cut_point(&policy, words.as_bytes(), 7));
+        // Going back to the space would give up more than allowed
+        assert_eq!(15, cut_point(&WordBoundary::new(3), words.as_bytes(), 15));
+        assert_eq!(11, cut_point(&WordBoundary::new(4), words.as_bytes(), 15));
+        assert_eq!(4, WordBoundary::new(4).max_rollback());
+
+        // Multi-byte whitespace counts, and the cut before it is still on a char boundary
+        let ideographic = "見見\u{3000}見見";
+        assert_eq!(9, cut_point(&policy, ideographic.as_bytes(), 14));
+        assert_eq!(6, cut_point(&policy, ideographic.as_bytes(), 8));
+        assert_eq!(3, cut_point(&WordBoundary::new(0), ideographic.as_bytes(), 5));
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[test]
+    fn invalid_cut_is_moved_to_char_boundary() {
+        assert_eq!(1, cut_point(&Bad, "a見".as_bytes(), 4));
+        assert_eq!(1, cut_point(&Bad, "a見".as_bytes(), 1));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "not a char boundary")]
+    fn invalid_cut_panics_in_debug() {
+        cut_point(&Bad, "a見".as_bytes(), 4);
+    }
+}
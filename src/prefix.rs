@@ -0,0 +1,191 @@
+use core::fmt;
+
+use crate::{Checkpoint, SliceTarget, Target, WriteBuf};
+
+/// Writes to a [`WriteBuf`], starting every line with a fixed prefix, such as the name of the module a multi-line
+/// message came from.
+///
+/// ```
+/// use fmtbuf::{PrefixWriter, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 64] = [0xff; 64];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// let mut out = PrefixWriter::new(&mut writer, "[net] ");
+/// write!(out, "link up\nspeed: {}", 100).unwrap();
+/// writeln!(out, " Mbit/s").unwrap();
+/// assert_eq!("[net] link up\n[net] speed: 100 Mbit/s\n", writer.written());
+/// ```
+///
+/// The prefix is written before the first character of each line, so it is never written for an empty line (`"\n"` or
+/// `"\r\n"`), and a line which starts in a later write than the `\n` before it still gets it. The prefix is written like
+/// any other content, so it is truncated the same way.
+///
+/// A `\r` which starts a line at the end of a write is written without the prefix. If the next write does not start
+/// with the `\n` which makes it an empty `"\r\n"` line, the `\r` is rolled back and written again after the prefix.
+pub struct PrefixWriter<'b, 'a, T = SliceTarget<'a>> {
+    buf: &'b mut WriteBuf<'a, T>,
+    prefix: &'b str,
+    at_line_start: bool,
+    /// Where a `\r` starting a line was written, if nothing has been written after it.
+    pending_cr: Option<Checkpoint>,
+}
+
+impl<'b, 'a, T: Target<'a>> PrefixWriter<'b, 'a, T> {
+    /// Create an instance which writes to `buf`, starting each line with `prefix`. If `buf` does not end with a `\n`,
+    /// the rest of its current line is not prefixed.
+    pub fn new(buf: &'b mut WriteBuf<'a, T>, prefix: &'b str) -> Self {
+        let at_line_start = buf.position() == 0 || buf.ends_with("\n");
+        Self {
+            buf,
+            prefix,
+            at_line_start,
+            pending_cr: None,
+        }
+    }
+
+    /// Get the prefix each line starts with.
+    pub fn prefix(&self) -> &str {
+        self.prefix
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for PrefixWriter<'b, 'a, T> {
+    /// Write `s`, with the prefix before each line of it which has anything in it.
+    ///
+    /// # Error
+    ///
+    /// If the prefix or `s` did not entirely fit, the same as [`WriteBuf`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        if let Some(checkpoint) = self.pending_cr.take() {
+            if !s.starts_with('\n') {
+                // The line is not empty after all, so it needs the prefix before the `\r`
+                let _ = self.buf.rollback_to(checkpoint);
+                self.buf.write_str(self.prefix)?;
+                self.buf.write_str("\r")?;
+            }
+        }
+        for line in s.split_inclusive('\n') {
+            if self.at_line_start && line == "\r" {
+                // This is the end of `s`, so only the next write tells if the line is empty
+                let checkpoint = self.buf.checkpoint();
+                self.buf.write_str(line)?;
+                self.pending_cr = Some(checkpoint);
+                self.at_line_start = false;
+                break;
+            }
+            if self.at_line_start && line != "\n" && line != "\r\n" {
+                self.buf.write_str(self.prefix)?;
+            }
+            self.buf.write_str(line)?;
+            self.at_line_start = line.ends_with('\n');
+        }
+        Ok(())
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Debug for PrefixWriter<'b, 'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrefixWriter")
+            .field("buf", &self.buf)
+            .field("prefix", &self.prefix)
+            .field("at_line_start", &self.at_line_start)
+            .field("pending_cr", &self.pending_cr)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn prefixes_lines() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = PrefixWriter::new(&mut writer, "> ");
+            assert_eq!("> ", out.prefix());
+            out.write_str("a\nb").unwrap();
+            // A newline at the end of one write and the line in the next
+            out.write_str("c\n").unwrap();
+            out.write_str("").unwrap();
+            out.write_char('d').unwrap();
+        }
+        assert_eq!("> a\n> bc\n> d", writer.written());
+    }
+
+    #[test]
+    fn empty_lines() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = PrefixWriter::new(&mut writer, "# ");
+            out.write_str("\n\n\n").unwrap();
+        }
+        assert_eq!("\n\n\n", writer.written());
+
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = PrefixWriter::new(&mut writer, "# ");
+            out.write_str("a\r\n\r\nb\r\n").unwrap();
+        }
+        assert_eq!("# a\r\n\r\n# b\r\n", writer.written());
+    }
+
+    #[test]
+    fn crlf_split_across_writes() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = PrefixWriter::new(&mut writer, "# ");
+            out.write_str("a\n\r").unwrap();
+            out.write_str("\nb\r\n").unwrap();
+            out.write_str("\r").unwrap();
+            out.write_str("").unwrap();
+            out.write_str("\n\r").unwrap();
+            // Not an empty line after all
+            out.write_str("c\n\r").unwrap();
+        }
+        // Nothing showed the last `\r` starts a line with anything in it
+        assert_eq!("# a\n\r\n# b\r\n\r\n# \rc\n\r", writer.written());
+    }
+
+    #[test]
+    fn continues_buffer_content() {
+        let mut buf: [u8; 32] = [0xff; 32];
+        let mut writer = WriteBuf::new(&mut buf);
+        writer.write_str("x").unwrap();
+        {
+            let mut out = PrefixWriter::new(&mut writer, "| ");
+            out.write_str("1\n2").unwrap();
+        }
+        assert_eq!("x1\n| 2", writer.written());
+    }
+
+    #[test]
+    fn truncated() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = PrefixWriter::new(&mut writer, "[net] ");
+            // The second prefix is cut off
+            out.write_str("a\nb").unwrap_err();
+            out.write_str("\n").unwrap_err();
+        }
+        assert!(writer.truncated());
+        assert_eq!("[net] a\n", writer.written());
+
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = PrefixWriter::new(&mut writer, "> ");
+            out.write_str("abcdefg").unwrap_err();
+        }
+        assert_eq!("> abcdef", writer.written());
+    }
+}
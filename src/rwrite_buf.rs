@@ -0,0 +1,307 @@
+use core::{fmt, ops};
+
+use crate::{measure, written_str};
+
+/// A write buffer which fills a `&mut [u8]` from the end toward the front, so every write is placed before the content
+/// written so far. This makes the content end flush with the end of the target without knowing its length up front,
+/// such as for right-aligned fields.
+///
+/// ```
+/// use fmtbuf::RWriteBuf;
+/// use std::fmt::Write;
+///
+/// let mut field: [u8; 7] = [b' '; 7];
+/// let mut writer = RWriteBuf::new(&mut field);
+/// write!(writer, "{:.1}", 42.71).unwrap();
+/// assert_eq!(Ok(3..7), writer.finish());
+/// assert_eq!(b"   42.7", &field);
+///
+/// // Digits can be written least-significant first
+/// let mut field: [u8; 6] = [b' '; 6];
+/// let mut writer = RWriteBuf::new(&mut field);
+/// let mut n = 907_u32;
+/// loop {
+///     writer.write_char(char::from(b'0' + (n % 10) as u8)).unwrap();
+///     n /= 10;
+///     if n == 0 {
+///         break;
+///     }
+/// }
+/// writer.write_str("#").unwrap();
+/// assert_eq!(Ok(2..6), writer.finish());
+/// assert_eq!(b"  #907", &field);
+/// ```
+///
+/// With `write!`, the entire format is placed before the previous content as a single piece, so it reads the same way
+/// it would have with a [`crate::WriteBuf`]. When there is not enough room, the front of the write is cut off at a
+/// `char` boundary, since that is the end the content grows toward, and like a [`crate::WriteBuf`], truncation makes
+/// every later write fail.
+pub struct RWriteBuf<'a> {
+    target: &'a mut [u8],
+    /// The index of `target` where the written content starts. Everything from here to the end has been written.
+    start: usize,
+    truncated: bool,
+}
+
+impl<'a> RWriteBuf<'a> {
+    /// Create an instance that will write to the end of the given `target`. The contents of the target do not matter,
+    /// but the bytes before the written content are left untouched, so filling it with spaces right-aligns the content.
+    pub fn new(target: &'a mut [u8]) -> Self {
+        let start = target.len();
+        Self {
+            target,
+            start,
+            truncated: false,
+        }
+    }
+
+    /// Get the number of bytes the target can hold.
+    pub fn capacity(&self) -> usize {
+        self.target.len()
+    }
+
+    /// Get the index of the target the written content starts at.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Get the number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.target.len() - self.start
+    }
+
+    /// Get if nothing has been written.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the number of bytes which can still be written before the content reaches the front of the target.
+    pub fn remaining(&self) -> usize {
+        self.start
+    }
+
+    /// Get if a write was truncated. See [`crate::WriteBuf::truncated`].
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Get the contents that have been written so far.
+    pub fn written(&self) -> &str {
+        written_str(&self.target[self.start..])
+    }
+
+    /// Finish the buffer, returning the range of the target the written content is in. This always ends at the end of
+    /// the target, so its start is the offset and its length is the length of the content.
+    ///
+    /// # Returns
+    ///
+    /// The range, in `Ok` if nothing was truncated or in `Err` if it was.
+    pub fn finish(self) -> Result<ops::Range<usize>, ops::Range<usize>> {
+        let range = self.start..self.target.len();
+        if self.truncated {
+            Err(range)
+        } else {
+            Ok(range)
+        }
+    }
+
+    /// Finish the buffer, returning the written content. See [`RWriteBuf::finish`].
+    pub fn finish_str(self) -> Result<&'a str, &'a str> {
+        let truncated = self.truncated;
+        let written = written_str(&self.target[self.start..]);
+        if truncated {
+            Err(written)
+        } else {
+            Ok(written)
+        }
+    }
+
+    /// Place the last bytes of `s` which fit before the content, cutting the front of it at a `char` boundary.
+    fn _prepend(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return if s.is_empty() { Ok(()) } else { Err(fmt::Error) };
+        }
+
+        let mut cut = s.len().saturating_sub(self.start);
+        while !s.is_char_boundary(cut) {
+            cut += 1;
+        }
+        let kept = &s.as_bytes()[cut..];
+        let start = self.start - kept.len();
+        self.target[start..self.start].copy_from_slice(kept);
+        self.start = start;
+        if cut == 0 {
+            Ok(())
+        } else {
+            self.truncated = true;
+            Err(fmt::Error)
+        }
+    }
+}
+
+impl<'a> fmt::Write for RWriteBuf<'a> {
+    /// Place `s` before the content written so far. If it does not entirely fit, the end of it which does is written, up
+    /// to a `char` boundary, and this buffer is marked as truncated.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self._prepend(s)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        let mut encoded = [0u8; 4];
+        self._prepend(c.encode_utf8(&mut encoded))
+    }
+
+    /// Place the entire formatted `args` before the content written so far, as if it were a single `write_str`. The
+    /// output of `args` is formatted twice: once to [`measure`] it and once to write it in place.
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        if self.truncated {
+            return if measure(args) == 0 { Ok(()) } else { Err(fmt::Error) };
+        }
+
+        let len = measure(args);
+        let skip = len.saturating_sub(self.start);
+        let region_start = self.start - (len - skip);
+        let mut region = Region {
+            target: &mut self.target[region_start..self.start],
+            skip,
+            position: 0,
+        };
+        let result = fmt::write(&mut region, args);
+        let written = region.position;
+
+        // If the output got shorter since it was measured, move it so it still ends where the content starts
+        let mut start = self.start - written;
+        self.target.copy_within(region_start..region_start + written, start);
+        // The cut can land in the middle of a `char`, so drop the rest of it
+        while start < self.start && (self.target[start] & 0b1100_0000) == 0b1000_0000 {
+            start += 1;
+        }
+        self.start = start;
+        if skip > 0 || result.is_err() {
+            self.truncated = true;
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Debug for RWriteBuf<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RWriteBuf")
+            .field("written", &self.written())
+            .field("capacity", &self.capacity())
+            .field("start", &self.start)
+            .field("truncated", &self.truncated)
+            .finish()
+    }
+}
+
+/// Writes formatted output into the region before the content of an [`RWriteBuf`], dropping the first `skip` bytes.
+struct Region<'b> {
+    target: &'b mut [u8],
+    skip: usize,
+    position: usize,
+}
+
+impl<'b> fmt::Write for Region<'b> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut input = s.as_bytes();
+        let skipped = self.skip.min(input.len());
+        self.skip -= skipped;
+        input = &input[skipped..];
+        let dest = self
+            .target
+            .get_mut(self.position..self.position + input.len())
+            .ok_or(fmt::Error)?;
+        dest.copy_from_slice(input);
+        self.position += input.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn prepends() {
+        let mut buf: [u8; 8] = [b'.'; 8];
+        let mut writer = RWriteBuf::new(&mut buf);
+        assert!(writer.is_empty());
+        writer.write_str("c").unwrap();
+        writer.write_str("ab").unwrap();
+        writer.write_char('見').unwrap();
+        assert_eq!("見abc", writer.written());
+        assert_eq!((2, 6, 2), (writer.start(), writer.len(), writer.remaining()));
+        writer.write_str("").unwrap();
+        assert_eq!(Ok(2..8), writer.finish());
+        assert_eq!(b"..\xe8\xa6\x8babc", &buf);
+    }
+
+    #[test]
+    fn truncates_the_front() {
+        let mut buf: [u8; 6] = [b'.'; 6];
+        let mut writer = RWriteBuf::new(&mut buf);
+        writer.write_str("b").unwrap();
+        // Only 5 bytes are left, which cuts the first "見"
+        writer.write_str("見見a").unwrap_err();
+        assert!(writer.truncated());
+        assert_eq!("見ab", writer.written());
+        // Truncation latches
+        writer.write_char('x').unwrap_err();
+        writer.write_str("").unwrap();
+        assert_eq!(Err("見ab"), writer.finish_str());
+        assert_eq!(b".\xe8\xa6\x8bab", &buf);
+
+        let mut writer = RWriteBuf::new(&mut []);
+        writer.write_char('a').unwrap_err();
+        assert_eq!(Err(0..0), writer.finish());
+    }
+
+    #[test]
+    fn write_fmt_is_one_piece() {
+        let mut buf: [u8; 12] = [b' '; 12];
+        let mut writer = RWriteBuf::new(&mut buf);
+        writer.write_str("|").unwrap();
+        let (value, unit) = (-3.21, "°C");
+        write!(writer, "{:.1}{}", value, unit).unwrap();
+        assert_eq!("-3.2°C|", writer.written());
+        assert_eq!(Ok(4..12), writer.finish());
+        assert_eq!("    -3.2°C|", core::str::from_utf8(&buf).unwrap());
+
+        // The front of the format is cut, without leaving part of a "見" behind
+        let mut buf: [u8; 6] = [b' '; 6];
+        let mut writer = RWriteBuf::new(&mut buf);
+        let word = "見見";
+        write!(writer, "{}{}", word, 12).unwrap_err();
+        assert_eq!("見12", writer.written());
+        let empty = "";
+        write!(writer, "{}", empty).unwrap();
+        write!(writer, "{}", 1).unwrap_err();
+        assert_eq!(Err(1..6), writer.finish());
+    }
+
+    #[test]
+    fn write_fmt_output_changes() {
+        use core::cell::Cell;
+
+        /// Formats shorter every time it is formatted.
+        struct Shrinking(Cell<usize>);
+
+        impl fmt::Display for Shrinking {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let len = self.0.get();
+                self.0.set(len - 1);
+                f.write_str(&"abcdef"[..len])
+            }
+        }
+
+        let mut buf: [u8; 8] = [b'.'; 8];
+        let mut writer = RWriteBuf::new(&mut buf);
+        writer.write_str("!").unwrap();
+        write!(writer, "{}", Shrinking(Cell::new(4))).unwrap();
+        assert_eq!("abc!", writer.written());
+        assert_eq!(Ok(4..8), writer.finish());
+    }
+}
@@ -0,0 +1,277 @@
+//! Writing a value with an SI prefix, such as `12.3 kHz`, used by [`crate::WriteBuf::write_si`].
+
+use crate::{float, int};
+
+/// The most significant figures written. An `f64` does not have more than this, so more would only be noise.
+const MAX_SIG_FIGS: usize = 17;
+
+/// The prefixes from pico to tera, each 1000 times the one before it. [`PREFIX_OFFSET`] is the one without a prefix.
+const PREFIXES: [&str; 9] = ["p", "n", "µ", "m", "", "k", "M", "G", "T"];
+
+/// [`PREFIXES`] with the micro sign written as `u`, for output which has to be ASCII.
+const ASCII_PREFIXES: [&str; 9] = ["p", "n", "u", "m", "", "k", "M", "G", "T"];
+
+/// The index of the empty prefix in [`PREFIXES`].
+const PREFIX_OFFSET: i32 = 4;
+
+/// A value split up for writing with an SI prefix.
+pub(crate) struct Si {
+    /// The text written instead of digits, for NaN and the infinities.
+    special: Option<&'static str>,
+    negative: bool,
+    /// The significant figures, of which the first `sig_figs` are used.
+    digits: [u8; MAX_SIG_FIGS],
+    sig_figs: usize,
+    /// The number of digits before the decimal point. If this is more than `sig_figs`, it is padded with zeros.
+    int_digits: usize,
+    prefix: &'static str,
+    /// The power of 10 written after the digits, when the value is outside of the range of the prefixes.
+    exponent: Option<i32>,
+}
+
+impl Si {
+    /// Split up `value` with `sig_figs` significant figures, which is clamped to between 1 and [`MAX_SIG_FIGS`].
+    pub(crate) fn new(value: f64, sig_figs: usize, ascii: bool) -> Self {
+        let sig_figs = sig_figs.clamp(1, MAX_SIG_FIGS);
+        let mut out = Self {
+            special: None,
+            negative: value.is_sign_negative(),
+            digits: [b'0'; MAX_SIG_FIGS],
+            sig_figs,
+            int_digits: 1,
+            prefix: "",
+            exponent: None,
+        };
+        if value.is_nan() {
+            out.special = Some("NaN");
+            return out;
+        }
+        if value.is_infinite() {
+            out.special = Some(if out.negative { "-inf" } else { "inf" });
+            return out;
+        }
+        let magnitude = f64::from_bits(value.to_bits() & !(1 << 63));
+        if magnitude == 0.0 {
+            return out;
+        }
+
+        // Round to the significant figures first, since rounding up can move the value to the next prefix
+        let exponent = float::significant_digits(magnitude, &mut out.digits[..sig_figs]);
+
+        let power = exponent.div_euclid(3);
+        if (-PREFIX_OFFSET..=PREFIX_OFFSET).contains(&power) {
+            let prefixes = if ascii { &ASCII_PREFIXES } else { &PREFIXES };
+            out.prefix = prefixes[(power + PREFIX_OFFSET) as usize];
+            out.int_digits = (exponent - power * 3) as usize + 1;
+        } else {
+            out.exponent = Some(exponent);
+        }
+        out
+    }
+
+    /// Get the number of bytes written with `unit` after the value.
+    pub(crate) fn len(&self, unit: &str) -> usize {
+        let text = match self.special {
+            Some(special) => special.len(),
+            None => {
+                let number = if self.int_digits >= self.sig_figs {
+                    self.int_digits
+                } else {
+                    self.sig_figs + 1
+                };
+                let exponent = self.exponent.map_or(0, |exponent| {
+                    let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+                    1 + int::decimal_u64(u64::from(exponent.unsigned_abs()), exponent < 0, &mut scratch).len()
+                });
+                usize::from(self.negative) + number + exponent
+            },
+        };
+        text + self.unit_len(unit)
+    }
+
+    /// Pass the output to `out` in pieces, with `unit` after the value. The pieces add up to [`Si::len`] bytes.
+    pub(crate) fn render(&self, unit: &str, mut out: impl FnMut(&[u8])) {
+        if let Some(special) = self.special {
+            out(special.as_bytes());
+        } else {
+            if self.negative {
+                out(b"-");
+            }
+            let digits = &self.digits[..self.sig_figs];
+            if self.int_digits >= self.sig_figs {
+                out(digits);
+                out(&[b'0'; 2][..self.int_digits - self.sig_figs]);
+            } else {
+                out(&digits[..self.int_digits]);
+                out(b".");
+                out(&digits[self.int_digits..]);
+            }
+            if let Some(exponent) = self.exponent {
+                let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+                out(b"e");
+                out(int::decimal_u64(
+                    u64::from(exponent.unsigned_abs()),
+                    exponent < 0,
+                    &mut scratch,
+                ));
+            }
+        }
+
+        if self.unit_len(unit) > 0 {
+            out(b" ");
+            out(self.prefix.as_bytes());
+            out(unit.as_bytes());
+        }
+    }
+
+    /// Get the number of bytes taken by the space, prefix and unit after the number. There is no space if there is
+    /// nothing after it.
+    fn unit_len(&self, unit: &str) -> usize {
+        let len = self.prefix.len() + unit.len();
+        if len == 0 {
+            0
+        } else {
+            len + 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+    use core::fmt::Write;
+
+    fn si(value: f64, unit: &str, sig_figs: usize) -> StackString<64> {
+        let si = Si::new(value, sig_figs, false);
+        let mut out: StackString<64> = StackString::new();
+        si.render(unit, |piece| {
+            out.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+        });
+        assert_eq!(si.len(unit), out.as_str().len(), "value={:e}", value);
+        out
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!("12.3 kHz", si(12_345.0, "Hz", 3));
+        assert_eq!("4.7 µA", si(4.7e-6, "A", 2));
+        assert_eq!("980 mV", si(0.98, "V", 3));
+        assert_eq!("1.00 pF", si(1e-12, "F", 3));
+        assert_eq!("999 TB", si(999e12, "B", 3));
+        assert_eq!("5 V", si(5.0, "V", 1));
+        assert_eq!("-2.50 MW", si(-2.5e6, "W", 3));
+        assert_eq!("250 n", si(250e-9, "", 3));
+        assert_eq!("42", si(42.0, "", 2));
+    }
+
+    #[test]
+    fn rounding() {
+        // Rounding up to 1000 moves to the next prefix
+        assert_eq!("1.00 kHz", si(999.6, "Hz", 3));
+        assert_eq!("1.0 MHz", si(999_999.0, "Hz", 2));
+        assert_eq!("10.0 mV", si(9.996e-3, "V", 3));
+        // Fewer figures than digits before the point pads with zeros
+        assert_eq!("990 mV", si(0.987, "V", 2));
+        assert_eq!("100 µs", si(99.7e-6, "s", 1));
+        assert_eq!("1 ks", si(995.0, "s", 1));
+        // Ties go to even
+        assert_eq!("2 V", si(2.5, "V", 1));
+        assert_eq!("4 V", si(3.5, "V", 1));
+        // The digits come from the exact value, which is rounded only once
+        assert_eq!("500 m", si(0.45, "", 1));
+        assert_eq!("71.5", si(71.55, "", 3));
+        assert_eq!("2.23", si(2.225, "", 3));
+        assert_eq!("1.0000000000000001e300", si(1e300, "", 17));
+        // The figures are clamped
+        assert_eq!("1 V", si(1.0, "V", 0));
+        assert_eq!("1.0000000000000000 V", si(1.0, "V", 100));
+    }
+
+    #[test]
+    fn outside_of_prefixes() {
+        assert_eq!("1.00e15 Hz", si(1e15, "Hz", 3));
+        assert_eq!("1.00e15 Hz", si(999.9e12, "Hz", 3));
+        assert_eq!("4.70e-13 A", si(4.7e-13, "A", 3));
+        // Rounding up can move a value back into the table
+        assert_eq!("1.00 pA", si(9.9999e-13, "A", 3));
+        assert_eq!("-1.8e308", si(f64::MIN, "", 2));
+        assert_eq!("4.9e-324 s", si(f64::from_bits(1), "s", 2));
+    }
+
+    #[test]
+    fn special_values() {
+        assert_eq!("0.00 V", si(0.0, "V", 3));
+        assert_eq!("-0 V", si(-0.0, "V", 1));
+        assert_eq!("0", si(0.0, "", 1));
+        assert_eq!("NaN V", si(f64::NAN, "V", 3));
+        assert_eq!("inf", si(f64::INFINITY, "", 3));
+        assert_eq!("-inf W", si(f64::NEG_INFINITY, "W", 3));
+    }
+
+    /// Get the significant figures and the power of 10 of the first one from text written by [`Si`] in ASCII without a
+    /// unit, so it can be compared to `{:e}`.
+    fn parse(text: &str, sig_figs: usize) -> (StackString<32>, i32) {
+        let (number, prefix) = text.split_once(' ').unwrap_or((text, ""));
+        let power = ASCII_PREFIXES.iter().position(|p| *p == prefix).unwrap() as i32 - PREFIX_OFFSET;
+        let (number, exponent) = number.split_once('e').unwrap_or((number, "0"));
+        let number = number.trim_start_matches('-');
+        let int_len = number.split('.').next().unwrap().len() as i32;
+        let mut digits: StackString<32> = StackString::new();
+        for c in number.chars().filter(|c| *c != '.').take(sig_figs) {
+            digits.write_char(c).unwrap();
+        }
+        (digits, exponent.parse::<i32>().unwrap() + power * 3 + int_len - 1)
+    }
+
+    #[test]
+    fn digits_match_fmt() {
+        // xorshift, so the same values are checked every run
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..20_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let sig_figs = (state % MAX_SIG_FIGS as u64) as usize + 1;
+            let bits = f64::from_bits(state);
+            let typical = (state >> 11) as f64 / (1u64 << 40) as f64 * 1e6 + 1e-15;
+            for value in [bits, typical] {
+                if !value.is_finite() || value == 0.0 {
+                    continue;
+                }
+                let si = Si::new(value, sig_figs, true);
+                let mut text: StackString<64> = StackString::new();
+                si.render("", |piece| {
+                    text.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+                });
+
+                let mut expected: StackString<64> = StackString::new();
+                write!(expected, "{:.*e}", sig_figs - 1, value.abs()).unwrap();
+                let (mantissa, exponent) = expected.as_str().split_once('e').unwrap();
+                let mut expected_digits: StackString<32> = StackString::new();
+                for c in mantissa.chars().filter(|c| *c != '.') {
+                    expected_digits.write_char(c).unwrap();
+                }
+                let (digits, actual_exponent) = parse(text.as_str(), sig_figs);
+                assert_eq!(
+                    (expected_digits.as_str(), exponent.parse::<i32>().unwrap()),
+                    (digits.as_str(), actual_exponent),
+                    "value={:e} sig_figs={} text={}",
+                    value,
+                    sig_figs,
+                    text
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let si = Si::new(4.7e-6, 2, true);
+        let mut out: StackString<16> = StackString::new();
+        si.render("A", |piece| {
+            out.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+        });
+        assert_eq!("4.7 uA", out);
+    }
+}
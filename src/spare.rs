@@ -0,0 +1,198 @@
+use core::{fmt, marker::PhantomData, ops};
+
+use crate::{target::sealed::Storage, WriteBuf};
+
+/// A [`WriteBuf`] over the spare capacity of a `Vec<u8>` or a `String`, made by [`WriteBuf::from_vec_spare`] or
+/// [`WriteBuf::from_string_spare`]. The collection never grows, so writing never reallocates: content which does not
+/// fit in the spare capacity is truncated the same way as with any other target.
+///
+/// Nothing is added to the collection until [`SpareWriteBuf::commit`] is called, so dropping this without committing
+/// leaves the collection as it was. This dereferences to the [`WriteBuf`], so all non-consuming operations are
+/// available. Replacing that [`WriteBuf`] with another one, such as with [`core::mem::swap`], makes committing panic.
+#[must_use = "the written content is only added to the collection by `commit`"]
+pub struct SpareWriteBuf<'a> {
+    buf: WriteBuf<'a>,
+    /// The collection `buf` writes into the spare capacity of. This is only used to set its length when committing,
+    /// which does not touch the spare capacity borrowed by `buf`.
+    vec: *mut Vec<u8>,
+    _vec: PhantomData<&'a mut Vec<u8>>,
+}
+
+impl<'a> SpareWriteBuf<'a> {
+    /// Create an instance over the spare capacity of `vec`.
+    ///
+    /// # Safety
+    ///
+    /// If `vec` belongs to a `String`, its content must be valid UTF-8. Committing only ever appends complete UTF-8
+    /// sequences, so it stays valid.
+    pub(crate) unsafe fn new(vec: &'a mut Vec<u8>) -> Self {
+        let vec: *mut Vec<u8> = vec;
+        // safety: `vec` came from a `&'a mut`, so the spare capacity can be borrowed for `'a`. It is a separate
+        // allocation from the `Vec` itself, which is only touched again by `commit`.
+        let spare = unsafe { (*vec).spare_capacity_mut() };
+        Self {
+            buf: WriteBuf::new_uninit(spare),
+            vec,
+            _vec: PhantomData,
+        }
+    }
+
+    /// Add the written content to the end of the collection. This is the same as [`WriteBuf::finish`].
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes added to the collection, in `Ok` if nothing was truncated or in `Err` if it was.
+    ///
+    /// # Panics
+    ///
+    /// The same as [`SpareWriteBuf::commit_with_or`].
+    pub fn commit(self) -> Result<usize, usize> {
+        self.commit_with_or("", "")
+    }
+
+    /// Add `normal_suffix` to the end of the content, or `truncated_suffix` if it was truncated, then add the content
+    /// to the end of the collection. The suffix is placed the same way as [`WriteBuf::finish_with_or`], so it only
+    /// ever rolls back content written by this writer, never what was in the collection before.
+    ///
+    /// # Returns
+    ///
+    /// The same as [`SpareWriteBuf::commit`].
+    ///
+    /// # Panics
+    ///
+    /// If the [`WriteBuf`] this dereferences to was replaced by one which does not write into the spare capacity of the
+    /// collection.
+    pub fn commit_with_or(mut self, normal_suffix: &str, truncated_suffix: &str) -> Result<usize, usize> {
+        // The `WriteBuf` can be swapped out through `DerefMut`, and the length of the collection must only ever cover
+        // bytes written into its own spare capacity
+        // safety: Reading the pointer and length does not touch the spare capacity `buf` borrows
+        let (spare_ptr, spare_len) = unsafe {
+            let vec = &*self.vec;
+            (vec.as_ptr().wrapping_add(vec.len()), vec.capacity() - vec.len())
+        };
+        assert!(
+            self.buf.target.as_ptr() == spare_ptr && self.buf.capacity() == spare_len,
+            "the WriteBuf of a SpareWriteBuf was replaced"
+        );
+
+        self.buf._finish_with(normal_suffix, truncated_suffix);
+        let result = self.buf._result();
+        let added = self.buf.position();
+        // safety: `vec` is still exclusively borrowed for `'a` and setting the length does not touch the spare capacity
+        // `buf` borrows. The first `added` bytes of the spare capacity were written, and they are valid UTF-8, so a
+        // `String` which was valid UTF-8 still is.
+        unsafe {
+            let vec = &mut *self.vec;
+            vec.set_len(vec.len() + added);
+        }
+        result
+    }
+}
+
+impl<'a> ops::Deref for SpareWriteBuf<'a> {
+    type Target = WriteBuf<'a>;
+
+    fn deref(&self) -> &WriteBuf<'a> {
+        &self.buf
+    }
+}
+
+impl<'a> ops::DerefMut for SpareWriteBuf<'a> {
+    fn deref_mut(&mut self) -> &mut WriteBuf<'a> {
+        &mut self.buf
+    }
+}
+
+impl<'a> fmt::Write for SpareWriteBuf<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buf.write_str(s)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.buf.write_char(c)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        self.buf.write_fmt(args)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::WriteBuf;
+    use core::fmt::Write;
+
+    #[test]
+    fn vec_never_grows() {
+        let mut vec = Vec::with_capacity(16);
+        vec.extend_from_slice(b"\x00\xff");
+        let (capacity, ptr) = (vec.capacity(), vec.as_ptr());
+
+        let mut writer = WriteBuf::from_vec_spare(&mut vec);
+        assert_eq!(capacity - 2, writer.capacity());
+        write!(writer, "{}", "x".repeat(capacity)).unwrap_err();
+        assert_eq!(Err(capacity - 2), writer.commit());
+        assert_eq!(capacity, vec.len());
+        assert_eq!((capacity, ptr), (vec.capacity(), vec.as_ptr()));
+        assert_eq!(b"\x00\xffxx", &vec[..4]);
+
+        // A full collection has no spare capacity, so nothing fits
+        let mut writer = WriteBuf::from_vec_spare(&mut vec);
+        writer.write_str("y").unwrap_err();
+        assert_eq!(Err(0), writer.commit());
+        assert_eq!(capacity, vec.len());
+    }
+
+    #[test]
+    fn string_stays_utf8() {
+        let mut s = String::with_capacity(16);
+        s.push_str("id=");
+        let spare = s.capacity() - s.len();
+
+        {
+            let mut writer = WriteBuf::from_string_spare(&mut s);
+            write!(writer, "{}", "見".repeat(spare)).unwrap_err();
+            writer.commit_with_or("", "…").unwrap_err();
+        }
+        assert!(s.starts_with("id=見"));
+        assert!(s.ends_with('…'));
+        assert!(s.len() <= s.capacity());
+        assert!(core::str::from_utf8(s.as_bytes()).is_ok());
+
+        // Dropping without committing leaves the collection alone
+        let before = s.clone();
+        {
+            let mut writer = WriteBuf::from_string_spare(&mut s);
+            let _ = writer.write_str("more");
+        }
+        assert_eq!(before, s);
+    }
+
+    #[test]
+    #[should_panic(expected = "the WriteBuf of a SpareWriteBuf was replaced")]
+    fn swapped_buffer_is_not_committed() {
+        let mut vec = Vec::with_capacity(8);
+        let mut other = [b'x'; 1000];
+        let mut other = WriteBuf::new(&mut other);
+        other.write_str(&"y".repeat(1000)).unwrap();
+
+        let mut writer = WriteBuf::from_vec_spare(&mut vec);
+        core::mem::swap(&mut *writer, &mut other);
+        let _ = writer.commit();
+    }
+
+    #[test]
+    fn buffer_swapped_back_is_committed() {
+        let mut vec = Vec::with_capacity(8);
+        let mut other = [0u8; 4];
+        let mut other = WriteBuf::new(&mut other);
+
+        let mut writer = WriteBuf::from_vec_spare(&mut vec);
+        writer.write_str("ab").unwrap();
+        core::mem::swap(&mut *writer, &mut other);
+        other.write_str("cd").unwrap();
+        core::mem::swap(&mut *writer, &mut other);
+        assert_eq!(Ok(4), writer.commit());
+        assert_eq!(b"abcd", &vec[..]);
+    }
+}
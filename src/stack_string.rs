@@ -0,0 +1,211 @@
+use core::{fmt, ops};
+
+use crate::{written_str, WriteBuf};
+
+/// An owned string of at most `N` bytes, which truncates writes the same way as [`WriteBuf`].
+///
+/// This is useful when the buffer does not have to outlive the writer, since it keeps the array and the length of the
+/// written content together.
+///
+/// ```
+/// use fmtbuf::StackString;
+/// use std::fmt::Write;
+///
+/// let mut s = StackString::<8>::new();
+/// write!(s, "{}", "much too long").unwrap_err();
+/// assert!(s.truncated());
+/// assert_eq!(Err("much ..."), s.finish_with_or("", "..."));
+/// assert_eq!("much ...", s);
+/// ```
+///
+/// Like a [`WriteBuf`], truncation latches, so every write after a truncated one is rejected until
+/// [`StackString::clear`] is called.
+#[derive(Clone)]
+pub struct StackString<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> StackString<N> {
+    /// Create an empty string.
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Get the written content.
+    pub fn as_str(&self) -> &str {
+        written_str(&self.buf[..self.len])
+    }
+
+    /// Get the maximum number of bytes this can hold, which is `N`.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Get if a write was truncated. See [`WriteBuf::truncated`].
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Remove all the content and the truncation flag.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+
+    /// Add `suffix` to the end of the content, rolling back content to make room for it if needed. This is the same as
+    /// [`WriteBuf::finish_with`], but this is not consumed, so more can be written after the suffix.
+    ///
+    /// # Returns
+    ///
+    /// The content, in `Ok` if nothing was truncated or in `Err` if it was.
+    pub fn finish_with(&mut self, suffix: &str) -> Result<&str, &str> {
+        self.finish_with_or(suffix, suffix)
+    }
+
+    /// Add `normal_suffix` to the end of the content, or `truncated_suffix` if it was truncated. This is the same as
+    /// [`WriteBuf::finish_with_or`], but this is not consumed, so more can be written after the suffix.
+    ///
+    /// # Returns
+    ///
+    /// The content, in `Ok` if nothing was truncated or in `Err` if it was.
+    pub fn finish_with_or(&mut self, normal_suffix: &str, truncated_suffix: &str) -> Result<&str, &str> {
+        self._with_writer(|writer| {
+            writer._finish_with(normal_suffix, truncated_suffix);
+        });
+        if self.truncated {
+            Err(self.as_str())
+        } else {
+            Ok(self.as_str())
+        }
+    }
+
+    /// Run `f` with a [`WriteBuf`] over the array which picks up where the last write left off, then take back its state.
+    fn _with_writer<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut WriteBuf<'_>) -> R,
+    {
+        let mut writer = WriteBuf::new(&mut self.buf);
+        writer.position.set(self.len);
+        writer.content_truncated = self.truncated;
+        let result = f(&mut writer);
+        let (len, truncated) = (writer.position(), writer.truncated());
+        self.len = len;
+        self.truncated = truncated;
+        result
+    }
+}
+
+impl<const N: usize> Default for StackString<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ops::Deref for StackString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> AsRef<str> for StackString<N> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Write for StackString<N> {
+    /// Append `s`, truncating it in the same way as writing to a [`WriteBuf`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self._with_writer(|writer| fmt::Write::write_str(writer, s))
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self._with_writer(|writer| fmt::Write::write_char(writer, c))
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        self._with_writer(|writer| fmt::Write::write_fmt(writer, args))
+    }
+}
+
+impl<const N: usize> fmt::Display for StackString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for StackString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> PartialEq<str> for StackString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'s, const N: usize> PartialEq<&'s str> for StackString<N> {
+    fn eq(&self, other: &&'s str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<const N: usize> PartialEq<StackString<N>> for &str {
+    fn eq(&self, other: &StackString<N>) -> bool {
+        *self == other.as_str()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn writes_like_write_buf() {
+        let mut s = StackString::<6>::default();
+        assert_eq!(6, s.capacity());
+        s.write_str("ab").unwrap();
+        let c = 'c';
+        write!(s, "{}", c).unwrap();
+        s.write_char('🚀').unwrap_err();
+        assert_eq!("abc", s);
+        assert!(s.truncated());
+        // Truncation latches
+        s.write_char('d').unwrap_err();
+        assert_eq!(3, s.len());
+
+        s.clear();
+        assert_eq!("", s.as_str());
+        s.write_str("見🚀").unwrap_err();
+        assert_eq!("見", s);
+    }
+
+    #[test]
+    fn finish_does_not_consume() {
+        let mut s = StackString::<8>::new();
+        s.write_str("abc").unwrap();
+        assert_eq!(Ok("abc;"), s.finish_with(";"));
+        s.write_str("defgh").unwrap_err();
+        assert_eq!(Err("abc;d..."), s.finish_with_or("", "..."));
+
+        let copy = s.clone();
+        s.clear();
+        assert_eq!("abc;d...", copy);
+        assert!(copy.truncated());
+
+        let mut out = StackString::<16>::new();
+        write!(out, "[{}|{:?}]", copy, "x").unwrap();
+        assert_eq!(r#"[abc;d...|"x"]"#, out.as_str());
+    }
+}
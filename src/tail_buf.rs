@@ -0,0 +1,344 @@
+use core::fmt;
+
+use crate::written_str;
+
+/// A writer which keeps the last bytes of everything written to it, overwriting the oldest content once the target is
+/// full. This is useful for keeping the end of a log for a crash report, where the most recent output matters most.
+///
+/// ```
+/// use fmtbuf::{TailBuf, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 16] = [0xff; 16];
+/// let mut tail = TailBuf::new(&mut buf);
+/// for i in 0..100 {
+///     writeln!(tail, "line {i}").unwrap();
+/// }
+///
+/// let mut report: [u8; 64] = [0xff; 64];
+/// let mut report = WriteBuf::new(&mut report);
+/// write!(report, "showing last {} of {} bytes:", tail.retained_len(), tail.total_written()).unwrap();
+/// assert_eq!("showing last 16 of 790 bytes:", report.written());
+/// assert_eq!("line 98\nline 99\n", tail.finish());
+/// ```
+///
+/// Writing never fails, since there is always room for new content. The retained content can start in the middle of a
+/// multibyte UTF-8 sequence when the oldest bytes of it were overwritten, so the bytes of that partial sequence are
+/// left out of the content returned by [`TailBuf::make_contiguous`] and [`TailBuf::finish`], which is always valid UTF-8.
+pub struct TailBuf<'a> {
+    target: &'a mut [u8],
+    /// The index of `target` the next byte is written to, which is also the start of the oldest content once the target
+    /// is full.
+    head: usize,
+    /// The number of bytes of `target` which hold content.
+    len: usize,
+    total_written: u64,
+}
+
+impl<'a> TailBuf<'a> {
+    /// Create an instance that will keep the last `target.len()` bytes written to it. The contents of the target do not
+    /// matter, as they will be overwritten by writing.
+    pub fn new(target: &'a mut [u8]) -> Self {
+        Self {
+            target,
+            head: 0,
+            len: 0,
+            total_written: 0,
+        }
+    }
+
+    /// Get the number of bytes the target can retain.
+    pub fn capacity(&self) -> usize {
+        self.target.len()
+    }
+
+    /// Get the number of bytes which are retained, which is the smaller of [`TailBuf::total_written`] and
+    /// [`TailBuf::capacity`]. This counts the bytes of a partial UTF-8 sequence at the start of the retained content.
+    pub fn retained_len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the number of bytes ever written, including the ones which were overwritten.
+    pub fn total_written(&self) -> u64 {
+        self.total_written
+    }
+
+    /// Get if any written content was overwritten.
+    pub fn truncated(&self) -> bool {
+        self.total_written > self.len as u64
+    }
+
+    /// Remove all the content and reset [`TailBuf::total_written`].
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.total_written = 0;
+    }
+
+    /// Rearrange the target so the retained content starts at the beginning of it, then get the content. If the oldest
+    /// retained bytes are the end of a UTF-8 sequence whose start was overwritten, they are left out. Writing can
+    /// continue after this.
+    pub fn make_contiguous(&mut self) -> &str {
+        self._rotate_to_start();
+        written_str(utf8_start(&self.target[..self.len]))
+    }
+
+    /// Finish writing and get the retained content. This is the same as [`TailBuf::make_contiguous`], but the content
+    /// lives as long as the target.
+    pub fn finish(mut self) -> &'a str {
+        self._rotate_to_start();
+        written_str(utf8_start(&self.target[..self.len]))
+    }
+
+    /// Finish writing and get the retained content, starting with `prefix` if any written content was overwritten, such
+    /// as a `"…"` to show that the start of a file path was cut off.
+    ///
+    /// ```
+    /// use fmtbuf::TailBuf;
+    /// use std::fmt::Write;
+    ///
+    /// let mut buf: [u8; 26] = [0xff; 26];
+    /// let mut tail = TailBuf::new(&mut buf);
+    /// write!(tail, "{}/{}", "/home/build/src", "deeply/nested/file.txt").unwrap();
+    /// assert_eq!(Err("…/deeply/nested/file.txt"), tail.finish_with_prefix("…"));
+    /// ```
+    ///
+    /// The prefix takes the place of the oldest retained bytes, and if that leaves the rest of a multibyte UTF-8
+    /// sequence after it, those bytes are left out too. If `prefix` does not fit in the target, only as much of the
+    /// start of it as fits is kept, up to the last `char` boundary, and none of the content.
+    ///
+    /// Writing only ever copies the bytes being written, since the target is used as a ring buffer, so the cost of
+    /// keeping the end is paid once here: the retained content is rotated to the start of the target, which is O(n) in
+    /// the size of the target, the same as [`TailBuf::make_contiguous`].
+    ///
+    /// # Returns
+    ///
+    /// The `Ok` case is the retained content when nothing was overwritten, while `Err` is the content with `prefix` at
+    /// the start of it when something was.
+    pub fn finish_with_prefix(mut self, prefix: &str) -> Result<&'a str, &'a str> {
+        if !self.truncated() {
+            return Ok(self.finish());
+        }
+
+        self._rotate_to_start();
+        let target = self.target;
+        let len = self.len;
+        if prefix.len() > len {
+            let mut end = len;
+            while !prefix.is_char_boundary(end) {
+                end -= 1;
+            }
+            target[..end].copy_from_slice(&prefix.as_bytes()[..end]);
+            return Err(written_str(&target[..end]));
+        }
+
+        let start = len - utf8_start(&target[prefix.len()..len]).len();
+        let marked = &mut target[start - prefix.len()..len];
+        marked[..prefix.len()].copy_from_slice(prefix.as_bytes());
+        Err(written_str(marked))
+    }
+
+    /// Move the oldest retained byte to the start of the target.
+    fn _rotate_to_start(&mut self) {
+        if self.len == self.target.len() {
+            self.target.rotate_left(self.head);
+        }
+        // When the target is not full, the content already starts at 0
+        self.head = self.len % self.target.len().max(1);
+    }
+}
+
+impl<'a> fmt::Write for TailBuf<'a> {
+    /// Append `s`, overwriting the oldest content if there is not enough room. This never fails.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.total_written = self.total_written.saturating_add(s.len() as u64);
+        let capacity = self.target.len();
+        let mut input = s.as_bytes();
+        if capacity <= input.len() {
+            // All of the previous content is overwritten and only the end of `input` is retained
+            input = &input[input.len() - capacity..];
+            self.target.copy_from_slice(input);
+            self.head = 0;
+            self.len = capacity;
+            return Ok(());
+        }
+
+        // Copy up to the end of the target, then wrap around to the start for the rest
+        let (first, second) = input.split_at(input.len().min(capacity - self.head));
+        self.target[self.head..self.head + first.len()].copy_from_slice(first);
+        self.target[..second.len()].copy_from_slice(second);
+        self.head = (self.head + input.len()) % capacity;
+        self.len = (self.len + input.len()).min(capacity);
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Debug for TailBuf<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TailBuf")
+            .field("capacity", &self.capacity())
+            .field("retained_len", &self.len)
+            .field("total_written", &self.total_written)
+            .finish()
+    }
+}
+
+/// Skip the continuation bytes at the start of `bytes`, which are the rest of a sequence whose first byte is gone.
+fn utf8_start(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|cu| (cu & 0b1100_0000) != 0b1000_0000)
+        .unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn keeps_the_tail() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut tail = TailBuf::new(&mut buf);
+        assert_eq!("", tail.make_contiguous());
+        tail.write_str("abc").unwrap();
+        assert!(!tail.truncated());
+        assert_eq!("abc", tail.make_contiguous());
+        // Writing can continue after making the content contiguous
+        tail.write_str("defgh").unwrap();
+        assert_eq!("abcdefgh", tail.make_contiguous());
+        tail.write_str("ij").unwrap();
+        assert!(tail.truncated());
+        assert_eq!((8, 10), (tail.retained_len(), tail.total_written()));
+        assert_eq!("cdefghij", tail.make_contiguous());
+        tail.write_str("kl").unwrap();
+        tail.write_str("").unwrap();
+        assert_eq!("efghijkl", tail.finish());
+        assert_eq!(b"efghijkl", &buf);
+    }
+
+    #[test]
+    fn write_longer_than_target() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("ab").unwrap();
+        tail.write_str("0123456789").unwrap();
+        assert_eq!(12, tail.total_written());
+        assert_eq!("6789", tail.make_contiguous());
+
+        tail.write_str("x見").unwrap();
+        assert_eq!("x見", tail.make_contiguous());
+        // Only the last byte of the first "見" is retained, so it is left out
+        tail.write_str("見").unwrap();
+        assert_eq!(4, tail.retained_len());
+        assert_eq!("見", tail.make_contiguous());
+
+        let mut tail = TailBuf::new(&mut []);
+        tail.write_str("abc").unwrap();
+        assert_eq!(3, tail.total_written());
+        assert_eq!("", tail.finish());
+    }
+
+    #[test]
+    fn finish_with_prefix() {
+        let mut buf: [u8; 8] = [0xff; 8];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("abcdefgh").unwrap();
+        // Nothing was overwritten, so there is no prefix
+        assert_eq!(Ok("abcdefgh"), tail.finish_with_prefix("…"));
+
+        let mut buf: [u8; 10] = [0xff; 10];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("/src/").unwrap();
+        tail.write_str("main.rs").unwrap();
+        assert_eq!(Err("…main.rs"), tail.finish_with_prefix("…"));
+        assert_eq!(b"\xe2\x80\xa6main.rs", &buf[..]);
+    }
+
+    #[test]
+    fn finish_with_prefix_realigns() {
+        // The prefix covers the first byte of a "見", so the rest of it is left out
+        let mut buf: [u8; 9] = [0xff; 9];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("ab見見見").unwrap();
+        assert_eq!(Err("..見見"), tail.finish_with_prefix(".."));
+        // The prefix covers exactly the first "見"
+        let mut buf: [u8; 9] = [0xff; 9];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("ab見見見").unwrap();
+        assert_eq!(Err("…見見"), tail.finish_with_prefix("…"));
+        // The retained content already starts in the middle of a "見"
+        let mut buf: [u8; 5] = [0xff; 5];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("見見x").unwrap();
+        assert_eq!(Err(".見x"), tail.finish_with_prefix("."));
+    }
+
+    #[test]
+    fn finish_with_prefix_too_long() {
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("abcdef").unwrap();
+        assert_eq!(Err("[cut"), tail.finish_with_prefix("[cut]"));
+
+        let mut buf: [u8; 4] = [0xff; 4];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("abcdef").unwrap();
+        assert_eq!(Err("…"), tail.finish_with_prefix("……"));
+
+        let mut tail = TailBuf::new(&mut []);
+        tail.write_str("a").unwrap();
+        assert_eq!(Err(""), tail.finish_with_prefix("…"));
+    }
+
+    #[test]
+    fn multibyte_straddles_wrap_point() {
+        // '🚀' is 4 bytes and starts 1 byte before the end of the target, so it is split across the wrap
+        let mut buf: [u8; 7] = [0xff; 7];
+        let mut tail = TailBuf::new(&mut buf);
+        tail.write_str("abcdef").unwrap();
+        tail.write_str("🚀").unwrap();
+        assert_eq!((7, 10), (tail.retained_len(), tail.total_written()));
+        assert_eq!("def🚀", tail.make_contiguous());
+
+        tail.write_str("xy").unwrap();
+        assert_eq!("f🚀xy", tail.make_contiguous());
+        // The oldest retained bytes are the last 3 bytes of the 🚀, which are left out
+        tail.write_str("zw").unwrap();
+        assert_eq!(7, tail.retained_len());
+        assert_eq!("xyzw", tail.make_contiguous());
+    }
+
+    #[test]
+    fn utf8_start_after_overwrite() {
+        // Every offset of a multibyte sequence at the start of the retained content, with the wrap in a different place
+        for prefix_len in 0..=7 {
+            for start in 0..5 {
+                let mut buf: [u8; 5] = [0xff; 5];
+                let mut tail = TailBuf::new(&mut buf);
+                for _ in 0..start {
+                    tail.write_str("_").unwrap();
+                }
+                for c in "ab見xy🚀z".chars().take(prefix_len) {
+                    tail.write_char(c).unwrap();
+                }
+                let retained = tail.make_contiguous();
+                let retained_len = retained.len();
+                let written: &str = &"_____ab見xy🚀z"[5 - start..];
+                let written = &written[..written
+                    .char_indices()
+                    .nth(start + prefix_len)
+                    .map_or(written.len(), |(i, _)| i)];
+                assert!(
+                    written.ends_with(retained),
+                    "{:?} should end with {:?}",
+                    written,
+                    retained
+                );
+                // Only a partial sequence is left out
+                assert!(tail.retained_len() - retained_len < 4);
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use core::{mem::MaybeUninit, ops};
+
+/// Storage a [`WriteBuf`](crate::WriteBuf) writes into. All of the writing, truncation and finishing logic is written
+/// against this trait, so every kind of storage gets the same behavior.
+///
+/// This trait is sealed, so it can not be implemented outside of this crate. [`SliceTarget`], which every constructor
+/// of [`WriteBuf`](crate::WriteBuf) uses, is the only implementation.
+pub trait Target<'a>: sealed::Storage<'a> {}
+
+pub(crate) mod sealed {
+    use core::ops;
+
+    /// The operations a [`super::Target`] provides. The bytes at the start of the storage which have been written are
+    /// called initialized; only those are ever read.
+    pub trait Storage<'a> {
+        /// Get the number of bytes which can be stored.
+        fn capacity(&self) -> usize;
+
+        /// Get a pointer to the first byte.
+        fn as_ptr(&self) -> *const u8;
+
+        /// Get a pointer to the first byte, which can be written through.
+        fn as_mut_ptr(&mut self) -> *mut u8;
+
+        /// Copy `bytes` to `idx`, which must not be past the initialized bytes, so writing never leaves a gap.
+        fn write_at(&mut self, idx: usize, bytes: &[u8]);
+
+        /// Copy the initialized bytes in `src` to `dest`, like [`slice::copy_within`]. This can leave a gap between the
+        /// initialized bytes and `dest`, so the copied bytes are not counted as initialized until
+        /// [`Storage::assume_init`] is called once the gap is filled.
+        fn copy_within(&mut self, src: ops::Range<usize>, dest: usize);
+
+        /// Count the first `len` bytes as initialized.
+        ///
+        /// # Safety
+        ///
+        /// All of the first `len` bytes must have been written.
+        unsafe fn assume_init(&mut self, len: usize);
+
+        /// Get the first `len` bytes, which must be initialized.
+        fn read_back(&self, len: usize) -> &[u8];
+
+        /// Get the first `len` bytes, zeroing the ones which are not initialized yet.
+        fn read_back_mut(&mut self, len: usize) -> &mut [u8];
+
+        /// Get the first `len` bytes for the rest of `'a`. They must be initialized.
+        fn into_read_back(self, len: usize) -> &'a [u8];
+
+        /// Get the first `len` bytes, which must be initialized, and the rest of the storage, which is zeroed where it is
+        /// not initialized, for the rest of `'a`.
+        fn into_split(self, len: usize) -> (&'a [u8], &'a mut [u8]);
+    }
+}
+
+/// A [`Target`] over a borrowed slice of bytes, which do not need to be initialized. This is the storage of a
+/// [`WriteBuf`](crate::WriteBuf) made by any of its constructors.
+pub struct SliceTarget<'a> {
+    bytes: &'a mut [MaybeUninit<u8>],
+    /// The number of bytes at the start of `bytes` which are known to be initialized. This is all of `bytes` unless it
+    /// came from [`WriteBuf::new_uninit`](crate::WriteBuf::new_uninit), and it is never less than the position of the
+    /// writer.
+    init_len: usize,
+}
+
+impl<'a> SliceTarget<'a> {
+    pub(crate) fn new(bytes: &'a mut [u8]) -> Self {
+        let init_len = bytes.len();
+        // safety: `MaybeUninit<u8>` has the same layout as `u8` and only initialized bytes are ever written to `bytes`
+        let bytes = unsafe { &mut *(bytes as *mut [u8] as *mut [MaybeUninit<u8>]) };
+        Self { bytes, init_len }
+    }
+
+    pub(crate) fn new_uninit(bytes: &'a mut [MaybeUninit<u8>]) -> Self {
+        Self { bytes, init_len: 0 }
+    }
+
+    /// Split into the storage for `..idx` and for `idx..`.
+    pub(crate) fn split_at(self, idx: usize) -> (Self, Self) {
+        let (front, back) = self.bytes.split_at_mut(idx);
+        let front = Self {
+            bytes: front,
+            init_len: self.init_len.min(idx),
+        };
+        let back = Self {
+            bytes: back,
+            init_len: self.init_len.saturating_sub(idx),
+        };
+        (front, back)
+    }
+
+    /// Zero the bytes from the end of the initialized bytes up to `len`.
+    fn initialize_to(&mut self, len: usize) {
+        if let Some(uninit) = self.bytes.get_mut(self.init_len..len) {
+            for cu in uninit {
+                *cu = MaybeUninit::new(0);
+            }
+            self.init_len = len;
+        }
+    }
+}
+
+impl<'a> sealed::Storage<'a> for SliceTarget<'a> {
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        self.bytes.as_ptr() as *const u8
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.bytes.as_mut_ptr() as *mut u8
+    }
+
+    fn write_at(&mut self, idx: usize, bytes: &[u8]) {
+        debug_assert!(idx <= self.init_len, "write leaves a gap of uninitialized bytes");
+        let dest = &mut self.bytes[idx..idx + bytes.len()];
+        // safety: `dest` was bounds checked to hold `bytes.len()` bytes and does not overlap `bytes`, which is not
+        // borrowed from this storage. The copy is done through a pointer, so the bytes it replaces are never read.
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), dest.as_mut_ptr() as *mut u8, bytes.len()) };
+        self.init_len = self.init_len.max(idx + bytes.len());
+    }
+
+    fn copy_within(&mut self, src: ops::Range<usize>, dest: usize) {
+        debug_assert!(src.end <= self.init_len);
+        self.bytes.copy_within(src, dest);
+    }
+
+    unsafe fn assume_init(&mut self, len: usize) {
+        debug_assert!(len <= self.bytes.len());
+        self.init_len = self.init_len.max(len);
+    }
+
+    fn read_back(&self, len: usize) -> &[u8] {
+        assert!(len <= self.init_len, "read past the initialized bytes");
+        // safety: Just checked that all the bytes are initialized
+        unsafe { assume_init(&self.bytes[..len]) }
+    }
+
+    fn read_back_mut(&mut self, len: usize) -> &mut [u8] {
+        self.initialize_to(len);
+        // safety: All of the bytes were just initialized
+        unsafe { assume_init_mut(&mut self.bytes[..len]) }
+    }
+
+    fn into_read_back(self, len: usize) -> &'a [u8] {
+        assert!(len <= self.init_len, "read past the initialized bytes");
+        // safety: Just checked that all the bytes are initialized
+        unsafe { assume_init(&self.bytes[..len]) }
+    }
+
+    fn into_split(mut self, len: usize) -> (&'a [u8], &'a mut [u8]) {
+        assert!(len <= self.init_len, "read past the initialized bytes");
+        self.initialize_to(self.bytes.len());
+        // safety: All of the bytes were just initialized
+        let (front, back) = unsafe { assume_init_mut(self.bytes) }.split_at_mut(len);
+        (front, back)
+    }
+}
+
+impl<'a> Target<'a> for SliceTarget<'a> {}
+
+/// View initialized bytes as `u8`s. This is the same as the unstable `MaybeUninit::slice_assume_init_ref`.
+///
+/// # Safety
+///
+/// Every byte of `bytes` must be initialized.
+pub(crate) unsafe fn assume_init(bytes: &[MaybeUninit<u8>]) -> &[u8] {
+    &*(bytes as *const [MaybeUninit<u8>] as *const [u8])
+}
+
+/// View initialized bytes as `u8`s. This is the same as the unstable `MaybeUninit::slice_assume_init_mut`.
+///
+/// # Safety
+///
+/// Every byte of `bytes` must be initialized. Since the result can be written to, uninitialized bytes must never be
+/// written through it.
+unsafe fn assume_init_mut(bytes: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    &mut *(bytes as *mut [MaybeUninit<u8>] as *mut [u8])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sealed::Storage, SliceTarget};
+    use core::mem::MaybeUninit;
+
+    #[test]
+    fn slice_target_tracks_initialized_bytes() {
+        let mut bytes = [MaybeUninit::<u8>::uninit(); 8];
+        let mut target = SliceTarget::new_uninit(&mut bytes);
+        assert_eq!(8, target.capacity());
+        target.write_at(0, b"abc");
+        assert_eq!(b"abc", target.read_back(3));
+
+        // The bytes copied past the initialized ones only count once the gap is filled
+        target.copy_within(0..2, 4);
+        target.write_at(3, b"-");
+        // safety: `..3` and `3` were written, and `4..6` was copied from initialized bytes
+        unsafe { target.assume_init(6) };
+        assert_eq!(b"abc-ab", target.read_back(6));
+
+        let (front, back) = target.split_at(4);
+        assert_eq!(b"abc-", front.into_read_back(4));
+        let (written, rest) = back.into_split(2);
+        assert_eq!(b"ab", written);
+        assert_eq!(&[0, 0], rest);
+    }
+
+    #[test]
+    #[should_panic(expected = "read past the initialized bytes")]
+    fn slice_target_rejects_uninitialized_reads() {
+        let mut bytes = [MaybeUninit::<u8>::uninit(); 8];
+        let mut target = SliceTarget::new_uninit(&mut bytes);
+        target.write_at(0, b"ab");
+        let _ = target.read_back(3);
+    }
+}
@@ -0,0 +1,170 @@
+use core::fmt;
+
+use crate::WriteBuf;
+
+/// Writes the same output into two [`WriteBuf`]s at once, such as a UART buffer and a crash dump region.
+///
+/// ```
+/// use fmtbuf::{TeeBuf, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut uart: [u8; 8] = [0xff; 8];
+/// let mut dump: [u8; 32] = [0xff; 32];
+/// let mut tee = TeeBuf::new(WriteBuf::new(&mut uart), WriteBuf::new(&mut dump));
+/// write!(tee, "reset cause: {}", "watchdog").unwrap_err();
+/// assert!(tee.first_truncated());
+/// assert!(!tee.second_truncated());
+/// assert_eq!((Err(8), Ok(21)), tee.finish_both_with_or("", "…"));
+/// assert_eq!("reset…", std::str::from_utf8(&uart).unwrap());
+/// assert_eq!(b"reset cause: watchdog", &dump[..21]);
+/// ```
+///
+/// Each buffer truncates on its own, with its own `char` boundary logic, so when only one of them does not have room
+/// for a write, the other still gets all of it. A write returns `Err` if either buffer truncated it, and the state of
+/// each buffer can be checked with [`TeeBuf::first`] and [`TeeBuf::second`].
+pub struct TeeBuf<'a, 'b> {
+    first: WriteBuf<'a>,
+    second: WriteBuf<'b>,
+}
+
+impl<'a, 'b> TeeBuf<'a, 'b> {
+    /// Create an instance which writes to both `first` and `second`. Content already written to them is kept.
+    pub fn new(first: WriteBuf<'a>, second: WriteBuf<'b>) -> Self {
+        Self { first, second }
+    }
+
+    /// Get the first buffer.
+    pub fn first(&self) -> &WriteBuf<'a> {
+        &self.first
+    }
+
+    /// Get the second buffer.
+    pub fn second(&self) -> &WriteBuf<'b> {
+        &self.second
+    }
+
+    /// Get if a write to the first buffer was truncated. See [`WriteBuf::truncated`].
+    pub fn first_truncated(&self) -> bool {
+        self.first.truncated()
+    }
+
+    /// Get if a write to the second buffer was truncated. See [`WriteBuf::truncated`].
+    pub fn second_truncated(&self) -> bool {
+        self.second.truncated()
+    }
+
+    /// Get the two buffers back, to finish them separately.
+    pub fn into_inner(self) -> (WriteBuf<'a>, WriteBuf<'b>) {
+        (self.first, self.second)
+    }
+
+    /// Finish both buffers. See [`WriteBuf::finish`].
+    pub fn finish_both(self) -> (Result<usize, usize>, Result<usize, usize>) {
+        self.finish_both_with_or("", "")
+    }
+
+    /// Finish both buffers with `suffix`. See [`WriteBuf::finish_with`].
+    pub fn finish_both_with(self, suffix: &str) -> (Result<usize, usize>, Result<usize, usize>) {
+        self.finish_both_with_or(suffix, suffix)
+    }
+
+    /// Finish both buffers with `normal_suffix` or `truncated_suffix`. Each buffer picks its suffix by whether it was
+    /// truncated itself, so one can get the normal suffix while the other gets the truncated one. See
+    /// [`WriteBuf::finish_with_or`].
+    ///
+    /// # Returns
+    ///
+    /// The result of finishing the first buffer and the result of finishing the second.
+    pub fn finish_both_with_or(
+        self,
+        normal_suffix: &str,
+        truncated_suffix: &str,
+    ) -> (Result<usize, usize>, Result<usize, usize>) {
+        (
+            self.first.finish_with_or(normal_suffix, truncated_suffix),
+            self.second.finish_with_or(normal_suffix, truncated_suffix),
+        )
+    }
+}
+
+impl<'a, 'b> fmt::Write for TeeBuf<'a, 'b> {
+    /// Append `s` to both buffers. Both are always written to, even if the first one truncates `s`.
+    ///
+    /// # Error
+    ///
+    /// If either buffer did not have room for all of `s`.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let first = self.first.write_str(s);
+        let second = self.second.write_str(s);
+        first.and(second)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        let first = self.first.write_char(c);
+        let second = self.second.write_char(c);
+        first.and(second)
+    }
+}
+
+impl<'a, 'b> fmt::Debug for TeeBuf<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TeeBuf")
+            .field("first", &self.first)
+            .field("second", &self.second)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn asymmetric_truncation() {
+        let mut first: [u8; 5] = [0xff; 5];
+        let mut second: [u8; 8] = [0xff; 8];
+        let mut tee = TeeBuf::new(WriteBuf::new(&mut first), WriteBuf::new(&mut second));
+        // The 🚀 does not fit in the 3 bytes left in the first buffer, but the second has room for all of it
+        tee.write_str("ab🚀").unwrap_err();
+        assert_eq!("ab", tee.first().written());
+        assert_eq!("ab🚀", tee.second().written());
+        assert!(tee.first_truncated());
+        assert!(!tee.second_truncated());
+
+        // The first buffer latched, so only the second takes later writes
+        tee.write_char('c').unwrap_err();
+        assert_eq!("ab", tee.first().written());
+        assert_eq!("ab🚀c", tee.second().written());
+        tee.write_str("見").unwrap_err();
+        assert!(tee.second_truncated());
+        assert_eq!((Err(2), Err(7)), tee.finish_both());
+    }
+
+    #[test]
+    fn each_buffer_places_its_own_suffix() {
+        let mut first: [u8; 16] = [0xff; 16];
+        let mut second: [u8; 5] = [0xff; 5];
+        let mut tee = TeeBuf::new(WriteBuf::new(&mut first), WriteBuf::new(&mut second));
+        let c = '見';
+        write!(tee, "見{}", c).unwrap_err();
+        // The second buffer cut the second "見", then rolls back the first one to make room for "…"
+        assert_eq!((Ok(7), Err(3)), tee.finish_both_with_or(".", "…"));
+        assert_eq!("見見.", core::str::from_utf8(&first[..7]).unwrap());
+        assert_eq!("…", core::str::from_utf8(&second[..3]).unwrap());
+
+        // Content already in the buffers is kept, and each can still be finished on its own
+        let mut first: [u8; 8] = [0xff; 8];
+        let mut second: [u8; 8] = [0xff; 8];
+        let mut first_writer = WriteBuf::new(&mut first);
+        first_writer.write_str("1:").unwrap();
+        let mut tee = TeeBuf::new(first_writer, WriteBuf::new(&mut second));
+        tee.write_str("x").unwrap();
+        let (first_writer, second_writer) = tee.into_inner();
+        assert_eq!(Ok("1:x"), first_writer.finish_str());
+        assert_eq!(Ok("x!"), second_writer.finish_with_str("!"));
+
+        let tee = TeeBuf::new(WriteBuf::new(&mut first), WriteBuf::new(&mut second));
+        assert_eq!((Ok(1), Ok(1)), tee.finish_both_with(";"));
+    }
+}
@@ -0,0 +1,323 @@
+//! Writing a Unix timestamp as an RFC 3339 date and time, used by [`crate::WriteBuf::write_rfc3339`].
+
+use crate::int;
+
+/// How [`crate::WriteBuf::write_rfc3339`] writes a timestamp. The default has milliseconds and is in UTC, such as
+/// `2024-05-17T13:45:12.345Z`.
+///
+/// ```
+/// use fmtbuf::{TimestampOpts, WriteBuf};
+/// use std::fmt::Write;
+///
+/// const LOCAL: TimestampOpts = TimestampOpts::new().subsec_digits(0).utc_offset(-7 * 60);
+///
+/// let mut buf: [u8; 64] = [0xff; 64];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// writer.write_rfc3339(1_715_953_512, 0, LOCAL).unwrap();
+/// writer.write_str(" ").unwrap();
+/// writer.write_rfc3339(1_715_953_512, 0, TimestampOpts::new().subsec_digits(0).basic(true)).unwrap();
+/// assert_eq!("2024-05-17T06:45:12-07:00 20240517T134512Z", writer.written());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimestampOpts {
+    subsec_digits: usize,
+    offset_minutes: i32,
+    basic: bool,
+}
+
+impl TimestampOpts {
+    /// The largest UTC offset, in minutes either way, which is just under a day.
+    pub const MAX_OFFSET: i32 = 24 * 60 - 1;
+
+    /// Create options for milliseconds in UTC in the extended format, with `-` and `:` between the fields.
+    pub const fn new() -> Self {
+        Self {
+            subsec_digits: 3,
+            offset_minutes: 0,
+            basic: false,
+        }
+    }
+
+    /// Set the digits after the decimal point of the seconds, which is 0, 3, 6 or 9. Anything else is lowered to the
+    /// one below it, so 4 is 3 and 12 is 9. The digits are cut instead of rounded, so a timestamp is never moved into
+    /// the next second. With 0, there is no decimal point either.
+    pub const fn subsec_digits(mut self, digits: usize) -> Self {
+        self.subsec_digits = if digits > 9 { 9 } else { digits - digits % 3 };
+        self
+    }
+
+    /// Set the fixed offset from UTC in minutes, such as `330` for `+05:30`, which is clamped to
+    /// [`TimestampOpts::MAX_OFFSET`] either way. The time is written as the local time of that offset. An offset of 0
+    /// is written as `Z`.
+    pub const fn utc_offset(mut self, minutes: i32) -> Self {
+        self.offset_minutes = if minutes > Self::MAX_OFFSET {
+            Self::MAX_OFFSET
+        } else if minutes < -Self::MAX_OFFSET {
+            -Self::MAX_OFFSET
+        } else {
+            minutes
+        };
+        self
+    }
+
+    /// Set if the timestamp is in the ISO 8601 basic format, such as `20240517T134512Z`, which has no `-` or `:` between
+    /// the fields.
+    pub const fn basic(mut self, basic: bool) -> Self {
+        self.basic = basic;
+        self
+    }
+}
+
+impl Default for TimestampOpts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// A timestamp split up into the fields of a date and time.
+pub(crate) struct Timestamp {
+    year: i64,
+    month: u32,
+    day: u32,
+    /// The seconds since midnight.
+    time: u32,
+    nanos: u32,
+    opts: TimestampOpts,
+}
+
+impl Timestamp {
+    /// Split up `unix_secs` and `subsec_nanos`, which is treated as 999,999,999 if it is more than that, at the offset of
+    /// `opts`.
+    pub(crate) fn new(unix_secs: i64, subsec_nanos: u32, opts: TimestampOpts) -> Self {
+        // The offset is applied to the time of day instead of the seconds, which can not overflow
+        let mut days = unix_secs.div_euclid(SECS_PER_DAY);
+        let mut time = unix_secs.rem_euclid(SECS_PER_DAY) + i64::from(opts.offset_minutes) * 60;
+        if time < 0 {
+            days -= 1;
+            time += SECS_PER_DAY;
+        } else if time >= SECS_PER_DAY {
+            days += 1;
+            time -= SECS_PER_DAY;
+        }
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            time: time as u32,
+            nanos: subsec_nanos.min(999_999_999),
+            opts,
+        }
+    }
+
+    /// Get the number of bytes written.
+    pub(crate) fn len(&self) -> usize {
+        let separators = if self.opts.basic { 0 } else { 4 };
+        let subsec = if self.opts.subsec_digits == 0 {
+            0
+        } else {
+            1 + self.opts.subsec_digits
+        };
+        let offset = match (self.opts.offset_minutes, self.opts.basic) {
+            (0, _) => 1,
+            (_, true) => 5,
+            (_, false) => 6,
+        };
+        self.year_len() + 4 + 1 + 6 + separators + subsec + offset
+    }
+
+    /// Pass the output to `out` in pieces. The pieces add up to [`Timestamp::len`] bytes.
+    pub(crate) fn render(&self, mut out: impl FnMut(&[u8])) {
+        let (date_separator, time_separator): (&[u8], &[u8]) = if self.opts.basic { (b"", b"") } else { (b"-", b":") };
+
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        let year = int::decimal_u64(self.year.unsigned_abs(), false, &mut scratch);
+        if !(0..=9999).contains(&self.year) {
+            out(if self.year < 0 { b"-" } else { b"+" });
+        }
+        out(&b"000"[..4usize.saturating_sub(year.len())]);
+        out(year);
+        out(date_separator);
+        padded(self.month, 2, &mut out);
+        out(date_separator);
+        padded(self.day, 2, &mut out);
+
+        out(b"T");
+        padded(self.time / 3600, 2, &mut out);
+        out(time_separator);
+        padded(self.time / 60 % 60, 2, &mut out);
+        out(time_separator);
+        padded(self.time % 60, 2, &mut out);
+        let digits = self.opts.subsec_digits;
+        if digits > 0 {
+            out(b".");
+            padded(self.nanos / 10u32.pow(9 - digits as u32), digits, &mut out);
+        }
+
+        let offset = self.opts.offset_minutes;
+        if offset == 0 {
+            out(b"Z");
+        } else {
+            out(if offset < 0 { b"-" } else { b"+" });
+            let offset = offset.unsigned_abs();
+            padded(offset / 60, 2, &mut out);
+            out(time_separator);
+            padded(offset % 60, 2, &mut out);
+        }
+    }
+
+    /// Get the number of bytes of the year. RFC 3339 only has years 0000 to 9999, so the rest are written like the
+    /// expanded years of ISO 8601, with a sign and at least 4 digits.
+    fn year_len(&self) -> usize {
+        if (0..=9999).contains(&self.year) {
+            return 4;
+        }
+        let mut scratch = [0u8; int::MAX_DECIMAL_LEN];
+        1 + int::decimal_u64(self.year.unsigned_abs(), false, &mut scratch)
+            .len()
+            .max(4)
+    }
+}
+
+/// Pass `value` to `out` with leading zeros to `width` digits, which is at most 9.
+fn padded(value: u32, width: usize, out: &mut impl FnMut(&[u8])) {
+    let mut digits = [b'0'; 9];
+    int::digits_u64(u64::from(value), &mut digits[..width]);
+    out(&digits[..width]);
+}
+
+/// Get the year, month and day of the proleptic Gregorian calendar which is `days` after 1970-01-01. This is
+/// `civil_from_days` of Howard Hinnant's date algorithms, which works on 400-year eras starting on March 1st, so the
+/// leap day is at the end of each year.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+    use core::fmt::Write;
+
+    fn rfc3339(unix_secs: i64, subsec_nanos: u32, opts: TimestampOpts) -> StackString<64> {
+        let timestamp = Timestamp::new(unix_secs, subsec_nanos, opts);
+        let mut out: StackString<64> = StackString::new();
+        timestamp.render(|piece| {
+            out.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+        });
+        assert_eq!(timestamp.len(), out.as_str().len(), "unix_secs={}", unix_secs);
+        out
+    }
+
+    #[test]
+    fn known_timestamps() {
+        let cases: &[(i64, &str)] = &[
+            (0, "1970-01-01T00:00:00Z"),
+            (-1, "1969-12-31T23:59:59Z"),
+            (-14_182_940, "1969-07-20T20:17:40Z"),
+            (1_715_953_512, "2024-05-17T13:45:12Z"),
+            // Leap days, including 2000, which is a leap year for being divisible by 400
+            (1_709_251_199, "2024-02-29T23:59:59Z"),
+            (1_709_251_200, "2024-03-01T00:00:00Z"),
+            (951_825_600, "2000-02-29T12:00:00Z"),
+            // 1900 and 2100 are not leap years
+            (-2_203_891_201, "1900-02-28T23:59:59Z"),
+            (-2_203_891_200, "1900-03-01T00:00:00Z"),
+            (4_107_542_399, "2100-02-28T23:59:59Z"),
+            (4_107_542_400, "2100-03-01T00:00:00Z"),
+            // The end of a signed 32-bit time_t
+            (2_147_483_647, "2038-01-19T03:14:07Z"),
+            (2_147_483_648, "2038-01-19T03:14:08Z"),
+            (-62_135_596_800, "0001-01-01T00:00:00Z"),
+            (253_402_300_799, "9999-12-31T23:59:59Z"),
+            // Years outside of RFC 3339
+            (253_402_300_800, "+10000-01-01T00:00:00Z"),
+            (-62_167_219_201, "-0001-12-31T23:59:59Z"),
+            (i64::MAX, "+292277026596-12-04T15:30:07Z"),
+            (i64::MIN, "-292277022657-01-27T08:29:52Z"),
+        ];
+        for (unix_secs, expected) in cases {
+            assert_eq!(
+                *expected,
+                rfc3339(*unix_secs, 0, TimestampOpts::new().subsec_digits(0)),
+                "unix_secs={}",
+                unix_secs
+            );
+        }
+    }
+
+    #[test]
+    fn subsec_digits() {
+        let opts = TimestampOpts::new();
+        assert_eq!("2024-05-17T13:45:12.345Z", rfc3339(1_715_953_512, 345_678_901, opts));
+        assert_eq!("2024-05-17T13:45:12.000Z", rfc3339(1_715_953_512, 0, opts));
+        assert_eq!(
+            "2024-05-17T13:45:12.345678Z",
+            rfc3339(1_715_953_512, 345_678_901, opts.subsec_digits(6))
+        );
+        assert_eq!(
+            "2024-05-17T13:45:12.345678901Z",
+            rfc3339(1_715_953_512, 345_678_901, opts.subsec_digits(9))
+        );
+        // Digits are cut, not rounded into the next second
+        assert_eq!("1969-12-31T23:59:59.999Z", rfc3339(-1, 999_999_999, opts));
+        assert_eq!(
+            "1969-12-31T23:59:59.999999999Z",
+            rfc3339(-1, 2_000_000_000, opts.subsec_digits(9))
+        );
+        assert_eq!(opts.subsec_digits(3), opts.subsec_digits(5));
+        assert_eq!(opts.subsec_digits(9), opts.subsec_digits(100));
+        assert_eq!(TimestampOpts::new(), TimestampOpts::default());
+    }
+
+    #[test]
+    fn offsets() {
+        let opts = TimestampOpts::new().subsec_digits(0);
+        assert_eq!(
+            "2024-05-17T19:15:12+05:30",
+            rfc3339(1_715_953_512, 0, opts.utc_offset(330))
+        );
+        // Offsets move the date across midnight, and the year across its end
+        assert_eq!(
+            "2024-05-16T23:45:12-14:00",
+            rfc3339(1_715_953_512, 0, opts.utc_offset(-14 * 60))
+        );
+        assert_eq!("1970-01-01T08:00:00+08:00", rfc3339(0, 0, opts.utc_offset(8 * 60)));
+        assert_eq!("1969-12-31T19:00:00-05:00", rfc3339(0, 0, opts.utc_offset(-5 * 60)));
+        assert_eq!("1970-01-01T23:59:00+23:59", rfc3339(0, 0, opts.utc_offset(i32::MAX)));
+        assert_eq!(
+            "+292277026596-12-05T15:29:07+23:59",
+            rfc3339(i64::MAX, 0, opts.utc_offset(i32::MAX))
+        );
+        assert_eq!(
+            "-292277022657-01-26T08:30:52-23:59",
+            rfc3339(i64::MIN, 0, opts.utc_offset(i32::MIN))
+        );
+    }
+
+    #[test]
+    fn basic() {
+        let opts = TimestampOpts::new().subsec_digits(0).basic(true);
+        assert_eq!("20240517T134512Z", rfc3339(1_715_953_512, 0, opts));
+        assert_eq!("20240517T191512+0530", rfc3339(1_715_953_512, 0, opts.utc_offset(330)));
+        assert_eq!(
+            "20240517T134512.345Z",
+            rfc3339(1_715_953_512, 345_000_000, opts.subsec_digits(3))
+        );
+    }
+}
@@ -31,6 +31,48 @@ pub const fn utf8_char_width(code_unit: u8) -> Option<usize> {
     }
 }
 
+/// Decode the last code point of `buf`, which must be valid UTF-8. Returns `None` if `buf` is empty.
+///
+/// # Returns
+///
+/// The decoded character and the number of code units it occupies at the end of `buf`.
+pub fn last_char(buf: &[u8]) -> Option<(char, usize)> {
+    // Scan backwards past continuation code units to find the start of the last sequence
+    let mut start = buf.len();
+    loop {
+        start = start.checked_sub(1)?;
+        if utf8_char_width(buf[start]).is_some() {
+            break;
+        }
+    }
+
+    let width = buf.len() - start;
+    let mut code_point = match width {
+        1 => return Some((buf[start] as char, 1)),
+        2 => u32::from(buf[start] & 0x1f),
+        3 => u32::from(buf[start] & 0x0f),
+        _ => u32::from(buf[start] & 0x07),
+    };
+    for cu in &buf[start + 1..] {
+        code_point = (code_point << 6) | u32::from(cu & 0x3f);
+    }
+    core::char::from_u32(code_point).map(|c| (c, width))
+}
+
+/// Get the largest `char` boundary of `buf` which is not past `idx`, where `buf` is valid UTF-8. This looks at no more
+/// than 3 code units, since that is the most continuation code units a sequence can have.
+pub fn floor_char_boundary(buf: &[u8], idx: usize) -> usize {
+    if idx >= buf.len() {
+        return buf.len();
+    }
+    let lowest = idx.saturating_sub(3);
+    let mut idx = idx;
+    while idx > lowest && utf8_char_width(buf[idx]).is_none() {
+        idx -= 1;
+    }
+    idx
+}
+
 /// Find the end of the last valid UTF-8 code point.
 ///
 /// # Deprecated
@@ -64,6 +106,39 @@ pub fn rfind_utf8_end(buf: &[u8]) -> usize {
     position
 }
 
+/// Ranges of code points which combine with the character before them. This is an approximation of the nonspacing and
+/// enclosing mark (Mn and Me) general categories, covering the blocks of marks used with common scripts rather than
+/// every mark in Unicode.
+const COMBINING_MARKS: [(char, char); 12] = [
+    ('\u{0300}', '\u{036F}'), // Combining Diacritical Marks
+    ('\u{0483}', '\u{0489}'), // Cyrillic
+    ('\u{0591}', '\u{05BD}'), // Hebrew cantillation and points
+    ('\u{05BF}', '\u{05C7}'), // Hebrew points
+    ('\u{0610}', '\u{061A}'), // Arabic
+    ('\u{064B}', '\u{065F}'), // Arabic harakat
+    ('\u{0670}', '\u{0670}'), // Arabic superscript alef
+    ('\u{1AB0}', '\u{1AFF}'), // Combining Diacritical Marks Extended
+    ('\u{1DC0}', '\u{1DFF}'), // Combining Diacritical Marks Supplement
+    ('\u{20D0}', '\u{20FF}'), // Combining Diacritical Marks for Symbols
+    ('\u{3099}', '\u{309A}'), // Kana voiced sound marks
+    ('\u{FE20}', '\u{FE2F}'), // Combining Half Marks
+];
+
+/// Check if `c` is a combining mark, which is drawn on top of the character before it. See [`COMBINING_MARKS`] for
+/// which marks are covered.
+pub fn is_combining_mark(c: char) -> bool {
+    // Every mark in the table is past ASCII and Latin-1, which covers most text
+    if c < '\u{0300}' {
+        return false;
+    }
+    COMBINING_MARKS.iter().any(|(low, high)| (*low..=*high).contains(&c))
+}
+
+/// Check if `c` is a regional indicator symbol, two of which make up a flag emoji.
+pub fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -75,4 +150,45 @@ mod test {
         assert_eq!(rfind_utf8_end(b"\xf0\x9f\x9a\x80"), 4); // "🚀" with the bytes written out
         assert_eq!(rfind_utf8_end(b"\xf0\x9f\x9a"), 0); // "🚀" but missing the last byte
     }
+
+    #[test]
+    fn floor_char_boundary_examples() {
+        let rocket = "a🚀b".as_bytes();
+        assert_eq!(floor_char_boundary(rocket, 0), 0);
+        assert_eq!(floor_char_boundary(rocket, 1), 1);
+        assert_eq!(floor_char_boundary(rocket, 2), 1);
+        assert_eq!(floor_char_boundary(rocket, 4), 1);
+        assert_eq!(floor_char_boundary(rocket, 5), 5);
+        assert_eq!(floor_char_boundary(rocket, 6), 6);
+        assert_eq!(floor_char_boundary(rocket, 100), 6);
+        assert_eq!(floor_char_boundary("见/見".as_bytes(), 2), 0);
+    }
+
+    #[test]
+    fn last_char_examples() {
+        assert_eq!(last_char(b""), None);
+        assert_eq!(last_char("1234".as_bytes()), Some(('4', 1)));
+        assert_eq!(last_char("_ø".as_bytes()), Some(('ø', 2)));
+        assert_eq!(last_char("见/見".as_bytes()), Some(('見', 3)));
+        assert_eq!(last_char("rocket: 🚀".as_bytes()), Some(('🚀', 4)));
+        assert_eq!(last_char("\u{7f}".as_bytes()), Some(('\u{7f}', 1)));
+        assert_eq!(last_char("\u{10ffff}".as_bytes()), Some(('\u{10ffff}', 4)));
+    }
+
+    #[test]
+    fn is_combining_mark_examples() {
+        // A decomposed "é" is an "e" followed by a mark, while a composed one is a single letter
+        let mut decomposed = "e\u{301}".chars();
+        assert!(!is_combining_mark(decomposed.next().unwrap()));
+        assert!(is_combining_mark(decomposed.next().unwrap()));
+        assert!(!is_combining_mark('é'));
+        // Hangul jamo combine into syllables, but they are letters, not marks
+        for c in "\u{1100}\u{1161}\u{11A8}".chars() {
+            assert!(!is_combining_mark(c));
+        }
+        assert!(is_combining_mark('\u{20DD}'));
+        assert!(is_combining_mark('\u{3099}'));
+        assert!(!is_combining_mark('a'));
+        assert!(!is_combining_mark('\u{02FF}'));
+    }
 }
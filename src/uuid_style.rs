@@ -0,0 +1,170 @@
+//! Writing a UUID from its 16 bytes, used by [`crate::WriteBuf::write_uuid`].
+
+/// How [`crate::WriteBuf::write_uuid`] writes a UUID: the hyphens, what goes around it and the case of the hexadecimal
+/// digits. The default is [`UuidStyle::HYPHENATED`].
+///
+/// ```
+/// use fmtbuf::{UuidStyle, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let uuid = [
+///     0x67, 0xe5, 0x50, 0x44, 0x10, 0xb1, 0x42, 0x6f, 0x92, 0x47, 0xbb, 0x68, 0x0e, 0x5f, 0xe0, 0xc8,
+/// ];
+/// let mut buf: [u8; 96] = [0xff; 96];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// writer.write_uuid(&uuid, UuidStyle::BRACED.uppercase(true)).unwrap();
+/// writer.write_str(" ").unwrap();
+/// writer.write_uuid(&uuid, UuidStyle::URN).unwrap();
+/// assert_eq!(
+///     "{67E55044-10B1-426F-9247-BB680E5FE0C8} urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8",
+///     writer.written()
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UuidStyle {
+    hyphens: bool,
+    /// The text before the UUID and after it.
+    around: (&'static str, &'static str),
+    uppercase: bool,
+}
+
+impl UuidStyle {
+    /// Groups of 8, 4, 4, 4 and 12 digits with hyphens between them, such as `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    pub const HYPHENATED: Self = Self {
+        hyphens: true,
+        around: ("", ""),
+        uppercase: false,
+    };
+
+    /// The 32 digits without hyphens, such as `67e5504410b1426f9247bb680e5fe0c8`.
+    pub const SIMPLE: Self = Self {
+        hyphens: false,
+        around: ("", ""),
+        uppercase: false,
+    };
+
+    /// [`UuidStyle::HYPHENATED`] in braces, such as `{67e55044-10b1-426f-9247-bb680e5fe0c8}`, as used for GUIDs.
+    pub const BRACED: Self = Self {
+        hyphens: true,
+        around: ("{", "}"),
+        uppercase: false,
+    };
+
+    /// [`UuidStyle::HYPHENATED`] as a URN, such as `urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    pub const URN: Self = Self {
+        hyphens: true,
+        around: ("urn:uuid:", ""),
+        uppercase: false,
+    };
+
+    /// Set if the hexadecimal digits above 9 are written as `A` to `F` instead of `a` to `f`. The `urn:uuid:` of
+    /// [`UuidStyle::URN`] is always lowercase.
+    pub const fn uppercase(mut self, uppercase: bool) -> Self {
+        self.uppercase = uppercase;
+        self
+    }
+
+    /// Get the number of bytes a UUID takes in this style.
+    pub(crate) fn len(&self) -> usize {
+        let hyphens = if self.hyphens { 4 } else { 0 };
+        self.around.0.len() + 32 + hyphens + self.around.1.len()
+    }
+
+    /// Pass the text of `bytes` to `out` in pieces. The pieces add up to [`UuidStyle::len`] bytes.
+    pub(crate) fn render(&self, bytes: &[u8; 16], mut out: impl FnMut(&[u8])) {
+        let digit_chars: &[u8; 16] = if self.uppercase {
+            b"0123456789ABCDEF"
+        } else {
+            b"0123456789abcdef"
+        };
+        let mut scratch = [0u8; 36];
+        let mut used = 0;
+        for (idx, byte) in bytes.iter().enumerate() {
+            // The groups end after bytes 4, 6, 8 and 10
+            if self.hyphens && matches!(idx, 4 | 6 | 8 | 10) {
+                scratch[used] = b'-';
+                used += 1;
+            }
+            scratch[used] = digit_chars[usize::from(byte >> 4)];
+            scratch[used + 1] = digit_chars[usize::from(byte & 0xf)];
+            used += 2;
+        }
+        out(self.around.0.as_bytes());
+        out(&scratch[..used]);
+        out(self.around.1.as_bytes());
+    }
+}
+
+impl Default for UuidStyle {
+    fn default() -> Self {
+        Self::HYPHENATED
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+    use core::fmt::Write;
+
+    /// The example UUID of RFC 4122 section 3.
+    const RFC_4122: [u8; 16] = [
+        0xf8, 0x1d, 0x4f, 0xae, 0x7d, 0xec, 0x11, 0xd0, 0xa7, 0x65, 0x00, 0xa0, 0xc9, 0x1e, 0x6b, 0xf6,
+    ];
+
+    fn uuid(bytes: &[u8; 16], style: UuidStyle) -> StackString<64> {
+        let mut out: StackString<64> = StackString::new();
+        style.render(bytes, |piece| {
+            out.write_str(core::str::from_utf8(piece).unwrap()).unwrap();
+        });
+        assert_eq!(style.len(), out.as_str().len(), "style={:?}", style);
+        out
+    }
+
+    /// Parse the hexadecimal digits of `text`, skipping everything else, to check the bytes make it through.
+    fn parse(text: &str) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        let digits = text
+            .trim_start_matches("urn:uuid:")
+            .chars()
+            .filter_map(|c| c.to_digit(16));
+        for (idx, digit) in digits.enumerate() {
+            bytes[idx / 2] |= (digit as u8) << if idx % 2 == 0 { 4 } else { 0 };
+        }
+        bytes
+    }
+
+    #[test]
+    fn styles() {
+        let cases: &[(UuidStyle, &str)] = &[
+            (UuidStyle::HYPHENATED, "f81d4fae-7dec-11d0-a765-00a0c91e6bf6"),
+            (
+                UuidStyle::HYPHENATED.uppercase(true),
+                "F81D4FAE-7DEC-11D0-A765-00A0C91E6BF6",
+            ),
+            (UuidStyle::SIMPLE, "f81d4fae7dec11d0a76500a0c91e6bf6"),
+            (UuidStyle::SIMPLE.uppercase(true), "F81D4FAE7DEC11D0A76500A0C91E6BF6"),
+            (UuidStyle::BRACED, "{f81d4fae-7dec-11d0-a765-00a0c91e6bf6}"),
+            (UuidStyle::URN, "urn:uuid:f81d4fae-7dec-11d0-a765-00a0c91e6bf6"),
+            (
+                UuidStyle::URN.uppercase(true),
+                "urn:uuid:F81D4FAE-7DEC-11D0-A765-00A0C91E6BF6",
+            ),
+        ];
+        for (style, expected) in cases {
+            let text = uuid(&RFC_4122, *style);
+            assert_eq!(*expected, text, "style={:?}", style);
+            assert_eq!(RFC_4122, parse(text.as_str()), "style={:?}", style);
+        }
+        assert_eq!(UuidStyle::HYPHENATED, UuidStyle::default());
+    }
+
+    #[test]
+    fn nil_and_max() {
+        assert_eq!(
+            "00000000-0000-0000-0000-000000000000",
+            uuid(&[0; 16], UuidStyle::HYPHENATED)
+        );
+        assert_eq!("ffffffffffffffffffffffffffffffff", uuid(&[0xff; 16], UuidStyle::SIMPLE));
+    }
+}
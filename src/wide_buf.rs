@@ -0,0 +1,331 @@
+use core::fmt;
+
+/// A write buffer pointing to a `&mut [u16]`, which encodes written text as UTF-16. This is for the `WCHAR` arrays of
+/// Windows and UEFI APIs, so text does not need to be formatted into a byte buffer and transcoded.
+///
+/// ```
+/// use fmtbuf::WideBuf;
+/// use std::fmt::Write;
+///
+/// // Keep one code unit free for the NUL terminator
+/// let mut buf: [u16; 8] = [0xffff; 8];
+/// let mut writer = WideBuf::with_reserve(&mut buf, 1);
+/// write!(writer, "COM{}", 3).unwrap();
+/// assert_eq!(Ok(5), writer.finish_with("\0"));
+/// assert_eq!(&"COM3\0".encode_utf16().collect::<Vec<_>>(), &buf[..5]);
+/// ```
+///
+/// This truncates in the same way as a [`crate::WriteBuf`], except that the unit of everything is a UTF-16 code unit
+/// instead of a byte: a character is never split, so a surrogate pair is either written whole or not at all, and the
+/// positions and lengths are counts of code units.
+pub struct WideBuf<'a> {
+    target: &'a mut [u16],
+    position: usize,
+    reserve: usize,
+    truncated: bool,
+}
+
+impl<'a> WideBuf<'a> {
+    /// Create an instance that will write to the given `target`. The contents of the target do not matter, as they will
+    /// be overwritten by writing.
+    pub fn new(target: &'a mut [u16]) -> Self {
+        Self::with_reserve(target, 0)
+    }
+
+    /// Create an instance that will write to the given `target` and `reserve` code units at the end that will not be
+    /// written by `write_str` operations. See [`crate::WriteBuf::with_reserve`].
+    pub fn with_reserve(target: &'a mut [u16], reserve: usize) -> Self {
+        Self {
+            target,
+            position: 0,
+            reserve,
+            truncated: false,
+        }
+    }
+
+    /// Get the number of code units the target can hold, including the reserve.
+    pub fn capacity(&self) -> usize {
+        self.target.len()
+    }
+
+    /// Get the number of code units written so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Get the number of code units which can be written before reaching the reserve.
+    pub fn remaining(&self) -> usize {
+        self.target
+            .len()
+            .saturating_sub(self.position)
+            .saturating_sub(self.reserve)
+    }
+
+    /// Get the number of code units at the end of the target which are not written by `write_str`.
+    pub fn reserve(&self) -> usize {
+        self.reserve
+    }
+
+    /// Get if a write was truncated. See [`crate::WriteBuf::truncated`].
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Get the code units that have been written so far.
+    pub fn written(&self) -> &[u16] {
+        &self.target[..self.position]
+    }
+
+    /// Finish the buffer, returning the number of code units written in `Ok` if nothing was truncated or in `Err` if
+    /// it was.
+    pub fn finish(self) -> Result<usize, usize> {
+        self.finish_with_or("", "")
+    }
+
+    /// Finish the buffer by adding `suffix`, encoded as UTF-16. See [`crate::WriteBuf::finish_with`].
+    pub fn finish_with(self, suffix: &str) -> Result<usize, usize> {
+        self.finish_with_or(suffix, suffix)
+    }
+
+    /// Finish the buffer by adding `normal_suffix` if nothing was truncated or `truncated_suffix` if it was, encoded as
+    /// UTF-16. The suffix is placed the same way as [`crate::WriteBuf::finish_with_or`]: written content is rolled back
+    /// to make room for it, without splitting a surrogate pair, and if the suffix is longer than the entire target, only
+    /// its end is written.
+    ///
+    /// # Returns
+    ///
+    /// The number of code units written, including the suffix, in `Ok` if nothing was truncated and in `Err` if it was.
+    pub fn finish_with_or(mut self, normal_suffix: &str, truncated_suffix: &str) -> Result<usize, usize> {
+        let unwritten = self.target.len() - self.position;
+        for (suffix, should_test) in [(normal_suffix, !self.truncated), (truncated_suffix, true)] {
+            if should_test && utf16_len(suffix) <= unwritten {
+                let position = self.position;
+                self._write_units(position, suffix, 0);
+                return self._result();
+            }
+        }
+
+        self.truncated = true;
+        let suffix_len = utf16_len(truncated_suffix);
+        let (write_idx, skip) = match self.target.len().checked_sub(suffix_len) {
+            Some(max_end) => (rfind_utf16_end(&self.target[..max_end.min(self.position)]), 0),
+            // the suffix is longer than the entire target, so write the end of it
+            None => {
+                let mut skip = suffix_len - self.target.len();
+                if truncated_suffix
+                    .encode_utf16()
+                    .nth(skip)
+                    .map_or(false, is_low_surrogate)
+                {
+                    skip += 1;
+                }
+                (0, skip)
+            },
+        };
+        self._write_units(write_idx, truncated_suffix, skip);
+        self._result()
+    }
+
+    /// Write the UTF-16 encoding of `s` at `idx`, skipping the first `skip` code units, and move the position to the end
+    /// of it. The caller checks that it fits.
+    fn _write_units(&mut self, idx: usize, s: &str, skip: usize) {
+        let mut position = idx;
+        for unit in s.encode_utf16().skip(skip) {
+            self.target[position] = unit;
+            position += 1;
+        }
+        self.position = position;
+    }
+
+    fn _result(&self) -> Result<usize, usize> {
+        if self.truncated {
+            Err(self.position)
+        } else {
+            Ok(self.position)
+        }
+    }
+}
+
+impl<'a> fmt::Write for WideBuf<'a> {
+    /// Append `s`, encoded as UTF-16. If it does not entirely fit, as many whole characters as fit are written and this
+    /// buffer is marked as truncated, which makes every later write fail, like [`crate::WriteBuf`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return if s.is_empty() { Ok(()) } else { Err(fmt::Error) };
+        }
+
+        let end = self.target.len().saturating_sub(self.reserve).max(self.position);
+        let mut encoded = [0u16; 2];
+        for c in s.chars() {
+            let units = c.encode_utf16(&mut encoded);
+            if end - self.position < units.len() {
+                self.truncated = true;
+                return Err(fmt::Error);
+            }
+            self.target[self.position..self.position + units.len()].copy_from_slice(units);
+            self.position += units.len();
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Debug for WideBuf<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WideBuf")
+            .field("written", &Decoded(self.written()))
+            .field("capacity", &self.capacity())
+            .field("position", &self.position)
+            .field("reserve", &self.reserve)
+            .field("truncated", &self.truncated)
+            .finish()
+    }
+}
+
+/// Debug-formats UTF-16 code units like a `str`, without decoding them into a buffer first.
+struct Decoded<'s>(&'s [u16]);
+
+impl<'s> fmt::Debug for Decoded<'s> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Write::write_char(f, '"')?;
+        for c in core::char::decode_utf16(self.0.iter().copied()) {
+            let c = c.unwrap_or(core::char::REPLACEMENT_CHARACTER);
+            for escaped in c.escape_debug() {
+                fmt::Write::write_char(f, escaped)?;
+            }
+        }
+        fmt::Write::write_char(f, '"')
+    }
+}
+
+/// Get the number of UTF-16 code units `s` is encoded as.
+fn utf16_len(s: &str) -> usize {
+    s.chars().map(char::len_utf16).sum()
+}
+
+fn is_low_surrogate(unit: u16) -> bool {
+    (0xdc00..=0xdfff).contains(&unit)
+}
+
+/// Get the length of `written` without a high surrogate at its end, which would be split from its low surrogate.
+fn rfind_utf16_end(written: &[u16]) -> usize {
+    match written.last() {
+        Some(unit) if (0xd800..=0xdbff).contains(unit) => written.len() - 1,
+        _ => written.len(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::fmt::Write;
+
+    /// Encode `s` as UTF-16 into a fixed array, so the tests run without `std`.
+    fn wide<const N: usize>(s: &str) -> [u16; N] {
+        let mut out = [0u16; N];
+        let mut len = 0;
+        for unit in s.encode_utf16() {
+            out[len] = unit;
+            len += 1;
+        }
+        assert_eq!(N, len);
+        out
+    }
+
+    #[test]
+    fn surrogate_pair_at_boundary() {
+        // '🚀' is the surrogate pair 0xd83d 0xde80, which needs 2 code units
+        let mut buf: [u16; 4] = [0xffff; 4];
+        let mut writer = WideBuf::new(&mut buf);
+        writer.write_str("ab").unwrap();
+        writer.write_char('x').unwrap();
+        writer.write_str("🚀").unwrap_err();
+        assert!(writer.truncated());
+        assert_eq!(&wide::<3>("abx"), writer.written());
+        // Truncation latches, even for a write which would fit
+        writer.write_str("y").unwrap_err();
+        writer.write_str("").unwrap();
+        assert_eq!(Err(3), writer.finish());
+        assert_eq!(0xffff, buf[3]);
+
+        let mut buf: [u16; 5] = [0xffff; 5];
+        let mut writer = WideBuf::new(&mut buf);
+        writer.write_str("🚀🚀🚀").unwrap_err();
+        assert_eq!(&wide::<4>("🚀🚀"), writer.written());
+        assert_eq!(Err(4), writer.finish());
+    }
+
+    #[test]
+    fn nul_terminated() {
+        let mut buf: [u16; 6] = [0xffff; 6];
+        let mut writer = WideBuf::with_reserve(&mut buf, 1);
+        assert_eq!(5, writer.remaining());
+        let dir = "見";
+        write!(writer, "C:\\{}", dir).unwrap();
+        assert_eq!(Ok(5), writer.finish_with("\0"));
+        assert_eq!(wide::<5>("C:\\見\0"), buf[..5]);
+
+        // The reserve keeps room for the terminator, so truncated content is not rolled back for it
+        let mut buf: [u16; 6] = [0xffff; 6];
+        let mut writer = WideBuf::with_reserve(&mut buf, 1);
+        writer.write_str("abcd🚀").unwrap_err();
+        assert_eq!(1, writer.remaining());
+        assert_eq!(Err(5), writer.finish_with("\0"));
+        assert_eq!(wide::<5>("abcd\0"), buf[..5]);
+    }
+
+    #[test]
+    fn suffix_rolls_back_whole_characters() {
+        let mut buf: [u16; 6] = [0xffff; 6];
+        let mut writer = WideBuf::new(&mut buf);
+        writer.write_str("ab🚀🚀c").unwrap_err();
+        assert_eq!(6, writer.position());
+        // Making room for "…" by rolling back a single code unit would split the second 🚀
+        assert_eq!(Err(5), writer.finish_with_or("!", "…"));
+        assert_eq!(wide::<5>("ab🚀…"), buf[..5]);
+
+        let mut buf: [u16; 6] = [0xffff; 6];
+        let mut writer = WideBuf::new(&mut buf);
+        writer.write_str("🚀🚀🚀").unwrap();
+        assert_eq!(Err(5), writer.finish_with_or("!", "…"));
+        assert_eq!(wide::<5>("🚀🚀…"), buf[..5]);
+
+        let mut buf: [u16; 8] = [0xffff; 8];
+        let mut writer = WideBuf::new(&mut buf);
+        writer.write_str("ok").unwrap();
+        assert_eq!(Ok(4), writer.finish_with_or("🚀", "…"));
+        assert_eq!(wide::<4>("ok🚀"), buf[..4]);
+    }
+
+    #[test]
+    fn suffix_longer_than_target() {
+        // The suffix is cut from the front, without starting on the low half of a surrogate pair
+        let mut buf: [u16; 3] = [0xffff; 3];
+        let mut writer = WideBuf::new(&mut buf);
+        writer.write_str("abcd").unwrap_err();
+        assert_eq!(Err(2), writer.finish_with("x🚀🚀"));
+        assert_eq!(wide::<2>("🚀"), buf[..2]);
+
+        let mut buf: [u16; 3] = [0xffff; 3];
+        let writer = WideBuf::new(&mut buf);
+        assert_eq!(Err(3), writer.finish_with("🚀xyz"));
+        assert_eq!(wide::<3>("xyz"), buf);
+
+        let writer = WideBuf::new(&mut []);
+        assert_eq!(Err(0), writer.finish_with("🚀"));
+    }
+
+    #[test]
+    fn debug_decodes() {
+        let mut buf: [u16; 8] = [0xffff; 8];
+        let mut writer = WideBuf::new(&mut buf);
+        writer.write_str("\"🚀").unwrap();
+
+        let mut out: [u8; 128] = [0; 128];
+        let mut out = crate::WriteBuf::new(&mut out);
+        write!(out, "{:?}", writer).unwrap();
+        assert_eq!(
+            r#"WideBuf { written: "\"🚀", capacity: 8, position: 3, reserve: 0, truncated: false }"#,
+            out.written()
+        );
+    }
+}
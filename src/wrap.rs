@@ -0,0 +1,321 @@
+use core::fmt::{self, Write};
+
+use crate::{SliceTarget, Target, WriteBuf};
+
+/// Writes to a [`WriteBuf`], wrapping the text into lines of a fixed width, such as for a character LCD.
+///
+/// ```
+/// use fmtbuf::{WrapWriter, WriteBuf};
+/// use std::fmt::Write;
+///
+/// let mut buf: [u8; 64] = [0xff; 64];
+/// let mut writer = WriteBuf::new(&mut buf);
+/// let mut out = WrapWriter::new(&mut writer, 10);
+/// write!(out, "battery at {}%, charging", 81).unwrap();
+/// out.finish().unwrap();
+/// assert_eq!("battery at\n81%,\ncharging", writer.written());
+/// ```
+///
+/// Lines are broken at spaces. When a word does not fit on the rest of the line, it is moved to the next one and the
+/// spaces before it are replaced by the `\n`. A word longer than the width is broken onto as many lines as it takes,
+/// at `char` boundaries. A `\n` in the input ends the line as it is, and the spaces at the start of the line after it
+/// are kept, while the spaces at the start of a line from wrapping are dropped. The first line starts where `buf` is
+/// when this is created.
+///
+/// Words are written to the buffer as they come in, so the buffer always has everything written so far, and moving a
+/// word to the next line only moves it within the buffer. Spaces are held back until the next word, since they are
+/// dropped if that word is wrapped, so call [`WrapWriter::finish`] to write the spaces at the end.
+///
+/// Everything this writes to the buffer is truncated like any other write. When the buffer fills up in the middle of a
+/// word, the part of the word which fit is kept, the write returns an error, and while the buffer is latched (see
+/// [`WriteBuf::set_latch`]), every later write of anything other than the empty string returns an error without
+/// changing the buffer, including moving a word.
+pub struct WrapWriter<'b, 'a, T = SliceTarget<'a>> {
+    buf: &'b mut WriteBuf<'a, T>,
+    width: usize,
+    width_fn: fn(char) -> usize,
+    /// The width of the current line, not counting the held back spaces.
+    col: usize,
+    /// The number of spaces held back since the last word.
+    pending_spaces: usize,
+    /// If the current line was started by wrapping rather than by a `\n`.
+    wrapped: bool,
+    /// The word being written, if the last thing written was part of a word.
+    word: Option<Word>,
+}
+
+/// The position of the word being written through a [`WrapWriter`].
+#[derive(Clone, Copy, Debug)]
+struct Word {
+    /// The position in the buffer of the spaces before the word.
+    space_start: usize,
+    /// The width of the line before those spaces.
+    space_col: usize,
+    /// The position in the buffer of the first `char` of the word.
+    start: usize,
+    /// The width of the word so far.
+    width: usize,
+}
+
+impl<'b, 'a, T: Target<'a>> WrapWriter<'b, 'a, T> {
+    /// Create an instance which writes to `buf`, wrapping lines wider than `width`. Each `char` is one column wide,
+    /// which can be changed with [`WrapWriter::set_width_fn`].
+    pub fn new(buf: &'b mut WriteBuf<'a, T>, width: usize) -> Self {
+        Self {
+            buf,
+            width,
+            width_fn: |_| 1,
+            col: 0,
+            pending_spaces: 0,
+            wrapped: false,
+            word: None,
+        }
+    }
+
+    /// Set the function giving the number of columns each `char` takes up, such as for a display where some characters
+    /// are wider than others. Any `char` this returns a width over the width of a line for is put on a line of its own.
+    pub fn set_width_fn(&mut self, width_fn: fn(char) -> usize) {
+        self.width_fn = width_fn;
+    }
+
+    /// Get the width lines are wrapped at.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the width of the current line so far, not counting the spaces held back at the end of it.
+    pub fn column(&self) -> usize {
+        self.col
+    }
+
+    /// Write the spaces held back at the end of the input, as many as fit on the line.
+    ///
+    /// # Error
+    ///
+    /// If the spaces did not fit in the buffer, the same as [`WriteBuf`].
+    pub fn finish(mut self) -> fmt::Result {
+        let count = core::mem::take(&mut self.pending_spaces).min(self.width.saturating_sub(self.col));
+        self._write_spaces(count)
+    }
+
+    fn _write_spaces(&mut self, count: usize) -> fmt::Result {
+        for _ in 0..count {
+            self.buf.write_char(' ')?;
+        }
+        self.col += count;
+        Ok(())
+    }
+
+    /// End the current line, starting the next one by wrapping.
+    fn _wrap(&mut self) -> fmt::Result {
+        self.buf.write_char('\n')?;
+        self.col = 0;
+        self.wrapped = true;
+        Ok(())
+    }
+
+    /// Write the held back spaces and start a word which starts with a `char` `width` columns wide.
+    fn _start_word(&mut self, width: usize) -> fmt::Result {
+        let spaces = core::mem::take(&mut self.pending_spaces);
+        if self.col + spaces + width > self.width {
+            if self.col > 0 {
+                self._wrap()?;
+            } else {
+                // There is nothing to move the word away from, so only keep the spaces which fit before it
+                let space_start = self.buf.position();
+                self._write_spaces(spaces.min(self.width.saturating_sub(width)))?;
+                return self._set_word(space_start, 0);
+            }
+        }
+
+        let (space_start, space_col) = (self.buf.position(), self.col);
+        self._write_spaces(if self.col == 0 && self.wrapped { 0 } else { spaces })?;
+        self._set_word(space_start, space_col)
+    }
+
+    fn _set_word(&mut self, space_start: usize, space_col: usize) -> fmt::Result {
+        self.word = Some(Word {
+            space_start,
+            space_col,
+            start: self.buf.position(),
+            width: 0,
+        });
+        Ok(())
+    }
+
+    /// Make room on the line for a `char` `width` columns wide which continues the current word.
+    fn _continue_word(&mut self, mut word: Word, width: usize) -> fmt::Result {
+        // Anything goes at the start of a line, so a `char` wider than a line gets one of its own
+        if self.col + width <= self.width || self.col == 0 {
+            return Ok(());
+        }
+
+        if word.space_col > 0 && word.width + width <= self.width {
+            // Move the word to the next line, replacing the spaces before it with the newline
+            self.buf._remove(word.space_start..word.start);
+            self.buf.insert_str(word.space_start, "\n").map_err(|_| fmt::Error)?;
+            word.start = word.space_start + 1;
+            self.col = word.width;
+            self.wrapped = true;
+        } else {
+            // The word is too wide for any line, so break it here
+            self._wrap()?;
+            word.start = self.buf.position();
+            word.width = 0;
+        }
+        word.space_start = word.start;
+        word.space_col = 0;
+        self.word = Some(word);
+        Ok(())
+    }
+
+    fn _write_char(&mut self, c: char) -> fmt::Result {
+        match c {
+            '\n' => {
+                self.pending_spaces = 0;
+                self.word = None;
+                self.buf.write_char('\n')?;
+                self.col = 0;
+                self.wrapped = false;
+            },
+            ' ' => {
+                self.word = None;
+                if self.col > 0 || !self.wrapped {
+                    self.pending_spaces += 1;
+                }
+            },
+            _ => {
+                let width = (self.width_fn)(c);
+                if self.word.is_none() {
+                    self._start_word(width)?;
+                }
+                if let Some(word) = self.word {
+                    self._continue_word(word, width)?;
+                }
+                self.buf.write_char(c)?;
+                self.col += width;
+                if let Some(word) = &mut self.word {
+                    word.width += width;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Write for WrapWriter<'b, 'a, T> {
+    /// Write `s`, wrapping it onto new lines where the current one is full.
+    ///
+    /// # Error
+    ///
+    /// If anything did not fit in the buffer, the same as [`WriteBuf`].
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        if self.buf.truncated() && self.buf.is_latching() {
+            return Err(fmt::Error);
+        }
+        for c in s.chars() {
+            self._write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'b, 'a, T: Target<'a>> fmt::Debug for WrapWriter<'b, 'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WrapWriter")
+            .field("buf", &self.buf)
+            .field("width", &self.width)
+            .field("column", &self.col)
+            .field("pending_spaces", &self.pending_spaces)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StackString;
+
+    /// Write `pieces` through a [`WrapWriter`] `width` wide into a buffer of `N` bytes, returning what was written and
+    /// if every write succeeded.
+    fn wrap<const N: usize>(width: usize, pieces: &[&str]) -> (StackString<N>, bool) {
+        let mut buf = [0xff; N];
+        let mut writer = WriteBuf::new(&mut buf);
+        let mut out = WrapWriter::new(&mut writer, width);
+        let mut ok = true;
+        for piece in pieces {
+            ok &= out.write_str(piece).is_ok();
+        }
+        ok &= out.finish().is_ok();
+        let mut written = StackString::new();
+        written.write_str(writer.written()).unwrap();
+        (written, ok)
+    }
+
+    #[test]
+    fn wraps_words() {
+        assert_eq!("one two\nthree", wrap::<64>(8, &["one two three"]).0);
+        // A word exactly filling the line does not wrap
+        assert_eq!("one two\nthree", wrap::<64>(7, &["one two three"]).0);
+        // Every space at a wrap point is dropped, but spaces in a line are kept
+        assert_eq!("a  b\nc", wrap::<64>(4, &["a  b   c"]).0);
+        // Words are tracked across writes
+        assert_eq!("ab\ncde f", wrap::<64>(5, &["ab c", "de", " f"]).0);
+        assert_eq!("", wrap::<64>(5, &["", ""]).0);
+    }
+
+    #[test]
+    fn breaks_long_words() {
+        assert_eq!("abcd\nefgh\nij", wrap::<64>(4, &["abcdefghij"]).0);
+        // A word too long for any line is broken after what fits on its own line
+        assert_eq!("ab\ncdefg\nhij", wrap::<64>(5, &["ab cdefghij"]).0);
+        assert_eq!("見見\n見", wrap::<64>(2, &["見見見"]).0);
+        assert_eq!("a\nb\nc", wrap::<64>(0, &["abc"]).0);
+    }
+
+    #[test]
+    fn input_newlines() {
+        // Spaces at the start of a line after a newline in the input are kept
+        assert_eq!("ab\n  cd\nef\n\ng", wrap::<64>(5, &["ab  \n  cd ef\n", "\ng"]).0);
+        // Trailing spaces are kept if they fit
+        assert_eq!("ab  ", wrap::<64>(4, &["ab   "]).0);
+    }
+
+    #[test]
+    fn width_fn() {
+        let mut buf: [u8; 64] = [0xff; 64];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = WrapWriter::new(&mut writer, 4);
+            out.set_width_fn(|c| if c.is_ascii() { 1 } else { 2 });
+            out.write_str("a 見見 b").unwrap();
+            assert_eq!(1, out.column());
+            assert_eq!(4, out.width());
+            out.finish().unwrap();
+        }
+        assert_eq!("a\n見見\nb", writer.written());
+    }
+
+    #[test]
+    fn truncated() {
+        // The buffer fills in the middle of a word, keeping what fit, and later writes fail
+        let (written, ok) = wrap::<6>(8, &["abc defg", "h"]);
+        assert_eq!(("abc de", false), (written.as_str(), ok));
+
+        let mut buf: [u8; 6] = [0xff; 6];
+        let mut writer = WriteBuf::new(&mut buf);
+        {
+            let mut out = WrapWriter::new(&mut writer, 3);
+            out.write_str("ab cd").unwrap();
+            // The newline from wrapping is what does not fit
+            out.write_str("e f").unwrap_err();
+            out.write_str("").unwrap();
+            out.write_str("g").unwrap_err();
+        }
+        assert!(writer.truncated());
+        assert_eq!("ab\ncde", writer.written());
+    }
+}